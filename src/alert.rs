@@ -0,0 +1,191 @@
+// SPDX-License-Identifier: GPL-2.0
+// Alert module - --alert per-tier wait thresholds, checked by the TUI every tick
+
+use std::str::FromStr;
+
+use clap::ValueEnum;
+
+use crate::stats::{tier_wait_max_us, wait_mean_us, wait_percentile, WaitHist, TIER_NAMES};
+use crate::Tier;
+
+/// What an alert watches in a tier's wait over the last tick
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlertMetric {
+    /// Mean wait per run (the tier table's Wait µs)
+    Wait,
+    /// 99th percentile wait
+    P99,
+    /// Longest wait (top of the highest histogram bucket reached)
+    Max,
+}
+
+impl AlertMetric {
+    fn name(self) -> &'static str {
+        match self {
+            AlertMetric::Wait => "wait",
+            AlertMetric::P99 => "p99",
+            AlertMetric::Max => "max",
+        }
+    }
+}
+
+/// Wait threshold for one tier: TIER.METRIC=LIMIT, LIMIT in µs or with an
+/// ms suffix, e.g. frame.max=5ms
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TierAlert {
+    tier: Tier,
+    metric: AlertMetric,
+    limit_us: f64,
+}
+
+impl FromStr for TierAlert {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (lhs, limit) = s
+            .split_once('=')
+            .and_then(|(lhs, limit)| Some((lhs.split_once('.')?, limit)))
+            .ok_or_else(|| format!("expected TIER.METRIC=LIMIT, got '{}'", s))?;
+        let (tier, metric) = lhs;
+        let metric = match metric.trim().to_lowercase().as_str() {
+            "wait" => AlertMetric::Wait,
+            "p99" => AlertMetric::P99,
+            "max" => AlertMetric::Max,
+            other => return Err(format!("unknown metric '{}' (wait, p99, max)", other)),
+        };
+        let limit = limit.trim();
+        let (num, scale) = match limit.strip_suffix("ms") {
+            Some(ms) => (ms, 1000.0),
+            None => (
+                limit
+                    .strip_suffix("us")
+                    .or_else(|| limit.strip_suffix("µs"))
+                    .unwrap_or(limit),
+                1.0,
+            ),
+        };
+        let limit_us = num
+            .trim()
+            .parse::<f64>()
+            .map_err(|e| format!("bad limit in '{}': {}", s, e))?
+            * scale;
+        if !(limit_us > 0.0 && limit_us.is_finite()) {
+            return Err(format!("limit must be positive in '{}'", s));
+        }
+        Ok(Self {
+            tier: Tier::from_str(tier.trim(), true)?,
+            metric,
+            limit_us,
+        })
+    }
+}
+
+impl std::fmt::Display for TierAlert {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} > {}µs",
+            TIER_NAMES[self.tier as usize],
+            self.metric.name(),
+            self.limit_us
+        )
+    }
+}
+
+impl TierAlert {
+    /// The watched value in µs over one tick's histogram movement
+    fn value(&self, tick: &WaitHist) -> f64 {
+        let hist = &tick[self.tier as usize];
+        match self.metric {
+            AlertMetric::Wait => wait_mean_us(hist),
+            AlertMetric::P99 => wait_percentile(hist, 99.0),
+            AlertMetric::Max => tier_wait_max_us(hist) as f64,
+        }
+    }
+}
+
+/// --alert thresholds and which of them the last tick was over
+#[derive(Debug, Default)]
+pub struct Alerts {
+    alerts: Vec<TierAlert>,
+    firing: Vec<bool>,
+}
+
+impl Alerts {
+    pub fn new(alerts: &[TierAlert]) -> Self {
+        Self {
+            alerts: alerts.to_vec(),
+            firing: vec![false; alerts.len()],
+        }
+    }
+
+    /// Check one tick; returns a line for each alert that has just gone
+    /// over its limit (one that stays over is reported once)
+    pub fn update(&mut self, tick: &WaitHist) -> Vec<String> {
+        let mut fired = Vec::new();
+        for (alert, firing) in self.alerts.iter().zip(&mut self.firing) {
+            let value = alert.value(tick);
+            let over = value > alert.limit_us;
+            if over && !*firing {
+                fired.push(format!("{} ({:.0}µs)", alert, value));
+            }
+            *firing = over;
+        }
+        fired
+    }
+
+    /// Whether any alert on `tier` (optionally just `metric`) is over its limit
+    pub fn firing(&self, tier: usize, metric: Option<AlertMetric>) -> bool {
+        self.alerts
+            .iter()
+            .zip(&self.firing)
+            .any(|(a, &f)| f && a.tier as usize == tier && metric.is_none_or(|m| m == a.metric))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::WAIT_BUCKETS;
+
+    #[test]
+    fn test_parse() {
+        let a: TierAlert = "frame.max=5ms".parse().unwrap();
+        assert_eq!(
+            (a.tier, a.metric, a.limit_us),
+            (Tier::Frame, AlertMetric::Max, 5000.0)
+        );
+        assert_eq!(a.to_string(), "Frame max > 5000µs");
+        let b: TierAlert = "Interactive.P99=750us".parse().unwrap();
+        assert_eq!((b.metric, b.limit_us), (AlertMetric::P99, 750.0));
+        assert_eq!("bulk.wait=40".parse::<TierAlert>().unwrap().limit_us, 40.0);
+
+        for s in [
+            "frame=5ms",
+            "frame.mean=5ms",
+            "frame.max=",
+            "frame.max=0",
+            "game.max=1",
+        ] {
+            assert!(s.parse::<TierAlert>().is_err(), "{}", s);
+        }
+    }
+
+    #[test]
+    fn test_fires_once_per_crossing() {
+        let mut alerts = Alerts::new(&["frame.max=5ms".parse().unwrap()]);
+        let mut tick = [[0u64; WAIT_BUCKETS]; 4];
+        // 8192µs bucket: over 5ms
+        tick[Tier::Frame as usize][13] = 1;
+        assert_eq!(alerts.update(&tick), ["Frame max > 5000µs (8192µs)"]);
+        assert!(alerts.firing(Tier::Frame as usize, Some(AlertMetric::Max)));
+        assert!(!alerts.firing(Tier::Frame as usize, Some(AlertMetric::Wait)));
+        assert!(!alerts.firing(Tier::Bulk as usize, None));
+        assert!(alerts.update(&tick).is_empty());
+
+        let quiet = [[0u64; WAIT_BUCKETS]; 4];
+        assert!(alerts.update(&quiet).is_empty());
+        assert!(!alerts.firing(Tier::Frame as usize, None));
+        assert_eq!(alerts.update(&tick).len(), 1);
+    }
+}
@@ -0,0 +1,357 @@
+// SPDX-License-Identifier: GPL-2.0
+// Autotune module - hill-climbs quantum / new-flow bonus / starvation against a measured objective
+
+use std::collections::HashMap;
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Context, Result};
+use clap::ValueEnum;
+use log::{info, warn};
+
+use crate::stats::{TaskSampler, TaskStat};
+use crate::{Args, Profile, Scheduler, Tier};
+
+/// Tuned parameters: [quantum_us, new_flow_bonus_us, starvation_us]
+type Params = [u64; 3];
+
+const PARAM_NAMES: [&str; 3] = ["quantum", "new-flow-bonus", "starvation"];
+const PARAM_MIN: Params = [250, 0, 10_000];
+const PARAM_MAX: Params = [16_000, 64_000, 1_000_000];
+/// Smallest upward move, so a parameter at 0 can still climb
+const PARAM_FLOOR: Params = [50, 500, 1_000];
+
+/// Initial multiplicative step; halved (in log space) when no neighbour improves
+const START_STEP: f64 = 2.0;
+const MIN_STEP: f64 = 1.1;
+
+/// Let tasks reclassify under the new parameters before measuring
+const SETTLE: Duration = Duration::from_secs(2);
+
+/// Options for `autotune`
+#[derive(Debug, Clone, clap::Args)]
+pub struct AutotuneArgs {
+    /// Measurement window per trial in SECONDS (ignored with --bench-cmd)
+    #[arg(long, default_value_t = 20)]
+    pub trial_secs: u64,
+
+    /// Maximum number of trials (each one reloads the scheduler)
+    #[arg(long, default_value_t = 24)]
+    pub max_trials: u32,
+
+    /// Tiers whose p99 per-run wait is minimized (without --bench-cmd)
+    #[arg(long, value_enum, value_delimiter = ',', default_values = ["critical", "interactive"])]
+    pub tiers: Vec<Tier>,
+
+    /// Benchmark command (run with sh -c) once per trial; the last number it
+    /// prints on stdout is the score
+    #[arg(long)]
+    pub bench_cmd: Option<String>,
+
+    /// Higher --bench-cmd score is better (e.g. FPS); default is lower-is-better
+    #[arg(long, requires = "bench_cmd")]
+    pub maximize: bool,
+}
+
+/// Move one parameter up or down by `step`, clamped to its bounds
+fn neighbour(v: u64, i: usize, up: bool, step: f64) -> u64 {
+    let n = if up {
+        ((v as f64 * step) as u64).max(v + PARAM_FLOOR[i])
+    } else {
+        (v as f64 / step) as u64
+    };
+    n.clamp(PARAM_MIN[i], PARAM_MAX[i])
+}
+
+/// Coordinate-wise hill climb (lower score is better). A failed trial ends
+/// the search with the best result so far; only a failed first trial errors.
+fn hill_climb(
+    start: Params,
+    max_trials: u32,
+    mut eval: impl FnMut(&Params) -> Result<f64>,
+) -> Result<(Params, f64)> {
+    let mut best = start;
+    for (i, v) in best.iter_mut().enumerate() {
+        *v = (*v).clamp(PARAM_MIN[i], PARAM_MAX[i]);
+    }
+    let mut best_score = eval(&best)?;
+    let mut trials = 1;
+    let mut step = START_STEP;
+
+    while trials < max_trials && step > MIN_STEP {
+        let mut improved = false;
+        'params: for i in 0..best.len() {
+            for up in [true, false] {
+                let mut cand = best;
+                cand[i] = neighbour(best[i], i, up, step);
+                if cand[i] == best[i] {
+                    continue;
+                }
+                if trials >= max_trials {
+                    break 'params;
+                }
+                trials += 1;
+                let score = match eval(&cand) {
+                    Ok(s) => s,
+                    Err(e) => {
+                        warn!("Stopping autotune early: {:#}", e);
+                        return Ok((best, best_score));
+                    }
+                };
+                if score < best_score {
+                    best = cand;
+                    best_score = score;
+                    improved = true;
+                    break;
+                }
+            }
+        }
+        if !improved {
+            step = step.sqrt();
+        }
+    }
+    Ok((best, best_score))
+}
+
+/// p99 over tasks in `tier_mask` of their mean wait per run (µs) between snapshots
+fn p99_wait_us(before: &[TaskStat], after: &[TaskStat], tier_mask: u8) -> Option<f64> {
+    let prev: HashMap<u32, &TaskStat> = before.iter().map(|t| (t.pid, t)).collect();
+    let mut waits: Vec<f64> = after
+        .iter()
+        .filter(|t| tier_mask & (1 << (t.tier & 3)) != 0)
+        .filter_map(|t| {
+            let p = prev.get(&t.pid)?;
+            let runs = t.nr_runs.checked_sub(p.nr_runs).filter(|&r| r > 0)?;
+            Some(t.wait_ns.saturating_sub(p.wait_ns) as f64 / runs as f64 / 1000.0)
+        })
+        .collect();
+    if waits.is_empty() {
+        return None;
+    }
+    waits.sort_by(|a, b| a.total_cmp(b));
+    let idx = ((waits.len() as f64 * 0.99).ceil() as usize).clamp(1, waits.len()) - 1;
+    Some(waits[idx])
+}
+
+/// Last number printed by a benchmark command
+fn parse_score(stdout: &str) -> Option<f64> {
+    stdout
+        .split(|c: char| c.is_whitespace() || c == ',' || c == ':' || c == '=')
+        .rev()
+        .filter_map(|tok| tok.parse::<f64>().ok())
+        .find(|v| v.is_finite())
+}
+
+/// Sleep in short slices so Ctrl-C interrupts a trial promptly
+fn sleep_interruptible(d: Duration, shutdown: &AtomicBool) -> Result<()> {
+    let end = Instant::now() + d;
+    while Instant::now() < end {
+        if shutdown.load(Ordering::Relaxed) {
+            bail!("interrupted");
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+    Ok(())
+}
+
+/// Load the scheduler with `params`, measure, unload
+fn run_trial(
+    base: &Args,
+    params: &Params,
+    opts: &AutotuneArgs,
+    shutdown: &AtomicBool,
+) -> Result<f64> {
+    let mut args = base.clone();
+    args.quantum = Some(params[0]);
+    args.new_flow_bonus = Some(params[1]);
+    args.starvation = Some(params[2]);
+    // Keep trials comparable: no history, TUI or idle relaxation
+    args.verbose = false;
+    args.ui = crate::UiMode::Auto;
+    args.history_db = None;
+    args.idle_relax = 0;
+
+    let mut open_object = std::mem::MaybeUninit::uninit();
+    // No ETD calibration: nothing displays it and it costs seconds per trial
+    let sched = Scheduler::new(args, &mut open_object, Some(Vec::new()))?;
+    let _link = sched
+        .skel
+        .maps
+        .cake_ops
+        .attach_struct_ops()
+        .context("Failed to attach scheduler")?;
+    sleep_interruptible(SETTLE, shutdown)?;
+
+    match &opts.bench_cmd {
+        Some(cmd) => {
+            let out = Command::new("sh")
+                .args(["-c", cmd])
+                .output()
+                .with_context(|| format!("Failed to run {}", cmd))?;
+            if !out.status.success() {
+                bail!("benchmark exited with {}", out.status);
+            }
+            let score = parse_score(&String::from_utf8_lossy(&out.stdout))
+                .context("benchmark printed no number")?;
+            Ok(if opts.maximize { -score } else { score })
+        }
+        None => {
+            let sampler = TaskSampler::new(&sched.skel)?;
+            let before = sampler.snapshot()?;
+            sleep_interruptible(Duration::from_secs(opts.trial_secs.max(1)), shutdown)?;
+            let after = sampler.snapshot()?;
+
+            let mask = opts.tiers.iter().fold(0u8, |m, &t| m | (1 << t as u8));
+            p99_wait_us(&before, &after, mask)
+                .context("no task in the chosen tiers ran — start the workload first")
+        }
+    }
+}
+
+/// Run `autotune`: climb from the selected profile and print the best flags
+pub fn run(base: &Args, opts: &AutotuneArgs, shutdown: Arc<AtomicBool>) -> Result<()> {
+    let (q, nfb, starv) = base.effective_values();
+    let objective = match &opts.bench_cmd {
+        Some(cmd) => format!("benchmark score of `{}`", cmd),
+        None => format!("p99 wait/run of {:?}", opts.tiers),
+    };
+    info!(
+        "Autotune: up to {} trials from {:?} (quantum {}µs, new-flow-bonus {}µs, starvation {}µs), minimizing {}",
+        opts.max_trials, base.profile, q, nfb, starv, objective
+    );
+
+    let mut trial = 0;
+    let (best, score) = hill_climb([q, nfb, starv], opts.max_trials, |p| {
+        trial += 1;
+        let s = run_trial(base, p, opts, &shutdown)?;
+        info!(
+            "Trial {:>2}: {}={}µs {}={}µs {}={}µs → {:.2}",
+            trial,
+            PARAM_NAMES[0],
+            p[0],
+            PARAM_NAMES[1],
+            p[1],
+            PARAM_NAMES[2],
+            p[2],
+            if opts.maximize { -s } else { s }
+        );
+        Ok(s)
+    })?;
+
+    println!(
+        "\nRecommended for this machine/workload ({}: {:.2}):\n  {}",
+        objective,
+        if opts.maximize { -score } else { score },
+        recommended_command(base.profile, best)
+    );
+    Ok(())
+}
+
+/// The command line autotune suggests, with the profile as clap spells it
+fn recommended_command(profile: Profile, best: Params) -> String {
+    let profile = profile
+        .to_possible_value()
+        .map(|v| v.get_name().to_string())
+        .unwrap_or_default();
+    format!(
+        "scx_cake -p {} --quantum {} --new-flow-bonus {} --starvation {}",
+        profile, best[0], best[1], best[2]
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hill_climb_finds_bowl_minimum() {
+        // Optimum at quantum 1000, bonus 4000, starvation 50000
+        let target = [1000.0, 4000.0, 50_000.0];
+        let bowl = |p: &Params| -> Result<f64> {
+            Ok(p.iter()
+                .zip(target)
+                .map(|(&v, t)| ((v as f64).ln() - f64::ln(t)).powi(2))
+                .sum())
+        };
+        let start = [2000, 8000, 100_000];
+        let start_score = bowl(&start).unwrap();
+        let (best, score) = hill_climb(start, 64, bowl).unwrap();
+        assert!(score < start_score * 0.1, "best {:?} score {}", best, score);
+    }
+
+    #[test]
+    fn test_hill_climb_respects_trial_budget() {
+        let mut calls = 0;
+        hill_climb([2000, 8000, 100_000], 5, |_| {
+            calls += 1;
+            Ok(1.0)
+        })
+        .unwrap();
+        assert_eq!(calls, 5);
+    }
+
+    #[test]
+    fn test_failed_trial_keeps_best_so_far() {
+        let mut calls = 0;
+        let (best, _) = hill_climb([2000, 8000, 100_000], 10, |_| {
+            calls += 1;
+            if calls > 1 {
+                bail!("boom")
+            }
+            Ok(1.0)
+        })
+        .unwrap();
+        assert_eq!(best, [2000, 8000, 100_000]);
+    }
+
+    #[test]
+    fn test_neighbour_climbs_from_zero_and_clamps() {
+        assert_eq!(neighbour(0, 1, true, 2.0), 500);
+        assert_eq!(neighbour(16_000, 0, true, 2.0), 16_000);
+        assert_eq!(neighbour(300, 0, false, 2.0), 250);
+    }
+
+    fn task(pid: u32, tier: u8, wait_ns: u64, nr_runs: u64) -> TaskStat {
+        TaskStat {
+            pid,
+            tgid: pid,
+            cgid: 0,
+            comm: String::new(),
+            tier,
+            cpu: 0,
+            avg_runtime_us: 0,
+            runtime_ns: 0,
+            wait_ns,
+            nr_runs,
+        }
+    }
+
+    #[test]
+    fn test_p99_wait_filters_tiers() {
+        let before = [task(1, 0, 0, 0), task(2, 3, 0, 0)];
+        let after = [task(1, 0, 50_000, 10), task(2, 3, 9_000_000, 1)];
+        assert_eq!(p99_wait_us(&before, &after, 0b0001), Some(5.0));
+        assert_eq!(p99_wait_us(&before, &after, 0b1000), Some(9000.0));
+        assert_eq!(p99_wait_us(&before, &after, 0b0100), None);
+    }
+
+    #[test]
+    fn test_parse_score_takes_last_number() {
+        assert_eq!(parse_score("avg fps: 143.2\nscore=1234.5\n"), Some(1234.5));
+        assert_eq!(parse_score("p99 latency (usec): 42,"), Some(42.0));
+        assert_eq!(parse_score("no numbers"), None);
+    }
+
+    #[test]
+    fn test_recommended_command_parses() {
+        use clap::Parser;
+
+        let cmd = recommended_command(Profile::Gaming, [1500, 6000, 80_000]);
+        assert!(cmd.contains("-p gaming "), "{}", cmd);
+        let args = Args::try_parse_from(cmd.split_whitespace()).unwrap();
+        assert_eq!(args.profile, Profile::Gaming);
+        assert_eq!(args.quantum, Some(1500));
+    }
+}
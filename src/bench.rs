@@ -0,0 +1,375 @@
+// SPDX-License-Identifier: GPL-2.0
+// Benchmark module - correlates MangoHud frametime logs with scheduler stats sampled during a capture
+
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+
+use anyhow::{bail, Context, Result};
+use clap::{Args, Subcommand};
+use log::{info, warn};
+
+use crate::bpf_skel::types::cake_stats;
+use crate::bpf_skel::BpfSkel;
+use crate::stats::{aggregate_stats, wait_histograms, WaitHist};
+
+/// Benchmark subcommands
+#[derive(Debug, Clone, Subcommand)]
+pub enum BenchCommand {
+    /// Capture scheduler stats while a game runs and attribute frame spikes.
+    ///
+    /// Start MangoHud logging (default Shift+F2) during the capture window.
+    /// When the window closes, the log is aligned to the capture by its
+    /// modification time and every frametime spike is matched against the
+    /// scheduler counters that moved around it.
+    #[command(verbatim_doc_comment)]
+    Game(GameArgs),
+}
+
+/// Options for `bench game`
+#[derive(Debug, Clone, Args)]
+pub struct GameArgs {
+    /// MangoHud CSV log written during the capture
+    #[arg(long)]
+    pub mangohud_log: PathBuf,
+
+    /// Capture window in SECONDS (Ctrl-C ends early)
+    #[arg(long, default_value_t = 60)]
+    pub duration: u64,
+
+    /// Scheduler stats sampling period in MILLISECONDS
+    #[arg(long, default_value_t = 100)]
+    pub sample_ms: u64,
+
+    /// A frame is a spike when it exceeds this multiple of the median frametime
+    #[arg(long, default_value_t = 2.0)]
+    pub spike_factor: f64,
+
+    /// Write the report to this file instead of stdout
+    #[arg(long)]
+    pub report: Option<PathBuf>,
+}
+
+/// First wait_hist bucket of a wait spike: [2048, 4096)µs, so a run of a
+/// latency tier that waited 2ms or more
+const WAIT_SPIKE_BUCKET: usize = 12;
+
+/// Scheduler counters that can explain a frame spike
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct Counters {
+    starvation_preempts: u64,
+    cross_llc_migrations: u64,
+    wait_spikes: u64,
+    warmup_dispatches: u64,
+}
+
+impl Counters {
+    fn from_stats(stats: &cake_stats, waits: &WaitHist) -> Self {
+        Self {
+            starvation_preempts: stats.nr_starvation_preempts_tier.iter().sum(),
+            cross_llc_migrations: stats.nr_migrate_cross_llc.iter().sum(),
+            // Critical, Interactive and Frame: Bulk waiting is expected
+            wait_spikes: waits[..3]
+                .iter()
+                .flat_map(|hist| &hist[WAIT_SPIKE_BUCKET..])
+                .sum(),
+            warmup_dispatches: stats.nr_warmup_dispatches,
+        }
+    }
+
+    fn delta(&self, earlier: &Self) -> Self {
+        Self {
+            starvation_preempts: self
+                .starvation_preempts
+                .saturating_sub(earlier.starvation_preempts),
+            cross_llc_migrations: self
+                .cross_llc_migrations
+                .saturating_sub(earlier.cross_llc_migrations),
+            wait_spikes: self.wait_spikes.saturating_sub(earlier.wait_spikes),
+            warmup_dispatches: self
+                .warmup_dispatches
+                .saturating_sub(earlier.warmup_dispatches),
+        }
+    }
+}
+
+/// Counter snapshot at `t` seconds since capture start
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+    t: f64,
+    counters: Counters,
+}
+
+/// One MangoHud frame: end time (ns since log start) and frametime (ms)
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Frame {
+    elapsed_ns: u64,
+    frametime_ms: f64,
+}
+
+/// Parse a MangoHud CSV log. The system-info preamble is skipped; data
+/// starts after the header row that names the `frametime` column.
+fn parse_mangohud(text: &str) -> Result<Vec<Frame>> {
+    let mut lines = text.lines();
+    let header = lines
+        .by_ref()
+        .find(|l| l.split(',').any(|c| c.trim() == "frametime"))
+        .context("No frametime header found (is this a MangoHud log?)")?;
+
+    let cols: Vec<&str> = header.split(',').map(str::trim).collect();
+    let ft_col = cols.iter().position(|c| *c == "frametime").unwrap();
+    let Some(el_col) = cols.iter().position(|c| *c == "elapsed") else {
+        bail!("MangoHud log has no elapsed column");
+    };
+
+    let mut frames = Vec::new();
+    for line in lines {
+        let fields: Vec<&str> = line.split(',').collect();
+        let (Some(ft), Some(el)) = (fields.get(ft_col), fields.get(el_col)) else {
+            continue;
+        };
+        if let (Ok(frametime_ms), Ok(elapsed_ns)) =
+            (ft.trim().parse::<f64>(), el.trim().parse::<u64>())
+        {
+            if frametime_ms.is_finite() {
+                frames.push(Frame {
+                    elapsed_ns,
+                    frametime_ms,
+                });
+            }
+        }
+    }
+    Ok(frames)
+}
+
+fn median(values: &mut [f64]) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    values[values.len() / 2]
+}
+
+/// Counter movement across [from, to] seconds, widened to whole samples
+fn window_delta(samples: &[Sample], from: f64, to: f64) -> Counters {
+    let (Some(first), Some(last)) = (samples.first(), samples.last()) else {
+        return Counters::default();
+    };
+    let start = samples.iter().rev().find(|s| s.t <= from).unwrap_or(first);
+    let end = samples.iter().find(|s| s.t >= to).unwrap_or(last);
+    end.counters.delta(&start.counters)
+}
+
+/// Attribute each spike and render the text report
+fn build_report(samples: &[Sample], frames: &[Frame], offset_s: f64, spike_factor: f64) -> String {
+    let mut frametimes: Vec<f64> = frames.iter().map(|f| f.frametime_ms).collect();
+    let median_ms = median(&mut frametimes);
+    let threshold_ms = median_ms * spike_factor;
+
+    let mut out = String::new();
+    let _ = writeln!(out, "=== scx_cake Frametime Report ===\n");
+    let _ = writeln!(
+        out,
+        "Frames: {}  Median: {:.2}ms  Spike threshold: {:.2}ms ({:.1}x)\n",
+        frames.len(),
+        median_ms,
+        threshold_ms,
+        spike_factor
+    );
+    let _ = writeln!(out, "   Time(s)   Frame(ms)   Cause");
+    let _ = writeln!(
+        out,
+        "──────────────────────────────────────────────────────────"
+    );
+
+    let (mut spikes, mut starved, mut migrated, mut waited, mut warmup, mut unexplained) =
+        (0, 0, 0, 0, 0, 0);
+    for f in frames.iter().filter(|f| f.frametime_ms > threshold_ms) {
+        spikes += 1;
+        let end = offset_s + f.elapsed_ns as f64 / 1e9;
+        let begin = end - f.frametime_ms / 1000.0;
+        let d = window_delta(samples, begin, end);
+
+        let mut causes = Vec::new();
+        if d.starvation_preempts > 0 {
+            starved += 1;
+            causes.push(format!("starvation preempt ×{}", d.starvation_preempts));
+        }
+        if d.cross_llc_migrations > 0 {
+            migrated += 1;
+            causes.push(format!("cross-LLC migration ×{}", d.cross_llc_migrations));
+        }
+        if d.wait_spikes > 0 {
+            waited += 1;
+            causes.push(format!("wait spike ×{}", d.wait_spikes));
+        }
+        if d.warmup_dispatches > 0 {
+            warmup += 1;
+            causes.push("launch warm-up".to_string());
+        }
+        if causes.is_empty() {
+            unexplained += 1;
+            causes.push("unattributed".to_string());
+        }
+        let _ = writeln!(
+            out,
+            "{:>10.3}   {:>9.2}   {}",
+            end,
+            f.frametime_ms,
+            causes.join(", ")
+        );
+    }
+
+    let _ = writeln!(out, "\nSpikes: {}", spikes);
+    let _ = writeln!(out, "  Starvation preempt:  {}", starved);
+    let _ = writeln!(out, "  Cross-LLC migration: {}", migrated);
+    let _ = writeln!(out, "  Wait spike (≥2ms):   {}", waited);
+    let _ = writeln!(out, "  Launch warm-up:      {}", warmup);
+    let _ = writeln!(out, "  Unattributed:        {}", unexplained);
+    out
+}
+
+/// Seconds from capture start to MangoHud log start (log start = mtime − last elapsed)
+fn log_offset(path: &Path, frames: &[Frame], capture_start: SystemTime) -> Result<f64> {
+    let mtime = std::fs::metadata(path)?.modified()?;
+    let last_ns = frames.last().map(|f| f.elapsed_ns).unwrap_or(0);
+    let log_start = mtime
+        .checked_sub(Duration::from_nanos(last_ns))
+        .unwrap_or(mtime);
+    Ok(match log_start.duration_since(capture_start) {
+        Ok(d) => d.as_secs_f64(),
+        Err(e) => -e.duration().as_secs_f64(),
+    })
+}
+
+/// Run the `bench game` capture against the attached scheduler
+pub fn run_game(skel: &BpfSkel, shutdown: Arc<AtomicBool>, args: &GameArgs) -> Result<()> {
+    info!(
+        "Capturing scheduler stats for {}s — start MangoHud logging now",
+        args.duration
+    );
+
+    let capture_start = SystemTime::now();
+    let start = Instant::now();
+    let window = Duration::from_secs(args.duration);
+    let period = Duration::from_millis(args.sample_ms.max(10));
+    let mut samples = Vec::new();
+
+    while start.elapsed() < window && !shutdown.load(Ordering::Relaxed) {
+        if scx_utils::uei_exited!(skel, uei) {
+            warn!("BPF scheduler exited during capture");
+            break;
+        }
+        samples.push(Sample {
+            t: start.elapsed().as_secs_f64(),
+            counters: Counters::from_stats(&aggregate_stats(skel), &wait_histograms(skel)),
+        });
+        std::thread::sleep(period);
+    }
+
+    let text = std::fs::read_to_string(&args.mangohud_log)
+        .with_context(|| format!("Failed to read {}", args.mangohud_log.display()))?;
+    let frames = parse_mangohud(&text)?;
+    if frames.is_empty() {
+        bail!("MangoHud log contains no frames");
+    }
+    let offset_s = log_offset(&args.mangohud_log, &frames, capture_start)?;
+    let report = build_report(&samples, &frames, offset_s, args.spike_factor);
+
+    match &args.report {
+        Some(path) => {
+            std::fs::write(path, &report)
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+            info!("Report written to {}", path.display());
+        }
+        None => print!("{}", report),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOG: &str = "os,cpu,gpu,ram,kernel,driver,cpuscheduler\n\
+                       Arch,Ryzen 9 7950X3D,RX 7900 XTX,64GB,6.12,Mesa,\n\
+                       fps,frametime,cpu_load,gpu_load,elapsed\n\
+                       144,6.9,30,90,6900000\n\
+                       144,6.9,30,90,13800000\n\
+                       40,25.0,30,90,38800000\n";
+
+    fn sample(t: f64, starvation_preempts: u64) -> Sample {
+        Sample {
+            t,
+            counters: Counters {
+                starvation_preempts,
+                ..Default::default()
+            },
+        }
+    }
+
+    #[test]
+    fn test_parse_mangohud_skips_preamble() {
+        let frames = parse_mangohud(LOG).unwrap();
+        assert_eq!(frames.len(), 3);
+        assert_eq!(
+            frames[2],
+            Frame {
+                elapsed_ns: 38_800_000,
+                frametime_ms: 25.0
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_rejects_non_mangohud() {
+        assert!(parse_mangohud("a,b,c\n1,2,3\n").is_err());
+    }
+
+    #[test]
+    fn test_spike_attributed_to_starvation() {
+        let frames = parse_mangohud(LOG).unwrap();
+        let samples = [sample(0.0, 0), sample(0.02, 0), sample(0.04, 3)];
+        let report = build_report(&samples, &frames, 0.0, 2.0);
+        assert!(report.contains("starvation preempt ×3"));
+        assert!(report.contains("Spikes: 1"));
+    }
+
+    #[test]
+    fn test_spike_attributed_to_migration_and_wait() {
+        let frames = parse_mangohud(LOG).unwrap();
+        let mut cake = cake_stats::default();
+        let mut waits = [[0; crate::stats::WAIT_BUCKETS]; 4];
+        let before = Counters::from_stats(&cake, &waits);
+        cake.nr_migrate_cross_llc[2] = 2;
+        cake.nr_rebalance_moves = 7;
+        waits[2][WAIT_SPIKE_BUCKET] = 1;
+        waits[3][WAIT_SPIKE_BUCKET + 2] = 5; // Bulk: not a spike
+        waits[1][WAIT_SPIKE_BUCKET - 1] = 9; // 1-2ms: not a spike
+        let after = Counters::from_stats(&cake, &waits);
+        let samples = [
+            Sample {
+                t: 0.0,
+                counters: before,
+            },
+            Sample {
+                t: 0.04,
+                counters: after,
+            },
+        ];
+        let report = build_report(&samples, &frames, 0.0, 2.0);
+        assert!(report.contains("cross-LLC migration ×2"), "{}", report);
+        assert!(report.contains("wait spike ×1"), "{}", report);
+        assert!(report.contains("Wait spike (≥2ms):   1"), "{}", report);
+    }
+
+    #[test]
+    fn test_quiet_spike_is_unattributed() {
+        let frames = parse_mangohud(LOG).unwrap();
+        let samples = [sample(0.0, 5), sample(0.1, 5)];
+        let report = build_report(&samples, &frames, 0.0, 2.0);
+        assert!(report.contains("Unattributed:        1"));
+    }
+}
@@ -11,19 +11,163 @@ char _license[] SEC("license") = "GPL";
 /* Scheduler RODATA config - JIT constant-folds these for ~200 cycle savings per decision */
 const u64 quantum_ns = CAKE_DEFAULT_QUANTUM_NS;
 const u64 new_flow_bonus_ns = CAKE_DEFAULT_NEW_FLOW_BONUS_NS;
-const bool enable_stats = false;
+
+/* Stats counting - BSS rather than RODATA so `scx_cake set stats on|off` and
+ * the TUI can flip it on a live scheduler. The loader sets the initial value. */
+volatile u32 enable_stats SEC(".bss");
+
+/* Event stream (--events) — JIT eliminates every emit site when false */
+const bool has_events = false;
+/* --trace: also emit per-step enqueue/run/stop records (implies has_events) */
+const bool has_trace = false;
+
+/* Starvation immunity - bit N spares tier N from lower-tier starvation rescues.
+ * starvation_ns is the hard ceiling: immune tasks past it are preempted anyway. */
+const u8 starvation_immune_mask = CAKE_DEFAULT_IMMUNE_MASK;
+const u64 starvation_ns = CAKE_DEFAULT_STARVATION_NS;
 
 /* Topology config - JIT eliminates unused P/E-core steering when has_hybrid=false */
 const bool has_hybrid = false;
 
+/* SMT contention avoidance - JIT eliminates sibling checks when has_smt=false */
+const bool has_smt = false;
+const u8 cpu_sibling[CAKE_MAX_CPUS] = {};
+
 /* Per-LLC DSQ partitioning — populated by loader from topology detection.
  * Eliminates cross-CCD lock contention: each LLC has its own DSQ.
  * Single-CCD (9800X3D): nr_llcs=1, identical to single-DSQ behavior.
  * Multi-CCD (9950X): nr_llcs=2, halves contention, eliminates cross-CCD atomics. */
 const u32 nr_llcs = 1;
 const u32 nr_cpus = 8;  /* Set by loader — bounds kick scan loop (Rule 39) */
+/* Online CPUs: what an unrestricted task's nr_cpus_allowed reaches. Below
+ * nr_cpus (highest id + 1) when CPU ids have gaps. */
+const u32 nr_online = 8;
 const u32 cpu_llc_id[CAKE_MAX_CPUS] = {};
 
+/* NUMA: node of each CPU and of each LLC. Idle scans and dispatch stealing
+ * stay on the node before crossing the interconnect. has_numa=false on
+ * single-node machines eliminates the node passes. */
+const bool has_numa = false;
+const u32 cpu_node_id[CAKE_MAX_CPUS] = {};
+const u32 llc_node_id[CAKE_MAX_LLCS] = {};
+
+/* Idle search order by prev CPU, past prev itself: TopologyInfo::preference
+ * flattened (sibling, cluster, LLC, node, big, other), 0xFFFF-terminated.
+ * has_select_order is set on multi-LLC machines, where the kernel's pick
+ * can leave prev's LLC or node while a nearer CPU is idle. */
+const bool has_select_order = false;
+const u16 cpu_select_order[CAKE_MAX_CPUS][CAKE_MAX_CPUS] = {};
+
+/* L2 clusters: CPUs sharing prev's L2 beyond its SMT core (Gracemont
+ * E-core modules of four), empty where the cluster is just the core or the
+ * whole LLC. has_clusters=false eliminates the cluster pass. */
+const bool has_clusters = false;
+const u64 cpu_cluster_mask[CAKE_MAX_CPUS][CAKE_MASK_WORDS] = {};
+
+/* Hybrid: 1 = big (P) core. Only read for migration stats when has_hybrid. */
+const u8 cpu_is_big[CAKE_MAX_CPUS] = {};
+
+/* Capacity (sysfs cpu_capacity, 1024 = largest core, floored at 256):
+ * slices stretch and charged runtime shrinks on smaller cores so a task
+ * gets and is charged for the same work wherever it runs. has_capacity
+ * is set only on asymmetric machines and gates every path. */
+const bool has_capacity = false;
+const u16 cpu_capacity[CAKE_MAX_CPUS] = {};
+
+/* Background rebalancer — periodic BPF timer evens out LLC DSQ depth that
+ * wakeup placement can't fix. 0 = disabled (timer never armed). */
+const u64 rebalance_interval_ns = CAKE_DEFAULT_REBALANCE_NS;
+const u32 rebalance_threshold = CAKE_DEFAULT_REBALANCE_THRESH;
+
+/* --rebalance-imbalance: compare queued tasks per managed CPU of each LLC
+ * and act only past this percentage, moving Bulk alone. 0 = off (raw
+ * queue difference, any tier). llc_nr_cpus is only filled when set. */
+const u32 rebalance_imbalance_pct = 0;
+const u32 llc_nr_cpus[CAKE_MAX_LLCS] = {};
+
+/* Primary domain (--primary-domain) — tiers in primary_tier_mask only run on
+ * primary_cpus (e.g. the X3D CCD) and queue on primary_llc's DSQ; other
+ * tiers spill everywhere. has_primary gates every path. */
+const bool has_primary = false;
+const u64 primary_cpus[CAKE_MASK_WORDS] = { [0 ... CAKE_MASK_WORDS - 1] = ~0ULL };
+const u8 primary_tier_mask = 0;
+const u32 primary_llc = 0;
+
+/* Secondary domain (--x3d) — the soft counterpart: tiers in
+ * secondary_tier_mask prefer secondary_cpus (the frequency CCD) and queue
+ * on secondary_llc's DSQ, but take any CPU when none there is idle. */
+const bool has_secondary = false;
+const u64 secondary_cpus[CAKE_MASK_WORDS] = { [0 ... CAKE_MASK_WORDS - 1] = ~0ULL };
+const u8 secondary_tier_mask = 0;
+const u32 secondary_llc = 0;
+
+/* Powersave (--powersave) — tiers in powersave_tier_mask run on the first
+ * pack_width CPUs of cpu_by_pack (E-cores first, core by core) so the rest
+ * reach deep idle; userspace grows pack_width with their utilization.
+ * cpu_pack_rank is each CPU's position in cpu_by_pack, pack_llc the LLC
+ * of its first CPU. has_powersave gates every path. */
+const bool has_powersave = false;
+const u8 powersave_tier_mask = 0;
+const u8 cpu_by_pack[CAKE_MAX_CPUS] = {};
+const u8 cpu_pack_rank[CAKE_MAX_CPUS] = {};
+const u32 pack_llc = 0;
+
+/* SMT avoidance (--disable-smt) — bit N set: tier N doesn't share a core
+ * with a busy SMT sibling while a whole idle core exists or, once queued,
+ * until it has waited its tier wait budget (starvation limit for Bulk). */
+const u8 smt_avoid_mask = 0;
+
+/* SMT guard (--smt-guard) — bit N set: while tier N runs on a CPU, its SMT
+ * sibling is kicked off lower-tier work and only dispatches tier N or
+ * higher, or lower-tier tasks that have waited their tier wait budget.
+ * Reads the sibling's mailbox run_tier. */
+const u8 smt_guard_mask = 0;
+
+/* Preferred cores (--preferred-cores) — bit N set: tier N trades the idle
+ * CPU the kernel picked for a higher-boosting idle one in the same LLC.
+ * cpu_by_rank lists the managed CPUs best first by CPPC highest_perf;
+ * cpu_perf_rank is each CPU's position in it. */
+const u8 prefcore_tier_mask = 0;
+const u8 cpu_perf_rank[CAKE_MAX_CPUS] = {};
+const u8 cpu_by_rank[CAKE_MAX_CPUS] = {};
+
+/* Per-tier concurrency caps — max tasks of tier N running at once (0 = unlimited).
+ * has_tier_caps gates every cap path so the default build pays nothing. */
+const bool has_tier_caps = false;
+const u32 tier_cap[CAKE_TIER_MAX] = {};
+
+/* EPP hints (--epp-hints): publish run_tier like tier caps do, for the
+ * userspace thread that sets each CPU's energy_performance_preference */
+const bool has_epp_hints = false;
+
+/* CPU restriction (--cpus) — bit N set: cake places tasks on CPU N.
+ * has_cpu_mask gates every check so the default build pays nothing. */
+const bool has_cpu_mask = false;
+const u64 managed_cpus[CAKE_MASK_WORDS] = { [0 ... CAKE_MASK_WORDS - 1] = ~0ULL };
+
+/* Dynamic slice bounds (--slice-min/--slice-max) — dispatch scales the slice
+ * ceiling by runnable tasks per CPU. has_slice_bounds gates every path. */
+const bool has_slice_bounds = false;
+const u64 slice_min_ns = 0;
+const u64 slice_max_ns = 0;
+
+/* Nice → initial tier (--nice-map), indexed by static_prio - 100 (nice + 20).
+ * Defaults: nice < 0 → Critical, 0..10 → Interactive, > 10 → Bulk. */
+const u8 nice_tier[CAKE_NICE_LEVELS] = {
+    [0 ... 19]  = CAKE_TIER_CRITICAL,
+    [20 ... 30] = CAKE_TIER_INTERACT,
+    [31 ... 39] = CAKE_TIER_BULK,
+};
+
+/* Process-name tier rules (--tier-rule) — entries 0..nr_tier_rules-1 of
+ * the tier_rules map, filled by the loader. 0 = no rules, exec hook not loaded. */
+const u32 nr_tier_rules = 0;
+
+/* Game-launch warm-up — window length after burst detection (0 = disabled)
+ * and the thread-spawn count that marks a burst. */
+const u64 warmup_ns = CAKE_DEFAULT_WARMUP_NS;
+const u32 warmup_threads = CAKE_DEFAULT_WARMUP_THREADS;
+
 /* ═══════════════════════════════════════════════════════════════════════════
  * MEGA-MAILBOX: 64-byte per-CPU state (single cache line = optimal L1)
  * - Zero false sharing: each CPU writes ONLY to mega_mailbox[its_cpu]
@@ -47,9 +191,43 @@ struct cake_scratch {
 _Static_assert(sizeof(struct cake_scratch) <= 128,
     "cake_scratch exceeds 128B -- adjacent CPUs will false-share");
 
-/* Global stats BSS array - 0ns lookup vs 25ns helper, 256-byte aligned per CPU */
+/* Global stats BSS array - 0ns lookup vs 25ns helper, cache-line aligned per CPU */
 struct cake_stats global_stats[CAKE_MAX_CPUS] SEC(".bss") __attribute__((aligned(256)));
 
+/* Queue-depth gauges - written by depth_timerfn (while stats are on) */
+struct cake_dsq_depth dsq_depth SEC(".bss");
+
+/* User-idle relaxation - written by the userspace idle monitor (0 = user active).
+ * While set: 4x slices, no SMT spread / LLC rebalance (let work compact onto
+ * fewer cores), Bulk promoted to Frame. Cleared on the first input event. */
+volatile u32 user_idle SEC(".bss");
+
+/* Packed CPUs (--powersave) - written by the userspace pack controller,
+ * always at least one. Only read when has_powersave. */
+volatile u32 pack_width SEC(".bss");
+
+/* Live tunables - written by the control socket (`scx_cake set`).
+ * 0 = keep the rodata value the scheduler was loaded with. */
+volatile u64 live_quantum_ns SEC(".bss");
+volatile u64 live_new_flow_bonus_ns SEC(".bss");
+volatile u64 live_starvation_ns SEC(".bss");
+
+/* Entries in tier_pins - written by the TUI with the map. 0 = no manual
+ * pins, the stop path skips the lookup. */
+volatile u32 nr_tier_pins SEC(".bss");
+
+/* Events lost to a full ring buffer (--events consumer fell behind) */
+volatile u64 nr_events_dropped SEC(".bss");
+
+/* Tasks currently in the ext class (enable minus disable) - TUI status line */
+volatile u64 nr_tasks SEC(".bss");
+
+/* Stats generation - bumped the first time a stop sees enable_stats after
+ * depth_timerfn saw it off (stats_live cleared). A task whose run_delay baseline is from an
+ * older generation re-takes it instead of filing the off period as a wait. */
+volatile u32 stats_gen SEC(".bss");
+volatile u32 stats_live SEC(".bss");
+
 /* BSS tail guard - absorbs BTF truncation bugs instead of corrupting real data */
 u8 __bss_tail_guard[64] SEC(".bss") __attribute__((aligned(64)));
 
@@ -63,6 +241,115 @@ static __always_inline struct cake_stats *get_local_stats(void)
     return &global_stats[cpu & (CAKE_MAX_CPUS - 1)];
 }
 
+/* Is @cpu in @mask, a CAKE_MASK_WORDS-word CPU bitmask? */
+static __always_inline bool mask_test(const u64 *mask, u32 cpu)
+{
+    return (mask[(cpu >> 6) & (CAKE_MASK_WORDS - 1)] >> (cpu & 63)) & 1;
+}
+
+/* --cpus: may cake place tasks on @cpu? CPUs past CAKE_MAX_CPUS never are */
+static __always_inline bool cpu_managed(s32 cpu)
+{
+    return !has_cpu_mask ||
+           ((u32)cpu < CAKE_MAX_CPUS && mask_test(managed_cpus, cpu));
+}
+
+/* --primary-domain: can @p run on any primary CPU? Noinline: only reached
+ * for affinity-restricted tasks of a primary tier. */
+static __attribute__((noinline))
+bool primary_allowed_cold(struct task_struct *p)
+{
+    for (u32 i = 0; i < CAKE_MAX_CPUS; i++) {
+        if (i >= nr_cpus)
+            break;
+        if (mask_test(primary_cpus, i) && bpf_cpumask_test_cpu(i, p->cpus_ptr))
+            return true;
+    }
+    return false;
+}
+
+/* --primary-domain: may @p, at @tier, run on @cpu? Tasks whose affinity
+ * excludes every primary CPU are never restricted. */
+static __always_inline bool primary_ok(struct task_struct *p, u8 tier, s32 cpu)
+{
+    return !(primary_tier_mask & (1 << (tier & 3))) ||
+           ((u32)cpu < CAKE_MAX_CPUS && mask_test(primary_cpus, cpu)) ||
+           !primary_allowed_cold(p);
+}
+
+/* --powersave: may @p, at @tier, run on @cpu? Affinity-restricted tasks
+ * are never packed — they may not fit the packed CPUs. */
+static __always_inline bool pack_ok(struct task_struct *p, u8 tier, s32 cpu)
+{
+    return !(powersave_tier_mask & (1 << (tier & 3))) ||
+           cpu_pack_rank[cpu & (CAKE_MAX_CPUS - 1)] < pack_width ||
+           p->nr_cpus_allowed < nr_online;
+}
+
+/* --smt-guard: lowest-priority tier @cpu may run beside its sibling — the
+ * guarded tier the sibling runs, CAKE_TIER_MAX when nothing is guarded */
+static __always_inline u8 sibling_guard(u32 cpu)
+{
+    u32 sib = cpu_sibling[cpu & (CAKE_MAX_CPUS - 1)] & (CAKE_MAX_CPUS - 1);
+    u8 rt = mega_mailbox[sib].run_tier;
+
+    if (sib == (cpu & (CAKE_MAX_CPUS - 1)) || !rt ||
+        !(smt_guard_mask & (1 << ((rt - 1) & 3))))
+        return CAKE_TIER_MAX;
+    return (rt - 1) & 3;
+}
+
+/* Effective tunables: live override if set, else rodata */
+static __always_inline u64 cake_quantum_ns(void)
+{
+    u64 v = live_quantum_ns;
+    return v ? v : quantum_ns;
+}
+
+static __always_inline u64 cake_new_flow_bonus_ns(void)
+{
+    u64 v = live_new_flow_bonus_ns;
+    return v ? v : new_flow_bonus_ns;
+}
+
+static __always_inline u64 cake_starvation_ns(void)
+{
+    u64 v = live_starvation_ns;
+    return v ? v : starvation_ns;
+}
+
+/* Nobody is waiting on latency while the user is idle — trade it for fewer switches */
+static __always_inline u64 relax_slice(u64 slice)
+{
+    return user_idle ? slice << CAKE_IDLE_SLICE_SHIFT : slice;
+}
+
+/* Slice bounds: tier slice capped by this CPU's load-scaled ceiling, then
+ * clamped to [slice_min_ns, slice_max_ns]. Identity when bounds are off. */
+static __always_inline u64 bound_slice(u64 slice, u32 cpu)
+{
+    if (!has_slice_bounds)
+        return slice;
+
+    u64 ceil = mega_mailbox[cpu & (CAKE_MAX_CPUS - 1)].dyn_slice_ns;
+    if (ceil && slice > ceil)
+        slice = ceil;
+    if (slice < slice_min_ns)
+        return slice_min_ns;
+    return slice > slice_max_ns ? slice_max_ns : slice;
+}
+
+/* Capacity: a slice on @cpu is as long as @slice of work on the largest
+ * core takes there. Identity on symmetric machines. */
+static __always_inline u64 cap_slice(u64 slice, u32 cpu)
+{
+    if (!has_capacity)
+        return slice;
+
+    u32 cap = cpu_capacity[cpu & (CAKE_MAX_CPUS - 1)];
+    return cap ? (slice << 10) / cap : slice;
+}
+
 /* ETD surgical seek / find_surgical_victim_logical removed — select_cpu
  * now delegates idle selection to scx_bpf_select_cpu_dfl() which does
  * prev → sibling → LLC cascade internally with kernel-native topology. */
@@ -74,6 +361,30 @@ static __always_inline struct cake_stats *get_local_stats(void)
 /* User exit info for graceful scheduler exit */
 UEI_DEFINE(uei);
 
+#ifndef CLOCK_MONOTONIC
+#define CLOCK_MONOTONIC 1
+#endif
+
+/* Rebalancer timer — single-entry array, armed once in cake_init */
+struct rebalance_timer {
+    struct bpf_timer timer;
+};
+
+struct {
+    __uint(type, BPF_MAP_TYPE_ARRAY);
+    __uint(max_entries, 1);
+    __type(key, u32);
+    __type(value, struct rebalance_timer);
+} rebalance_timer SEC(".maps");
+
+/* Queue-depth sampler timer — same shape, armed only when stats are on */
+struct {
+    __uint(type, BPF_MAP_TYPE_ARRAY);
+    __uint(max_entries, 1);
+    __type(key, u32);
+    __type(value, struct rebalance_timer);
+} depth_timer SEC(".maps");
+
 /* Global vtime removed to prevent bus locking. Tasks inherit vtime from parent. */
 
 /* Optimization: Precomputed threshold to avoid division in hot path */
@@ -133,6 +444,69 @@ struct {
     __type(value, struct cake_task_ctx);
 } task_ctx SEC(".maps");
 
+/* Launch burst tracking — LRU so exited processes age out without a hook */
+struct {
+    __uint(type, BPF_MAP_TYPE_LRU_HASH);
+    __uint(max_entries, 1024);
+    __type(key, u32);
+    __type(value, struct cake_launch);
+} launch_state SEC(".maps");
+
+/* Process-name tier rules — written once by the loader before attach */
+struct {
+    __uint(type, BPF_MAP_TYPE_ARRAY);
+    __uint(max_entries, CAKE_MAX_TIER_RULES);
+    __type(key, u32);
+    __type(value, struct cake_tier_rule);
+} tier_rules SEC(".maps");
+
+/* Manual tier pins by TID — written by the TUI task view at runtime */
+struct {
+    __uint(type, BPF_MAP_TYPE_HASH);
+    __uint(max_entries, CAKE_MAX_TIER_PINS);
+    __type(key, u32);
+    __type(value, u8);
+} tier_pins SEC(".maps");
+
+/* Scheduling event stream (--events, --trace) — only written when has_events */
+struct {
+    __uint(type, BPF_MAP_TYPE_RINGBUF);
+    __uint(max_entries, CAKE_EVENTS_RINGBUF_SZ);
+} events SEC(".maps");
+
+/* --events / --trace: push one record for @p. A full buffer drops the
+ * event and counts it — the hot path never waits on userspace. Boot-clock
+ * timestamps line up with a Perfetto/trace-cmd kernel trace of the same
+ * run. Noinline: event builds only (Rule 5). */
+static __attribute__((noinline))
+void emit_event_cold(struct task_struct *p, u8 kind, u8 from, u8 to)
+{
+    struct cake_event *e = bpf_ringbuf_reserve(&events, sizeof(*e), 0);
+
+    if (!e) {
+        __sync_fetch_and_add(&nr_events_dropped, 1);
+        return;
+    }
+    e->ts_ns = bpf_ktime_get_boot_ns();
+    e->pid = p->pid;
+    e->tgid = p->tgid;
+    e->cpu = bpf_get_smp_processor_id();
+    e->kind = kind;
+    e->from = from;
+    e->to = to;
+    e->_pad = 0;
+    bpf_probe_read_kernel_str(e->comm, sizeof(e->comm), p->comm);
+    bpf_ringbuf_submit(e, 0);
+}
+
+/* Wait histograms, one per tier (stats on) — per-CPU, summed by userspace */
+struct {
+    __uint(type, BPF_MAP_TYPE_PERCPU_ARRAY);
+    __uint(max_entries, CAKE_TIER_MAX);
+    __type(key, u32);
+    __type(value, struct cake_wait_hist);
+} wait_hist SEC(".maps");
+
 /* RESTORE peek_legacy via scratch tunnel */
 __attribute__((noinline))
 struct task_struct *cake_bpf_dsq_peek_legacy(u64 dsq_id)
@@ -180,8 +554,9 @@ struct cake_task_ctx *alloc_task_ctx_cold(struct task_struct *p)
                                BPF_LOCAL_STORAGE_GET_F_CREATE);
     if (!ctx) return NULL;
 
-    ctx->next_slice = quantum_ns;
-    u16 init_deficit = (u16)((quantum_ns + new_flow_bonus_ns) >> 10);
+    u64 quantum = cake_quantum_ns();
+    ctx->next_slice = quantum;
+    u16 init_deficit = (u16)((quantum + cake_new_flow_bonus_ns()) >> 10);
     ctx->deficit_avg_fused = PACK_DEFICIT_AVG(init_deficit, 0);
     ctx->last_run_at = 0;
     ctx->reclass_counter = 0;
@@ -191,7 +566,8 @@ struct cake_task_ctx *alloc_task_ctx_cold(struct task_struct *p)
      * Two cheap signals set the starting point; avg_runtime classification
      * takes over after the first few execution bouts and is authoritative.
      *
-     * Signal 1: Nice value (u32 field read, ~2 cycles)
+     * Signal 1: Nice value (u32 field read + rodata lookup, ~3 cycles)
+     *   nice_tier[] table, --nice-map overrides. Defaults:
      *   - nice < 0 (prio < 120): OS/user explicitly prioritized
      *     System services (-20), pipewire (-11), games with nice (-5)
      *     → T0 initially, avg_runtime reclassifies after first runs
@@ -210,22 +586,12 @@ struct cake_task_ctx *alloc_task_ctx_cold(struct task_struct *p)
      *   Pure avg_runtime → tier mapping in reclassify_task_cold(). */
 
     /* Nice value: static_prio 100 = nice -20, 120 = nice 0, 139 = nice 19 */
-    u32 prio = p->static_prio;
-    u8 init_tier;
-
-    if (prio < 120) {
-        /* Negative nice: OS or user explicitly prioritized.
-         * avg_runtime=0 at init → T0 until first reclassify. */
-        init_tier = CAKE_TIER_CRITICAL;
-    } else if (prio > 130) {
-        /* High nice (>10): explicitly deprioritized.
-         * Background builds, indexers, low-priority daemons. */
-        init_tier = CAKE_TIER_BULK;
-    } else {
-        /* Default (nice 0-10): start at Interactive.
-         * avg_runtime reclassifies to correct tier within ~3 stops. */
-        init_tier = CAKE_TIER_INTERACT;
-    }
+    u32 nice_idx = p->static_prio - 100;
+    u8 init_tier = CAKE_TIER_INTERACT;
+
+    /* RT/DL tasks carry static_prio 120 too; out-of-range is defensive */
+    if (nice_idx < CAKE_NICE_LEVELS)
+        init_tier = nice_tier[nice_idx];
 
     u32 packed = 0;
     packed |= (255 & MASK_KALMAN_ERROR) << SHIFT_KALMAN_ERROR;
@@ -235,6 +601,9 @@ struct cake_task_ctx *alloc_task_ctx_cold(struct task_struct *p)
 
     ctx->packed_info = packed;
 
+    if (has_events)
+        emit_event_cold(p, CAKE_EV_NEW_FLOW, init_tier, init_tier);
+
     return ctx;
 }
 
@@ -256,12 +625,231 @@ static __always_inline struct cake_task_ctx *get_task_ctx(struct task_struct *p,
     return alloc_task_ctx_cold(p);
 }
 
+/* ═══════════════════════════════════════════════════════════════════════════
+ * GAME-LAUNCH WARM-UP: launch is where avg_runtime classification does its
+ * worst — shader compilers and asset loaders have no history yet and land
+ * next to the main thread at T1. A new process that spawns warmup_threads
+ * threads within CAKE_WARMUP_DETECT_NS is treated as a launching game:
+ *   - main thread (group leader): capped at T1 Interactive (boosted)
+ *   - threads spawned in the detection window, including the ones that
+ *     made up the burst: forced to T3 Bulk (loaders)
+ * for warmup_ns, then normal classification takes over.
+ * Runs at task init only (fork path) — zero cost on the scheduling hot path.
+ * ═══════════════════════════════════════════════════════════════════════════ */
+static __always_inline void mark_loader(struct cake_task_ctx *ctx, u32 end)
+{
+    ctx->warmup_end = end;
+    ctx->packed_info |= (u32)CAKE_FLOW_WARMUP_LOAD << SHIFT_FLAGS;
+}
+
+/* Burst just detected: the threads counted toward it were spawned before
+ * anyone knew, so mark them now by TID. One that already exited is skipped. */
+static __attribute__((noinline))
+void mark_burst_cold(struct cake_launch *ls, u32 n, u32 end)
+{
+    for (u32 i = 0; i < CAKE_WARMUP_MAX_BURST; i++) {
+        if (i >= n)
+            break;
+        u32 tid = ls->tids[i];
+        if (!tid)
+            continue;
+        struct task_struct *t = bpf_task_from_pid(tid);
+        if (!t)
+            continue;
+        struct cake_task_ctx *tctx = bpf_task_storage_get(&task_ctx, t, 0, 0);
+        if (tctx)
+            mark_loader(tctx, end);
+        bpf_task_release(t);
+    }
+}
+
+static __attribute__((noinline))
+void track_launch_cold(struct task_struct *p, struct cake_task_ctx *ctx)
+{
+    u32 tgid = p->tgid;
+    u64 now = scx_bpf_now();
+
+    if (p->pid == tgid) {
+        /* New process: open a detection window */
+        struct cake_launch fresh = { .start_ns = now };
+        bpf_map_update_elem(&launch_state, &tgid, &fresh, BPF_ANY);
+        return;
+    }
+
+    struct cake_launch *ls = bpf_map_lookup_elem(&launch_state, &tgid);
+    if (!ls)
+        return;
+
+    if (now - ls->start_ns > CAKE_WARMUP_DETECT_NS) {
+        /* Detection window closed: a slow spawner was never a launch burst,
+         * and a launch's later threads are its steady state, not loaders */
+        if (!ls->active)
+            bpf_map_delete_elem(&launch_state, &tgid);
+        return;
+    }
+
+    if (ls->active) {
+        /* Loader thread: Bulk until the warm-up window closes */
+        if (now - ls->burst_ns < warmup_ns)
+            mark_loader(ctx, (u32)((ls->burst_ns + warmup_ns) >> 20));
+        return;
+    }
+
+    u32 n = __sync_fetch_and_add(&ls->nr_threads, 1) + 1;
+    if (n > warmup_threads)
+        return;
+    ls->tids[(n - 1) & (CAKE_WARMUP_MAX_BURST - 1)] = p->pid;
+    if (n < warmup_threads)
+        return;
+
+    /* Burst detected — warm-up window starts now */
+    u32 end = (u32)((now + warmup_ns) >> 20);
+    ls->burst_ns = now;
+    ls->active = 1;
+
+    struct cake_task_ctx *lctx =
+        bpf_task_storage_get(&task_ctx, p->group_leader, 0, 0);
+    if (lctx) {
+        lctx->warmup_end = end;
+        u32 lpacked = cake_relaxed_load_u32(&lctx->packed_info);
+        cake_relaxed_store_u32(&lctx->packed_info,
+            lpacked | ((u32)CAKE_FLOW_WARMUP_MAIN << SHIFT_FLAGS));
+    }
+
+    /* This thread is the last of the burst; the others are marked by TID */
+    mark_loader(ctx, end);
+    mark_burst_cold(ls, n - 1, end);
+
+    if (enable_stats) {
+        struct cake_stats *s = get_local_stats();
+        s->nr_warmup_launches++;
+    }
+}
+
+/* ═══════════════════════════════════════════════════════════════════════════
+ * TIER RULES: pin known daemons / compilers to a tier by comm. Checked at
+ * task init and again at exec (comm changes there: gcc → cc1). A match sets
+ * the tier and CAKE_FLOW_PINNED, which reclassify_task_cold and the warm-up
+ * override honor; a task exec'ing into an unmatched name is unpinned and
+ * avg_runtime classification takes over again.
+ * ═══════════════════════════════════════════════════════════════════════════ */
+static __always_inline bool tier_rule_matches(const char *comm, struct cake_tier_rule *r)
+{
+    for (u32 j = 0; j < sizeof(r->comm); j++) {
+        if (j >= r->len)
+            return r->prefix || comm[j] == '\0';
+        if (comm[j] != r->comm[j])
+            return false;
+    }
+    return true;
+}
+
+static __attribute__((noinline))
+void apply_tier_rule_cold(struct task_struct *p, struct cake_task_ctx *ctx)
+{
+    char comm[16] = {};
+    u32 pin = (u32)CAKE_FLOW_PINNED << SHIFT_FLAGS;
+    u32 packed = cake_relaxed_load_u32(&ctx->packed_info);
+
+    bpf_probe_read_kernel_str(comm, sizeof(comm), p->comm);
+
+    for (u32 i = 0; i < CAKE_MAX_TIER_RULES; i++) {
+        if (i >= nr_tier_rules)
+            break;
+        u32 key = i;
+        struct cake_tier_rule *r = bpf_map_lookup_elem(&tier_rules, &key);
+        if (!r)
+            break;
+        if (!tier_rule_matches(comm, r))
+            continue;
+
+        u8 tier = r->tier & MASK_TIER;
+        packed &= ~((u32)MASK_TIER << SHIFT_TIER);
+        packed |= ((u32)tier << SHIFT_TIER) | pin;
+        cake_relaxed_store_u32(&ctx->packed_info, packed);
+        ctx->next_slice = (cake_quantum_ns() * UNPACK_MULTIPLIER(tier_configs[tier])) >> 10;
+        return;
+    }
+
+    if (packed & pin)
+        cake_relaxed_store_u32(&ctx->packed_info, packed & ~pin);
+}
+
+/* Manual pin from the TUI task view: set the pinned tier before
+ * reclassify_task_cold runs and tell it to hold the tier, so a pinned task
+ * never flips between its avg_runtime tier and the pin. Returns whether @p
+ * is pinned. No CAKE_FLOW_PINNED, so deleting the entry hands the task back
+ * to avg_runtime classification at its next stop. */
+static __attribute__((noinline))
+bool apply_tier_pin_cold(struct task_struct *p, struct cake_task_ctx *ctx)
+{
+    u32 pid = p->pid;
+    u8 *pinned = bpf_map_lookup_elem(&tier_pins, &pid);
+    if (!pinned)
+        return false;
+
+    u8 tier = *pinned & MASK_TIER;
+    u32 packed = cake_relaxed_load_u32(&ctx->packed_info);
+    if (((packed >> SHIFT_TIER) & MASK_TIER) == tier)
+        return true;
+    packed &= ~((u32)MASK_TIER << SHIFT_TIER);
+    packed |= (u32)tier << SHIFT_TIER;
+    cake_relaxed_store_u32(&ctx->packed_info, packed);
+    ctx->next_slice = (cake_quantum_ns() * UNPACK_MULTIPLIER(tier_configs[tier])) >> 10;
+    return true;
+}
+
+/* Warm-up tier override at enqueue — only reached when a warm-up flag is set.
+ * Expired windows clear both flags so the task never pays for this again. */
+static __attribute__((noinline))
+u8 warmup_tier_cold(struct cake_task_ctx *tctx, u32 packed, u8 tier, u64 now)
+{
+    if ((s32)(tctx->warmup_end - (u32)(now >> 20)) <= 0) {
+        u32 clear = (u32)(CAKE_FLOW_WARMUP_MAIN | CAKE_FLOW_WARMUP_LOAD) << SHIFT_FLAGS;
+        cake_relaxed_store_u32(&tctx->packed_info, packed & ~clear);
+        return tier;
+    }
+
+    /* A --tier-rule pin outranks launch heuristics */
+    if (packed & ((u32)CAKE_FLOW_PINNED << SHIFT_FLAGS))
+        return tier;
+
+    if (enable_stats) {
+        struct cake_stats *s = get_local_stats();
+        s->nr_warmup_dispatches++;
+    }
+
+    if (packed & ((u32)CAKE_FLOW_WARMUP_MAIN << SHIFT_FLAGS))
+        return tier > CAKE_TIER_INTERACT ? CAKE_TIER_INTERACT : tier;
+    return CAKE_TIER_BULK;
+}
+
 /* Noinline accounting - math-heavy ops moved here to free registers (now fully async in tick) */
 
 /* T0 victim cold path removed — when all CPUs are busy, tasks go through
  * enqueue → per-LLC DSQ where vtime ordering ensures T0 tasks get pulled
  * first. Preemption handled by cake_tick starvation checks. */
 
+/* TIER CAPS: count CPUs other than @self running a @tier task. Reads the
+ * per-CPU mailbox run_tier (written in running/stopping) — racy by design,
+ * a cap may briefly overshoot by the number of CPUs dispatching at once. */
+static __attribute__((noinline))
+bool tier_at_cap_cold(u32 self, u8 tier)
+{
+    u32 cap = tier_cap[tier & 3];
+    u32 n = 0;
+
+    if (!cap)
+        return false;
+    for (u32 i = 0; i < CAKE_MAX_CPUS; i++) {
+        if (i >= nr_cpus)
+            break;
+        if (i != self && mega_mailbox[i].run_tier == (tier & 3) + 1)
+            n++;
+    }
+    return n >= cap;
+}
+
 /* ═══════════════════════════════════════════════════════════════════════════
  * KERNEL-FIRST FLAT SELECT_CPU: ~20 instructions vs ~200+ in the old cascade.
  *
@@ -289,20 +877,552 @@ static __always_inline struct cake_task_ctx *get_task_ctx(struct task_struct *p,
  * waker's CPU may not be in woken task's cpumask. Returns -1 to signal
  * fallthrough to kernel path which handles cpumask correctly. */
 static __attribute__((noinline))
-s32 dispatch_sync_cold(struct task_struct *p, u64 wake_flags)
+s32 dispatch_sync_cold(struct task_struct *p, u64 wake_flags)
+{
+    s32 raw = bpf_get_smp_processor_id();
+    u32 cpu = raw & (CAKE_MAX_CPUS - 1);
+    if (!bpf_cpumask_test_cpu(cpu, p->cpus_ptr) || !cpu_managed(raw))
+        return -1;
+
+    /* Use tier-adjusted slice, not raw quantum. Without this, the kernel's
+     * slice countdown preempts at 2ms before cake_tick can check the
+     * tier-adjusted threshold — making multipliers dead code for SYNC. */
+    struct cake_task_ctx *tctx = bpf_task_storage_get(&task_ctx, p, 0, 0);
+    u64 slice = relax_slice(tctx ? tctx->next_slice : cake_quantum_ns());
+
+    /* Tier at its concurrency cap: queue it, dispatch decides */
+    if (has_tier_caps && tctx && tier_at_cap_cold(cpu, GET_TIER(tctx)))
+        return -1;
+
+    /* Primary tier woken from outside the primary domain: queue it there */
+    if (has_primary && tctx && !primary_ok(p, GET_TIER(tctx), cpu))
+        return -1;
+
+    /* Waker's sibling runs a guarded higher tier: queue it, dispatch decides */
+    if (smt_guard_mask && tctx && (GET_TIER(tctx) & 3) > sibling_guard(cpu))
+        return -1;
+
+    /* Packed tier woken from an unpacked CPU: queue it for the packed set */
+    if (has_powersave && tctx && !pack_ok(p, GET_TIER(tctx), cpu))
+        return -1;
+
+    scx_bpf_dsq_insert(p, SCX_DSQ_LOCAL_ON | cpu, slice, wake_flags);
+    return (s32)cpu;
+}
+
+/* --disable-smt: is @cpu's SMT sibling running something? */
+static __always_inline bool sibling_busy(const struct cpumask *idle, u32 cpu)
+{
+    u32 sib = cpu_sibling[cpu & (CAKE_MAX_CPUS - 1)] & (CAKE_MAX_CPUS - 1);
+    return sib != cpu && !bpf_cpumask_test_cpu(sib, idle);
+}
+
+/* --disable-smt: a queued task of an avoiding tier has waited long enough
+ * to take a half-busy core. vtime low 56 bits carry the enqueue time (the
+ * new-flow credit counts as waiting). */
+static __always_inline bool smt_wait_expired(struct task_struct *p, u8 tier, u64 now)
+{
+    u64 cfg = tier_configs[tier & 7];
+    u64 bound = UNPACK_BUDGET_NS(cfg) ?: UNPACK_STARVATION_NS(cfg);
+    return ((now - p->scx.dsq_vtime) & 0x00FFFFFFFFFFFFFFULL) >= bound;
+}
+
+/* TIER CAPS / SMT AVOIDANCE / SMT GUARD / PRIMARY DOMAIN / POWERSAVE: move
+ * the first task in @dsq_id whose tier is under its cap, not avoiding SMT
+ * when this CPU's sibling is busy, not below the tier --smt-guard protects
+ * on the sibling, and allowed on this CPU by --primary-domain and
+ * --powersave.
+ * Capped tasks stay queued in vtime order; the CPU that ends a capped run
+ * picks them up (see cap_release_cold). SMT-avoiding tasks go to the next
+ * CPU dispatching on a whole idle core, or anywhere once their wait
+ * budget runs out; guarded-against tasks the same, flagging this CPU for
+ * a kick when the guarded sibling stops. */
+static __attribute__((noinline))
+bool move_uncapped_cold(u32 self, u64 dsq_id)
+{
+    u32 running[CAKE_TIER_MAX] = {};
+    u32 skipped[CAKE_TIER_MAX] = {};
+    struct task_struct *p;
+    u32 scanned = 0;
+    bool moved = false;
+    u8 avoid = 0;
+
+    if (smt_avoid_mask) {
+        const struct cpumask *idle = scx_bpf_get_idle_cpumask();
+        if (sibling_busy(idle, self))
+            avoid = smt_avoid_mask;
+        scx_bpf_put_idle_cpumask(idle);
+    }
+    bool outside = has_primary && !mask_test(primary_cpus, self);
+    bool unpacked = has_powersave && cpu_pack_rank[self & (CAKE_MAX_CPUS - 1)] >= pack_width;
+    u8 guard = smt_guard_mask ? sibling_guard(self) : CAKE_TIER_MAX;
+    bool deferred = false;
+    if (!has_tier_caps && !avoid && !outside && !unpacked && guard >= CAKE_TIER_MAX)
+        return scx_bpf_dsq_move_to_local(dsq_id);
+
+    u64 now = scx_bpf_now();
+
+    for (u32 i = 0; has_tier_caps && i < CAKE_MAX_CPUS; i++) {
+        if (i >= nr_cpus)
+            break;
+        u8 rt = mega_mailbox[i].run_tier;
+        if (rt && i != self)
+            running[(rt - 1) & 3]++;
+    }
+
+    bpf_for_each(scx_dsq, p, dsq_id, 0) {
+        if (++scanned > CAKE_CAP_SCAN_MAX)
+            break;
+        /* No context: kthread inserted at Critical by enqueue */
+        struct cake_task_ctx *tctx = bpf_task_storage_get(&task_ctx, p, 0, 0);
+        u8 tier = tctx ? GET_TIER(tctx) & 3 : CAKE_TIER_CRITICAL;
+        u32 cap = tier_cap[tier];
+
+        if ((avoid & (1 << tier)) && !smt_wait_expired(p, tier, now))
+            continue;
+        if (tier > guard && !smt_wait_expired(p, tier, now)) {
+            deferred = true;
+            continue;
+        }
+        if (outside && tctx && !primary_ok(p, tier, self))
+            continue;
+        if (unpacked && tctx && !pack_ok(p, tier, self))
+            continue;
+
+        if (cap && running[tier] >= cap) {
+            skipped[tier]++;
+            continue;
+        }
+        if (scx_bpf_dsq_move(BPF_FOR_EACH_ITER, p, SCX_DSQ_LOCAL, 0)) {
+            moved = true;
+            break;
+        }
+    }
+    if (deferred && !moved)
+        mega_mailbox[self & (CAKE_MAX_CPUS - 1)].smt_deferred = 1;
+
+    if (enable_stats) {
+        struct cake_stats *s = get_local_stats();
+        for (u32 t = 0; t < CAKE_TIER_MAX; t++) {
+            if (skipped[t]) {
+                s->nr_cap_deferrals[t]++;
+                s->nr_cap_queued[t] += skipped[t];
+            }
+        }
+    }
+    return moved;
+}
+
+/* TIER CAPS: a capped-tier task gave up its CPU. If that CPU picks other
+ * work, queued tasks of the tier would wait for the next capped stop —
+ * wake an idle CPU so it dispatches against the freed slot. */
+static __attribute__((noinline))
+void cap_release_cold(struct task_struct *p)
+{
+    s32 cpu = scx_bpf_pick_idle_cpu(p->cpus_ptr, 0);
+    if (cpu >= 0)
+        scx_bpf_kick_cpu(cpu, SCX_KICK_IDLE);
+}
+
+/* --cpus: the kernel claimed idle @cpu outside the managed set. Release it
+ * and claim an idle managed CPU the task may use, preferring @cpu's LLC,
+ * then its NUMA node. Returns -1 when none is idle — the task then queues
+ * in its LLC DSQ. */
+static __attribute__((noinline))
+s32 managed_idle_cold(struct task_struct *p, s32 cpu)
+{
+    const struct cpumask *idle = scx_bpf_get_idle_cpumask();
+    u32 llc = cpu_llc_id[cpu & (CAKE_MAX_CPUS - 1)];
+    u32 node = cpu_node_id[cpu & (CAKE_MAX_CPUS - 1)];
+    s32 best = -1;
+
+    for (u32 pass = 0; pass < 3 && best < 0; pass++) {
+        if (pass == 1 && !has_numa)
+            continue;
+        for (u32 i = 0; i < CAKE_MAX_CPUS; i++) {
+            if (i >= nr_cpus)
+                break;
+            if (!mask_test(managed_cpus, i) ||
+                (pass == 0 && cpu_llc_id[i] != llc) ||
+                (pass == 1 && cpu_node_id[i] != node) ||
+                !bpf_cpumask_test_cpu(i, idle) ||
+                !bpf_cpumask_test_cpu(i, p->cpus_ptr))
+                continue;
+            if (scx_bpf_test_and_clear_cpu_idle(i)) {
+                best = i;
+                break;
+            }
+        }
+    }
+    scx_bpf_put_idle_cpumask(idle);
+
+    scx_bpf_kick_cpu(cpu, SCX_KICK_IDLE);
+    return best;
+}
+
+/* --cpus: true when @p may run on at least one managed CPU. Tasks that
+ * can't (pinned kthreads, narrow affinity) go to the global DSQ, which
+ * the kernel drains on every CPU before calling dispatch. */
+static __attribute__((noinline))
+bool managed_allowed_cold(struct task_struct *p)
+{
+    if (p->nr_cpus_allowed >= nr_online)
+        return true;
+    for (u32 i = 0; i < CAKE_MAX_CPUS; i++) {
+        if (i >= nr_cpus)
+            break;
+        if (mask_test(managed_cpus, i) && bpf_cpumask_test_cpu(i, p->cpus_ptr))
+            return true;
+    }
+    return false;
+}
+
+/* Move one task from @dsq_id to the local DSQ, honouring tier caps,
+ * --disable-smt, --smt-guard, --primary-domain and --powersave */
+static __always_inline bool cake_move_to_local(u32 self, u64 dsq_id)
+{
+    if (has_tier_caps || smt_avoid_mask || smt_guard_mask || has_primary || has_powersave)
+        return move_uncapped_cold(self, dsq_id);
+    return scx_bpf_dsq_move_to_local(dsq_id);
+}
+
+/* NUMA STEAL: other LLCs on this CPU's node first, remote nodes only when
+ * the whole node is drained — a remote task drags its working set across
+ * the interconnect. Noinline: only built on multi-node machines (Rule 5). */
+static __attribute__((noinline))
+bool steal_numa_cold(u32 self, u32 my_llc)
+{
+    u32 node = llc_node_id[my_llc & (CAKE_MAX_LLCS - 1)];
+
+    for (u32 pass = 0; pass < 2; pass++) {
+        for (u32 i = 1; i < CAKE_MAX_LLCS; i++) {
+            if (i >= nr_llcs)
+                break;
+            u32 victim = my_llc + i;
+            if (victim >= nr_llcs)
+                victim -= nr_llcs;
+            bool same = llc_node_id[victim & (CAKE_MAX_LLCS - 1)] == node;
+            if (same != (pass == 0))
+                continue;
+            if (cake_move_to_local(self, LLC_DSQ_BASE + victim))
+                return true;
+        }
+    }
+    return false;
+}
+
+/* SMT SPREAD: heavy wakeup landed on a CPU whose sibling runs a heavy tier.
+ * Scan the LLC for an idle CPU whose sibling is idle or running a light
+ * tier, claim it and release the original claim with an idle kick.
+ * Returns the CPU to dispatch on (original if nothing better exists).
+ * Noinline: only reached when SMT is on and two heavies would co-locate. */
+static __attribute__((noinline))
+s32 smt_spread_cold(struct task_struct *p, s32 cpu, u32 sib)
+{
+    const struct cpumask *idle = scx_bpf_get_idle_cpumask();
+    u32 llc = cpu_llc_id[cpu & (CAKE_MAX_CPUS - 1)];
+    s32 best = -1;
+
+    /* Sibling went idle since the mailbox was written — nothing to avoid */
+    if (bpf_cpumask_test_cpu(sib, idle)) {
+        scx_bpf_put_idle_cpumask(idle);
+        return cpu;
+    }
+
+    for (u32 i = 0; i < CAKE_MAX_CPUS; i++) {
+        if (i >= nr_cpus)
+            break;
+        if (cpu_llc_id[i] != llc || !bpf_cpumask_test_cpu(i, idle) ||
+            !bpf_cpumask_test_cpu(i, p->cpus_ptr))
+            continue;
+
+        u32 s = cpu_sibling[i] & (CAKE_MAX_CPUS - 1);
+        if (s != i && !bpf_cpumask_test_cpu(s, idle) &&
+            MBOX_IS_HEAVY(mega_mailbox[s].flags))
+            continue;
+
+        if (scx_bpf_test_and_clear_cpu_idle(i)) {
+            best = i;
+            break;
+        }
+    }
+    scx_bpf_put_idle_cpumask(idle);
+
+    if (enable_stats) {
+        struct cake_stats *st = get_local_stats();
+        if (best >= 0)
+            st->nr_smt_spread++;
+        else
+            st->nr_smt_colocated++;
+    }
+
+    if (best < 0)
+        return cpu;
+
+    /* Release the kernel's claim: the kicked CPU re-enters idle and
+     * re-publishes itself in the idle mask. */
+    scx_bpf_kick_cpu(cpu, SCX_KICK_IDLE);
+    return best;
+}
+
+/* LOCALITY: the kernel claimed idle @cpu outside @prev_cpu's LLC. Its
+ * default pass takes a whole idle core anywhere over a half-idle one
+ * nearby and only knows NUMA nodes with per-node idle masks, so walk
+ * prev's select order and claim the first idle CPU ahead of @cpu,
+ * releasing @cpu with an idle kick. Returns @cpu when none is nearer.
+ * Noinline: only built on multi-LLC machines. */
+static __attribute__((noinline))
+s32 nearer_idle_cold(struct task_struct *p, s32 prev_cpu, s32 cpu)
+{
+    u32 prev = prev_cpu & (CAKE_MAX_CPUS - 1);
+    const struct cpumask *idle = scx_bpf_get_idle_cpumask();
+    s32 best = -1;
+
+    for (u32 i = 0; i < CAKE_MAX_CPUS; i++) {
+        u32 c = cpu_select_order[prev][i];
+        if (c >= CAKE_MAX_CPUS || c == (u32)cpu)
+            break;
+        if (!bpf_cpumask_test_cpu(c, idle) || !bpf_cpumask_test_cpu(c, p->cpus_ptr))
+            continue;
+        if (scx_bpf_test_and_clear_cpu_idle(c)) {
+            best = c;
+            break;
+        }
+    }
+    scx_bpf_put_idle_cpumask(idle);
+
+    if (best < 0)
+        return cpu;
+
+    scx_bpf_kick_cpu(cpu, SCX_KICK_IDLE);
+    return best;
+}
+
+/* L2 CLUSTER: the kernel claimed idle @cpu outside @prev_cpu's L2 cluster.
+ * Claim an idle CPU of that cluster instead so the task keeps its L2 warm,
+ * releasing @cpu with an idle kick. Returns @cpu when the cluster is busy.
+ * Noinline: only built on machines with L2 clusters. */
+static __attribute__((noinline))
+s32 cluster_idle_cold(struct task_struct *p, s32 prev_cpu, s32 cpu)
+{
+    const u64 *cluster = cpu_cluster_mask[prev_cpu & (CAKE_MAX_CPUS - 1)];
+    const struct cpumask *idle = scx_bpf_get_idle_cpumask();
+    s32 best = -1;
+
+    for (u32 i = 0; i < CAKE_MAX_CPUS; i++) {
+        if (i >= nr_cpus)
+            break;
+        if (!mask_test(cluster, i) || !bpf_cpumask_test_cpu(i, idle) ||
+            !bpf_cpumask_test_cpu(i, p->cpus_ptr))
+            continue;
+        if (scx_bpf_test_and_clear_cpu_idle(i)) {
+            best = i;
+            break;
+        }
+    }
+    scx_bpf_put_idle_cpumask(idle);
+
+    if (best < 0)
+        return cpu;
+
+    scx_bpf_kick_cpu(cpu, SCX_KICK_IDLE);
+    return best;
+}
+
+/* PRIMARY DOMAIN: the kernel claimed @cpu outside the primary domain for a
+ * primary-tier task. Claim an idle primary CPU instead; without one,
+ * release the claim and return -1 so the task queues on the primary LLC.
+ * Noinline: only built with --primary-domain. */
+static __attribute__((noinline))
+s32 primary_idle_cold(struct task_struct *p, s32 cpu)
+{
+    const struct cpumask *idle = scx_bpf_get_idle_cpumask();
+    s32 best = -1;
+
+    for (u32 i = 0; i < CAKE_MAX_CPUS; i++) {
+        if (i >= nr_cpus)
+            break;
+        if (!mask_test(primary_cpus, i) || !bpf_cpumask_test_cpu(i, idle) ||
+            !bpf_cpumask_test_cpu(i, p->cpus_ptr))
+            continue;
+        if (scx_bpf_test_and_clear_cpu_idle(i)) {
+            best = i;
+            break;
+        }
+    }
+    scx_bpf_put_idle_cpumask(idle);
+
+    scx_bpf_kick_cpu(cpu, SCX_KICK_IDLE);
+    return best;
+}
+
+/* SECONDARY DOMAIN: the kernel claimed @cpu outside the secondary domain
+ * for a secondary-tier task. Claim an idle secondary CPU instead, releasing
+ * @cpu; without one keep @cpu (the domain is a preference, not a fence).
+ * Noinline: only built with --x3d. */
+static __attribute__((noinline))
+s32 secondary_idle_cold(struct task_struct *p, s32 cpu)
+{
+    const struct cpumask *idle = scx_bpf_get_idle_cpumask();
+    s32 best = -1;
+
+    for (u32 i = 0; i < CAKE_MAX_CPUS; i++) {
+        if (i >= nr_cpus)
+            break;
+        if (!mask_test(secondary_cpus, i) || !bpf_cpumask_test_cpu(i, idle) ||
+            !bpf_cpumask_test_cpu(i, p->cpus_ptr))
+            continue;
+        if (scx_bpf_test_and_clear_cpu_idle(i)) {
+            best = i;
+            break;
+        }
+    }
+    scx_bpf_put_idle_cpumask(idle);
+
+    if (best < 0)
+        return cpu;
+
+    scx_bpf_kick_cpu(cpu, SCX_KICK_IDLE);
+    return best;
+}
+
+/* POWERSAVE: the kernel claimed @cpu outside the packed set for a packed
+ * tier. Claim an idle packed CPU instead, first in packing order; without
+ * one, release the claim and return -1 so the task queues on pack_llc
+ * (the pack controller widens the set when it stays busy).
+ * Noinline: only built with --powersave. */
+static __attribute__((noinline))
+s32 pack_idle_cold(struct task_struct *p, s32 cpu)
+{
+    const struct cpumask *idle = scx_bpf_get_idle_cpumask();
+    u32 width = pack_width;
+    s32 best = -1;
+
+    for (u32 r = 0; r < CAKE_MAX_CPUS; r++) {
+        if (r >= width || r >= nr_cpus)
+            break;
+        u32 i = cpu_by_pack[r] & (CAKE_MAX_CPUS - 1);
+        if (!bpf_cpumask_test_cpu(i, idle) || !bpf_cpumask_test_cpu(i, p->cpus_ptr))
+            continue;
+        if (scx_bpf_test_and_clear_cpu_idle(i)) {
+            best = i;
+            break;
+        }
+    }
+    scx_bpf_put_idle_cpumask(idle);
+
+    scx_bpf_kick_cpu(cpu, SCX_KICK_IDLE);
+    return best;
+}
+
+/* PREFERRED CORES: the kernel claimed idle @cpu for a ranked tier. Walk the
+ * CPUs that boost higher, best first, and claim the first idle one in
+ * @cpu's LLC (crossing LLCs would cost the task its cache), releasing
+ * @cpu with an idle kick. Returns @cpu when none is better.
+ * Noinline: only built with --preferred-cores on a ranked machine. */
+static __attribute__((noinline))
+s32 preferred_idle_cold(struct task_struct *p, s32 cpu)
 {
-    u32 cpu = bpf_get_smp_processor_id() & (CAKE_MAX_CPUS - 1);
-    if (!bpf_cpumask_test_cpu(cpu, p->cpus_ptr))
-        return -1;
+    u32 self = cpu & (CAKE_MAX_CPUS - 1);
+    u32 rank = cpu_perf_rank[self];
+    u32 llc = cpu_llc_id[self];
+    const struct cpumask *idle = scx_bpf_get_idle_cpumask();
+    s32 best = -1;
+
+    for (u32 r = 0; r < CAKE_MAX_CPUS; r++) {
+        if (r >= rank)
+            break;
+        u32 i = cpu_by_rank[r] & (CAKE_MAX_CPUS - 1);
+        if (cpu_llc_id[i] != llc || !bpf_cpumask_test_cpu(i, idle) ||
+            !bpf_cpumask_test_cpu(i, p->cpus_ptr))
+            continue;
+        if (scx_bpf_test_and_clear_cpu_idle(i)) {
+            best = i;
+            break;
+        }
+    }
+    scx_bpf_put_idle_cpumask(idle);
 
-    /* Use tier-adjusted slice, not raw quantum. Without this, the kernel's
-     * slice countdown preempts at 2ms before cake_tick can check the
-     * tier-adjusted threshold — making multipliers dead code for SYNC. */
-    struct cake_task_ctx *tctx = bpf_task_storage_get(&task_ctx, p, 0, 0);
-    u64 slice = tctx ? tctx->next_slice : quantum_ns;
+    if (best < 0)
+        return cpu;
+    scx_bpf_kick_cpu(cpu, SCX_KICK_IDLE);
+    return best;
+}
 
-    scx_bpf_dsq_insert(p, SCX_DSQ_LOCAL_ON | cpu, slice, wake_flags);
-    return (s32)cpu;
+/* SMT AVOIDANCE: the kernel claimed @cpu but its sibling is busy. Trade it
+ * for a whole idle core; without one, release the claim and return -1 so
+ * the task queues until dispatch finds it a whole core (or its wait budget
+ * runs out). Noinline: only built with --disable-smt. */
+static __attribute__((noinline))
+s32 smt_avoid_cold(struct task_struct *p, s32 cpu)
+{
+    const struct cpumask *idle = scx_bpf_get_idle_cpumask();
+    bool busy = sibling_busy(idle, cpu);
+    scx_bpf_put_idle_cpumask(idle);
+
+    if (!busy)
+        return cpu;
+
+    s32 core = scx_bpf_pick_idle_cpu(p->cpus_ptr, SCX_PICK_IDLE_CORE);
+    if (core >= 0 && !cpu_managed(core)) {
+        scx_bpf_kick_cpu(core, SCX_KICK_IDLE);
+        core = -1;
+    }
+    scx_bpf_kick_cpu(cpu, SCX_KICK_IDLE);
+    return core;
+}
+
+/* SMT GUARD: a guarded @tier task starts on @cpu. A lower-tier task on the
+ * sibling gets preempted; dispatch there then holds lower tiers back until
+ * this task stops or they reach their wait budget. Noinline: only built
+ * with --smt-guard. */
+static __attribute__((noinline))
+void smt_guard_kick_cold(u32 cpu, u8 tier)
+{
+    u32 sib = cpu_sibling[cpu & (CAKE_MAX_CPUS - 1)] & (CAKE_MAX_CPUS - 1);
+    u8 rt = mega_mailbox[sib].run_tier;
+
+    if (sib != (cpu & (CAKE_MAX_CPUS - 1)) && rt && ((rt - 1) & 3) > tier)
+        scx_bpf_kick_cpu(sib, SCX_KICK_PREEMPT);
+}
+
+/* SMT GUARD: a guarded task left @cpu. If the sibling held work back for
+ * it and went idle, wake it to dispatch that work now. Noinline: only
+ * built with --smt-guard. */
+static __attribute__((noinline))
+void smt_guard_release_cold(u32 cpu)
+{
+    u32 sib = cpu_sibling[cpu & (CAKE_MAX_CPUS - 1)] & (CAKE_MAX_CPUS - 1);
+
+    if (sib != (cpu & (CAKE_MAX_CPUS - 1)) && mega_mailbox[sib].smt_deferred)
+        scx_bpf_kick_cpu(sib, SCX_KICK_IDLE);
+}
+
+/* Count a select_cpu outcome. CAKE_SEL_PREV means "placed on @cpu" and is
+ * refined by where @cpu sits relative to @prev_cpu. Noinline: stats on
+ * only (Rule 5). */
+static __attribute__((noinline))
+void account_select_cold(u32 rung, s32 prev_cpu, s32 cpu)
+{
+    if (rung == CAKE_SEL_PREV && cpu != prev_cpu) {
+        u32 prev = prev_cpu & (CAKE_MAX_CPUS - 1);
+        u32 to = cpu & (CAKE_MAX_CPUS - 1);
+
+        if (has_smt && (cpu_sibling[prev] & (CAKE_MAX_CPUS - 1)) == to)
+            rung = CAKE_SEL_SIBLING;
+        else if (has_clusters && mask_test(cpu_cluster_mask[prev], to))
+            rung = CAKE_SEL_CLUSTER;
+        else if (cpu_llc_id[prev] == cpu_llc_id[to])
+            rung = CAKE_SEL_LLC;
+        else if (has_numa && cpu_node_id[prev] == cpu_node_id[to])
+            rung = CAKE_SEL_NODE;
+        else if (has_hybrid && cpu_is_big[to])
+            rung = CAKE_SEL_BIG;
+        else
+            rung = CAKE_SEL_OTHER;
+    }
+    if (rung < CAKE_SEL_MAX)
+        get_local_stats()->nr_select[rung]++;
 }
 
 s32 BPF_STRUCT_OPS(cake_select_cpu, struct task_struct *p, s32 prev_cpu,
@@ -313,25 +1433,115 @@ s32 BPF_STRUCT_OPS(cake_select_cpu, struct task_struct *p, s32 prev_cpu,
      * instructions). Returns -1 if cpumask disallows → fall through. */
     if (wake_flags & SCX_WAKE_SYNC) {
         s32 sync_cpu = dispatch_sync_cold(p, wake_flags);
-        if (sync_cpu >= 0)
+        if (sync_cpu >= 0) {
+            if (enable_stats)
+                account_select_cold(CAKE_SEL_SYNC, prev_cpu, sync_cpu);
             return sync_cpu;
+        }
     }
 
     u32 tc_id = bpf_get_smp_processor_id() & (CAKE_MAX_CPUS - 1);
     struct cake_scratch *scr = &global_scratch[tc_id];
     s32 cpu = scx_bpf_select_cpu_dfl(p, prev_cpu, wake_flags, &scr->dummy_idle);
 
+    /* Kernel picked an idle CPU outside --cpus: trade it for a managed one */
+    if (has_cpu_mask && scr->dummy_idle && !cpu_managed(cpu)) {
+        cpu = managed_idle_cold(p, cpu);
+        scr->dummy_idle = cpu >= 0;
+    }
+
     if (scr->dummy_idle) {
         /* Kernel found & claimed an idle CPU — direct dispatch.
          * Use tier-adjusted slice so kernel preemption matches tick's check.
          * Falls back to raw quantum for unclassified tasks (first wakeup).
          * No tunnel needed — enqueue never runs on this path. */
         struct cake_task_ctx *tctx = bpf_task_storage_get(&task_ctx, p, 0, 0);
-        u64 slice = tctx ? tctx->next_slice : quantum_ns;
-        scx_bpf_dsq_insert(p, SCX_DSQ_LOCAL_ON | cpu, slice, wake_flags);
-        return cpu;
+        u64 slice = relax_slice(tctx ? tctx->next_slice : cake_quantum_ns());
+        bool place = true;
+
+        /* LOCALITY: the kernel left prev's LLC; take a nearer idle CPU
+         * (sibling, LLC, then NUMA node) if one is free */
+        if (has_select_order && cpu != prev_cpu &&
+            cpu_llc_id[cpu & (CAKE_MAX_CPUS - 1)] != cpu_llc_id[prev_cpu & (CAKE_MAX_CPUS - 1)])
+            cpu = nearer_idle_cold(p, prev_cpu, cpu);
+
+        /* L2 CLUSTER: the kernel only knows SMT cores and LLCs, so it may
+         * leave prev's E-core module for another with prev's L2 idle */
+        if (has_clusters && cpu != prev_cpu) {
+            /* A cluster holds prev itself; prev outside it = no cluster */
+            const u64 *cluster = cpu_cluster_mask[prev_cpu & (CAKE_MAX_CPUS - 1)];
+            if (mask_test(cluster, prev_cpu) && !mask_test(cluster, cpu))
+                cpu = cluster_idle_cold(p, prev_cpu, cpu);
+        }
+
+        /* --primary-domain: primary tiers only take a primary CPU */
+        if (has_primary && tctx && !primary_ok(p, GET_TIER(tctx), cpu)) {
+            s32 prim = primary_idle_cold(p, cpu);
+            place = prim >= 0;
+            if (place)
+                cpu = prim;
+        }
+
+        /* --x3d: background tiers move to the frequency CCD when it has room */
+        if (has_secondary && place && tctx &&
+            (secondary_tier_mask & (1 << (GET_TIER(tctx) & 3))) &&
+            !mask_test(secondary_cpus, cpu))
+            cpu = secondary_idle_cold(p, cpu);
+
+        /* --powersave: packed tiers only take a packed CPU */
+        if (has_powersave && place && tctx && !pack_ok(p, GET_TIER(tctx), cpu)) {
+            s32 packed = pack_idle_cold(p, cpu);
+            place = packed >= 0;
+            if (place)
+                cpu = packed;
+        }
+
+        /* --preferred-cores: this tier takes the best-boosting idle core */
+        if (prefcore_tier_mask && place && tctx &&
+            (prefcore_tier_mask & (1 << (GET_TIER(tctx) & 3))))
+            cpu = preferred_idle_cold(p, cpu);
+
+        /* --disable-smt: this tier only takes a whole idle core */
+        if (smt_avoid_mask && place && tctx &&
+            (smt_avoid_mask & (1 << (GET_TIER(tctx) & 3)))) {
+            s32 whole = smt_avoid_cold(p, cpu);
+            place = whole >= 0;
+            if (place)
+                cpu = whole;
+        }
+
+        /* SMT CONTENTION: kernel only falls back to a half-idle core when no
+         * whole idle core exists — it then ignores what the sibling runs.
+         * A heavy (Frame) wakeup next to a heavy sibling gets re-placed.
+         * Skipped while the user is idle: co-location compacts work. */
+        if (has_smt && place && !user_idle && tctx && GET_TIER(tctx) == CAKE_TIER_FRAME) {
+            u32 sib = cpu_sibling[cpu & (CAKE_MAX_CPUS - 1)] & (CAKE_MAX_CPUS - 1);
+            if (sib != (u32)cpu && MBOX_IS_HEAVY(mega_mailbox[sib].flags))
+                cpu = smt_spread_cold(p, cpu, sib);
+        }
+
+        /* --smt-guard: the sibling runs a guarded higher tier, so this task
+         * queues like a capped one below and waits for dispatch to allow it */
+        if (smt_guard_mask && place && tctx &&
+            (GET_TIER(tctx) & 3) > sibling_guard(cpu))
+            place = false;
+
+        /* Tier at its concurrency cap: fall through to the LLC DSQ. The
+         * claimed CPU gets the enqueue's resched, finds the tier capped in
+         * dispatch and goes back to idle. */
+        if (place && !(has_tier_caps && tctx &&
+                       tier_at_cap_cold(cpu & (CAKE_MAX_CPUS - 1), GET_TIER(tctx)))) {
+            scx_bpf_dsq_insert(p, SCX_DSQ_LOCAL_ON | cpu, slice, wake_flags);
+            if (enable_stats)
+                account_select_cold(CAKE_SEL_PREV, prev_cpu, cpu);
+            return cpu;
+        }
+        prev_cpu = cpu;
     }
 
+    if (enable_stats)
+        account_select_cold(CAKE_SEL_QUEUED, prev_cpu, -1);
+
     /* ALL BUSY: tunnel LLC ID + timestamp for enqueue (~22ns saved on
      * the 90% idle path above where these were previously wasted).
      * select_cpu runs on same CPU as enqueue — safe to tunnel. */
@@ -360,12 +1570,18 @@ void BPF_STRUCT_OPS(cake_enqueue, struct task_struct *p, u64 enq_flags)
     u64 now_cached = scr->cached_now;
     u32 enq_llc = scr->cached_llc;
 
+    /* --cpus: nowhere managed to run — let the kernel's global DSQ place it */
+    if (has_cpu_mask && unlikely(!managed_allowed_cold(p_reg))) {
+        scx_bpf_dsq_insert(p_reg, SCX_DSQ_GLOBAL, cake_quantum_ns(), enq_flags);
+        return;
+    }
+
     struct cake_task_ctx *tctx = get_task_ctx(p_reg, false);
 
     /* Kthread cold path (inlined — reuses now_cached + enq_llc) */
     if (unlikely((task_flags & PF_KTHREAD) && !tctx)) {
         u64 vtime = ((u64)CAKE_TIER_CRITICAL << 56) | (now_cached & 0x00FFFFFFFFFFFFFFULL);
-        scx_bpf_dsq_insert_vtime(p_reg, LLC_DSQ_BASE + enq_llc, quantum_ns, vtime, enq_flags);
+        scx_bpf_dsq_insert_vtime(p_reg, LLC_DSQ_BASE + enq_llc, cake_quantum_ns(), vtime, enq_flags);
         return;
     }
 
@@ -374,20 +1590,46 @@ void BPF_STRUCT_OPS(cake_enqueue, struct task_struct *p, u64 enq_flags)
     /* Handle Yields/Background */
     if (!(enq_flags & (SCX_ENQ_WAKEUP | SCX_ENQ_PREEMPT))) {
         u64 vtime = ((u64)CAKE_TIER_BULK << 56) | (now_cached & 0x00FFFFFFFFFFFFFFULL);
-        scx_bpf_dsq_insert_vtime(p_reg, LLC_DSQ_BASE + enq_llc, quantum_ns, vtime, enq_flags);
+        scx_bpf_dsq_insert_vtime(p_reg, LLC_DSQ_BASE + enq_llc, cake_quantum_ns(), vtime, enq_flags);
         return;
     }
 
     if (unlikely(!tctx_reg)) {
         /* No context yet - use Frame tier */
         u64 vtime = ((u64)CAKE_TIER_FRAME << 56) | (now_cached & 0x00FFFFFFFFFFFFFFULL);
-        scx_bpf_dsq_insert_vtime(p_reg, LLC_DSQ_BASE + enq_llc, quantum_ns, vtime, enq_flags);
+        scx_bpf_dsq_insert_vtime(p_reg, LLC_DSQ_BASE + enq_llc, cake_quantum_ns(), vtime, enq_flags);
         return;
     }
 
     /* Standard Tier Logic */
-    u8 tier = GET_TIER(tctx_reg) & 3;
-    u64 slice = tctx_reg->next_slice;
+    u32 task_packed = cake_relaxed_load_u32(&tctx_reg->packed_info);
+    u8 tier = GET_TIER_RAW(task_packed) & 3;
+    u64 slice = relax_slice(tctx_reg->next_slice);
+
+    /* Launch warm-up: main thread boosted, loaders demoted (rare, cold) */
+    if (unlikely(task_packed & ((u32)(CAKE_FLOW_WARMUP_MAIN | CAKE_FLOW_WARMUP_LOAD) << SHIFT_FLAGS)))
+        tier = warmup_tier_cold(tctx_reg, task_packed, tier, now_cached) & 3;
+
+    /* User idle: no foreground to protect, let background work drain with Frame */
+    bool idle_promote = user_idle && tier == CAKE_TIER_BULK;
+    if (unlikely(idle_promote))
+        tier = CAKE_TIER_FRAME;
+
+    /* --primary-domain: queue primary tiers where primary CPUs drain first */
+    if (has_primary && (primary_tier_mask & (1 << tier)) &&
+        (p_reg->nr_cpus_allowed >= nr_online || primary_allowed_cold(p_reg)))
+        enq_llc = primary_llc;
+
+    /* --x3d: background tiers queue on the frequency CCD; the cache CCD
+     * still steals them when it runs dry */
+    if (has_secondary && (secondary_tier_mask & (1 << tier)) &&
+        p_reg->nr_cpus_allowed >= nr_online)
+        enq_llc = secondary_llc;
+
+    /* --powersave: packed tiers queue where the packed CPUs drain first */
+    if (has_powersave && (powersave_tier_mask & (1 << tier)) &&
+        p_reg->nr_cpus_allowed >= nr_online)
+        enq_llc = pack_llc;
 
     if (enable_stats) {
         struct cake_stats *s = get_local_stats();
@@ -398,6 +1640,8 @@ void BPF_STRUCT_OPS(cake_enqueue, struct task_struct *p, u64 enq_flags)
 
         if (tier < CAKE_TIER_MAX)
             s->nr_tier_dispatches[tier]++;
+        if (idle_promote)
+            s->nr_idle_promotions++;
     }
 
     /* A+B: Vtime-encoded priority: (tier << 56) | timestamp
@@ -406,38 +1650,73 @@ void BPF_STRUCT_OPS(cake_enqueue, struct task_struct *p, u64 enq_flags)
      * newly spawned threads instant responsiveness (e.g., game launching a
      * new worker). Cleared by reclassify_task_cold when deficit exhausts. */
     u64 vtime = ((u64)tier << 56) | (now_cached & 0x00FFFFFFFFFFFFFFULL);
-    u32 task_packed = cake_relaxed_load_u32(&tctx_reg->packed_info);
     if (task_packed & ((u32)CAKE_FLOW_NEW << SHIFT_FLAGS))
-        vtime -= new_flow_bonus_ns;
+        vtime -= cake_new_flow_bonus_ns();
     scx_bpf_dsq_insert_vtime(p_reg, LLC_DSQ_BASE + enq_llc, slice, vtime, enq_flags);
+
+    if (has_trace)
+        emit_event_cold(p_reg, CAKE_EV_ENQUEUE, tier, tier);
 }
 
 /* Dispatch: per-LLC DSQ scan with cross-LLC stealing fallback.
  * Direct-dispatched tasks (SCX_DSQ_LOCAL_ON) bypass this callback entirely —
  * kernel handles them natively. Only tasks that went through
  * cake_enqueue → per-LLC DSQ arrive here. */
+/* SLICE SCALING: ceiling = slice_max / (1 + runnable tasks per CPU), never
+ * below slice_min. Waiters are this CPU's local DSQ plus its share of the
+ * LLC DSQ. Sampled once per dispatch and read back by cake_running / tick.
+ * Noinline: only built when --slice-min/--slice-max are set (Rule 5). */
+static __attribute__((noinline))
+void scale_slice_cold(s32 cpu, u32 llc)
+{
+    u32 llc_cpus = nr_llcs ? nr_online / nr_llcs : nr_online;
+    u64 waiting = scx_bpf_dsq_nr_queued(SCX_DSQ_LOCAL) +
+                  scx_bpf_dsq_nr_queued(LLC_DSQ_BASE + llc) / (llc_cpus ?: 1);
+    u64 ceil = slice_max_ns / (1 + waiting);
+
+    if (ceil < slice_min_ns)
+        ceil = slice_min_ns;
+    mega_mailbox[cpu & (CAKE_MAX_CPUS - 1)].dyn_slice_ns = (u32)ceil;
+}
+
 void BPF_STRUCT_OPS(cake_dispatch, s32 raw_cpu, struct task_struct *prev)
 {
     u32 my_llc = cpu_llc_id[raw_cpu & (CAKE_MAX_CPUS - 1)];
 
+    /* Outside --cpus: only the global DSQ (drained by the kernel) runs here */
+    if (!cpu_managed(raw_cpu))
+        return;
+
+    if (has_slice_bounds)
+        scale_slice_cold(raw_cpu, my_llc);
+
+    /* --smt-guard: re-flagged below if this pass still holds work back */
+    if (smt_guard_mask)
+        mega_mailbox[raw_cpu & (CAKE_MAX_CPUS - 1)].smt_deferred = 0;
+
     /* Local LLC first — zero cross-CCD contention in steady state */
-    if (scx_bpf_dsq_move_to_local(LLC_DSQ_BASE + my_llc))
+    if (cake_move_to_local(raw_cpu, LLC_DSQ_BASE + my_llc))
         return;
 
     /* Steal from other LLCs (only when local is empty).
      * RODATA gate: Clang doesn't constant-fold RODATA globals, so without
-     * this check, single-LLC systems (9800X3D) execute 7 unrolled
-     * load+branch pairs that always break immediately. (Rule 5) */
+     * this check, single-LLC systems (9800X3D) enter a CAKE_MAX_LLCS
+     * loop that always breaks immediately. (Rule 5) */
     if (nr_llcs <= 1)
         return;
 
+    if (has_numa) {
+        steal_numa_cold(raw_cpu, my_llc);
+        return;
+    }
+
     for (u32 i = 1; i < CAKE_MAX_LLCS; i++) {
         if (i >= nr_llcs)
             break;
         u32 victim = my_llc + i;
         if (victim >= nr_llcs)
             victim -= nr_llcs;
-        if (scx_bpf_dsq_move_to_local(LLC_DSQ_BASE + victim))
+        if (cake_move_to_local(raw_cpu, LLC_DSQ_BASE + victim))
             return;
     }
 }
@@ -455,6 +1734,58 @@ const u32 tier_perf_target[8] = {
     768, 768, 768, 768,  /* padding */
 };
 
+/* STARVATION IMMUNITY: decide whether an immune-tier task past its
+ * starvation threshold should be spared. Peeks the head of this CPU's LLC
+ * DSQ — vtime bits [63:56] carry the waiter's tier. Only a same-or-higher
+ * tier waiter may preempt. Empty LLC DSQ means the waiters were direct
+ * dispatched to the local DSQ; the task's own (short) slice bounds them.
+ * Noinline: only reached on the rare over-threshold path (Rule 5). */
+static __attribute__((noinline))
+bool starvation_immune_cold(u32 cpu, u8 tier, u64 runtime)
+{
+    /* Hard ceiling — immunity never outlives the global starvation limit */
+    if (runtime > cake_starvation_ns())
+        return false;
+
+    u32 llc = cpu_llc_id[cpu & (CAKE_MAX_CPUS - 1)];
+    struct task_struct *waiter;
+    bool immune = true;
+
+    bpf_for_each(scx_dsq, waiter, LLC_DSQ_BASE + llc, 0) {
+        immune = (u8)(waiter->scx.dsq_vtime >> 56) > tier;
+        break;
+    }
+    return immune;
+}
+
+/* Tier of the waiter a starvation preempt on @cpu makes room for: the head
+ * of its LLC DSQ (vtime bits [63:56]). An empty LLC DSQ means the waiter
+ * sits in the local DSQ: CAKE_TIER_MAX. Noinline: stats/event builds only. */
+static __attribute__((noinline))
+u32 preempt_waiter_tier_cold(u32 cpu)
+{
+    u32 llc = cpu_llc_id[cpu & (CAKE_MAX_CPUS - 1)];
+    struct task_struct *waiter;
+    u32 src = CAKE_TIER_MAX;
+
+    bpf_for_each(scx_dsq, waiter, LLC_DSQ_BASE + llc, 0) {
+        src = (waiter->scx.dsq_vtime >> 56) & 3;
+        break;
+    }
+    return src;
+}
+
+/* Preempt matrix: file a starvation preempt of a @victim-tier task under
+ * its waiter's tier. Noinline: stats on only (Rule 5). */
+static __attribute__((noinline))
+void account_preempt_cold(u32 cpu, u8 victim)
+{
+    u32 src = preempt_waiter_tier_cold(cpu);
+
+    if (src <= CAKE_TIER_MAX)
+        get_local_stats()->preempt_matrix[src][victim & 3]++;
+}
+
 void BPF_STRUCT_OPS(cake_tick, struct task_struct *p)
 {
     /* Register pin p to r6 to avoid stack spills */
@@ -476,7 +1807,8 @@ void BPF_STRUCT_OPS(cake_tick, struct task_struct *p)
     u64 runtime = (u64)(now - last_run);
 
     /* Slice exceeded: force context switch */
-    if (unlikely(runtime > tctx_reg->next_slice)) {
+    if (unlikely(runtime > relax_slice(cap_slice(bound_slice(tctx_reg->next_slice, cpu_id_reg),
+                                                 cpu_id_reg)))) {
         scx_bpf_kick_cpu(cpu_id_reg, SCX_KICK_PREEMPT);
         return;
     }
@@ -502,13 +1834,29 @@ void BPF_STRUCT_OPS(cake_tick, struct task_struct *p)
 
             u64 threshold = UNPACK_STARVATION_NS(tier_configs[tier_reg & 7]);
             if (unlikely(runtime > threshold)) {
-                scx_bpf_kick_cpu(cpu_id_reg, SCX_KICK_PREEMPT);
-
-                if (enable_stats && tier_reg < CAKE_TIER_MAX) {
-                    struct cake_stats *s = get_local_stats();
-                    if (s) s->nr_starvation_preempts_tier[tier_reg]++;
+                /* Immune tier: spare it unless the waiter outranks it.
+                 * Spared tasks fall through to mailbox/DVFS and keep running. */
+                bool spare = (starvation_immune_mask & (1 << (tier_reg & 3))) &&
+                             starvation_immune_cold(cpu_id_reg, tier_reg, runtime);
+
+                if (spare) {
+                    if (enable_stats && tier_reg < CAKE_TIER_MAX) {
+                        struct cake_stats *s = get_local_stats();
+                        if (s) s->nr_starvation_immune_tier[tier_reg]++;
+                    }
+                } else {
+                    scx_bpf_kick_cpu(cpu_id_reg, SCX_KICK_PREEMPT);
+
+                    if (enable_stats && tier_reg < CAKE_TIER_MAX) {
+                        struct cake_stats *s = get_local_stats();
+                        if (s) s->nr_starvation_preempts_tier[tier_reg]++;
+                        account_preempt_cold(cpu_id_reg, tier_reg);
+                    }
+                    if (has_events)
+                        emit_event_cold(p_reg, CAKE_EV_STARVATION, tier_reg,
+                                        preempt_waiter_tier_cold(cpu_id_reg));
+                    return;  /* Already kicked — skip mailbox/DVFS */
                 }
-                return;  /* Already kicked — skip mailbox/DVFS */
             }
         } else {
             /* No contention — grow confidence (saturate at 255) */
@@ -544,6 +1892,37 @@ void BPF_STRUCT_OPS(cake_tick, struct task_struct *p)
     }
 }
 
+/* Migration stats: where this run landed relative to the previous one —
+ * same LLC, another LLC (filed by tier, to catch CCD bouncing), another
+ * NUMA node, and on hybrid parts P↔E moves. Noinline: stats on only (Rule 5). */
+static __attribute__((noinline))
+void account_migration_cold(struct cake_task_ctx *tctx)
+{
+    u32 cpu = bpf_get_smp_processor_id() & (CAKE_MAX_CPUS - 1);
+    u32 last = tctx->last_cpu;
+
+    tctx->last_cpu = cpu + 1;
+    if (!last || --last == cpu)
+        return;
+    last &= CAKE_MAX_CPUS - 1;
+
+    struct cake_stats *s = get_local_stats();
+    if (cpu_llc_id[last] == cpu_llc_id[cpu])
+        s->nr_migrate_local++;
+    else
+        s->nr_migrate_cross_llc[GET_TIER(tctx) & 3]++;
+
+    if (has_numa && cpu_node_id[last] != cpu_node_id[cpu])
+        s->nr_migrate_cross_node++;
+
+    if (has_hybrid && cpu_is_big[last] != cpu_is_big[cpu]) {
+        if (cpu_is_big[last])
+            s->nr_migrate_p_to_e++;
+        else
+            s->nr_migrate_e_to_p++;
+    }
+}
+
 /* Task started running - stamp last_run_at for runtime measurement.
  * DVFS moved to cake_tick where rq lock is held (cpuperf_set ~15-20ns vs
  * ~30-80ns unlocked here). Saves ~44-84 cycles per context switch. */
@@ -553,6 +1932,29 @@ void BPF_STRUCT_OPS(cake_running, struct task_struct *p)
     if (!tctx)
         return;
     tctx->last_run_at = (u32)scx_bpf_now();
+
+    if (enable_stats)
+        account_migration_cold(tctx);
+
+    if (has_trace)
+        emit_event_cold(p, CAKE_EV_RUN, GET_TIER(tctx) & 3, GET_TIER(tctx) & 3);
+
+    /* Slice bounds, capacity: replace the enqueue-time slice with the
+     * load-scaled one, stretched for this core's capacity */
+    if (has_slice_bounds || has_capacity) {
+        u32 cpu = bpf_get_smp_processor_id();
+        p->scx.slice = relax_slice(cap_slice(bound_slice(tctx->next_slice, cpu), cpu));
+    }
+
+    /* Tier caps, EPP hints, SMT guard: publish what this CPU runs (own
+     * mailbox entry only) */
+    if (has_tier_caps || has_epp_hints || smt_guard_mask)
+        mega_mailbox[bpf_get_smp_processor_id() & (CAKE_MAX_CPUS - 1)].run_tier =
+            (GET_TIER(tctx) & 3) + 1;
+
+    /* --smt-guard: push lower-tier work off this core's other thread */
+    if (smt_guard_mask & (1 << (GET_TIER(tctx) & 3)))
+        smt_guard_kick_cold(bpf_get_smp_processor_id(), GET_TIER(tctx) & 3);
 }
 
 /* ═══════════════════════════════════════════════════════════════════════════
@@ -567,7 +1969,7 @@ void BPF_STRUCT_OPS(cake_running, struct task_struct *p)
  * at the same tier.
  * ═══════════════════════════════════════════════════════════════════════════ */
 static __attribute__((noinline))
-void reclassify_task_cold(struct cake_task_ctx *tctx)
+void reclassify_task_cold(struct cake_task_ctx *tctx, bool pinned)
 {
     u32 packed = cake_relaxed_load_u32(&tctx->packed_info);
 
@@ -578,6 +1980,12 @@ void reclassify_task_cold(struct cake_task_ctx *tctx)
         return;  /* Never ran — skip (safety gate) */
 
     u32 runtime_raw = now - last_run;
+
+    /* Capacity: classify on the work done, not the wall time a smaller
+     * core took for it (stopping runs on the CPU the task ran on) */
+    if (has_capacity)
+        runtime_raw = ((u64)runtime_raw *
+                       cpu_capacity[bpf_get_smp_processor_id() & (CAKE_MAX_CPUS - 1)]) >> 10;
     u32 runtime_us = runtime_raw >> 10;  /* ns → ~μs (÷1024 ≈ ÷1000) */
 
     /* Clamp to u16 max for EWMA field (65ms max, more than any reasonable burst) */
@@ -679,6 +2087,10 @@ void reclassify_task_cold(struct cake_task_ctx *tctx)
     else if (new_avg < g2) new_tier = 2;
     else                   new_tier = 3;
 
+    /* --tier-rule or TUI pin: keep EWMA/deficit bookkeeping, never move the tier */
+    if (unlikely(pinned || (packed & ((u32)CAKE_FLOW_PINNED << SHIFT_FLAGS))))
+        new_tier = old_tier;
+
     /* ── WRITE PACKED_INFO (MESI-friendly: skip if unchanged) ── */
     bool tier_changed = (new_tier != old_tier);
 
@@ -705,17 +2117,418 @@ void reclassify_task_cold(struct cake_task_ctx *tctx)
     if (tier_changed) {
         u64 cfg = tier_configs[new_tier & 7];
         u64 mult = UNPACK_MULTIPLIER(cfg);
-        tctx->next_slice = (quantum_ns * mult) >> 10;
+        tctx->next_slice = (cake_quantum_ns() * mult) >> 10;
         tctx->reclass_counter = 0;
     }
 }
 
+/* --events: what reclassify_task_cold changed — a tier move, or the
+ * new-flow flag dropped when the DRR++ deficit ran out. Noinline: event
+ * builds only (Rule 5). */
+static __attribute__((noinline))
+void reclassify_events_cold(struct task_struct *p, u32 before, u32 after)
+{
+    u8 from = (before >> SHIFT_TIER) & MASK_TIER;
+    u8 to = (after >> SHIFT_TIER) & MASK_TIER;
+    u32 new_flow = (u32)CAKE_FLOW_NEW << SHIFT_FLAGS;
+
+    if (from != to)
+        emit_event_cold(p, CAKE_EV_TIER_CHANGE, from, to);
+    if ((before & new_flow) && !(after & new_flow))
+        emit_event_cold(p, CAKE_EV_FLOW_SPENT, to, to);
+}
+
 /* Task stopping — avg_runtime reclassification + DRR++ deficit tracking */
+/* Histogram bucket of a wait: 0 under 1µs, else floor(log2(µs)) + 1,
+ * clamped to the last bucket */
+static __always_inline u32 wait_bucket(u64 wait_ns)
+{
+    u64 us = wait_ns / 1000;
+    u32 b = 1;
+
+    if (!us)
+        return 0;
+    if (us >> 16) { b += 16; us >>= 16; }
+    if (us >> 8)  { b += 8;  us >>= 8; }
+    if (us >> 4)  { b += 4;  us >>= 4; }
+    if (us >> 2)  { b += 2;  us >>= 2; }
+    if (us >> 1)  { b += 1; }
+    return b < CAKE_WAIT_BUCKETS ? b : CAKE_WAIT_BUCKETS - 1;
+}
+
+/* Stats just turned on (load, `scx_cake set stats`, SIGUSR1, the TUI):
+ * open a new generation and restart the depth sampler, which stops itself
+ * while stats are off unless --powersave needs it. */
+static __attribute__((noinline))
+void stats_on_cold(void)
+{
+    u32 key = 0;
+    struct rebalance_timer *dt = bpf_map_lookup_elem(&depth_timer, &key);
+
+    stats_live = 1;
+    stats_gen++;
+    if (dt && !has_powersave)
+        bpf_timer_start(&dt->timer, CAKE_DEPTH_SAMPLE_NS, 0);
+}
+
+/* Per-tier CPU time and per-CPU/per-tier wait for the run ending here.
+ * Runtime is now - last_run_at (u32 ns, fine for any real slice). run_delay
+ * grows by the time @p sat runnable before this run (charged at
+ * switch-in), so the delta since the last stop is that wait. Both are filed
+ * under the tier it ran in. The first stop of a stats generation only takes
+ * the wait baseline — run_delay also covers time before cake and any time
+ * stats were off. Noinline: stats on only (Rule 5). */
+static __attribute__((noinline))
+void account_run_cold(struct task_struct *p, struct cake_task_ctx *tctx)
+{
+    u64 rd = p->sched_info.run_delay;
+    struct cake_stats *s = get_local_stats();
+    u32 tier = GET_TIER(tctx) & 3;
+
+    if (!stats_live)
+        stats_on_cold();
+
+    if (tctx->last_run_at)
+        s->tier_runtime_ns[tier] += (u32)((u32)scx_bpf_now() - tctx->last_run_at);
+
+    if (tctx->stats_gen == stats_gen) {
+        u64 wait = rd - tctx->run_delay_seen;
+        struct cake_wait_hist *h = bpf_map_lookup_elem(&wait_hist, &tier);
+
+        s->wait_ns += wait;
+        s->nr_runs++;
+        if (h)
+            h->bucket[wait_bucket(wait) & (CAKE_WAIT_BUCKETS - 1)]++;
+    }
+    tctx->stats_gen = stats_gen;
+    tctx->run_delay_seen = rd;
+}
+
 void BPF_STRUCT_OPS(cake_stopping, struct task_struct *p, bool runnable)
 {
     struct cake_task_ctx *tctx = get_task_ctx(p, false);
-    if (tctx)
-        reclassify_task_cold(tctx);
+
+    if (has_tier_caps || has_epp_hints || smt_guard_mask) {
+        u32 cpu = bpf_get_smp_processor_id() & (CAKE_MAX_CPUS - 1);
+        struct mega_mailbox_entry *mbox = &mega_mailbox[cpu];
+        u8 rt = mbox->run_tier;
+        mbox->run_tier = 0;
+        if (rt && tier_cap[(rt - 1) & 3])
+            cap_release_cold(p);
+        if (rt && (smt_guard_mask & (1 << ((rt - 1) & 3))))
+            smt_guard_release_cold(cpu);
+    }
+
+    if (enable_stats && tctx)
+        account_run_cold(p, tctx);
+
+    if (!tctx)
+        return;
+    if (has_trace)
+        emit_event_cold(p, CAKE_EV_STOP, GET_TIER(tctx) & 3, runnable);
+
+    /* Pin first: the pin's own tier move is not a classifier event */
+    bool pinned = nr_tier_pins && apply_tier_pin_cold(p, tctx);
+
+    if (has_events) {
+        u32 before = tctx->packed_info;
+        reclassify_task_cold(tctx, pinned);
+        reclassify_events_cold(p, before, tctx->packed_info);
+    } else {
+        reclassify_task_cold(tctx, pinned);
+    }
+}
+
+/* ═══════════════════════════════════════════════════════════════════════════
+ * BACKGROUND REBALANCER: periodic LLC DSQ depth correction.
+ * Wakeup placement only sees the instant; dispatch stealing only fires
+ * when a local DSQ is empty. A CCD that stays busy keeps its backlog while
+ * the other CCD drains its own. Every rebalance_interval_ns the timer
+ * compares LLC DSQ depths and, past rebalance_threshold, moves up to half
+ * the difference (capped at CAKE_REBALANCE_MAX_MOVES) from the tail of the
+ * busiest DSQ — lowest tier first, so hot T0/T1 tasks keep their cache.
+ * With rebalance_imbalance_pct the LLCs are compared by queued tasks per
+ * CPU instead (unequal CCDs, hybrid clusters), and only Bulk — tasks that
+ * run long enough to repay a cold cache — is moved.
+ * Runs unlocked (timer context) → scx_bpf_dsq_move_vtime is permitted.
+ * ═══════════════════════════════════════════════════════════════════════════ */
+
+/* Move up to @budget tasks from the tail of @busiest's DSQ to @idlest's and
+ * wake as many idle CPUs there */
+static __always_inline void rebalance_move(u64 budget, u32 busiest, u32 idlest,
+                                           bool bulk_only)
+{
+    struct task_struct *p;
+    u32 moved = 0;
+
+    if (budget > CAKE_REBALANCE_MAX_MOVES)
+        budget = CAKE_REBALANCE_MAX_MOVES;
+
+    bpf_for_each(scx_dsq, p, LLC_DSQ_BASE + busiest, SCX_DSQ_ITER_REV) {
+        if (moved >= budget)
+            break;
+        u8 tier = (p->scx.dsq_vtime >> 56) & 3;
+        /* Tier-ordered vtime puts Bulk at the tail: past it, nothing left */
+        if (bulk_only && tier != CAKE_TIER_BULK)
+            break;
+        /* Affinity-restricted tasks stay put — no LLC cpumask in BPF */
+        if (p->nr_cpus_allowed < nr_online)
+            continue;
+        /* --powersave: packed tiers stay on pack_llc's packed CPUs */
+        if (has_powersave && (powersave_tier_mask & (1 << tier)))
+            continue;
+        /* Preserve tier-encoded vtime across the move */
+        scx_bpf_dsq_move_set_vtime(BPF_FOR_EACH_ITER, p->scx.dsq_vtime);
+        if (scx_bpf_dsq_move_vtime(BPF_FOR_EACH_ITER, p, LLC_DSQ_BASE + idlest, 0))
+            moved++;
+    }
+
+    /* Wake idle CPUs in the receiving LLC — idle CPUs don't dispatch */
+    u32 woken = 0;
+    for (u32 i = 0; i < CAKE_MAX_CPUS; i++) {
+        if (i >= nr_cpus || woken >= moved)
+            break;
+        if (cpu_llc_id[i] == idlest && scx_bpf_test_and_clear_cpu_idle(i)) {
+            scx_bpf_kick_cpu(i, SCX_KICK_IDLE);
+            woken++;
+        }
+    }
+
+    if (enable_stats) {
+        struct cake_stats *s = get_local_stats();
+        s->nr_rebalance_passes++;
+        s->nr_rebalance_moves += moved;
+    }
+}
+
+/* --rebalance-imbalance: busiest and idlest LLC by queued tasks per CPU;
+ * past the percentage, move Bulk until both would hold the same share.
+ * Noinline: only built with --rebalance-imbalance. */
+static __attribute__((noinline))
+void rebalance_imbalance_cold(void)
+{
+    u32 busiest = 0, idlest = 0;
+    u64 max_load = 0, min_load = ~0ULL;
+
+    for (u32 i = 0; i < CAKE_MAX_LLCS; i++) {
+        if (i >= nr_llcs)
+            break;
+        if (!llc_nr_cpus[i])
+            continue;
+        u64 load = (scx_bpf_dsq_nr_queued(LLC_DSQ_BASE + i) << 10) / llc_nr_cpus[i];
+        if (load > max_load) {
+            max_load = load;
+            busiest = i;
+        }
+        if (load < min_load) {
+            min_load = load;
+            idlest = i;
+        }
+    }
+
+    /* User idle: leave work compacted on the LLCs it already occupies */
+    if (user_idle || busiest == idlest ||
+        max_load * 100 <= min_load * (100 + rebalance_imbalance_pct))
+        return;
+
+    /* x tasks even it out: (max_q - x) / n_busy == (min_q + x) / n_idle */
+    u64 n_busy = llc_nr_cpus[busiest & (CAKE_MAX_LLCS - 1)];
+    u64 n_idle = llc_nr_cpus[idlest & (CAKE_MAX_LLCS - 1)];
+    u64 max_q = scx_bpf_dsq_nr_queued(LLC_DSQ_BASE + busiest);
+    u64 min_q = scx_bpf_dsq_nr_queued(LLC_DSQ_BASE + idlest);
+    if (max_q * n_idle <= min_q * n_busy)
+        return;
+    u64 budget = (max_q * n_idle - min_q * n_busy) / (n_busy + n_idle);
+    if (budget)
+        rebalance_move(budget, busiest, idlest, true);
+}
+
+static int rebalance_timerfn(void *map, int *key, struct bpf_timer *timer)
+{
+    u32 busiest = 0, idlest = 0;
+    u64 max_q = 0, min_q = ~0ULL;
+
+    if (rebalance_imbalance_pct) {
+        rebalance_imbalance_cold();
+        bpf_timer_start(timer, rebalance_interval_ns, 0);
+        return 0;
+    }
+
+    for (u32 i = 0; i < CAKE_MAX_LLCS; i++) {
+        if (i >= nr_llcs)
+            break;
+        u64 q = scx_bpf_dsq_nr_queued(LLC_DSQ_BASE + i);
+        if (q > max_q) {
+            max_q = q;
+            busiest = i;
+        }
+        if (q < min_q) {
+            min_q = q;
+            idlest = i;
+        }
+    }
+
+    /* User idle: leave work compacted on the LLCs it already occupies */
+    if (!user_idle && max_q - min_q > rebalance_threshold)
+        rebalance_move((max_q - min_q) >> 1, busiest, idlest, false);
+
+    bpf_timer_start(timer, rebalance_interval_ns, 0);
+    return 0;
+}
+
+/* POWERSAVE: a CPU the pack controller just added stays idle while work
+ * queues on pack_llc — idle CPUs don't dispatch. Wake the first idle
+ * packed CPU when pack_llc has a backlog. Noinline: only built with
+ * --powersave. */
+static __attribute__((noinline))
+void pack_wake_cold(void)
+{
+    u32 width = pack_width;
+
+    if (!scx_bpf_dsq_nr_queued(LLC_DSQ_BASE + pack_llc))
+        return;
+    for (u32 r = 0; r < CAKE_MAX_CPUS; r++) {
+        if (r >= width || r >= nr_cpus)
+            break;
+        u32 i = cpu_by_pack[r] & (CAKE_MAX_CPUS - 1);
+        if (scx_bpf_test_and_clear_cpu_idle(i)) {
+            scx_bpf_kick_cpu(i, SCX_KICK_IDLE);
+            return;
+        }
+    }
+}
+
+/* DEPTH GAUGES: every CAKE_DEPTH_SAMPLE_NS, walk the LLC DSQs counting
+ * queued tasks by the tier in their vtime, and read each CPU's local DSQ
+ * depth. Sampling from a timer keeps the walk off every scheduling path.
+ * It also wakes packed CPUs for --powersave, so it only runs while stats
+ * are on or with --powersave: with stats off it lets itself lapse and
+ * stats_on_cold starts it again. */
+static int depth_timerfn(void *map, int *key, struct bpf_timer *timer)
+{
+    u32 tiers[CAKE_TIER_MAX] = {};
+    struct task_struct *p;
+
+    if (has_powersave)
+        pack_wake_cold();
+
+    if (!enable_stats) {
+        stats_live = 0;
+        if (!has_powersave)
+            return 0;
+        goto rearm;
+    }
+
+    for (u32 i = 0; i < CAKE_MAX_LLCS; i++) {
+        if (i >= nr_llcs)
+            break;
+        bpf_for_each(scx_dsq, p, LLC_DSQ_BASE + i, 0)
+            tiers[(p->scx.dsq_vtime >> 56) & 3]++;
+    }
+    for (u32 t = 0; t < CAKE_TIER_MAX; t++) {
+        dsq_depth.tier[t] = tiers[t];
+        dsq_depth.tier_sum[t] += tiers[t];
+    }
+
+    for (u32 i = 0; i < CAKE_MAX_CPUS; i++) {
+        if (i >= nr_cpus)
+            break;
+        u32 q = scx_bpf_dsq_nr_queued(SCX_DSQ_LOCAL_ON | i);
+        dsq_depth.cpu[i] = q;
+        dsq_depth.cpu_sum[i] += q;
+    }
+    dsq_depth.nr_samples++;
+
+rearm:
+    bpf_timer_start(timer, CAKE_DEPTH_SAMPLE_NS, 0);
+    return 0;
+}
+
+/* Task init — allocate the per-task context up front so classification
+ * starts from the first wakeup, and watch forks for launch bursts. */
+s32 BPF_STRUCT_OPS(cake_init_task, struct task_struct *p,
+                   struct scx_init_task_args *args)
+{
+    struct cake_task_ctx *ctx = get_task_ctx(p, true);
+    if (!ctx)
+        return -ENOMEM;
+
+    if (warmup_ns && args->fork)
+        track_launch_cold(p, ctx);
+
+    if (nr_tier_rules)
+        apply_tier_rule_cold(p, ctx);
+
+    return 0;
+}
+
+/* Tier rules: exec replaces comm — re-match the new name. Only loaded
+ * when --tier-rule is set (the loader disables autoload otherwise). */
+SEC("tp_btf/sched_process_exec")
+int BPF_PROG(cake_exec, struct task_struct *p, pid_t old_pid, struct linux_binprm *bprm)
+{
+    struct cake_task_ctx *ctx = bpf_task_storage_get(&task_ctx, p, 0, 0);
+
+    if (ctx)
+        apply_tier_rule_cold(p, ctx);
+    return 0;
+}
+
+/* Managed task count: enable/disable bracket a task's time in the ext
+ * class, so SCX_OPS_SWITCH_PARTIAL counts only SCHED_EXT tasks. */
+void BPF_STRUCT_OPS(cake_enable, struct task_struct *p)
+{
+    __sync_fetch_and_add(&nr_tasks, 1);
+}
+
+void BPF_STRUCT_OPS(cake_disable, struct task_struct *p)
+{
+    __sync_fetch_and_sub(&nr_tasks, 1);
+}
+
+/* Task exit: drop a manual pin so a reused TID starts unpinned. The TUI
+ * prunes its copy of the pins when it sees the entry gone. */
+void BPF_STRUCT_OPS(cake_exit_task, struct task_struct *p,
+                   struct scx_exit_task_args *args)
+{
+    if (nr_tier_pins) {
+        u32 pid = p->pid;
+        bpf_map_delete_elem(&tier_pins, &pid);
+    }
+}
+
+/* Task view iterator — one cake_task_rec per task that has a cake context.
+ * Runs only when userspace reads the iterator link, never on the hot path.
+ * The loader pins a link at /sys/fs/bpf/scx_cake_tasks for `scx_cake ctl`. */
+SEC("iter/task")
+int dump_tasks(struct bpf_iter__task *ctx)
+{
+    struct seq_file *seq = ctx->meta->seq;
+    struct task_struct *p = ctx->task;
+    struct cake_task_rec rec = {};
+
+    if (!p)
+        return 0;
+
+    struct cake_task_ctx *tctx = bpf_task_storage_get(&task_ctx, p, 0, 0);
+    if (!tctx)
+        return 0;
+
+    u32 packed = tctx->packed_info;
+    rec.pid = p->pid;
+    rec.tgid = p->tgid;
+    rec.runtime_ns = p->se.sum_exec_runtime;
+    rec.wait_ns = p->sched_info.run_delay;
+    rec.nr_runs = p->sched_info.pcount;
+    rec.cgid = BPF_CORE_READ(p, cgroups, dfl_cgrp, kn, id);
+    rec.avg_runtime_us = tctx->avg_runtime_us;
+    rec.tier = (packed >> SHIFT_TIER) & MASK_TIER;
+    rec.flags = (packed >> SHIFT_FLAGS) & MASK_FLAGS;
+    rec.cpu = scx_bpf_task_cpu(p);
+    bpf_probe_read_kernel_str(rec.comm, sizeof(rec.comm), p->comm);
+
+    bpf_seq_write(seq, &rec, sizeof(rec));
+    return 0;
 }
 
 /* Initialize the scheduler */
@@ -735,6 +2548,37 @@ s32 BPF_STRUCT_OPS_SLEEPABLE(cake_init)
             return ret;
     }
 
+    /* Arm the background rebalancer — single-LLC systems have nothing to balance */
+    if (rebalance_interval_ns && nr_llcs > 1) {
+        u32 key = 0;
+        struct rebalance_timer *rt = bpf_map_lookup_elem(&rebalance_timer, &key);
+        if (!rt)
+            return -ENOENT;
+
+        bpf_timer_init(&rt->timer, &rebalance_timer, CLOCK_MONOTONIC);
+        bpf_timer_set_callback(&rt->timer, rebalance_timerfn);
+        s32 ret = bpf_timer_start(&rt->timer, rebalance_interval_ns, 0);
+        if (ret < 0)
+            return ret;
+    }
+
+    /* Set up the queue-depth sampler always, since stats can be turned on
+     * live; only start it when something reads it now */
+    {
+        u32 key = 0;
+        struct rebalance_timer *dt = bpf_map_lookup_elem(&depth_timer, &key);
+        if (!dt)
+            return -ENOENT;
+
+        bpf_timer_init(&dt->timer, &depth_timer, CLOCK_MONOTONIC);
+        bpf_timer_set_callback(&dt->timer, depth_timerfn);
+        if (enable_stats || has_powersave) {
+            s32 ret = bpf_timer_start(&dt->timer, CAKE_DEPTH_SAMPLE_NS, 0);
+            if (ret < 0)
+                return ret;
+        }
+    }
+
     return 0;
 }
 
@@ -751,6 +2595,10 @@ SCX_OPS_DEFINE(cake_ops,
                .tick           = (void *)cake_tick,
                .running        = (void *)cake_running,
                .stopping       = (void *)cake_stopping,
+               .init_task      = (void *)cake_init_task,
+               .enable         = (void *)cake_enable,
+               .disable        = (void *)cake_disable,
+               .exit_task      = (void *)cake_exit_task,
                .init           = (void *)cake_init,
                .exit           = (void *)cake_exit,
                .flags          = SCX_OPS_KEEP_BUILTIN_IDLE,
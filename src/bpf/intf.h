@@ -33,15 +33,99 @@ enum cake_tier {
     CAKE_TIER_MAX       = 4,
 };
 
-#define CAKE_MAX_CPUS 64
-#define CAKE_MAX_LLCS 8
+#define CAKE_MAX_CPUS 256
+/* u64 words in a CPU bitmask (managed_cpus, primary_cpus, cpu_cluster_mask) */
+#define CAKE_MASK_WORDS (CAKE_MAX_CPUS / 64)
+/* One DSQ per LLC even if every CPU is its own LLC. Only the first nr_llcs
+ * entries of per-LLC tables are filled, and loops over them stop there. */
+#define CAKE_MAX_LLCS CAKE_MAX_CPUS
 
 /* Per-LLC DSQ base — DSQ IDs are LLC_DSQ_BASE + llc_index (0..nr_llcs-1) */
 #define LLC_DSQ_BASE 200
 
-/* Flow state flags (only CAKE_FLOW_NEW currently used) */
+/* Tier concurrency caps — DSQ tasks examined per dispatch looking for an uncapped one */
+#define CAKE_CAP_SCAN_MAX 32
+
+/* Background rebalancer — max tasks moved between LLC DSQs per pass */
+#define CAKE_REBALANCE_MAX_MOVES 8
+
+/* Queue-depth gauge sampling period (gauges update while stats are on) */
+#define CAKE_DEPTH_SAMPLE_NS (10ULL * 1000 * 1000)  /* 10ms */
+
+/* Run queue depth gauges, written by the depth timer (one writer) and read
+ * by userspace. tier: tasks queued in the LLC DSQs, split by the tier in
+ * their vtime; cpu: tasks in each CPU's local DSQ. *_sum accumulate every
+ * sample, so (sum delta / nr_samples delta) is an interval's mean depth —
+ * the CPU analog of CAKE's backlog. */
+struct cake_dsq_depth {
+    u64 nr_samples;
+    u64 tier_sum[CAKE_TIER_MAX];
+    u64 cpu_sum[CAKE_MAX_CPUS];
+    u32 tier[CAKE_TIER_MAX];     /* Latest sample */
+    u32 cpu[CAKE_MAX_CPUS];      /* Latest sample */
+};
+
+/* Flow state flags (4 bits in packed_info) */
 enum cake_flow_flags {
-    CAKE_FLOW_NEW = 1 << 0,  /* Task is newly created */
+    CAKE_FLOW_NEW          = 1 << 0,  /* Task is newly created */
+    CAKE_FLOW_WARMUP_MAIN  = 1 << 1,  /* Launching game's main thread: boosted */
+    CAKE_FLOW_WARMUP_LOAD  = 1 << 2,  /* Thread spawned in a launch burst: Bulk */
+    CAKE_FLOW_PINNED       = 1 << 3,  /* Tier fixed by a --tier-rule: never reclassified */
+};
+
+/* Nice levels -20..19 — size of the nice → initial tier table */
+#define CAKE_NICE_LEVELS 40
+
+/* Process-name tier rules (--tier-rule) — first match wins */
+#define CAKE_MAX_TIER_RULES 32
+/* Manual TID → tier pins set from the TUI task view (tier_pins map) */
+#define CAKE_MAX_TIER_PINS 256
+
+struct cake_tier_rule {
+    char comm[16];         /* Name or prefix, NUL-padded (TASK_COMM_LEN) */
+    u8 len;                /* Bytes of comm to compare */
+    u8 prefix;             /* 1 = comm* (prefix match), 0 = exact */
+    u8 tier;               /* Tier to pin (0-3) */
+    u8 _pad[5];
+};
+
+/* Where cake_select_cpu placed a wakeup, nearest first: the waker's CPU
+ * (SYNC), prev_cpu, its SMT sibling, its L2 cluster (E-core modules), its
+ * LLC, another LLC on its NUMA node, a big core elsewhere (hybrid), any
+ * other CPU, or nowhere idle (queued for enqueue) */
+enum cake_select_rung {
+    CAKE_SEL_SYNC    = 0,
+    CAKE_SEL_PREV    = 1,
+    CAKE_SEL_SIBLING = 2,
+    CAKE_SEL_CLUSTER = 3,
+    CAKE_SEL_LLC     = 4,
+    CAKE_SEL_NODE    = 5,
+    CAKE_SEL_BIG     = 6,
+    CAKE_SEL_OTHER   = 7,
+    CAKE_SEL_QUEUED  = 8,
+    CAKE_SEL_MAX     = 9,
+};
+
+/* Per-tier runnable-wait histograms: bucket 0 = under 1µs, bucket b =
+ * [2^(b-1), 2^b) µs; the last bucket also takes anything longer */
+#define CAKE_WAIT_BUCKETS 32
+
+struct cake_wait_hist {
+    u64 bucket[CAKE_WAIT_BUCKETS];
+};
+
+/* Game-launch warm-up detection: a new process spawning warmup_threads
+ * threads within CAKE_WARMUP_DETECT_NS is treated as a launching game. */
+#define CAKE_WARMUP_DETECT_NS  (2ULL * 1000 * 1000 * 1000)  /* 2s */
+#define CAKE_WARMUP_MAX_BURST  32  /* Max --warmup-threads: TIDs kept until the burst is seen */
+
+/* Per-process launch tracking (keyed by tgid) */
+struct cake_launch {
+    u64 start_ns;          /* Process creation: the detection window opens */
+    u64 burst_ns;          /* Burst detection time: the warm-up window opens */
+    u32 nr_threads;        /* Threads spawned inside the detection window */
+    u32 active;            /* Burst detected — warm-up policy in force */
+    u32 tids[CAKE_WARMUP_MAX_BURST]; /* Threads counted toward the burst */
 };
 
 /* Per-task flow state - 64B aligned, first 16B coalesced for cake_stopping writes */
@@ -70,7 +154,19 @@ struct cake_task_ctx {
     /* --- Graduated backoff counter [Bytes 20-21] --- */
     u16 reclass_counter;   /* 2B: Per-task stop counter for per-tier backoff */
 
-    u8 __pad[42];          /* Pad to 64 bytes: 8+8+4+2+42 = 64 */
+    u8 __pad0[2];          /* 2B: Keep warmup_end 4B-aligned */
+
+    /* --- Launch warm-up deadline [Bytes 24-27] --- */
+    u32 warmup_end;        /* 4B: Warm-up expiry in ~ms (now >> 20), wraps 49 days */
+
+    /* --- Per-CPU wait accounting (stats builds) [Bytes 28-39] --- */
+    u32 stats_gen;         /* 4B: stats_gen run_delay_seen was taken in */
+    u64 run_delay_seen;    /* 8B: sched_info.run_delay at the last stop */
+
+    /* --- Migration accounting (stats builds) [Bytes 40-43] --- */
+    u32 last_cpu;          /* 4B: CPU of the previous run + 1 (0 = not run yet) */
+
+    u8 __pad[20];          /* Pad to 64 bytes: 8+8+4+2+2+4+4+8+4+20 = 64 */
 } __attribute__((aligned(64)));
 
 /* Bitfield layout for packed_info (write-set co-located, Rule 24 mask fusion):
@@ -116,6 +212,8 @@ struct cake_task_ctx {
 #define MBOX_IS_VICTIM(f)  ((f) & MBOX_VICTIM_BIT)
 #define MBOX_IS_IDLE(f)    ((f) & MBOX_IDLE_BIT)
 #define MBOX_IS_WARM(f)    ((f) & MBOX_WARM_BIT)
+/* Heavy = CPU-bound tier (Frame/Bulk) running — SMT co-location hazard */
+#define MBOX_IS_HEAVY(f)   (MBOX_GET_TIER(f) >= CAKE_TIER_FRAME)
 
 /* 64-byte mega-mailbox entry (single cache line = optimal L1 efficiency)
  * Per-CPU write isolation: each CPU writes ONLY its own entry.
@@ -125,7 +223,10 @@ struct mega_mailbox_entry {
     u8 flags;              /* [1:0]=tier — written by cake_tick */
     u8 dsq_hint;           /* DVFS perf target cache — written by cake_tick */
     u8 tick_counter;       /* 2-tick starvation gate — alternates rq lookup */
-    u8 __reserved[61];     /* Pad to 64B cache line, available for future use */
+    u8 run_tier;           /* tier + 1 of the running task, 0 = none (tier caps, EPP hints, SMT guard) */
+    u32 dyn_slice_ns;      /* load-scaled slice ceiling — written by cake_dispatch (slice bounds only) */
+    u8 smt_deferred;       /* dispatch left work queued for --smt-guard, kick when the sibling stops */
+    u8 __reserved[55];     /* Pad to 64B cache line, available for future use */
 } __attribute__((aligned(64)));
 
 /* Statistics shared with userspace */
@@ -134,15 +235,89 @@ struct cake_stats {
     u64 nr_old_flow_dispatches;    /* Tasks dispatched from old-flow */
     u64 nr_tier_dispatches[CAKE_TIER_MAX]; /* Per-tier dispatch counts */
     u64 nr_starvation_preempts_tier[CAKE_TIER_MAX]; /* Per-tier starvation preempts */
-    u64 _pad[22];                  /* Pad to 256 bytes: (2+4+4+22)*8 = 256 */
+    u64 nr_starvation_immune_tier[CAKE_TIER_MAX];   /* Per-tier preempts skipped by immunity */
+    u64 nr_smt_spread;             /* Heavy wakeups moved off a heavy sibling */
+    u64 nr_smt_colocated;          /* Heavy wakeups left next to a heavy sibling */
+    u64 nr_rebalance_passes;       /* Rebalancer passes that found an imbalance */
+    u64 nr_rebalance_moves;        /* Tasks moved between LLC DSQs by the rebalancer */
+    u64 nr_warmup_launches;        /* Game-launch bursts detected */
+    u64 nr_warmup_dispatches;      /* Dispatches with warm-up tier override */
+    u64 nr_idle_promotions;        /* Bulk enqueues promoted to Frame while user idle */
+    u64 nr_cap_deferrals[CAKE_TIER_MAX]; /* Dispatch passes that left a capped tier queued */
+    u64 nr_cap_queued[CAKE_TIER_MAX];    /* Capped tasks left queued, summed over those passes */
+    u64 nr_runs;                   /* Runs ended on this CPU */
+    u64 wait_ns;                   /* Runnable wait before those runs (sched_info) */
+    u64 tier_runtime_ns[CAKE_TIER_MAX];  /* CPU time consumed, by the tier it ran in */
+    u64 nr_migrate_local;          /* Runs on a new CPU in the same LLC */
+    u64 nr_migrate_cross_llc[CAKE_TIER_MAX]; /* Runs on another LLC (CCD), by tier */
+    u64 nr_migrate_p_to_e;         /* Hybrid: big core → little core */
+    u64 nr_migrate_e_to_p;         /* Hybrid: little core → big core */
+    u64 nr_migrate_cross_node;     /* Runs on another NUMA node */
+    u64 nr_select[CAKE_SEL_MAX];   /* select_cpu outcomes by rung (enum cake_select_rung) */
+    /* Starvation preempts by [waiter tier][victim tier]; waiter row
+     * CAKE_TIER_MAX = waiter already in the local DSQ (tier unknown) */
+    u64 preempt_matrix[CAKE_TIER_MAX + 1][CAKE_TIER_MAX];
+    /* 576 with no padding left: (2+4+4+4+2+2+2+1+4+4+2+4+8+9+20)*8 = 576 */
 } __attribute__((aligned(64)));
 
+/* Per-task record emitted by the dump_tasks iterator (TUI task/cgroup views).
+ * Fixed 64-byte layout: userspace reads the seq_file as a record array. */
+struct cake_task_rec {
+    u32 pid;
+    u32 tgid;
+    u64 runtime_ns;        /* se.sum_exec_runtime */
+    u64 wait_ns;           /* sched_info.run_delay (runnable, not running) */
+    u64 cgid;              /* cgroup v2 id (kernfs node id = cgroupfs inode) */
+    u64 nr_runs;           /* sched_info.pcount (times run on a CPU) */
+    u16 avg_runtime_us;    /* EMA runtime from cake_task_ctx */
+    u8 tier;               /* Current tier (0-3) */
+    u8 flags;              /* Flow flags (CAKE_FLOW_*) */
+    u32 cpu;               /* CPU the task last ran on (IRQ steering) */
+    char comm[16];         /* TASK_COMM_LEN */
+};
+
+/* Scheduling events (--events, --trace) — one ring buffer record per event.
+ * --trace resizes the buffer at load: it carries every enqueue/run/stop. */
+#define CAKE_EVENTS_RINGBUF_SZ (256 * 1024)
+#define CAKE_TRACE_RINGBUF_SZ  (16 * 1024 * 1024)
+
+enum cake_event_kind {
+    CAKE_EV_NEW_FLOW    = 1,  /* Task context created: from = to = initial tier */
+    CAKE_EV_TIER_CHANGE = 2,  /* avg_runtime reclassification: from → to */
+    CAKE_EV_FLOW_SPENT  = 3,  /* DRR++ deficit exhausted: new-flow bonus dropped */
+    CAKE_EV_STARVATION  = 4,  /* Starvation preempt: from = victim tier, to = waiter
+                               * tier (CAKE_TIER_MAX = waiter in the local DSQ) */
+    /* --trace only: one per scheduling step */
+    CAKE_EV_ENQUEUE     = 5,  /* Tier-path enqueue on an LLC DSQ: from = to = tier */
+    CAKE_EV_RUN         = 6,  /* Started running (dispatched): from = to = tier */
+    CAKE_EV_STOP        = 7,  /* Stopped: from = tier, to = 1 if still runnable
+                               * (preempted / slice expired), 0 if it blocked */
+};
+
+/* Fixed 40-byte layout, read by userspace with read_unaligned */
+struct cake_event {
+    u64 ts_ns;             /* bpf_ktime_get_boot_ns (CLOCK_BOOTTIME, as ftrace "boot") */
+    u32 pid;
+    u32 cpu;
+    u8 kind;               /* enum cake_event_kind */
+    u8 from;
+    u8 to;
+    u8 _pad;
+    u32 tgid;
+    char comm[16];         /* TASK_COMM_LEN */
+};
+
 /* Topology flags - enables zero-cost specialization (false = code path eliminated by verifier) */
 
 /* Default values (Gaming profile) */
 #define CAKE_DEFAULT_QUANTUM_NS         (2 * 1000 * 1000)   /* 2ms */
 #define CAKE_DEFAULT_NEW_FLOW_BONUS_NS  (8 * 1000 * 1000)   /* 8ms */
 #define CAKE_DEFAULT_STARVATION_NS      (100 * 1000 * 1000) /* 100ms */
+#define CAKE_DEFAULT_REBALANCE_NS       0                   /* off: --rebalance-interval opts in */
+#define CAKE_DEFAULT_REBALANCE_THRESH   4                   /* queued tasks */
+#define CAKE_DEFAULT_WARMUP_NS          0                   /* off: --warmup opts in */
+#define CAKE_DEFAULT_WARMUP_THREADS     8                   /* threads in 2s */
+#define CAKE_IDLE_SLICE_SHIFT           2                   /* 4x slices while user idle */
 
 /* Default tier arrays (Gaming profile) — 4 tiers */
 
@@ -152,6 +327,12 @@ struct cake_stats {
 #define CAKE_DEFAULT_STARVATION_T2  40000000   /* Frame: 40ms */
 #define CAKE_DEFAULT_STARVATION_T3  100000000  /* Bulk: 100ms */
 
+/* Starvation-preemption immunity (bit N = tier N immune).
+ * Immune tiers are only preempted by starvation when the waiter is the
+ * same or a higher tier — a Bulk rescue never interrupts the audio mixer.
+ * Hard ceiling (starvation_ns) still applies so immunity can't wedge a CPU. */
+#define CAKE_DEFAULT_IMMUNE_MASK    ((1 << CAKE_TIER_CRITICAL) | (1 << CAKE_TIER_INTERACT))
+
 /* Tier quantum multipliers (fixed-point, 1024 = 1.0x)
  * Power-of-4 progression: each tier gets 4x the quantum of the tier above.
  * T2 at 4ms lets 300fps+ render threads complete entire frames without preemption.
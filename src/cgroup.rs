@@ -0,0 +1,255 @@
+// SPDX-License-Identifier: GPL-2.0
+// Cgroup module - maps cgroup v2 ids to paths and rolls per-task stats up the hierarchy
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write as _;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+
+use crate::stats::{ProcessStat, TIER_NAMES};
+
+/// cgroup v2 mount point
+pub const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+
+/// One cgroup with totals over itself and all descendants
+#[derive(Debug, Clone, PartialEq)]
+pub struct CgroupNode {
+    /// Path relative to the cgroup root ("/" for the root itself)
+    pub path: String,
+    pub depth: usize,
+    pub tasks: u32,
+    pub tier_counts: [u32; 4],
+    /// % of all CPU time used by cake-managed tasks this interval
+    pub cpu_share: f64,
+    /// Runs started per second; each run is one dispatch
+    pub runs_per_s: f64,
+    pub wait_ms_per_s: f64,
+}
+
+impl CgroupNode {
+    /// Last path component, for tree display
+    pub fn name(&self) -> &str {
+        if self.path == "/" {
+            "/"
+        } else {
+            self.path.rsplit('/').next().unwrap_or(&self.path)
+        }
+    }
+
+    /// Mean runnable wait before each run, in µs (0 without runs)
+    pub fn avg_wait_us(&self) -> f64 {
+        if self.runs_per_s > 0.0 {
+            self.wait_ms_per_s * 1000.0 / self.runs_per_s
+        } else {
+            0.0
+        }
+    }
+}
+
+/// cgroup id → path. On cgroup v2 the kernfs node id is the directory inode.
+pub fn cgroup_paths(root: &Path) -> HashMap<u64, String> {
+    fn walk(root: &Path, dir: &Path, out: &mut HashMap<u64, String>) {
+        let Ok(meta) = std::fs::metadata(dir) else {
+            return;
+        };
+        let rel = dir.strip_prefix(root).unwrap_or(dir);
+        out.insert(meta.ino(), format!("/{}", rel.display()));
+
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            if entry.file_type().is_ok_and(|t| t.is_dir()) {
+                walk(root, &entry.path(), out);
+            }
+        }
+    }
+
+    let mut out = HashMap::new();
+    walk(root, root, &mut out);
+    out
+}
+
+/// Parent of a cgroup path ("/a/b" → "/a", "/a" → "/")
+fn parent(path: &str) -> Option<&str> {
+    match path.rfind('/') {
+        _ if path == "/" => None,
+        Some(0) => Some("/"),
+        Some(i) => Some(&path[..i]),
+        None => None,
+    }
+}
+
+/// Roll thread rates into every cgroup on their path, pre-order by path.
+/// Only cgroups that contain cake-managed tasks (and their ancestors) appear.
+pub fn build_tree(procs: &[ProcessStat], paths: &HashMap<u64, String>) -> Vec<CgroupNode> {
+    let total_cpu: f64 = procs.iter().map(|p| p.cpu_pct).sum();
+    let mut nodes: BTreeMap<String, CgroupNode> = BTreeMap::new();
+
+    for t in procs.iter().flat_map(|p| &p.threads) {
+        let mut path = paths
+            .get(&t.cgid)
+            .map(String::as_str)
+            .unwrap_or("/(unknown)");
+        loop {
+            let node = nodes.entry(path.to_string()).or_insert_with(|| CgroupNode {
+                path: path.to_string(),
+                depth: if path == "/" {
+                    0
+                } else {
+                    path.matches('/').count()
+                },
+                tasks: 0,
+                tier_counts: [0; 4],
+                cpu_share: 0.0,
+                runs_per_s: 0.0,
+                wait_ms_per_s: 0.0,
+            });
+            node.tasks += 1;
+            node.tier_counts[(t.tier & 3) as usize] += 1;
+            if total_cpu > 0.0 {
+                node.cpu_share += t.cpu_pct * 100.0 / total_cpu;
+            }
+            node.runs_per_s += t.runs_per_s;
+            node.wait_ms_per_s += t.wait_ms_per_s;
+
+            match parent(path) {
+                Some(p) => path = p,
+                None => break,
+            }
+        }
+    }
+
+    // BTreeMap order on "/a", "/a/b", "/a-x" is not pre-order; sort by components
+    let mut out: Vec<CgroupNode> = nodes.into_values().collect();
+    out.sort_by(|a, b| a.path.split('/').cmp(b.path.split('/')));
+    out
+}
+
+/// Plain-text tree for `scx_cake ctl cgroups`
+pub fn render_tree(nodes: &[CgroupNode]) -> String {
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "{:<48} {:>6} {:>7} {:>9} {:>10} {:>10}   Tiers ({})",
+        "Cgroup",
+        "Tasks",
+        "CPU%",
+        "Runs/s",
+        "Wait ms/s",
+        "AvgWait µs",
+        TIER_NAMES.join("/")
+    );
+    for n in nodes {
+        let name = format!("{}{}", "  ".repeat(n.depth), n.name());
+        let _ = writeln!(
+            out,
+            "{:<48} {:>6} {:>7.1} {:>9.0} {:>10.2} {:>10.1}   {}",
+            name,
+            n.tasks,
+            n.cpu_share,
+            n.runs_per_s,
+            n.wait_ms_per_s,
+            n.avg_wait_us(),
+            n.tier_counts.map(|c| c.to_string()).join("/")
+        );
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::TaskRate;
+
+    fn thread(pid: u32, cgid: u64, tier: u8, cpu_pct: f64) -> TaskRate {
+        TaskRate {
+            pid,
+            cgid,
+            comm: String::new(),
+            tier,
+            cpu: 0,
+            avg_runtime_us: 0,
+            cpu_pct,
+            wait_ms_per_s: 1.0,
+            runs_per_s: 100.0,
+        }
+    }
+
+    fn procs(threads: Vec<TaskRate>) -> Vec<ProcessStat> {
+        let cpu_pct = threads.iter().map(|t| t.cpu_pct).sum();
+        vec![ProcessStat {
+            tgid: 1,
+            comm: String::new(),
+            cpu_pct,
+            wait_ms_per_s: 0.0,
+            tier_counts: [0; 4],
+            threads,
+        }]
+    }
+
+    fn paths() -> HashMap<u64, String> {
+        [
+            (1, "/"),
+            (2, "/user.slice"),
+            (3, "/user.slice/app-steam.scope"),
+            (4, "/system.slice"),
+        ]
+        .into_iter()
+        .map(|(id, p)| (id, p.to_string()))
+        .collect()
+    }
+
+    #[test]
+    fn test_parent() {
+        assert_eq!(parent("/a/b"), Some("/a"));
+        assert_eq!(parent("/a"), Some("/"));
+        assert_eq!(parent("/"), None);
+    }
+
+    #[test]
+    fn test_rolls_up_to_ancestors() {
+        let tree = build_tree(
+            &procs(vec![
+                thread(10, 3, 2, 60.0),
+                thread(11, 3, 2, 20.0),
+                thread(20, 4, 3, 20.0),
+            ]),
+            &paths(),
+        );
+        let by_path: HashMap<&str, &CgroupNode> =
+            tree.iter().map(|n| (n.path.as_str(), n)).collect();
+
+        assert_eq!(by_path["/"].tasks, 3);
+        assert_eq!(by_path["/"].cpu_share, 100.0);
+        assert_eq!(by_path["/user.slice"].cpu_share, 80.0);
+        assert_eq!(
+            by_path["/user.slice/app-steam.scope"].tier_counts,
+            [0, 0, 2, 0]
+        );
+        assert_eq!(by_path["/system.slice"].wait_ms_per_s, 1.0);
+        assert_eq!(by_path["/user.slice"].runs_per_s, 200.0);
+        // 2 ms/s of wait over 200 runs/s is 10 µs per run
+        assert_eq!(by_path["/user.slice"].avg_wait_us(), 10.0);
+    }
+
+    #[test]
+    fn test_preorder_children_follow_parent() {
+        let tree = build_tree(
+            &procs(vec![thread(10, 3, 1, 1.0), thread(20, 4, 1, 1.0)]),
+            &paths(),
+        );
+        let order: Vec<&str> = tree.iter().map(|n| n.path.as_str()).collect();
+        assert_eq!(
+            order,
+            [
+                "/",
+                "/system.slice",
+                "/user.slice",
+                "/user.slice/app-steam.scope"
+            ]
+        );
+        assert_eq!(tree[3].depth, 2);
+        assert_eq!(tree[3].name(), "app-steam.scope");
+    }
+}
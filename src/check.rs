@@ -0,0 +1,427 @@
+// SPDX-License-Identifier: GPL-2.0
+// Check module - --check-config dry run: report what would be loaded, then exit
+
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+use anyhow::{anyhow, Result};
+use serde_json::{json, Value};
+
+use crate::bpf_skel::types;
+use crate::stats::SELECT_RUNGS;
+use crate::topology::{CpuMask, TopologyInfo, MAX_CPUS};
+use crate::{detect_topology, irq, open_configured, Args};
+
+const TIER_NAMES: [&str; 4] = ["Critical", "Interactive", "Frame", "Bulk"];
+
+/// Per-tier slice, starvation and wait budget decoded from the fused tier configs
+fn format_tiers(tier_configs: &[u64; 8], quantum_ns: u64, immune: u8, caps: &[u32; 4]) -> String {
+    let mut out = String::from("Tier         Mult   Slice     Starve     Budget    Immune  Cap\n");
+    for (t, name) in TIER_NAMES.iter().enumerate() {
+        let cfg = tier_configs[t];
+        let mult = cfg & 0xFFF;
+        let budget_ns = ((cfg >> 28) & 0xFFFF) << 10;
+        let starve_ns = (cfg >> 44) << 10;
+        let _ = writeln!(
+            out,
+            "{:<12} {:>4.2}x {:>7}µs {:>8}µs {:>7}µs  {:<6}  {}",
+            name,
+            mult as f64 / 1024.0,
+            ((quantum_ns * mult) >> 10) / 1000,
+            starve_ns / 1000,
+            budget_ns / 1000,
+            if immune & (1 << t) != 0 { "yes" } else { "no" },
+            match caps[t] {
+                0 => "-".to_string(),
+                n => n.to_string(),
+            }
+        );
+    }
+    out
+}
+
+/// CPU → LLC / node / sibling / core type / capacity / CPPC perf / managed
+/// map (what BPF placement sees)
+fn format_cpus(topo: &TopologyInfo) -> String {
+    let mut out = String::from("CPU  LLC  Node  Sibling  Type   Cap  Perf  Managed\n");
+    for cpu in 0..topo.nr_cpus.min(MAX_CPUS) {
+        if !topo.online_mask.test(cpu) {
+            let _ = writeln!(out, "{:>3}  offline", cpu);
+            continue;
+        }
+        let _ = writeln!(
+            out,
+            "{:>3}  {:>3}  {:>4}  {:>7}  {:<4}  {:>4}  {:>4}  {}",
+            cpu,
+            topo.cpu_llc_id[cpu],
+            topo.cpu_node_id[cpu],
+            topo.cpu_sibling_map[cpu],
+            if topo.cpu_is_big[cpu] != 0 { "P" } else { "E" },
+            topo.cpu_capacity[cpu],
+            match topo.cpu_perf[cpu] {
+                0 => "-".to_string(),
+                perf => perf.to_string(),
+            },
+            if topo.managed_mask.test(cpu) {
+                "yes"
+            } else if topo.isolated_mask.test(cpu) {
+                "isolated"
+            } else {
+                "no"
+            }
+        );
+    }
+    out
+}
+
+/// Each managed CPU's idle search order (TopologyInfo::preference), one
+/// line per CPU with the rungs that have CPUs in them
+fn format_preference(topo: &TopologyInfo) -> String {
+    let mut out = String::from("Idle search order by prev CPU:\n");
+    for cpu in 0..topo.nr_cpus.min(MAX_CPUS) {
+        if !(topo.managed_mask & topo.online_mask).test(cpu) {
+            continue;
+        }
+        let rungs: Vec<String> = topo
+            .preference(cpu)
+            .iter()
+            .enumerate()
+            .filter(|(_, cpus)| !cpus.is_empty())
+            .map(|(rung, cpus)| format!("{} {}", SELECT_RUNGS[rung + 1], cpu_list(cpus)))
+            .collect();
+        let _ = writeln!(out, "{:>3}  {}", cpu, rungs.join(" | "));
+    }
+    out
+}
+
+/// Everything detection found, per CPU and per LLC, with the preference,
+/// --preferred-cores and --powersave orders derived from it
+fn topology_json(topo: &TopologyInfo) -> Value {
+    let cpus: Vec<Value> = (0..topo.nr_cpus.min(MAX_CPUS))
+        .map(|cpu| {
+            if !topo.online_mask.test(cpu) {
+                return json!({ "cpu": cpu, "online": false });
+            }
+            let preference: serde_json::Map<String, Value> = topo
+                .preference(cpu)
+                .into_iter()
+                .enumerate()
+                .map(|(rung, cpus)| (SELECT_RUNGS[rung + 1].to_string(), json!(cpus)))
+                .collect();
+            json!({
+                "cpu": cpu,
+                "online": true,
+                "managed": topo.managed_mask.test(cpu),
+                "isolated": topo.isolated_mask.test(cpu),
+                "llc": topo.cpu_llc_id[cpu],
+                "node": topo.cpu_node_id[cpu],
+                "core": topo.cpu_core_id[cpu],
+                "sibling": topo.cpu_sibling_map[cpu],
+                "cluster": mask_cpus(topo.cpu_cluster_mask[cpu]),
+                "big": topo.cpu_is_big[cpu] != 0,
+                "capacity": topo.cpu_capacity[cpu],
+                "perf": topo.cpu_perf[cpu],
+                "dsq": topo.cpu_dsq_id[cpu],
+                "preference": preference,
+            })
+        })
+        .collect();
+    let llcs: Vec<Value> = (0..topo.nr_llcs)
+        .map(|llc| {
+            json!({
+                "llc": llc,
+                "node": topo.llc_node_id[llc],
+                "cpus": mask_cpus(topo.llc_cpu_mask[llc]),
+                "cache_kb": topo.llc_cache_kb[llc],
+            })
+        })
+        .collect();
+    json!({
+        "nr_cpus": topo.nr_cpus,
+        "nr_online": topo.nr_online(),
+        "nr_llcs": topo.nr_llcs,
+        "nr_nodes": topo.nr_nodes,
+        "smt": topo.smt_enabled,
+        "hybrid": topo.has_hybrid_cores,
+        "clusters": topo.has_clusters,
+        "capacity": topo.has_capacity,
+        "preferred_cores": topo.has_preferred_cores,
+        "dual_ccd": topo.has_dual_ccd,
+        "vcache_llc": topo.vcache_llc(),
+        "online": mask_cpus(topo.online_mask),
+        "managed": mask_cpus(topo.managed_mask & topo.online_mask),
+        "isolated": mask_cpus(topo.isolated_mask),
+        "big": mask_cpus(topo.big_cpu_mask & topo.online_mask),
+        "perf_order": topo.perf_order(),
+        "packing_order": topo.packing_order(),
+        "llcs": llcs,
+        "cpus": cpus,
+    })
+}
+
+fn mask_cpus(mask: CpuMask) -> Vec<usize> {
+    mask.iter().collect()
+}
+
+fn cpu_list(cpus: &[usize]) -> String {
+    mask_list(cpus.iter().copied().collect())
+}
+
+pub(crate) fn mask_list(mask: CpuMask) -> String {
+    let cpus: BTreeSet<u32> = mask.iter().map(|c| c as u32).collect();
+    irq::format_cpu_list(&cpus)
+}
+
+/// Tunables, tiers and placement as loaded into rodata: the body of
+/// --check-config and of the TUI config tab
+pub(crate) fn format_rodata(rodata: &types::rodata, topo: &TopologyInfo) -> String {
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "Tunables:     quantum={}µs new_flow_bonus={}µs starvation={}µs",
+        rodata.quantum_ns / 1000,
+        rodata.new_flow_bonus_ns / 1000,
+        rodata.starvation_ns / 1000
+    );
+    if rodata.has_slice_bounds {
+        let _ = writeln!(
+            out,
+            "Slices:       {}-{}µs, scaled by runnable tasks per CPU",
+            rodata.slice_min_ns / 1000,
+            rodata.slice_max_ns / 1000
+        );
+    }
+    match rodata.rebalance_imbalance_pct {
+        _ if rodata.rebalance_interval_ns == 0 => out.push_str("Rebalance:    off\n"),
+        0 => {
+            let _ = writeln!(
+                out,
+                "Rebalance:    every {}ms above {} queued tasks",
+                rodata.rebalance_interval_ns / 1_000_000,
+                rodata.rebalance_threshold
+            );
+        }
+        pct => {
+            let _ = writeln!(
+                out,
+                "Rebalance:    every {}ms, Bulk past {}% more queued per CPU",
+                rodata.rebalance_interval_ns / 1_000_000,
+                pct
+            );
+        }
+    }
+    match rodata.warmup_ns {
+        0 => out.push_str("Warm-up:      off\n"),
+        ns => {
+            let _ = writeln!(
+                out,
+                "Warm-up:      {}s after {} thread spawns",
+                ns / 1_000_000_000,
+                rodata.warmup_threads
+            );
+        }
+    }
+    out.push('\n');
+    out.push_str(&format_tiers(
+        &rodata.tier_configs,
+        rodata.quantum_ns,
+        rodata.starvation_immune_mask,
+        &rodata.tier_cap,
+    ));
+    out.push('\n');
+    let _ = writeln!(
+        out,
+        "Topology:     {} CPUs, {} LLC DSQ(s), {} NUMA node(s), SMT {}, hybrid {}",
+        topo.nr_online(),
+        rodata.nr_llcs,
+        topo.nr_nodes,
+        if rodata.has_smt { "on" } else { "off" },
+        if rodata.has_hybrid { "on" } else { "off" }
+    );
+    if rodata.has_primary {
+        let tiers: Vec<&str> = (0..4)
+            .filter(|t| rodata.primary_tier_mask & (1 << t) != 0)
+            .map(|t| TIER_NAMES[t])
+            .collect();
+        let _ = writeln!(
+            out,
+            "Primary:      CPUs {} (LLC {}) for {}",
+            mask_list(CpuMask(rodata.primary_cpus)),
+            rodata.primary_llc,
+            tiers.join(", ")
+        );
+    }
+    if rodata.has_secondary {
+        let tiers: Vec<&str> = (0..4)
+            .filter(|t| rodata.secondary_tier_mask & (1 << t) != 0)
+            .map(|t| TIER_NAMES[t])
+            .collect();
+        let _ = writeln!(
+            out,
+            "Secondary:    CPUs {} (LLC {}) preferred by {}",
+            mask_list(CpuMask(rodata.secondary_cpus)),
+            rodata.secondary_llc,
+            tiers.join(", ")
+        );
+    }
+    if rodata.smt_avoid_mask != 0 {
+        let tiers: Vec<&str> = (0..4)
+            .filter(|t| rodata.smt_avoid_mask & (1 << t) != 0)
+            .map(|t| TIER_NAMES[t])
+            .collect();
+        let _ = writeln!(out, "SMT avoid:    {}", tiers.join(", "));
+    }
+    if rodata.smt_guard_mask != 0 {
+        let tiers: Vec<&str> = (0..4)
+            .filter(|t| rodata.smt_guard_mask & (1 << t) != 0)
+            .map(|t| TIER_NAMES[t])
+            .collect();
+        let _ = writeln!(out, "SMT guard:    {}", tiers.join(", "));
+    }
+    if rodata.prefcore_tier_mask != 0 {
+        let tiers: Vec<&str> = (0..4)
+            .filter(|t| rodata.prefcore_tier_mask & (1 << t) != 0)
+            .map(|t| TIER_NAMES[t])
+            .collect();
+        let order: Vec<String> = topo.perf_order().iter().map(|c| c.to_string()).collect();
+        let _ = writeln!(
+            out,
+            "Preferred:    {} (CPUs best first: {})",
+            tiers.join(", "),
+            order.join(",")
+        );
+    }
+    if rodata.has_powersave {
+        let tiers: Vec<&str> = (0..4)
+            .filter(|t| rodata.powersave_tier_mask & (1 << t) != 0)
+            .map(|t| TIER_NAMES[t])
+            .collect();
+        let order: Vec<String> = topo.packing_order().iter().map(|c| c.to_string()).collect();
+        let _ = writeln!(
+            out,
+            "Powersave:    {} packed (CPUs in packing order: {})",
+            tiers.join(", "),
+            order.join(",")
+        );
+    }
+    let _ = writeln!(
+        out,
+        "Managed CPUs: {}",
+        mask_list(CpuMask(rodata.managed_cpus))
+    );
+    out
+}
+
+/// Parse, detect topology and open + configure the BPF skeleton without
+/// loading or attaching it, then print the resulting configuration
+pub fn run(args: &Args) -> Result<()> {
+    let topo = detect_topology(args)?;
+    let mut open_object = std::mem::MaybeUninit::uninit();
+    let open_skel = open_configured(args, &topo, &mut open_object)?;
+    let rodata = open_skel
+        .maps
+        .rodata_data
+        .as_ref()
+        .ok_or_else(|| anyhow!("BPF skeleton has no rodata"))?;
+
+    println!("Profile:      {:?}", args.profile);
+    println!(
+        "Mode:         {}",
+        match args.partial {
+            true => "partial (SCHED_EXT tasks only)",
+            false => "full (all fair-class tasks)",
+        }
+    );
+    if !args.tier_rule.is_empty() {
+        let rules: Vec<String> = args.tier_rule.iter().map(|r| r.to_string()).collect();
+        println!("Tier rules:   {}", rules.join(", "));
+    }
+    print!("{}", format_rodata(rodata, &topo));
+    print!("{}", format_cpus(&topo));
+    println!();
+    println!("Configuration OK: BPF skeleton opened and configured, not loaded or attached");
+    Ok(())
+}
+
+/// `scx_cake dump-topology`: what detection found, without BPF or root
+pub fn dump_topology(args: &Args, json: bool) -> Result<()> {
+    let topo = detect_topology(args)?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&topology_json(&topo))?);
+        return Ok(());
+    }
+    println!(
+        "Topology:     {} CPUs, {} LLC(s), {} NUMA node(s), SMT {}, hybrid {}",
+        topo.nr_online(),
+        topo.nr_llcs,
+        topo.nr_nodes,
+        if topo.smt_enabled { "on" } else { "off" },
+        if topo.has_hybrid_cores { "on" } else { "off" }
+    );
+    if topo.nr_online() < topo.nr_cpus.min(MAX_CPUS) {
+        println!("Online CPUs:  {}", mask_list(topo.online_mask));
+    }
+    println!(
+        "Managed CPUs: {}",
+        mask_list(topo.managed_mask & topo.online_mask)
+    );
+    if !topo.isolated_mask.is_empty() {
+        println!("Isolated:     {}", mask_list(topo.isolated_mask));
+    }
+    if topo.has_clusters {
+        let clusters: Vec<String> = topo.clusters().into_iter().map(mask_list).collect();
+        println!("L2 clusters:  {}", clusters.join(" "));
+    }
+    if topo.has_capacity {
+        let classes: Vec<String> = topo
+            .capacity_classes()
+            .into_iter()
+            .map(|(cap, cpus)| format!("{} ({})", cap, mask_list(cpus)))
+            .collect();
+        println!("Capacity:     {}", classes.join(", "));
+    }
+    if let Some(llc) = topo.vcache_llc() {
+        println!(
+            "V-Cache:      LLC {} (CPUs {}, {}M L3)",
+            llc,
+            mask_list(topo.llc_cpu_mask[llc]),
+            topo.llc_cache_kb[llc] / 1024
+        );
+    }
+    print!("{}", format_cpus(&topo));
+    println!();
+    print!("{}", format_preference(&topo));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Profile;
+
+    #[test]
+    fn test_format_tiers_decodes_fused_config() {
+        let configs = Profile::Gaming.tier_configs(
+            2000,
+            Profile::Gaming.tier_multiplier(),
+            Profile::Gaming.starvation_threshold(),
+        );
+        let table = format_tiers(&configs, 2_000_000, 0b0011, &[0, 0, 0, 4]);
+        let bulk = table.lines().nth(4).unwrap();
+        assert!(bulk.starts_with("Bulk"), "{}", bulk);
+        assert!(bulk.contains("1.40x"), "{}", bulk);
+        assert!(bulk.contains("2800µs"), "{}", bulk);
+        assert!(bulk.trim_end().ends_with("no      4"), "{}", bulk);
+        assert!(table.lines().nth(1).unwrap().contains("yes"));
+    }
+
+    #[test]
+    fn test_mask_list() {
+        assert_eq!(mask_list(0xff00ff.into()), "0-7,16-23");
+        assert_eq!(mask_list(CpuMask::cpu(5)), "5");
+        assert_eq!(
+            mask_list([62, 63, 64, 200].into_iter().collect()),
+            "62-64,200"
+        );
+    }
+}
@@ -0,0 +1,367 @@
+// SPDX-License-Identifier: GPL-2.0
+// Config module - TOML config file layered under the command line and SCX_CAKE_* env
+
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail, Context, Result};
+use clap::parser::ValueSource;
+use clap::{ArgMatches, CommandFactory, FromArgMatches, ValueEnum};
+use log::{info, warn};
+use serde::Deserialize;
+
+use crate::keymap::{KeyList, Keymap};
+use crate::theme::Theme;
+use crate::{Args, Command, Profile, Tier, UiMode};
+
+/// Current --config schema. Bump it when a key is renamed or changes
+/// meaning, and add the upgrade from the previous version to migrate_step.
+pub const CONFIG_VERSION: i64 = 1;
+
+/// Tunables accepted in --config. Keys are the long option names with
+/// underscores; enum values use the CLI spelling ("gaming", "bulk").
+#[derive(Debug, Default, Deserialize)]
+struct FileConfig {
+    profile: Option<String>,
+    quantum: Option<u64>,
+    new_flow_bonus: Option<u64>,
+    starvation: Option<u64>,
+    slice_min: Option<u64>,
+    slice_max: Option<u64>,
+    starvation_immune: Option<Vec<String>>,
+    no_starvation_immunity: Option<bool>,
+    tier_cap: Option<Vec<String>>,
+    tier_quantum: Option<Vec<String>>,
+    tier_starvation: Option<Vec<String>>,
+    nice_map: Option<Vec<String>>,
+    tier_rule: Option<Vec<String>>,
+    primary_domain: Option<String>,
+    x3d: Option<bool>,
+    primary_tiers: Option<Vec<String>>,
+    disable_smt: Option<Vec<String>>,
+    smt_guard: Option<Vec<String>>,
+    preferred_cores: Option<Vec<String>>,
+    cpus: Option<String>,
+    topology_override: Option<PathBuf>,
+    include_isolated: Option<bool>,
+    partial: Option<bool>,
+    partial_comm: Option<Vec<String>>,
+    warmup: Option<u64>,
+    warmup_threads: Option<u32>,
+    rebalance_interval: Option<u64>,
+    rebalance_threshold: Option<u32>,
+    rebalance_imbalance: Option<u32>,
+    idle_relax: Option<u64>,
+    epp_hints: Option<String>,
+    powersave: Option<u32>,
+    irq_steer: Option<bool>,
+    irq_devices: Option<Vec<String>>,
+    irq_steer_tiers: Option<Vec<String>>,
+    schedule: Option<Vec<String>>,
+    schedule_file: Option<PathBuf>,
+    verbose: Option<bool>,
+    ui: Option<String>,
+    tui: Option<bool>,
+    theme: Option<String>,
+    no_color: Option<bool>,
+    snapshot_dir: Option<PathBuf>,
+    alert: Option<Vec<String>>,
+    alert_bell: Option<bool>,
+    alert_log: Option<PathBuf>,
+    stats: Option<u64>,
+    headless_stats: Option<bool>,
+    stats_server: Option<bool>,
+    otlp_endpoint: Option<String>,
+    statsd: Option<String>,
+    interval: Option<u64>,
+    history_db: Option<PathBuf>,
+    log_stats: Option<PathBuf>,
+    summary_file: Option<PathBuf>,
+    events: Option<String>,
+    trace: Option<PathBuf>,
+    history_retention: Option<u64>,
+    exit_dump_len: Option<u32>,
+    /// [keys] table: TUI action name to key(s)
+    keys: Option<HashMap<String, KeyList>>,
+    /// Keys this version doesn't know — warned about, not fatal
+    #[serde(flatten)]
+    unknown: toml::Table,
+}
+
+fn value_enum<T: ValueEnum>(key: &str, v: &str) -> Result<T> {
+    T::from_str(v, true).map_err(|e| anyhow!("{}: {}", key, e))
+}
+
+fn tiers(key: &str, v: &[String]) -> Result<Vec<Tier>> {
+    v.iter().map(|t| value_enum(key, t)).collect()
+}
+
+fn parsed<T: FromStr>(key: &str, v: &[String]) -> Result<Vec<T>>
+where
+    T::Err: std::fmt::Display,
+{
+    v.iter()
+        .map(|s| s.parse().map_err(|e| anyhow!("{}: {}", key, e)))
+        .collect()
+}
+
+impl FileConfig {
+    /// Copy every key neither the command line nor the environment set into `args`
+    fn apply(self, args: &mut Args, matches: &ArgMatches) -> Result<()> {
+        let explicit = |id: &str| {
+            matches!(
+                matches.value_source(id),
+                Some(ValueSource::CommandLine | ValueSource::EnvVariable)
+            )
+        };
+
+        macro_rules! layer {
+            ($field:ident, |$v:ident| $conv:expr) => {
+                if let Some($v) = self.$field {
+                    if !explicit(stringify!($field)) {
+                        args.$field = $conv;
+                    }
+                }
+            };
+            ($field:ident) => {
+                layer!($field, |v| v)
+            };
+        }
+
+        layer!(profile, |v| value_enum::<Profile>("profile", &v)?);
+        layer!(quantum, |v| Some(v));
+        layer!(new_flow_bonus, |v| Some(v));
+        layer!(starvation, |v| Some(v));
+        layer!(slice_min, |v| Some(v));
+        layer!(slice_max, |v| Some(v));
+        layer!(starvation_immune, |v| Some(tiers("starvation_immune", &v)?));
+        layer!(no_starvation_immunity);
+        layer!(tier_cap, |v| parsed("tier_cap", &v)?);
+        layer!(tier_quantum, |v| parsed("tier_quantum", &v)?);
+        layer!(tier_starvation, |v| parsed("tier_starvation", &v)?);
+        layer!(nice_map, |v| parsed("nice_map", &v)?);
+        layer!(tier_rule, |v| parsed("tier_rule", &v)?);
+        layer!(primary_domain, |v| Some(
+            crate::topology::parse_cpu_list(&v).context("primary_domain")?
+        ));
+        layer!(x3d);
+        layer!(primary_tiers, |v| tiers("primary_tiers", &v)?);
+        layer!(disable_smt, |v| Some(tiers("disable_smt", &v)?));
+        layer!(smt_guard, |v| Some(tiers("smt_guard", &v)?));
+        layer!(preferred_cores, |v| Some(tiers("preferred_cores", &v)?));
+        layer!(cpus, |v| Some(
+            crate::topology::parse_cpu_list(&v).context("cpus")?
+        ));
+        layer!(topology_override, |v| Some(v));
+        layer!(include_isolated);
+        layer!(partial);
+        layer!(partial_comm);
+        layer!(warmup);
+        layer!(warmup_threads, |v| match v {
+            1..=32 => v,
+            _ => bail!("warmup_threads: {} is not in 1-32", v),
+        });
+        layer!(rebalance_interval);
+        layer!(rebalance_threshold);
+        layer!(rebalance_imbalance);
+        layer!(idle_relax);
+        layer!(epp_hints, |v| Some(
+            v.parse().map_err(|e| anyhow!("epp_hints: {}", e))?
+        ));
+        layer!(powersave, |v| match v {
+            1..=100 => Some(v),
+            _ => bail!("powersave: {} is not a percentage (1-100)", v),
+        });
+        layer!(irq_steer);
+        layer!(irq_devices);
+        layer!(irq_steer_tiers, |v| tiers("irq_steer_tiers", &v)?);
+        layer!(schedule, |v| parsed("schedule", &v)?);
+        layer!(schedule_file, |v| Some(v));
+        layer!(verbose);
+        layer!(ui, |v| value_enum::<UiMode>("ui", &v)?);
+        layer!(tui);
+        layer!(theme, |v| value_enum::<Theme>("theme", &v)?);
+        layer!(no_color);
+        layer!(snapshot_dir, |v| Some(v));
+        layer!(alert, |v| parsed("alert", &v)?);
+        layer!(alert_bell);
+        layer!(alert_log, |v| Some(v));
+        layer!(stats, |v| Some(v));
+        layer!(headless_stats);
+        layer!(stats_server);
+        layer!(otlp_endpoint, |v| Some(v));
+        layer!(statsd, |v| Some(v));
+        layer!(interval);
+        layer!(history_db, |v| Some(v));
+        layer!(log_stats, |v| Some(v));
+        layer!(summary_file, |v| Some(v));
+        layer!(events, |v| Some(v));
+        layer!(trace, |v| Some(v));
+        layer!(history_retention);
+        layer!(exit_dump_len);
+        if let Some(keys) = &self.keys {
+            args.keys = Keymap::with_overrides(keys).context("keys")?;
+        }
+        Ok(())
+    }
+}
+
+/// Upgrade a table written for schema `version` to `version + 1`
+fn migrate_step(_table: &mut toml::Table, version: i64) -> Result<()> {
+    match version {
+        // Files from before `version` existed use the version 1 keys
+        0 => Ok(()),
+        _ => bail!("no migration from config version {}", version),
+    }
+}
+
+/// Parse a config file of any supported version into the current schema
+fn parse(text: &str) -> Result<FileConfig> {
+    let mut table: toml::Table = toml::from_str(text)?;
+    let version = match table.remove("version") {
+        None => 0,
+        Some(toml::Value::Integer(v)) if v >= 1 => v,
+        Some(v) => bail!("version must be a positive integer, got {}", v),
+    };
+    if version > CONFIG_VERSION {
+        bail!(
+            "config version {} is newer than this scx_cake understands ({})",
+            version,
+            CONFIG_VERSION
+        );
+    }
+    for v in version..CONFIG_VERSION {
+        migrate_step(&mut table, v)?;
+    }
+    if version < CONFIG_VERSION {
+        info!(
+            "Config is version {}, migrated to {}; add `version = {}` to silence this",
+            version, CONFIG_VERSION, CONFIG_VERSION
+        );
+    }
+    Ok(table.try_into()?)
+}
+
+fn from_matches(matches: &ArgMatches) -> Result<Args> {
+    let mut args = Args::from_arg_matches(matches).unwrap_or_else(|e| e.exit());
+    if let Some(Command::Run) = args.command {
+        args.command = None;
+    }
+    if let Some(path) = &args.config {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config {}", path.display()))?;
+        let file = parse(&text).with_context(|| format!("Invalid config {}", path.display()))?;
+        for key in file.unknown.keys() {
+            warn!("{}: unknown key '{}' ignored", path.display(), key);
+        }
+        file.apply(&mut args, matches)?;
+    }
+    Ok(args)
+}
+
+/// `scx_cake run [OPTIONS]` is bare `scx_cake [OPTIONS]`: drop the word so
+/// scheduler options may follow it
+fn strip_run(argv: impl IntoIterator<Item = OsString>) -> Vec<OsString> {
+    let mut argv: Vec<OsString> = argv.into_iter().collect();
+    if argv.get(1).is_some_and(|a| a == "run") {
+        argv.remove(1);
+    }
+    argv
+}
+
+/// Parse the command line and environment, then fill unset options from --config
+pub fn load() -> Result<Args> {
+    from_matches(&Args::command().get_matches_from(strip_run(std::env::args_os())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layered(argv: &[&str], toml_text: &str) -> Result<Args> {
+        let matches =
+            Args::command().try_get_matches_from(strip_run(argv.iter().map(OsString::from)))?;
+        let mut args = from_matches(&matches)?;
+        parse(toml_text)?.apply(&mut args, &matches)?;
+        Ok(args)
+    }
+
+    #[test]
+    fn test_file_fills_unset_options() {
+        let args = layered(
+            &["scx_cake"],
+            "profile = \"esports\"\nquantum = 1500\ninterval = 5\ntier_cap = [\"bulk=25%\"]\n\
+             tier_quantum = [\"critical=0.5\", \"bulk=4.0\"]\n\
+             tier_starvation = [\"bulk=500000\"]",
+        )
+        .unwrap();
+        assert_eq!(args.profile, Profile::Esports);
+        assert_eq!(args.quantum, Some(1500));
+        assert_eq!(args.interval, 5);
+        assert_eq!(args.tier_cap.len(), 1);
+        assert_eq!(args.tier_multiplier()[..4], [512, 1024, 1229, 4095]);
+        assert_eq!(args.tier_starvation()[3], 500_000_000);
+
+        let args = layered(&["scx_cake"], "[keys]\nheatmap = \"H\"").unwrap();
+        assert_eq!(args.keys.remapped().len(), 1);
+    }
+
+    #[test]
+    fn test_cli_overrides_file() {
+        let args = layered(
+            &["scx_cake", "--quantum", "3000", "--interval", "1"],
+            "quantum = 1500\ninterval = 5\nstarvation = 80000",
+        )
+        .unwrap();
+        assert_eq!(args.quantum, Some(3000));
+        assert_eq!(args.interval, 1, "explicit default still wins");
+        assert_eq!(args.starvation, Some(80000));
+    }
+
+    #[test]
+    fn test_run_is_bare_invocation() {
+        let args = layered(&["scx_cake", "run", "--quantum", "1500"], "").unwrap();
+        assert!(args.command.is_none());
+        assert_eq!(args.quantum, Some(1500));
+        let args = layered(&["scx_cake", "-p", "esports", "run"], "").unwrap();
+        assert!(args.command.is_none());
+        assert!(layered(&["scx_cake", "dump-topology"], "")
+            .unwrap()
+            .command
+            .is_some());
+    }
+
+    #[test]
+    fn test_versions_and_unknown_keys() {
+        let file = parse("version = 1\nquantum = 1500\nsparse_threshold = 3").unwrap();
+        assert_eq!(file.quantum, Some(1500));
+        assert!(file.unknown.contains_key("sparse_threshold"));
+        assert!(parse("quantum = 1500").is_ok(), "unversioned files migrate");
+        assert!(parse("version = 2").is_err());
+        assert!(parse("version = 0").is_err());
+        assert!(parse("version = \"1\"").is_err());
+    }
+
+    #[test]
+    fn test_bad_values_are_errors() {
+        assert!(layered(&["scx_cake"], "profile = \"turbo\"").is_err());
+        assert!(layered(&["scx_cake"], "quantum = \"fast\"").is_err());
+        assert!(layered(&["scx_cake"], "irq_steer_tiers = [\"fast\"]").is_err());
+        assert!(layered(&["scx_cake"], "tier_quantum = [\"bulk=5.0\"]").is_err());
+        assert!(layered(&["scx_cake"], "tier_starvation = [\"bulk=2000000\"]").is_err());
+        assert!(layered(&["scx_cake"], "[keys]\nwarp = \"w\"").is_err());
+    }
+
+    #[test]
+    fn test_env_overrides_file() {
+        // Only this test sets SCX_CAKE_WARMUP_THREADS
+        std::env::set_var("SCX_CAKE_WARMUP_THREADS", "12");
+        let env = layered(&["scx_cake"], "warmup_threads = 4");
+        let cli = layered(&["scx_cake", "--warmup-threads", "6"], "warmup_threads = 4");
+        std::env::remove_var("SCX_CAKE_WARMUP_THREADS");
+        assert_eq!(env.unwrap().warmup_threads, 12);
+        assert_eq!(cli.unwrap().warmup_threads, 6);
+    }
+}
@@ -0,0 +1,294 @@
+// SPDX-License-Identifier: GPL-2.0
+// Control module - unix-socket server for live tunables, stats on/off and the `set` client
+
+use anyhow::{anyhow, bail, Result};
+use clap::{Args as ClapArgs, ValueEnum};
+use log::info;
+
+use crate::linesock::{self, LineServer};
+
+/// Listening socket of the running scheduler (root only)
+pub const SOCKET_PATH: &str = "/run/scx_cake/ctl.sock";
+
+/// Tunables that can change without reloading the scheduler (MICROSECONDS,
+/// except Stats)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Tunable {
+    /// Base time slice (--quantum)
+    Quantum,
+    /// New-flow bonus (--new-flow-bonus)
+    NewFlowBonus,
+    /// Starvation hard ceiling (--starvation)
+    Starvation,
+    /// BPF stats counting, "on" or "off"
+    Stats,
+}
+
+impl Tunable {
+    pub(crate) const ALL: [Tunable; 3] =
+        [Tunable::Quantum, Tunable::NewFlowBonus, Tunable::Starvation];
+
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            Tunable::Quantum => "quantum",
+            Tunable::NewFlowBonus => "new-flow-bonus",
+            Tunable::Starvation => "starvation",
+            Tunable::Stats => "stats",
+        }
+    }
+
+    /// Accepted range in microseconds (Stats is a switch, handled apart)
+    pub(crate) fn range(&self) -> (u64, u64) {
+        match self {
+            Tunable::Quantum => (50, 100_000),
+            Tunable::NewFlowBonus => (1, 1_000_000),
+            Tunable::Starvation => (1_000, 10_000_000),
+            Tunable::Stats => (0, 1),
+        }
+    }
+
+    /// `us` moved `steps` arrow-key steps (TUI config tab), kept in range
+    pub(crate) fn nudge(&self, us: u64, steps: i64) -> u64 {
+        let step = match self {
+            Tunable::Quantum => 100,
+            Tunable::NewFlowBonus => 500,
+            Tunable::Starvation => 10_000,
+            Tunable::Stats => 1,
+        };
+        let (lo, hi) = self.range();
+        us.saturating_add_signed(steps * step).clamp(lo, hi)
+    }
+}
+
+/// `scx_cake set [TUNABLE VALUE]`
+#[derive(Debug, Clone, ClapArgs)]
+pub struct SetArgs {
+    /// Tunable to change; omit to print the current values
+    #[arg(value_enum, requires = "value")]
+    tunable: Option<Tunable>,
+
+    /// New value in MICROSECONDS ("on" / "off" for stats), or "default"
+    /// for the value loaded at startup
+    value: Option<String>,
+}
+
+/// "default" restores the startup value; anything else is microseconds
+fn parse_value(s: &str) -> Result<Option<u64>, String> {
+    match s {
+        "default" => Ok(None),
+        _ => s.parse().map(Some).map_err(|e| format!("{}", e)),
+    }
+}
+
+/// Pointers to the BPF live_* BSS variables, in Tunable::ALL order, plus
+/// the enable_stats switch
+pub struct LiveTunables {
+    values: [*mut u64; 3],
+    stats: *mut u32,
+}
+
+// SAFETY: the BSS mmap outlives the server (ControlServer joins on drop and
+// is dropped before the skeleton); volatile loads/stores are the only access.
+unsafe impl Send for LiveTunables {}
+
+impl LiveTunables {
+    /// # Safety
+    /// Each pointer must point into the skeleton's mmap'd BSS and stay valid
+    /// until the ControlServer holding them is dropped.
+    pub unsafe fn new(
+        quantum: *mut u64,
+        new_flow_bonus: *mut u64,
+        starvation: *mut u64,
+        enable_stats: *mut u32,
+    ) -> Self {
+        Self {
+            values: [quantum, new_flow_bonus, starvation],
+            stats: enable_stats,
+        }
+    }
+
+    fn store(&self, values_us: &[u64; 3], stats: bool) {
+        for (ptr, us) in self.values.iter().zip(values_us) {
+            // SAFETY: see LiveTunables::new
+            unsafe { std::ptr::write_volatile(*ptr, us * 1000) }
+        }
+        // SAFETY: see LiveTunables::new
+        unsafe { std::ptr::write_volatile(self.stats, stats as u32) }
+    }
+
+    /// Current values in µs, read back rather than cached since the TUI
+    /// config tab writes the same variables; 0 (never set) is the default
+    fn load(&self, defaults: &[u64; 3]) -> [u64; 3] {
+        std::array::from_fn(|i| {
+            // SAFETY: see LiveTunables::new
+            match unsafe { std::ptr::read_volatile(self.values[i]) } {
+                0 => defaults[i],
+                ns => ns / 1000,
+            }
+        })
+    }
+
+    /// Read back rather than cached: the TUI flips the same flag
+    fn stats(&self) -> bool {
+        // SAFETY: see LiveTunables::new
+        unsafe { std::ptr::read_volatile(self.stats) != 0 }
+    }
+}
+
+/// Current values as one `name=us` line, stats last
+fn format_values(values: &[u64; 3], stats: bool) -> String {
+    Tunable::ALL
+        .iter()
+        .zip(values)
+        .map(|(t, v)| format!("{}={}", t.name(), v))
+        .chain([format!("stats={}", if stats { "on" } else { "off" })])
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Apply one request line to `values` / `stats`: `get`, `set NAME US`,
+/// `set NAME default` or `set stats on|off`
+fn handle_request(
+    line: &str,
+    values: &mut [u64; 3],
+    stats: &mut bool,
+    defaults: &[u64; 3],
+) -> Result<String> {
+    let mut words = line.split_whitespace();
+    match (words.next(), words.next(), words.next(), words.next()) {
+        (Some("get"), None, None, None) => Ok(format_values(values, *stats)),
+        (Some("set"), Some(name), Some(value), None) => {
+            let tunable =
+                Tunable::from_str(&name.replace('_', "-"), true).map_err(|e| anyhow!(e))?;
+            if tunable == Tunable::Stats {
+                *stats = match value {
+                    "on" => true,
+                    "off" => false,
+                    _ => bail!("stats must be on or off"),
+                };
+                return Ok(format_values(values, *stats));
+            }
+            let idx = tunable as usize;
+            let us = match parse_value(value).map_err(|e| anyhow!("bad value: {}", e))? {
+                Some(us) => us,
+                None => defaults[idx],
+            };
+            let (lo, hi) = tunable.range();
+            if !(lo..=hi).contains(&us) {
+                bail!("{} must be {}-{}µs", tunable.name(), lo, hi);
+            }
+            values[idx] = us;
+            Ok(format_values(values, *stats))
+        }
+        _ => bail!("expected 'get' or 'set NAME VALUE', got '{}'", line.trim()),
+    }
+}
+
+/// Background thread serving SOCKET_PATH; removes the socket when dropped
+pub struct ControlServer(LineServer);
+
+impl ControlServer {
+    /// `defaults` are the loaded (quantum, new_flow_bonus, starvation) in µs
+    pub fn spawn(live: LiveTunables, defaults: [u64; 3]) -> Result<Self> {
+        LineServer::spawn(SOCKET_PATH, 0o600, "cake-control", move || {
+            move |line: &str| {
+                let mut values = live.load(&defaults);
+                let mut stats = live.stats();
+                match handle_request(line, &mut values, &mut stats, &defaults) {
+                    Ok(reply) => {
+                        if line.starts_with("set") {
+                            live.store(&values, stats);
+                            info!("Control: {}", reply);
+                        }
+                        format!("ok {}", reply)
+                    }
+                    Err(e) => format!("error {}", e),
+                }
+            }
+        })
+        .map(Self)
+    }
+}
+
+/// Run `scx_cake set` against the running scheduler
+pub fn run_set(args: &SetArgs) -> Result<()> {
+    let request = match (args.tunable, &args.value) {
+        (Some(t), Some(value)) => format!("set {} {}", t.name(), value),
+        _ => "get".to_string(),
+    };
+
+    let reply = linesock::request(SOCKET_PATH, &request)?;
+
+    match reply.trim_end().split_once(' ') {
+        Some(("ok", values)) => {
+            println!("{}", values);
+            Ok(())
+        }
+        Some(("error", msg)) => bail!("{}", msg),
+        _ => bail!("unexpected reply: '{}'", reply.trim_end()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DEFAULTS: [u64; 3] = [2000, 8000, 100000];
+
+    #[test]
+    fn test_nudge_stays_in_range() {
+        assert_eq!(Tunable::Quantum.nudge(2000, 1), 2100);
+        assert_eq!(Tunable::Quantum.nudge(100, -3), 50);
+        assert_eq!(Tunable::Starvation.nudge(10_000_000, 1), 10_000_000);
+        assert_eq!(Tunable::NewFlowBonus.nudge(8000, -2), 7000);
+    }
+
+    #[test]
+    fn test_get_and_set() {
+        let mut values = DEFAULTS;
+        let mut stats = false;
+        assert_eq!(
+            handle_request("get\n", &mut values, &mut stats, &DEFAULTS).unwrap(),
+            "quantum=2000 new-flow-bonus=8000 starvation=100000 stats=off"
+        );
+        handle_request("set quantum 1500\n", &mut values, &mut stats, &DEFAULTS).unwrap();
+        handle_request(
+            "set new_flow_bonus 4000",
+            &mut values,
+            &mut stats,
+            &DEFAULTS,
+        )
+        .unwrap();
+        assert_eq!(values, [1500, 4000, 100000]);
+        handle_request("set quantum default", &mut values, &mut stats, &DEFAULTS).unwrap();
+        assert_eq!(values[0], 2000);
+        assert_eq!(
+            handle_request("set stats on", &mut values, &mut stats, &DEFAULTS).unwrap(),
+            "quantum=2000 new-flow-bonus=4000 starvation=100000 stats=on"
+        );
+        assert!(stats);
+    }
+
+    #[test]
+    fn test_rejects_bad_requests() {
+        let mut values = DEFAULTS;
+        let mut stats = false;
+        for line in [
+            "set quantum 0",
+            "set quantum 1500 extra",
+            "set sparse_threshold 3",
+            "set starvation abc",
+            "set stats 1",
+            "set stats default",
+            "reload",
+        ] {
+            assert!(
+                handle_request(line, &mut values, &mut stats, &DEFAULTS).is_err(),
+                "{}",
+                line
+            );
+        }
+        assert_eq!(values, DEFAULTS);
+        assert!(!stats);
+    }
+}
@@ -0,0 +1,258 @@
+// SPDX-License-Identifier: GPL-2.0
+// CSV log module - --log-stats appends one row of interval counters per --interval
+
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use log::{info, warn};
+
+use crate::bpf_skel::types::cake_stats;
+use crate::metrics::{Snapshot, StatsSource};
+use crate::stats::{SELECT_RUNGS, TIER_NAMES};
+
+/// Shutdown latency bound for the writer thread
+const POLL: Duration = Duration::from_millis(250);
+
+/// Column names after timestamp/interval_s, in counters() order (also
+/// the --statsd metric names)
+pub fn counter_columns() -> Vec<String> {
+    let per_tier = |stem: &str| {
+        TIER_NAMES
+            .iter()
+            .map(move |t| format!("{}_{}", stem, t.to_lowercase()))
+            .collect::<Vec<_>>()
+    };
+    let mut cols = per_tier("dispatch");
+    cols.extend(["new_flow".into(), "old_flow".into()]);
+    cols.extend(per_tier("starvation_preempts"));
+    cols.extend(per_tier("starvation_immune"));
+    cols.extend(per_tier("cap_deferrals"));
+    cols.extend(per_tier("cap_queued"));
+    cols.extend(per_tier("runtime_ns"));
+    cols.extend(per_tier("migrate_cross_llc"));
+    cols.extend(SELECT_RUNGS.iter().map(|r| format!("select_{}", r)));
+    cols.extend(
+        [
+            "smt_spread",
+            "smt_colocated",
+            "rebalance_passes",
+            "rebalance_moves",
+            "warmup_launches",
+            "warmup_dispatches",
+            "idle_promotions",
+            "runs",
+            "wait_ns",
+            "migrate_local",
+            "migrate_p_to_e",
+            "migrate_e_to_p",
+            "migrate_cross_node",
+        ]
+        .map(String::from),
+    );
+    cols
+}
+
+/// Every counter, in counter_columns() order
+pub fn counters(s: &cake_stats) -> Vec<u64> {
+    let mut v = s.nr_tier_dispatches.to_vec();
+    v.extend([s.nr_new_flow_dispatches, s.nr_old_flow_dispatches]);
+    v.extend(s.nr_starvation_preempts_tier);
+    v.extend(s.nr_starvation_immune_tier);
+    v.extend(s.nr_cap_deferrals);
+    v.extend(s.nr_cap_queued);
+    v.extend(s.tier_runtime_ns);
+    v.extend(s.nr_migrate_cross_llc);
+    v.extend(s.nr_select);
+    v.extend([
+        s.nr_smt_spread,
+        s.nr_smt_colocated,
+        s.nr_rebalance_passes,
+        s.nr_rebalance_moves,
+        s.nr_warmup_launches,
+        s.nr_warmup_dispatches,
+        s.nr_idle_promotions,
+        s.nr_runs,
+        s.wait_ns,
+        s.nr_migrate_local,
+        s.nr_migrate_p_to_e,
+        s.nr_migrate_e_to_p,
+        s.nr_migrate_cross_node,
+    ]);
+    v
+}
+
+/// Inverse of counters(): a cake_stats holding `v`, missing trailing
+/// values read as 0
+pub fn stats_from_counters(v: &[u64]) -> cake_stats {
+    let mut s = cake_stats::default();
+    let mut v = v.iter().copied();
+    let fields = [
+        &mut s.nr_tier_dispatches[..],
+        std::slice::from_mut(&mut s.nr_new_flow_dispatches),
+        std::slice::from_mut(&mut s.nr_old_flow_dispatches),
+        &mut s.nr_starvation_preempts_tier[..],
+        &mut s.nr_starvation_immune_tier[..],
+        &mut s.nr_cap_deferrals[..],
+        &mut s.nr_cap_queued[..],
+        &mut s.tier_runtime_ns[..],
+        &mut s.nr_migrate_cross_llc[..],
+        &mut s.nr_select[..],
+        std::slice::from_mut(&mut s.nr_smt_spread),
+        std::slice::from_mut(&mut s.nr_smt_colocated),
+        std::slice::from_mut(&mut s.nr_rebalance_passes),
+        std::slice::from_mut(&mut s.nr_rebalance_moves),
+        std::slice::from_mut(&mut s.nr_warmup_launches),
+        std::slice::from_mut(&mut s.nr_warmup_dispatches),
+        std::slice::from_mut(&mut s.nr_idle_promotions),
+        std::slice::from_mut(&mut s.nr_runs),
+        std::slice::from_mut(&mut s.wait_ns),
+        std::slice::from_mut(&mut s.nr_migrate_local),
+        std::slice::from_mut(&mut s.nr_migrate_p_to_e),
+        std::slice::from_mut(&mut s.nr_migrate_e_to_p),
+        std::slice::from_mut(&mut s.nr_migrate_cross_node),
+    ];
+    for field in fields {
+        for x in field {
+            *x = v.next().unwrap_or(0);
+        }
+    }
+    s
+}
+
+fn header() -> String {
+    format!(
+        "timestamp,interval_s,{},user_idle",
+        counter_columns().join(",")
+    )
+}
+
+/// One row of interval movement. Resets are epoch-based and leave the BPF
+/// counters alone, but a counter that went backwards counts from zero.
+fn row(ts: u64, cur: &Snapshot, prev: &Snapshot) -> String {
+    let deltas: Vec<String> = counters(&cur.stats)
+        .iter()
+        .zip(counters(&prev.stats))
+        .map(|(&now, before)| if now >= before { now - before } else { now })
+        .map(|d| d.to_string())
+        .collect();
+    format!(
+        "{},{:.3},{},{}",
+        ts,
+        cur.at.duration_since(prev.at).as_secs_f64(),
+        deltas.join(","),
+        cur.user_idle as u8
+    )
+}
+
+/// Background thread appending a CSV row every interval; flushes each row
+/// so the file is usable while the scheduler keeps running
+pub struct CsvLogger {
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl CsvLogger {
+    pub fn spawn(path: &Path, source: StatsSource, interval: Duration) -> Result<Self> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Failed to open stats log {}", path.display()))?;
+        // Appending to an existing log keeps its header
+        if file.metadata()?.len() == 0 {
+            writeln!(file, "{}", header())?;
+        }
+        info!(
+            "Logging stats to {} every {}s",
+            path.display(),
+            interval.as_secs()
+        );
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let stop_thread = stop.clone();
+        let handle = std::thread::Builder::new()
+            .name("cake-csv".into())
+            .spawn(move || log_loop(file, source, interval, stop_thread))
+            .context("Failed to start the stats log thread")?;
+        Ok(Self {
+            stop,
+            handle: Some(handle),
+        })
+    }
+}
+
+impl Drop for CsvLogger {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(h) = self.handle.take() {
+            let _ = h.join();
+        }
+    }
+}
+
+fn log_loop(mut file: File, source: StatsSource, interval: Duration, stop: Arc<AtomicBool>) {
+    let mut prev = source.snapshot();
+    while !stop.load(Ordering::Relaxed) {
+        std::thread::sleep(POLL);
+        if prev.at.elapsed() < interval {
+            continue;
+        }
+        let cur = source.snapshot();
+        let ts = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        if let Err(e) = writeln!(file, "{}", row(ts, &cur, &prev)).and_then(|_| file.flush()) {
+            warn!("Stats log write failed, logging stopped: {}", e);
+            return;
+        }
+        prev = cur;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    #[test]
+    fn test_row_matches_header() {
+        let at = Instant::now();
+        let mut prev = Snapshot {
+            stats: cake_stats::default(),
+            waits: Default::default(),
+            user_idle: false,
+            at,
+        };
+        prev.stats.nr_tier_dispatches = [10, 0, 0, 50];
+        let mut cur = Snapshot {
+            stats: prev.stats,
+            waits: Default::default(),
+            user_idle: true,
+            at: at + Duration::from_millis(1500),
+        };
+        cur.stats.nr_tier_dispatches = [25, 0, 0, 7];
+        cur.stats.nr_idle_promotions = 3;
+
+        let header = header();
+        let row = row(1_700_000_000, &cur, &prev);
+        assert_eq!(header.split(',').count(), row.split(',').count());
+        assert!(header.starts_with("timestamp,interval_s,dispatch_critical,"));
+        // Bulk went backwards (counter reset): its value is the movement since
+        assert!(row.starts_with("1700000000,1.500,15,0,0,7,"), "{}", row);
+        assert!(row.ends_with(",3,0,0,0,0,0,1"), "{}", row);
+    }
+
+    #[test]
+    fn test_stats_from_counters_round_trips() {
+        let v: Vec<u64> = (1..=counter_columns().len() as u64).collect();
+        assert_eq!(counters(&stats_from_counters(&v)), v);
+        assert_eq!(stats_from_counters(&v[..4]).nr_new_flow_dispatches, 0);
+    }
+}
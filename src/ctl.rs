@@ -0,0 +1,153 @@
+// SPDX-License-Identifier: GPL-2.0
+// Ctl module - inspect and steer a running scx_cake instance
+
+use std::path::Path;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use clap::{Subcommand, ValueEnum};
+
+use crate::stats::{self, TaskSampler, TierSummary, TopBy};
+use crate::{cgroup, schedule, Profile, Tier};
+
+/// Control/inspection subcommands (require a running scheduler)
+#[derive(Debug, Clone, Subcommand)]
+pub enum CtlCommand {
+    /// Cgroup hierarchy with per-cgroup tier distribution, CPU share,
+    /// dispatches and wait
+    Cgroups {
+        /// Sampling window in MILLISECONDS
+        #[arg(long, default_value_t = 1000)]
+        interval_ms: u64,
+    },
+
+    /// Busiest threads by CPU, wait or run count, e.g. who lives in Bulk
+    Top {
+        /// Sort key
+        #[arg(long, value_enum, default_value_t = TopBy::Cpu)]
+        by: TopBy,
+
+        /// Only threads currently in this tier
+        #[arg(long, value_enum)]
+        tier: Option<Tier>,
+
+        /// Threads to show
+        #[arg(short, long, default_value_t = 20)]
+        count: usize,
+
+        /// Sampling window in MILLISECONDS
+        #[arg(long, default_value_t = 1000)]
+        interval_ms: u64,
+    },
+
+    /// Override the --schedule profile until `ctl profile auto`.
+    ///
+    /// Takes effect within 5 seconds. Only a daemon started with
+    /// --schedule/--schedule-file follows the override.
+    #[command(verbatim_doc_comment)]
+    Profile {
+        /// Profile to force, or "auto" to follow the schedule again
+        #[arg(value_parser = parse_override)]
+        profile: Option<Profile>,
+    },
+}
+
+/// "auto" clears the override; anything else must name a profile
+fn parse_override(s: &str) -> Result<Option<Profile>, String> {
+    match s {
+        "auto" => Ok(None),
+        _ => Profile::from_str(s, true).map(Some),
+    }
+}
+
+/// Run a `ctl` subcommand against the running scheduler
+pub fn run(cmd: &CtlCommand) -> Result<()> {
+    match cmd {
+        CtlCommand::Cgroups { interval_ms } => {
+            let mut sampler = TaskSampler::open_pinned()?;
+            // First sample only primes the runtime/wait baselines
+            sampler.sample()?;
+            std::thread::sleep(Duration::from_millis((*interval_ms).max(100)));
+            let procs = sampler.sample()?;
+
+            let paths = cgroup::cgroup_paths(Path::new(cgroup::CGROUP_ROOT));
+            print!(
+                "{}",
+                cgroup::render_tree(&cgroup::build_tree(&procs, &paths))
+            );
+        }
+        CtlCommand::Top {
+            by,
+            tier,
+            count,
+            interval_ms,
+        } => {
+            let mut sampler = TaskSampler::open_pinned()?;
+            sampler.sample()?;
+            std::thread::sleep(Duration::from_millis((*interval_ms).max(100)));
+            let procs = sampler.sample()?;
+            let top = stats::top_tasks(&procs, *by, tier.map(|t| t as u8), *count);
+            print!("{}", stats::format_top(&top));
+        }
+        CtlCommand::Profile { profile } => {
+            schedule::write_override(*profile)?;
+            match profile {
+                Some(p) => println!("Profile override: {:?}", p),
+                None => println!("Profile override cleared, following the schedule"),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// `scx_cake monitor`: per-tier summary of the running scheduler every
+/// `interval` seconds until interrupted.
+///
+/// With `baseline`, an existing file is compared against: each tick prints
+/// this run's mean so far and its change from the file. A missing file is
+/// recorded instead, rewritten every tick with the mean of the run, so
+/// stopping at any point leaves a complete baseline behind.
+pub fn monitor(interval: u64, baseline: Option<&Path>) -> Result<()> {
+    let base = match baseline {
+        Some(path) if path.exists() => Some(load_baseline(path)?),
+        Some(path) => {
+            println!("Recording baseline to {} (Ctrl-C to stop)", path.display());
+            None
+        }
+        None => None,
+    };
+
+    let mut sampler = TaskSampler::open_pinned()?;
+    // First sample only primes the runtime/wait baselines
+    sampler.sample()?;
+    let mut mean = TierSummary::default();
+    for n in 1.. {
+        std::thread::sleep(Duration::from_secs(interval.max(1)));
+        let procs = sampler.sample()?;
+        mean.average_in(&TierSummary::from_procs(&procs), n);
+        match (&base, baseline) {
+            (Some(base), _) => println!("{}", stats::format_tier_diff(&mean, base, n)),
+            (None, Some(path)) => {
+                save_baseline(path, &mean)?;
+                println!("{}", stats::format_tier_summary(&procs));
+            }
+            (None, None) => println!("{}", stats::format_tier_summary(&procs)),
+        }
+    }
+    Ok(())
+}
+
+fn load_baseline(path: &Path) -> Result<TierSummary> {
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read baseline {}", path.display()))?;
+    serde_json::from_str(&text)
+        .with_context(|| format!("{} is not a monitor baseline", path.display()))
+}
+
+/// Write-then-rename, so an interrupted write never leaves half a baseline
+fn save_baseline(path: &Path, summary: &TierSummary) -> Result<()> {
+    let tmp = path.with_extension("tmp");
+    std::fs::write(&tmp, serde_json::to_string_pretty(summary)?)
+        .and_then(|_| std::fs::rename(&tmp, path))
+        .with_context(|| format!("Failed to write baseline {}", path.display()))
+}
@@ -0,0 +1,232 @@
+// SPDX-License-Identifier: GPL-2.0
+// EPP module - per-CPU energy_performance_preference hints from the tiers BPF runs on each CPU
+
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Result};
+use log::{info, warn};
+
+use crate::bpf_skel::types::mega_mailbox_entry;
+
+/// How often the per-CPU run_tier mailboxes are sampled
+const SAMPLE_PERIOD: Duration = Duration::from_millis(20);
+
+/// A CPU keeps the latency preference this long after it last ran a
+/// latency tier, so a frame loop's gaps don't flap EPP every sample
+const HOLD: Duration = Duration::from_secs(1);
+
+/// Values amd-pstate and intel_pstate (active mode) accept
+const PREFERENCES: [&str; 5] = [
+    "default",
+    "performance",
+    "balance_performance",
+    "balance_power",
+    "power",
+];
+
+fn epp_path(cpu: usize) -> String {
+    format!(
+        "/sys/devices/system/cpu/cpu{}/cpufreq/energy_performance_preference",
+        cpu
+    )
+}
+
+/// --epp-hints LATENCY:RELAXED — EPP while Critical, Interactive or Frame
+/// run on a CPU, and once only Bulk (or nothing) has run there for HOLD
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EppHints {
+    pub latency: String,
+    pub relaxed: String,
+}
+
+impl FromStr for EppHints {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (latency, relaxed) = s
+            .split_once(':')
+            .ok_or_else(|| format!("expected LATENCY:RELAXED, got '{}'", s))?;
+        for pref in [latency, relaxed] {
+            if !PREFERENCES.contains(&pref) {
+                return Err(format!(
+                    "unknown EPP '{}' (one of {})",
+                    pref,
+                    PREFERENCES.join(", ")
+                ));
+            }
+        }
+        Ok(Self {
+            latency: latency.to_string(),
+            relaxed: relaxed.to_string(),
+        })
+    }
+}
+
+/// Pointer to the BPF mega_mailbox array, read for each CPU's run_tier
+pub struct RunTiers(*const mega_mailbox_entry);
+
+// SAFETY: the BSS mmap outlives the hinter (EppHinter joins on drop and is
+// dropped before the skeleton); volatile loads are the only access.
+unsafe impl Send for RunTiers {}
+
+impl RunTiers {
+    /// # Safety
+    /// `ptr` must point to the skeleton's mmap'd mega_mailbox array and stay
+    /// valid until the EppHinter holding it is dropped.
+    pub unsafe fn new(ptr: *const mega_mailbox_entry) -> Self {
+        Self(ptr)
+    }
+
+    /// Tier + 1 of the task running on `cpu` (below MAX_CPUS), 0 = none
+    fn get(&self, cpu: usize) -> u8 {
+        // SAFETY: see RunTiers::new
+        unsafe { std::ptr::read_volatile(std::ptr::addr_of!((*self.0.add(cpu)).run_tier)) }
+    }
+}
+
+/// Per-CPU latency/relaxed state with the HOLD hysteresis
+#[derive(Debug, Default)]
+struct CpuHint {
+    last_latency: Option<Instant>,
+    latency: bool,
+}
+
+impl CpuHint {
+    /// Feed one run_tier sample; Some(latency) when the preference flips
+    fn update(&mut self, run_tier: u8, now: Instant) -> Option<bool> {
+        // run_tier 1..=3: Critical, Interactive or Frame on the CPU
+        if (1..=3).contains(&run_tier) {
+            self.last_latency = Some(now);
+        }
+        let latency = self
+            .last_latency
+            .is_some_and(|t| now.duration_since(t) < HOLD);
+        (latency != self.latency).then(|| {
+            self.latency = latency;
+            latency
+        })
+    }
+}
+
+/// Background thread moving each managed CPU's EPP with the tiers it runs;
+/// puts back the values it found when dropped
+pub struct EppHinter {
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+    saved: Vec<(usize, String)>,
+}
+
+impl EppHinter {
+    pub fn spawn(hints: EppHints, tiers: RunTiers, cpus: Vec<usize>) -> Result<Self> {
+        let saved: Vec<(usize, String)> = cpus
+            .iter()
+            .filter_map(|&cpu| {
+                let pref = std::fs::read_to_string(epp_path(cpu)).ok()?;
+                Some((cpu, pref.trim().to_string()))
+            })
+            .collect();
+        if saved.is_empty() {
+            bail!("no EPP to set (needs amd-pstate or intel_pstate in active mode)");
+        }
+        info!(
+            "EPP hints on {} CPUs: {} for latency tiers, {} otherwise",
+            saved.len(),
+            hints.latency,
+            hints.relaxed
+        );
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let stop_thread = stop.clone();
+        let cpus: Vec<usize> = saved.iter().map(|&(cpu, _)| cpu).collect();
+        let handle = std::thread::Builder::new()
+            .name("cake-epp".into())
+            .spawn(move || hint(hints, tiers, cpus, stop_thread))
+            .map_err(|e| warn!("Failed to start EPP hint thread: {}", e))
+            .ok();
+
+        Ok(Self {
+            stop,
+            handle,
+            saved,
+        })
+    }
+}
+
+impl Drop for EppHinter {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(h) = self.handle.take() {
+            let _ = h.join();
+        }
+        for (cpu, pref) in &self.saved {
+            let _ = std::fs::write(epp_path(*cpu), pref);
+        }
+    }
+}
+
+fn hint(hints: EppHints, tiers: RunTiers, mut cpus: Vec<usize>, stop: Arc<AtomicBool>) {
+    let mut state: Vec<CpuHint> = cpus.iter().map(|_| CpuHint::default()).collect();
+    // Start every CPU relaxed; the first latency run flips it
+    for &cpu in &cpus {
+        let _ = std::fs::write(epp_path(cpu), &hints.relaxed);
+    }
+
+    while !stop.load(Ordering::Relaxed) && !cpus.is_empty() {
+        let now = Instant::now();
+        let mut failed = Vec::new();
+        for (i, &cpu) in cpus.iter().enumerate() {
+            let Some(latency) = state[i].update(tiers.get(cpu), now) else {
+                continue;
+            };
+            let pref = if latency {
+                &hints.latency
+            } else {
+                &hints.relaxed
+            };
+            // The performance governor pins EPP and rejects other values
+            if let Err(e) = std::fs::write(epp_path(cpu), pref) {
+                warn!(
+                    "EPP hints: CPU {} rejected '{}' ({}), leaving it",
+                    cpu, pref, e
+                );
+                failed.push(i);
+            }
+        }
+        for i in failed.into_iter().rev() {
+            cpus.remove(i);
+            state.remove(i);
+        }
+        std::thread::sleep(SAMPLE_PERIOD);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_hints() {
+        let hints: EppHints = "performance:balance_power".parse().unwrap();
+        assert_eq!(hints.latency, "performance");
+        assert_eq!(hints.relaxed, "balance_power");
+        assert!("performance".parse::<EppHints>().is_err());
+        assert!("turbo:power".parse::<EppHints>().is_err());
+    }
+
+    #[test]
+    fn test_hold() {
+        let t0 = Instant::now();
+        let mut cpu = CpuHint::default();
+        assert_eq!(cpu.update(0, t0), None, "idle stays relaxed");
+        assert_eq!(cpu.update(4, t0), None, "Bulk stays relaxed");
+        assert_eq!(cpu.update(3, t0), Some(true), "Frame flips to latency");
+        // Bulk and idle gaps inside HOLD keep the latency preference
+        assert_eq!(cpu.update(4, t0 + HOLD / 2), None);
+        assert_eq!(cpu.update(0, t0 + HOLD), Some(false));
+        assert_eq!(cpu.update(1, t0 + HOLD * 2), Some(true));
+    }
+}
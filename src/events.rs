@@ -0,0 +1,445 @@
+// SPDX-License-Identifier: GPL-2.0
+// Events module - --events / --trace consumer for the BPF scheduling-event ring buffer
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::mem::size_of;
+use std::path::Path;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use libbpf_rs::{MapHandle, RingBufferBuilder};
+use log::{info, warn};
+
+use crate::bpf_intf::cake_event;
+use crate::stats::TIER_NAMES;
+use crate::trace::TraceWriter;
+
+/// Ring buffer poll timeout, which also bounds shutdown latency
+const POLL: Duration = Duration::from_millis(250);
+
+/// Events the TUI log pane keeps, oldest dropped first
+const FEED_LEN: usize = 1000;
+
+/// enum cake_event_kind
+pub(crate) const EV_NEW_FLOW: u8 = 1;
+pub(crate) const EV_TIER_CHANGE: u8 = 2;
+pub(crate) const EV_FLOW_SPENT: u8 = 3;
+pub(crate) const EV_STARVATION: u8 = 4;
+/// --trace only
+pub(crate) const EV_ENQUEUE: u8 = 5;
+pub(crate) const EV_RUN: u8 = 6;
+pub(crate) const EV_STOP: u8 = 7;
+
+/// Where decoded events go: the log, JSON lines appended to a file, a
+/// --trace file, or the TUI log pane
+enum Sink {
+    Log,
+    File(File),
+    Trace(TraceWriter<BufWriter<File>>),
+    Feed(EventFeed),
+}
+
+impl Sink {
+    fn write(&mut self, ev: &Event) -> std::io::Result<()> {
+        match self {
+            // Per-step records are for the trace; they would drown the rest
+            Sink::Log | Sink::File(_) | Sink::Feed(_) if ev.is_step() => Ok(()),
+            Sink::Log => {
+                info!("event: {}", ev.describe());
+                Ok(())
+            }
+            Sink::File(f) => writeln!(f, "{}", ev.json()),
+            Sink::Trace(t) => t.record(ev),
+            Sink::Feed(feed) => {
+                feed.push(ev.clone());
+                Ok(())
+            }
+        }
+    }
+
+    fn finish(&mut self) -> std::io::Result<()> {
+        match self {
+            Sink::Trace(t) => t.finish(),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// One decoded ring buffer record
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Event {
+    /// Wall clock, seconds since the epoch
+    pub(crate) time: f64,
+    /// CLOCK_BOOTTIME as recorded by BPF
+    pub(crate) ts_ns: u64,
+    pub(crate) pid: u32,
+    pub(crate) tgid: u32,
+    pub(crate) cpu: u32,
+    pub(crate) kind: u8,
+    pub(crate) from: u8,
+    pub(crate) to: u8,
+    pub(crate) comm: String,
+}
+
+/// enum cake_event_kind as written to --events files
+fn kind_name(kind: u8) -> &'static str {
+    match kind {
+        EV_NEW_FLOW => "new_flow",
+        EV_TIER_CHANGE => "tier_change",
+        EV_FLOW_SPENT => "flow_spent",
+        EV_STARVATION => "starvation_preempt",
+        EV_ENQUEUE => "enqueue",
+        EV_RUN => "run",
+        EV_STOP => "stop",
+        _ => "unknown",
+    }
+}
+
+/// Local wall-clock time of day of unix time `time`, "HH:MM:SS.mmm"
+pub(crate) fn local_clock(time: f64) -> String {
+    let secs = time as libc::time_t;
+    // SAFETY: localtime_r only writes the provided tm
+    let tm = unsafe {
+        let mut tm: libc::tm = std::mem::zeroed();
+        libc::localtime_r(&secs, &mut tm);
+        tm
+    };
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        tm.tm_hour,
+        tm.tm_min,
+        tm.tm_sec,
+        (time.fract() * 1000.0) as u32
+    )
+}
+
+/// Tier name, or "local" for the starvation waiter row past the last tier
+pub(crate) fn tier_name(t: u8) -> &'static str {
+    TIER_NAMES.get(t as usize).copied().unwrap_or("local")
+}
+
+impl Event {
+    /// `boot_to_wall` is CLOCK_REALTIME - CLOCK_BOOTTIME in seconds
+    fn decode(data: &[u8], boot_to_wall: f64) -> Option<Self> {
+        if data.len() < size_of::<cake_event>() {
+            return None;
+        }
+        // SAFETY: length checked; cake_event is plain old data
+        let rec: cake_event = unsafe { std::ptr::read_unaligned(data.as_ptr() as *const _) };
+        let comm: Vec<u8> = rec
+            .comm
+            .iter()
+            .take_while(|&&c| c != 0)
+            .map(|&c| c as u8)
+            .collect();
+        Some(Self {
+            time: rec.ts_ns as f64 / 1e9 + boot_to_wall,
+            ts_ns: rec.ts_ns,
+            pid: rec.pid,
+            tgid: rec.tgid,
+            cpu: rec.cpu,
+            kind: rec.kind,
+            from: rec.from,
+            to: rec.to,
+            comm: String::from_utf8_lossy(&comm).into_owned(),
+        })
+    }
+
+    pub(crate) fn kind_name(&self) -> &'static str {
+        kind_name(self.kind)
+    }
+
+    /// One --events JSON line read back; tgid and the BPF timestamp are
+    /// not in the file and come back as 0
+    pub(crate) fn from_json(line: &str) -> Option<Self> {
+        let v: serde_json::Value = serde_json::from_str(line).ok()?;
+        let tier = |key: &str| {
+            let name = v.get(key)?.as_str()?;
+            let t = TIER_NAMES.iter().position(|&t| t == name);
+            Some(t.unwrap_or(TIER_NAMES.len()) as u8)
+        };
+        let event = v.get("event")?.as_str()?;
+        Some(Self {
+            time: v.get("time")?.as_f64()?,
+            ts_ns: 0,
+            pid: v.get("pid")?.as_u64()? as u32,
+            tgid: 0,
+            cpu: v.get("cpu")?.as_u64()? as u32,
+            kind: (EV_NEW_FLOW..=EV_STOP).find(|&k| kind_name(k) == event)?,
+            from: tier("from")?,
+            to: tier("to")?,
+            comm: v.get("comm")?.as_str()?.to_string(),
+        })
+    }
+
+    /// Enqueue / run / stop, emitted only under --trace
+    fn is_step(&self) -> bool {
+        matches!(self.kind, EV_ENQUEUE | EV_RUN | EV_STOP)
+    }
+
+    /// One log line, e.g. "tier_change cpu 3 pid 812 (kwin_wayland) Interactive -> Frame"
+    pub(crate) fn describe(&self) -> String {
+        let what = match self.kind {
+            EV_TIER_CHANGE => format!("{} -> {}", tier_name(self.from), tier_name(self.to)),
+            EV_STARVATION => format!(
+                "{} preempted for a {} waiter",
+                tier_name(self.from),
+                tier_name(self.to)
+            ),
+            _ => tier_name(self.to).to_string(),
+        };
+        format!(
+            "{} cpu {} pid {} ({}) {}",
+            self.kind_name(),
+            self.cpu,
+            self.pid,
+            self.comm,
+            what
+        )
+    }
+
+    /// Local wall-clock time of day, "HH:MM:SS.mmm"
+    pub(crate) fn clock(&self) -> String {
+        local_clock(self.time)
+    }
+
+    fn json(&self) -> String {
+        format!(
+            "{{\"time\":{:.6},\"event\":\"{}\",\"cpu\":{},\"pid\":{},\"comm\":\"{}\",\"from\":\"{}\",\"to\":\"{}\"}}",
+            self.time,
+            self.kind_name(),
+            self.cpu,
+            self.pid,
+            self.comm.replace('\\', "\\\\").replace('"', "\\\""),
+            tier_name(self.from),
+            tier_name(self.to)
+        )
+    }
+}
+
+/// CLOCK_REALTIME - CLOCK_BOOTTIME, to put BPF timestamps on the wall clock
+fn boot_to_wall() -> f64 {
+    let mut ts = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    // SAFETY: ts is a valid out-pointer
+    unsafe { libc::clock_gettime(libc::CLOCK_BOOTTIME, &mut ts) };
+    let boot = ts.tv_sec as f64 + ts.tv_nsec as f64 / 1e9;
+    let wall = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs_f64())
+        .unwrap_or(0.0);
+    wall - boot
+}
+
+/// The latest non-step events, shared between the stream thread and the TUI
+#[derive(Debug, Clone, Default)]
+pub struct EventFeed(Arc<Mutex<VecDeque<Event>>>);
+
+impl EventFeed {
+    fn push(&self, ev: Event) {
+        let mut q = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        if q.len() == FEED_LEN {
+            q.pop_front();
+        }
+        q.push_back(ev);
+    }
+
+    /// Copy of the kept events, oldest first
+    pub(crate) fn snapshot(&self) -> Vec<Event> {
+        let q = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        q.iter().cloned().collect()
+    }
+}
+
+/// Background thread draining the `events` ring buffer into the log, a
+/// JSON-lines file, a --trace file and/or the TUI's feed. The thread owns its own handle on
+/// the map, so the skeleton stays on the main thread.
+pub struct EventStream {
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl EventStream {
+    /// `events` is "log" or a file path to append JSON lines to; `trace` is
+    /// a file to (over)write with a Perfetto-loadable trace; `feed` is the
+    /// TUI log pane
+    pub fn spawn(
+        map: MapHandle,
+        events: Option<&str>,
+        trace: Option<&Path>,
+        feed: Option<EventFeed>,
+    ) -> Result<Self> {
+        let mut sinks: Vec<Sink> = feed.into_iter().map(Sink::Feed).collect();
+        match events {
+            Some("log") => sinks.push(Sink::Log),
+            Some(path) => sinks.push(Sink::File(
+                OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .with_context(|| format!("Failed to open event log {}", path))?,
+            )),
+            None => {}
+        }
+        if let Some(events) = events {
+            info!("Streaming scheduling events to {}", events);
+        }
+        if let Some(path) = trace {
+            let file = File::create(path)
+                .with_context(|| format!("Failed to create trace {}", path.display()))?;
+            sinks.push(Sink::Trace(TraceWriter::new(BufWriter::new(file))?));
+            info!("Tracing scheduling events to {}", path.display());
+        }
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let stop_thread = stop.clone();
+        let handle = std::thread::Builder::new()
+            .name("cake-events".into())
+            .spawn(move || {
+                if let Err(e) = consume(&map, sinks, &stop_thread) {
+                    warn!("Event stream stopped: {:#}", e);
+                }
+            })
+            .context("Failed to start the event stream thread")?;
+        Ok(Self {
+            stop,
+            handle: Some(handle),
+        })
+    }
+}
+
+impl Drop for EventStream {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(h) = self.handle.take() {
+            let _ = h.join();
+        }
+    }
+}
+
+fn consume(map: &MapHandle, sinks: Vec<Sink>, stop: &AtomicBool) -> Result<()> {
+    let offset = boot_to_wall();
+    // Shared with the callback so the trace can be closed after the last poll
+    let sinks = Rc::new(RefCell::new(sinks));
+    let cb_sinks = sinks.clone();
+    let mut builder = RingBufferBuilder::new();
+    builder.add(map, move |data: &[u8]| {
+        let Some(ev) = Event::decode(data, offset) else {
+            return 0;
+        };
+        for sink in cb_sinks.borrow_mut().iter_mut() {
+            // Non-zero stops the poll; the thread then reports and exits
+            if sink.write(&ev).is_err() {
+                return -1;
+            }
+        }
+        0
+    })?;
+    let ring = builder.build()?;
+    while !stop.load(Ordering::Relaxed) {
+        ring.poll(POLL)
+            .context("Event log write or ring buffer poll failed")?;
+    }
+    // Whatever arrived before shutdown
+    let _ = ring.consume();
+    drop(ring);
+    for sink in sinks.borrow_mut().iter_mut() {
+        sink.finish().context("Failed to finish the trace")?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(kind: u8, from: u8, to: u8) -> Vec<u8> {
+        // SAFETY: all-zero is a valid cake_event
+        let mut rec: cake_event = unsafe { std::mem::zeroed() };
+        rec.ts_ns = 2_500_000_000;
+        rec.pid = 812;
+        rec.tgid = 800;
+        rec.cpu = 3;
+        rec.kind = kind;
+        rec.from = from;
+        rec.to = to;
+        for (d, s) in rec.comm.iter_mut().zip(b"kwin\"x") {
+            *d = *s as _;
+        }
+        // SAFETY: plain old data viewed as bytes
+        unsafe {
+            std::slice::from_raw_parts(
+                &rec as *const cake_event as *const u8,
+                size_of::<cake_event>(),
+            )
+        }
+        .to_vec()
+    }
+
+    #[test]
+    fn test_decode_and_format() {
+        let ev = Event::decode(&record(EV_TIER_CHANGE, 1, 2), 1_700_000_000.0).unwrap();
+        assert_eq!(ev.time, 1_700_000_002.5);
+        assert_eq!((ev.ts_ns, ev.tgid), (2_500_000_000, 800));
+        assert!(!ev.is_step());
+        assert_eq!(
+            ev.describe(),
+            "tier_change cpu 3 pid 812 (kwin\"x) Interactive -> Frame"
+        );
+        assert_eq!(
+            ev.json(),
+            "{\"time\":1700000002.500000,\"event\":\"tier_change\",\"cpu\":3,\"pid\":812,\
+             \"comm\":\"kwin\\\"x\",\"from\":\"Interactive\",\"to\":\"Frame\"}"
+        );
+
+        let starv = Event::decode(&record(EV_STARVATION, 3, 4), 0.0).unwrap();
+        assert!(starv
+            .describe()
+            .ends_with("Bulk preempted for a local waiter"));
+        assert!(Event::decode(&[0; 8], 0.0).is_none());
+    }
+
+    #[test]
+    fn test_json_reads_back() {
+        let ev = Event::decode(&record(EV_STARVATION, 3, 4), 1_700_000_000.0).unwrap();
+        let back = Event::from_json(&ev.json()).unwrap();
+        assert_eq!(
+            back,
+            Event {
+                ts_ns: 0,
+                tgid: 0,
+                ..ev
+            }
+        );
+        assert!(Event::from_json("{\"time\":1.0}").is_none());
+        assert!(Event::from_json("not json").is_none());
+    }
+
+    #[test]
+    fn test_feed_keeps_the_latest_non_step_events() {
+        let feed = EventFeed::default();
+        let mut sink = Sink::Feed(feed.clone());
+        let run = Event::decode(&record(EV_RUN, 1, 1), 0.0).unwrap();
+        sink.write(&run).unwrap();
+        assert!(feed.snapshot().is_empty());
+
+        let mut ev = Event::decode(&record(EV_TIER_CHANGE, 1, 2), 0.0).unwrap();
+        for pid in 0..FEED_LEN as u32 + 5 {
+            ev.pid = pid;
+            sink.write(&ev).unwrap();
+        }
+        let kept = feed.snapshot();
+        assert_eq!(kept.len(), FEED_LEN);
+        assert_eq!(kept[0].pid, 5);
+        assert_eq!(kept.last().unwrap().pid, FEED_LEN as u32 + 4);
+    }
+}
@@ -0,0 +1,389 @@
+// SPDX-License-Identifier: GPL-2.0
+// History module - long-term interval snapshots in SQLite for comparing tuning across sessions
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use clap::{Subcommand, ValueEnum};
+use log::info;
+use rusqlite::{params, Connection};
+
+use crate::bpf_skel::types::cake_stats;
+
+/// How often old rows are pruned while recording
+const PRUNE_PERIOD: Duration = Duration::from_secs(3600);
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS snapshots (
+    ts                  INTEGER NOT NULL,  -- unix seconds at end of interval
+    interval_ms         INTEGER NOT NULL,
+    profile             TEXT    NOT NULL,
+    dispatch_t0         INTEGER NOT NULL,
+    dispatch_t1         INTEGER NOT NULL,
+    dispatch_t2         INTEGER NOT NULL,
+    dispatch_t3         INTEGER NOT NULL,
+    new_flow            INTEGER NOT NULL,
+    old_flow            INTEGER NOT NULL,
+    starvation_preempts INTEGER NOT NULL,
+    immune_skips        INTEGER NOT NULL,
+    smt_spread          INTEGER NOT NULL,
+    rebalance_moves     INTEGER NOT NULL,
+    warmup_dispatches   INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS snapshots_ts ON snapshots (ts);
+";
+
+/// History subcommands
+#[derive(Debug, Clone, Subcommand)]
+pub enum HistoryCommand {
+    /// Aggregate recorded snapshots per hour or day
+    Query {
+        /// Database written by --history-db
+        #[arg(long)]
+        db: PathBuf,
+
+        /// Only include the last N DAYS (0 = everything retained)
+        #[arg(long, default_value_t = 7)]
+        days: u64,
+
+        /// Aggregation bucket
+        #[arg(long, value_enum, default_value_t = Bucket::Day)]
+        by: Bucket,
+
+        /// Only include snapshots recorded under this profile (e.g. gaming)
+        #[arg(long)]
+        profile: Option<String>,
+    },
+}
+
+/// Time bucket for `history query`
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum Bucket {
+    Hour,
+    Day,
+}
+
+impl Bucket {
+    fn strftime(self) -> &'static str {
+        match self {
+            Bucket::Hour => "%Y-%m-%d %H:00",
+            Bucket::Day => "%Y-%m-%d",
+        }
+    }
+}
+
+fn open_db(path: &Path) -> Result<Connection> {
+    let conn = Connection::open(path)
+        .with_context(|| format!("Failed to open history database {}", path.display()))?;
+    conn.execute_batch(SCHEMA)
+        .context("Failed to create history schema")?;
+    Ok(conn)
+}
+
+fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+/// Per-interval counter movement. Resets are epoch-based and leave the BPF
+/// counters alone, but a counter that went backwards counts from zero.
+fn delta(now: u64, prev: u64) -> i64 {
+    (if now >= prev { now - prev } else { now }) as i64
+}
+
+/// Writes one row per stats interval and prunes rows past retention
+pub struct Recorder {
+    conn: Connection,
+    profile: String,
+    retention_secs: i64,
+    prev: cake_stats,
+    last_record: Instant,
+    last_prune: Option<Instant>,
+}
+
+impl Recorder {
+    pub fn open(path: &Path, profile: &str, retention_days: u64) -> Result<Self> {
+        info!(
+            "Recording stats history to {} ({} day retention)",
+            path.display(),
+            retention_days
+        );
+        Ok(Self::with_connection(
+            open_db(path)?,
+            profile,
+            retention_days,
+        ))
+    }
+
+    fn with_connection(conn: Connection, profile: &str, retention_days: u64) -> Self {
+        Self {
+            conn,
+            profile: profile.to_lowercase(),
+            retention_secs: (retention_days * 86_400) as i64,
+            prev: Default::default(),
+            last_record: Instant::now(),
+            last_prune: None,
+        }
+    }
+
+    /// Record the movement since the previous call
+    pub fn record(&mut self, stats: &cake_stats) -> Result<()> {
+        let interval_ms = self.last_record.elapsed().as_millis() as i64;
+        self.last_record = Instant::now();
+        self.insert(unix_now(), interval_ms, stats)?;
+
+        if self.last_prune.is_none_or(|t| t.elapsed() >= PRUNE_PERIOD) {
+            self.last_prune = Some(Instant::now());
+            self.prune(unix_now())?;
+        }
+        Ok(())
+    }
+
+    fn insert(&mut self, ts: i64, interval_ms: i64, s: &cake_stats) -> Result<()> {
+        let p = &self.prev;
+        let sum = |a: &[u64; 4]| a.iter().sum::<u64>();
+        self.conn
+            .execute(
+                "INSERT INTO snapshots VALUES
+                 (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)",
+                params![
+                    ts,
+                    interval_ms,
+                    self.profile,
+                    delta(s.nr_tier_dispatches[0], p.nr_tier_dispatches[0]),
+                    delta(s.nr_tier_dispatches[1], p.nr_tier_dispatches[1]),
+                    delta(s.nr_tier_dispatches[2], p.nr_tier_dispatches[2]),
+                    delta(s.nr_tier_dispatches[3], p.nr_tier_dispatches[3]),
+                    delta(s.nr_new_flow_dispatches, p.nr_new_flow_dispatches),
+                    delta(s.nr_old_flow_dispatches, p.nr_old_flow_dispatches),
+                    delta(
+                        sum(&s.nr_starvation_preempts_tier),
+                        sum(&p.nr_starvation_preempts_tier)
+                    ),
+                    delta(
+                        sum(&s.nr_starvation_immune_tier),
+                        sum(&p.nr_starvation_immune_tier)
+                    ),
+                    delta(s.nr_smt_spread, p.nr_smt_spread),
+                    delta(s.nr_rebalance_moves, p.nr_rebalance_moves),
+                    delta(s.nr_warmup_dispatches, p.nr_warmup_dispatches),
+                ],
+            )
+            .context("Failed to insert history snapshot")?;
+        self.prev = *s;
+        Ok(())
+    }
+
+    fn prune(&self, now: i64) -> Result<usize> {
+        if self.retention_secs == 0 {
+            return Ok(0);
+        }
+        self.conn
+            .execute(
+                "DELETE FROM snapshots WHERE ts < ?1",
+                params![now - self.retention_secs],
+            )
+            .context("Failed to prune history")
+    }
+}
+
+/// One aggregated row of `history query`
+#[derive(Debug, Clone, PartialEq)]
+struct Row {
+    period: String,
+    minutes: f64,
+    dispatches: i64,
+    tier_pct: [f64; 4],
+    new_flow_pct: f64,
+    starvation_preempts: i64,
+    immune_skips: i64,
+    rebalance_moves: i64,
+    warmup_dispatches: i64,
+}
+
+fn query_rows(
+    conn: &Connection,
+    since: i64,
+    by: Bucket,
+    profile: Option<&str>,
+) -> Result<Vec<Row>> {
+    let mut stmt = conn.prepare(
+        "SELECT strftime(?1, ts, 'unixepoch', 'localtime') AS period,
+                SUM(interval_ms), SUM(dispatch_t0), SUM(dispatch_t1),
+                SUM(dispatch_t2), SUM(dispatch_t3), SUM(new_flow), SUM(old_flow),
+                SUM(starvation_preempts), SUM(immune_skips),
+                SUM(rebalance_moves), SUM(warmup_dispatches)
+         FROM snapshots
+         WHERE ts >= ?2 AND (?3 IS NULL OR profile = ?3)
+         GROUP BY period ORDER BY period",
+    )?;
+
+    let rows = stmt.query_map(
+        params![by.strftime(), since, profile.map(str::to_lowercase)],
+        |r| {
+            let tiers: [i64; 4] = [r.get(2)?, r.get(3)?, r.get(4)?, r.get(5)?];
+            let (new_flow, old_flow): (i64, i64) = (r.get(6)?, r.get(7)?);
+            let dispatches: i64 = tiers.iter().sum();
+            let pct = |n: i64, d: i64| {
+                if d > 0 {
+                    n as f64 * 100.0 / d as f64
+                } else {
+                    0.0
+                }
+            };
+            Ok(Row {
+                period: r.get(0)?,
+                minutes: r.get::<_, i64>(1)? as f64 / 60_000.0,
+                dispatches,
+                tier_pct: tiers.map(|t| pct(t, dispatches)),
+                new_flow_pct: pct(new_flow, new_flow + old_flow),
+                starvation_preempts: r.get(8)?,
+                immune_skips: r.get(9)?,
+                rebalance_moves: r.get(10)?,
+                warmup_dispatches: r.get(11)?,
+            })
+        },
+    )?;
+    rows.collect::<rusqlite::Result<_>>()
+        .context("Failed to read history rows")
+}
+
+/// Run a `history` subcommand (no BPF or root required)
+pub fn run(cmd: &HistoryCommand) -> Result<()> {
+    let HistoryCommand::Query {
+        db,
+        days,
+        by,
+        profile,
+    } = cmd;
+
+    let conn = open_db(db)?;
+    let since = if *days == 0 {
+        0
+    } else {
+        unix_now() - (*days * 86_400) as i64
+    };
+    let rows = query_rows(&conn, since, *by, profile.as_deref())?;
+    if rows.is_empty() {
+        println!("No snapshots recorded in the selected range");
+        return Ok(());
+    }
+
+    println!(
+        "{:<16} {:>8} {:>12} {:>6} {:>6} {:>6} {:>6} {:>8} {:>11} {:>10} {:>10} {:>8}",
+        "Period",
+        "Minutes",
+        "Dispatches",
+        "T0%",
+        "T1%",
+        "T2%",
+        "T3%",
+        "NewFlow%",
+        "StarvPreempt",
+        "ImmuneSkip",
+        "Rebalance",
+        "Warmup"
+    );
+    for r in rows {
+        println!(
+            "{:<16} {:>8.1} {:>12} {:>6.1} {:>6.1} {:>6.1} {:>6.1} {:>8.1} {:>11} {:>10} {:>10} {:>8}",
+            r.period,
+            r.minutes,
+            r.dispatches,
+            r.tier_pct[0],
+            r.tier_pct[1],
+            r.tier_pct[2],
+            r.tier_pct[3],
+            r.new_flow_pct,
+            r.starvation_preempts,
+            r.immune_skips,
+            r.rebalance_moves,
+            r.warmup_dispatches
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn recorder() -> Recorder {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(SCHEMA).unwrap();
+        Recorder::with_connection(conn, "Gaming", 30)
+    }
+
+    fn stats(t1: u64, preempts: u64) -> cake_stats {
+        let mut s: cake_stats = Default::default();
+        s.nr_tier_dispatches[1] = t1;
+        s.nr_old_flow_dispatches = t1;
+        s.nr_starvation_preempts_tier[3] = preempts;
+        s
+    }
+
+    fn count(r: &Recorder) -> i64 {
+        r.conn
+            .query_row("SELECT COUNT(*) FROM snapshots", [], |row| row.get(0))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_records_interval_deltas() {
+        let mut r = recorder();
+        r.insert(1_000, 1000, &stats(100, 2)).unwrap();
+        r.insert(1_001, 1000, &stats(150, 5)).unwrap();
+
+        let rows = query_rows(&r.conn, 0, Bucket::Day, None).unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].dispatches, 150);
+        assert_eq!(rows[0].starvation_preempts, 5);
+        assert_eq!(rows[0].tier_pct[1], 100.0);
+    }
+
+    #[test]
+    fn test_counter_reset_is_not_negative() {
+        let mut r = recorder();
+        r.insert(1_000, 1000, &stats(500, 0)).unwrap();
+        // Scheduler restarted on the same database: counters begin again
+        r.insert(1_001, 1000, &stats(20, 0)).unwrap();
+
+        let rows = query_rows(&r.conn, 0, Bucket::Day, None).unwrap();
+        assert_eq!(rows[0].dispatches, 520);
+    }
+
+    #[test]
+    fn test_prune_drops_rows_past_retention() {
+        let mut r = recorder();
+        let now = 100 * 86_400;
+        r.insert(now - 31 * 86_400, 1000, &stats(1, 0)).unwrap();
+        r.insert(now - 86_400, 1000, &stats(2, 0)).unwrap();
+
+        assert_eq!(r.prune(now).unwrap(), 1);
+        assert_eq!(count(&r), 1);
+    }
+
+    #[test]
+    fn test_query_filters_since_and_profile() {
+        let mut r = recorder();
+        r.insert(1_000, 1000, &stats(10, 0)).unwrap();
+        r.insert(200_000, 1000, &stats(30, 0)).unwrap();
+
+        let recent = query_rows(&r.conn, 100_000, Bucket::Hour, None).unwrap();
+        assert_eq!(recent.len(), 1);
+        assert_eq!(recent[0].dispatches, 20);
+
+        assert!(query_rows(&r.conn, 0, Bucket::Day, Some("esports"))
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            query_rows(&r.conn, 0, Bucket::Day, Some("GAMING"))
+                .unwrap()
+                .len(),
+            2
+        );
+    }
+}
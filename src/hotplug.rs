@@ -0,0 +1,103 @@
+// SPDX-License-Identifier: GPL-2.0
+// Hotplug module - watches the online CPU set and stops the scheduler for a reload on the new topology
+
+use std::fs::File;
+use std::io::Write;
+use std::os::fd::{AsFd, BorrowedFd, FromRawFd};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use log::warn;
+
+use crate::topology::{sysfs_cpu_mask, CpuMask};
+
+const ONLINE_PATH: &str = "/sys/devices/system/cpu/online";
+
+/// How often the watcher re-reads the online CPU list
+const CHECK_PERIOD: Duration = Duration::from_secs(1);
+
+/// CPUs online right now, None if sysfs can't be read
+pub fn online_cpus() -> Option<CpuMask> {
+    sysfs_cpu_mask(&std::fs::read_to_string(ONLINE_PATH).ok()?).filter(|mask| !mask.is_empty())
+}
+
+/// Watches /sys/devices/system/cpu/online; stops the running scheduler (via
+/// the shutdown flag) when a CPU goes offline or comes online, so it can be
+/// loaded again with maps built for the new topology
+pub struct HotplugWatcher {
+    stop: Arc<AtomicBool>,
+    changed: Arc<Mutex<Option<(CpuMask, CpuMask)>>>,
+    wake: Option<Arc<File>>,
+    handle: Option<JoinHandle<()>>,
+}
+
+/// Non-blocking eventfd, None if the kernel refuses one
+fn eventfd() -> Option<File> {
+    // SAFETY: eventfd has no memory preconditions
+    let fd = unsafe { libc::eventfd(0, libc::EFD_CLOEXEC | libc::EFD_NONBLOCK) };
+    // SAFETY: a non-negative fd from eventfd is ours alone
+    (fd >= 0).then(|| unsafe { File::from_raw_fd(fd) })
+}
+
+impl HotplugWatcher {
+    /// `online` is the mask the running scheduler's topology was built from
+    pub fn spawn(online: CpuMask, shutdown: Arc<AtomicBool>) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let changed = Arc::new(Mutex::new(None));
+        let wake = eventfd().map(Arc::new);
+        let (stop_t, changed_t, wake_t) = (stop.clone(), changed.clone(), wake.clone());
+
+        let handle = std::thread::Builder::new()
+            .name("cake-hotplug".into())
+            .spawn(move || {
+                let running =
+                    || !stop_t.load(Ordering::Relaxed) && !shutdown.load(Ordering::Relaxed);
+                let mut next_check = Instant::now();
+                while running() {
+                    if Instant::now() >= next_check {
+                        match online_cpus() {
+                            Some(now) if now != online => {
+                                *changed_t.lock().unwrap() = Some((online, now));
+                                shutdown.store(true, Ordering::Relaxed);
+                                if let Some(wake) = &wake_t {
+                                    let _ = (&**wake).write_all(&1u64.to_ne_bytes());
+                                }
+                                break;
+                            }
+                            _ => {}
+                        }
+                        next_check = Instant::now() + CHECK_PERIOD;
+                    }
+                    // Short naps so finish() doesn't stall shutdown
+                    std::thread::sleep(Duration::from_millis(200));
+                }
+            })
+            .map_err(|e| warn!("Failed to start CPU hotplug watcher: {}", e))
+            .ok();
+
+        Self {
+            stop,
+            changed,
+            wake,
+            handle,
+        }
+    }
+
+    /// Readable once a hotplug has set the shutdown flag, so a loop blocked
+    /// in poll() stops at once instead of on its next timeout
+    pub fn wake_fd(&self) -> Option<BorrowedFd<'_>> {
+        self.wake.as_ref().map(|f| f.as_fd())
+    }
+
+    /// Stop the watcher; Some((before, after)) online masks if a hotplug
+    /// stopped the scheduler
+    pub fn finish(mut self) -> Option<(CpuMask, CpuMask)> {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(h) = self.handle.take() {
+            let _ = h.join();
+        }
+        self.changed.lock().unwrap().take()
+    }
+}
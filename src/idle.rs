@@ -0,0 +1,354 @@
+// SPDX-License-Identifier: GPL-2.0
+// Idle module - user-idle detection (evdev input + logind) driving the BPF user_idle flag
+
+use std::fs::File;
+use std::io::Read;
+use std::os::fd::{AsFd, AsRawFd};
+use std::os::unix::fs::OpenOptionsExt;
+use std::process::{Child, ChildStdout, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use log::{info, warn};
+use nix::poll::{poll, PollFd, PollFlags, PollTimeout};
+
+/// Poll timeout — bounds how long shutdown waits for the monitor thread
+const POLL_MS: u16 = 1000;
+/// Re-scan /dev/input so hotplugged controllers/keyboards are watched
+const RESCAN_PERIOD: Duration = Duration::from_secs(30);
+/// How often logind is asked for IdleHint and idle inhibitors (video, calls)
+/// when its PropertiesChanged signals cannot be watched
+const LOGIND_PERIOD: Duration = Duration::from_secs(30);
+
+/// Pointer to the BPF `user_idle` BSS variable
+pub struct IdleFlag(*mut u32);
+
+// SAFETY: the BSS mmap outlives the monitor (IdleMonitor joins on drop and is
+// dropped before the skeleton); a u32 volatile store is the only access.
+unsafe impl Send for IdleFlag {}
+
+impl IdleFlag {
+    /// # Safety
+    /// `ptr` must point into the skeleton's mmap'd BSS and stay valid until
+    /// the IdleMonitor holding this flag is dropped.
+    pub unsafe fn new(ptr: *mut u32) -> Self {
+        Self(ptr)
+    }
+
+    fn set(&self, idle: bool) {
+        // SAFETY: see IdleFlag::new
+        unsafe { std::ptr::write_volatile(self.0, idle as u32) }
+    }
+}
+
+/// Background thread that relaxes the latency policy while the user is away
+pub struct IdleMonitor {
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl IdleMonitor {
+    pub fn spawn(flag: IdleFlag, idle_after: Duration) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let stop_thread = stop.clone();
+        let handle = std::thread::Builder::new()
+            .name("cake-idle".into())
+            .spawn(move || monitor(flag, idle_after, stop_thread))
+            .ok();
+        if handle.is_none() {
+            warn!("Failed to start idle monitor thread");
+        }
+        Self { stop, handle }
+    }
+}
+
+impl Drop for IdleMonitor {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(h) = self.handle.take() {
+            let _ = h.join();
+        }
+    }
+}
+
+/// Open every evdev node non-blocking (keyboards, mice, gamepads)
+fn open_input_devices() -> Vec<File> {
+    let Ok(entries) = std::fs::read_dir("/dev/input") else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .filter(|e| e.file_name().to_string_lossy().starts_with("event"))
+        .filter_map(|e| {
+            std::fs::OpenOptions::new()
+                .read(true)
+                .custom_flags(libc::O_NONBLOCK)
+                .open(e.path())
+                .ok()
+        })
+        .collect()
+}
+
+/// Read a logind Manager property via busctl (None if logind is unavailable)
+fn logind_property(name: &str) -> Option<String> {
+    let out = Command::new("busctl")
+        .args([
+            "--system",
+            "get-property",
+            "org.freedesktop.login1",
+            "/org/freedesktop/login1",
+            "org.freedesktop.login1.Manager",
+            name,
+        ])
+        .output()
+        .ok()?;
+    out.status
+        .success()
+        .then(|| String::from_utf8_lossy(&out.stdout).trim().to_string())
+}
+
+/// `s "handle-power-key:idle:sleep"` → true when an idle inhibitor is held
+fn parse_idle_inhibited(reply: &str) -> bool {
+    reply
+        .strip_prefix("s ")
+        .map(|v| v.trim_matches('"').split(':').any(|w| w == "idle"))
+        .unwrap_or(false)
+}
+
+/// `b true` → true
+fn parse_idle_hint(reply: &str) -> bool {
+    reply == "b true"
+}
+
+/// Whether a logind session state file describes a graphical session
+fn is_graphical_session(state: &str) -> bool {
+    state
+        .lines()
+        .any(|l| matches!(l, "TYPE=x11" | "TYPE=wayland" | "TYPE=mir"))
+}
+
+/// Any graphical session logged in, from logind's state files. Without one
+/// (a headless box) logind's IdleHint is always set and says nothing about
+/// a user.
+fn graphical_session() -> bool {
+    let Ok(entries) = std::fs::read_dir("/run/systemd/sessions") else {
+        return false;
+    };
+    entries
+        .flatten()
+        .any(|e| std::fs::read_to_string(e.path()).is_ok_and(|state| is_graphical_session(&state)))
+}
+
+/// A PropertiesChanged line from `busctl monitor` that touches a property
+/// the monitor acts on
+fn names_idle_property(line: &str) -> bool {
+    line.contains("\"IdleHint\"") || line.contains("\"BlockInhibited\"")
+}
+
+/// logind's PropertiesChanged signals, streamed by one long-lived
+/// `busctl monitor` instead of asking logind on a timer
+struct LogindWatch {
+    child: Child,
+    out: ChildStdout,
+    pending: Vec<u8>,
+}
+
+impl LogindWatch {
+    fn spawn() -> Option<Self> {
+        let mut child = Command::new("busctl")
+            .args([
+                "--system",
+                "--json=short",
+                "monitor",
+                "--match",
+                "type='signal',sender='org.freedesktop.login1',\
+                 path='/org/freedesktop/login1',\
+                 interface='org.freedesktop.DBus.Properties',member='PropertiesChanged'",
+            ])
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .ok()?;
+        let out = child.stdout.take()?;
+        // SAFETY: fcntl on a pipe fd owned by `out` for the duration of the call
+        unsafe {
+            let fd = out.as_raw_fd();
+            libc::fcntl(
+                fd,
+                libc::F_SETFL,
+                libc::fcntl(fd, libc::F_GETFL) | libc::O_NONBLOCK,
+            );
+        }
+        Some(Self {
+            child,
+            out,
+            pending: Vec::new(),
+        })
+    }
+
+    /// Drain the pipe: whether a complete signal names IdleHint or
+    /// BlockInhibited, or None once busctl has gone away
+    fn changed(&mut self) -> Option<bool> {
+        let mut buf = [0u8; 4096];
+        loop {
+            match (&self.out).read(&mut buf) {
+                Ok(0) => return None,
+                Ok(n) => self.pending.extend_from_slice(&buf[..n]),
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => break,
+                Err(_) => return None,
+            }
+        }
+        let mut hit = false;
+        while let Some(end) = self.pending.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.pending.drain(..=end).collect();
+            hit |= names_idle_property(&String::from_utf8_lossy(&line));
+        }
+        Some(hit)
+    }
+}
+
+impl Drop for LogindWatch {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+fn monitor(flag: IdleFlag, idle_after: Duration, stop: Arc<AtomicBool>) {
+    let mut devices = open_input_devices();
+    let mut graphical = graphical_session();
+    if devices.is_empty() && graphical {
+        info!("Idle relax: no readable input devices, following logind IdleHint only");
+    } else if devices.is_empty() {
+        info!("Idle relax: no input devices and no graphical session, staying strict");
+    }
+    let mut watch = LogindWatch::spawn();
+    if watch.is_none() {
+        info!(
+            "Idle relax: cannot watch logind signals, asking every {}s",
+            LOGIND_PERIOD.as_secs()
+        );
+    }
+    let mut last_rescan = Instant::now();
+    let mut last_input = Instant::now();
+    // None: watched, the next signal says when to ask again
+    let mut next_logind = Some(Instant::now());
+    let (mut idle_hint, mut inhibited) = (false, false);
+    let mut relaxed = false;
+    let mut buf = [0u8; 1024];
+
+    while !stop.load(Ordering::Relaxed) {
+        if last_rescan.elapsed() >= RESCAN_PERIOD {
+            devices = open_input_devices();
+            graphical = graphical_session();
+            last_rescan = Instant::now();
+        }
+
+        let mut fds: Vec<PollFd> = devices
+            .iter()
+            .map(|f| PollFd::new(f.as_fd(), PollFlags::POLLIN))
+            .collect();
+        if let Some(w) = &watch {
+            fds.push(PollFd::new(w.out.as_fd(), PollFlags::POLLIN));
+        }
+        let ready = poll(&mut fds, PollTimeout::from(POLL_MS)).unwrap_or(0);
+
+        if ready > 0 {
+            let revents: Vec<PollFlags> = fds
+                .iter()
+                .map(|f| f.revents().unwrap_or(PollFlags::empty()))
+                .collect();
+            drop(fds);
+
+            let mut gone = Vec::new();
+            for (i, ev) in revents.iter().take(devices.len()).enumerate() {
+                if ev.intersects(PollFlags::POLLERR | PollFlags::POLLHUP | PollFlags::POLLNVAL) {
+                    gone.push(i); // Unplugged — polling it would spin
+                } else if ev.contains(PollFlags::POLLIN) {
+                    last_input = Instant::now();
+                    while matches!((&devices[i]).read(&mut buf), Ok(n) if n > 0) {}
+                }
+            }
+            if revents.len() > devices.len() && !revents[devices.len()].is_empty() {
+                match watch.as_mut().map(LogindWatch::changed) {
+                    Some(Some(true)) => next_logind = Some(Instant::now()),
+                    Some(Some(false)) => {}
+                    _ => {
+                        info!(
+                            "Idle relax: logind watch ended, asking every {}s",
+                            LOGIND_PERIOD.as_secs()
+                        );
+                        watch = None;
+                        next_logind = Some(Instant::now());
+                    }
+                }
+            }
+            for i in gone.into_iter().rev() {
+                devices.swap_remove(i);
+            }
+        }
+
+        if next_logind.is_some_and(|t| Instant::now() >= t) {
+            // Video players and calls hold an idle inhibitor: stay strict
+            idle_hint = logind_property("IdleHint").is_some_and(|r| parse_idle_hint(&r));
+            inhibited = logind_property("BlockInhibited").is_some_and(|r| parse_idle_inhibited(&r));
+            next_logind = watch.is_none().then(|| Instant::now() + LOGIND_PERIOD);
+        }
+
+        // No input visibility: the compositor's idle hint is all we have,
+        // and with no graphical session there is no user to be idle
+        let away = if devices.is_empty() {
+            graphical && idle_hint
+        } else {
+            last_input.elapsed() >= idle_after
+        };
+        let idle = away && !inhibited;
+        if idle != relaxed {
+            flag.set(idle);
+            relaxed = idle;
+            if idle {
+                info!("User idle — relaxing latency policy");
+            } else {
+                info!("User active — latency policy restored");
+            }
+        }
+    }
+
+    flag.set(false);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_idle_inhibited() {
+        assert!(parse_idle_inhibited("s \"handle-power-key:idle:sleep\""));
+        assert!(parse_idle_inhibited("s \"idle\""));
+        assert!(!parse_idle_inhibited("s \"handle-lid-switch:sleep\""));
+        assert!(!parse_idle_inhibited("s \"\""));
+        assert!(!parse_idle_inhibited("garbage"));
+    }
+
+    #[test]
+    fn test_parse_idle_hint() {
+        assert!(parse_idle_hint("b true"));
+        assert!(!parse_idle_hint("b false"));
+    }
+
+    #[test]
+    fn test_graphical_session_and_signal_lines() {
+        assert!(is_graphical_session("UID=1000\nTYPE=wayland\nCLASS=user\n"));
+        assert!(!is_graphical_session("UID=0\nTYPE=tty\n"));
+        assert!(!is_graphical_session("TYPE=x11-ish\n"));
+        assert!(names_idle_property(
+            r#"{"member":"PropertiesChanged","payload":{"data":["org.freedesktop.login1.Manager",{"IdleHint":{"type":"b","data":true}},[]]}}"#
+        ));
+        assert!(!names_idle_property(
+            r#"{"member":"PropertiesChanged","payload":{"data":["org.freedesktop.login1.Manager",{"NCurrentSessions":{}},[]]}}"#
+        ));
+    }
+}
@@ -0,0 +1,278 @@
+// SPDX-License-Identifier: GPL-2.0
+// IRQ module - steers device IRQ affinities away from CPUs running latency-critical tasks
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use log::{debug, info, warn};
+
+use crate::stats::{TaskSampler, TaskStat};
+use crate::topology::CpuMask;
+
+const PROC_INTERRUPTS: &str = "/proc/interrupts";
+const PROC_IRQ: &str = "/proc/irq";
+
+/// Task sampling period
+const SAMPLE_PERIOD: Duration = Duration::from_secs(2);
+/// A CPU stays protected this long after its last hot sample (no flapping)
+const HOLD: Duration = Duration::from_secs(10);
+/// Share of one CPU a protected-tier task must use to claim its CPU
+const MIN_CPU_PCT: f64 = 10.0;
+
+/// IRQ numbers whose action names start with one of `devices`
+/// (`nvme` matches `nvme0q1`, `enp` matches `enp5s0-rx-0`)
+fn parse_interrupts(text: &str, devices: &[String]) -> Vec<(u32, String)> {
+    text.lines()
+        .filter_map(|line| {
+            let (irq, rest) = line.trim_start().split_once(':')?;
+            let irq: u32 = irq.parse().ok()?;
+            let name = rest
+                .split(|c: char| c.is_whitespace() || c == ',')
+                .find(|tok| devices.iter().any(|d| tok.starts_with(d.as_str())))?;
+            Some((irq, name.to_string()))
+        })
+        .collect()
+}
+
+/// `[0, 1, 2, 3, 8]` → `"0-3,8"` (smp_affinity_list format)
+pub fn format_cpu_list(cpus: &BTreeSet<u32>) -> String {
+    let mut out: Vec<String> = Vec::new();
+    let mut iter = cpus.iter().copied().peekable();
+    while let Some(start) = iter.next() {
+        let mut end = start;
+        while iter.peek() == Some(&(end + 1)) {
+            end = iter.next().unwrap();
+        }
+        out.push(match start == end {
+            true => start.to_string(),
+            false => format!("{}-{}", start, end),
+        });
+    }
+    out.join(",")
+}
+
+/// CPUs where a task in `tier_mask` used at least MIN_CPU_PCT between snapshots
+fn hot_cpus(before: &[TaskStat], after: &[TaskStat], tier_mask: u8, interval_ns: u64) -> Vec<u32> {
+    let prev: HashMap<u32, u64> = before.iter().map(|t| (t.pid, t.runtime_ns)).collect();
+    let min_ns = (interval_ns as f64 * MIN_CPU_PCT / 100.0) as u64;
+    after
+        .iter()
+        .filter(|t| tier_mask & (1 << (t.tier & 3)) != 0)
+        .filter(|t| {
+            prev.get(&t.pid)
+                .is_some_and(|&r| t.runtime_ns.saturating_sub(r) >= min_ns)
+        })
+        .map(|t| t.cpu)
+        .collect()
+}
+
+/// Device IRQs being steered, with the affinity to restore on exit
+struct Steerer {
+    original: BTreeMap<u32, String>,
+    applied: Option<BTreeSet<u32>>,
+}
+
+impl Steerer {
+    fn new(devices: &[String]) -> Self {
+        let text = std::fs::read_to_string(PROC_INTERRUPTS).unwrap_or_default();
+        let mut original = BTreeMap::new();
+        for (irq, name) in parse_interrupts(&text, devices) {
+            let path = format!("{}/{}/smp_affinity_list", PROC_IRQ, irq);
+            if let Ok(list) = std::fs::read_to_string(&path) {
+                debug!("IRQ steering: watching IRQ {} ({})", irq, name);
+                original.insert(irq, list.trim().to_string());
+            }
+        }
+        Self {
+            original,
+            applied: None,
+        }
+    }
+
+    /// Point every watched IRQ at `allowed`. Kernel-managed IRQs (per-queue
+    /// NVMe vectors) reject the write and are dropped from the set.
+    fn apply(&mut self, allowed: &BTreeSet<u32>) {
+        if self.applied.as_ref() == Some(allowed) {
+            return;
+        }
+        let list = format_cpu_list(allowed);
+        self.original.retain(|irq, _| {
+            let path = format!("{}/{}/smp_affinity_list", PROC_IRQ, irq);
+            match std::fs::write(&path, &list) {
+                Ok(()) => true,
+                Err(e) => {
+                    debug!("IRQ steering: IRQ {} not steerable: {}", irq, e);
+                    false
+                }
+            }
+        });
+        self.applied = Some(allowed.clone());
+    }
+
+    fn restore(&mut self) {
+        for (irq, list) in &self.original {
+            let path = format!("{}/{}/smp_affinity_list", PROC_IRQ, irq);
+            if let Err(e) = std::fs::write(&path, list) {
+                warn!("IRQ steering: failed to restore IRQ {}: {}", irq, e);
+            }
+        }
+        self.applied = None;
+    }
+}
+
+/// Background thread that keeps device IRQs off protected-tier CPUs and
+/// restores the original affinities when dropped
+pub struct IrqSteering {
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl IrqSteering {
+    /// Samples through the pinned task iterator (see stats::PinnedTaskIter)
+    pub fn spawn(devices: Vec<String>, tier_mask: u8, online: CpuMask) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let stop_thread = stop.clone();
+        let handle = std::thread::Builder::new()
+            .name("cake-irq".into())
+            .spawn(move || steer(devices, tier_mask, online, stop_thread))
+            .ok();
+        if handle.is_none() {
+            warn!("Failed to start IRQ steering thread");
+        }
+        Self { stop, handle }
+    }
+}
+
+impl Drop for IrqSteering {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(h) = self.handle.take() {
+            let _ = h.join();
+        }
+    }
+}
+
+fn steer(devices: Vec<String>, tier_mask: u8, online: CpuMask, stop: Arc<AtomicBool>) {
+    let sampler = match TaskSampler::open_pinned() {
+        Ok(s) => s,
+        Err(e) => {
+            warn!("IRQ steering disabled: {:#}", e);
+            return;
+        }
+    };
+    let mut steerer = Steerer::new(&devices);
+    if steerer.original.is_empty() {
+        warn!("IRQ steering: no IRQs match {:?}", devices);
+        return;
+    }
+    info!(
+        "IRQ steering: {} IRQs ({})",
+        steerer.original.len(),
+        devices.join(",")
+    );
+
+    let all: BTreeSet<u32> = online.iter().map(|c| c as u32).collect();
+    let mut hot: HashMap<u32, Instant> = HashMap::new();
+    let mut prev = sampler.snapshot().unwrap_or_default();
+    let mut last = Instant::now();
+
+    while !stop.load(Ordering::Relaxed) {
+        let deadline = Instant::now() + SAMPLE_PERIOD;
+        while Instant::now() < deadline && !stop.load(Ordering::Relaxed) {
+            std::thread::sleep(Duration::from_millis(100));
+        }
+        let Ok(tasks) = sampler.snapshot() else {
+            continue;
+        };
+        let now = Instant::now();
+        for cpu in hot_cpus(&prev, &tasks, tier_mask, (now - last).as_nanos() as u64) {
+            hot.insert(cpu, now);
+        }
+        hot.retain(|_, seen| now.duration_since(*seen) < HOLD);
+        prev = tasks;
+        last = now;
+
+        // Never leave an IRQ with nowhere to go: if every CPU is hot, fall back
+        let allowed: BTreeSet<u32> = all
+            .iter()
+            .filter(|c| !hot.contains_key(c))
+            .copied()
+            .collect();
+        if allowed.is_empty() {
+            steerer.apply(&all);
+        } else {
+            steerer.apply(&allowed);
+        }
+    }
+
+    steerer.restore();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INTERRUPTS: &str = "\
+            CPU0       CPU1
+   0:         42          0   IO-APIC    2-edge      timer
+  87:      12345        678  IR-PCI-MSI 524288-edge      nvme0q0
+ 129:          0      99999  IR-PCI-MSIX-0000:03:00.0    0-edge      enp5s0-rx-0
+ 140:       7777          0  IR-PCI-MSI 1572864-edge      amdgpu
+ NMI:          0          0   Non-maskable interrupts
+";
+
+    fn devices(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_interrupts_matches_device_prefixes() {
+        let irqs = parse_interrupts(INTERRUPTS, &devices(&["nvme", "enp", "amdgpu"]));
+        assert_eq!(
+            irqs,
+            vec![
+                (87, "nvme0q0".to_string()),
+                (129, "enp5s0-rx-0".to_string()),
+                (140, "amdgpu".to_string()),
+            ]
+        );
+        assert!(parse_interrupts(INTERRUPTS, &devices(&["nvidia"])).is_empty());
+    }
+
+    #[test]
+    fn test_format_cpu_list_ranges() {
+        let cpus: BTreeSet<u32> = [0, 1, 2, 3, 8, 10, 11].into_iter().collect();
+        assert_eq!(format_cpu_list(&cpus), "0-3,8,10-11");
+        assert_eq!(format_cpu_list(&BTreeSet::from([5])), "5");
+    }
+
+    fn task(pid: u32, tier: u8, cpu: u32, runtime_ns: u64) -> TaskStat {
+        TaskStat {
+            pid,
+            tgid: pid,
+            cgid: 0,
+            comm: String::new(),
+            tier,
+            cpu,
+            avg_runtime_us: 0,
+            runtime_ns,
+            wait_ns: 0,
+            nr_runs: 0,
+        }
+    }
+
+    #[test]
+    fn test_hot_cpus_needs_protected_tier_and_load() {
+        let before = [task(1, 2, 3, 0), task(2, 3, 5, 0), task(3, 0, 7, 0)];
+        let after = [
+            task(1, 2, 3, 500_000_000), // Frame at 50%: hot
+            task(2, 3, 5, 900_000_000), // Bulk: ignored
+            task(3, 0, 7, 1_000_000),   // Critical but nearly idle
+        ];
+        let mask = (1 << 0) | (1 << 2);
+        assert_eq!(hot_cpus(&before, &after, mask, 1_000_000_000), vec![3]);
+    }
+}
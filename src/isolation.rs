@@ -0,0 +1,27 @@
+// SPDX-License-Identifier: GPL-2.0
+// Isolation module - CPUs reserved by isolcpus=, nohz_full= and isolated cpuset partitions
+
+use crate::topology::{sysfs_cpu_mask, CpuMask};
+
+/// Where the kernel publishes each kind of isolation, by the name users
+/// configure it with
+const SOURCES: [(&str, &str); 3] = [
+    ("isolcpus=", "/sys/devices/system/cpu/isolated"),
+    ("nohz_full=", "/sys/devices/system/cpu/nohz_full"),
+    (
+        "cpuset.cpus.partition=isolated",
+        "/sys/fs/cgroup/cpuset.cpus.isolated",
+    ),
+];
+
+/// Isolated CPUs by source, only the sources that isolate any. Missing files
+/// (older kernels, cgroup v1) and nohz_full's "(null)" count as none.
+pub fn isolated_cpus() -> Vec<(&'static str, CpuMask)> {
+    SOURCES
+        .iter()
+        .filter_map(|&(name, path)| {
+            let mask = sysfs_cpu_mask(&std::fs::read_to_string(path).ok()?)?;
+            (!mask.is_empty()).then_some((name, mask))
+        })
+        .collect()
+}
@@ -0,0 +1,366 @@
+// SPDX-License-Identifier: GPL-2.0
+// Keymap module - TUI key bindings: defaults (with vim keys) and [keys] overrides from --config
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, bail, Result};
+use clap::ValueEnum;
+use crossterm::event::KeyCode;
+use serde::Deserialize;
+
+/// How long the first key of a sequence (gg) waits for the second before
+/// it acts on its own
+pub const SEQ_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Something a key does in the TUI. Names are the [keys] table keys
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, ValueEnum)]
+pub enum Action {
+    /// Config tab: step the selected tunable down / up
+    Left,
+    Right,
+    /// Config tab: apply the pending edit / restore the loaded value
+    Apply,
+    Restore,
+    /// Task view: expand or collapse the selected process
+    Expand,
+    Up,
+    Down,
+    PageUp,
+    PageDown,
+    Top,
+    Bottom,
+    Help,
+    Quit,
+    Copy,
+    Snapshot,
+    Tasks,
+    NextTab,
+    #[value(name = "tab-1")]
+    Tab1,
+    #[value(name = "tab-2")]
+    Tab2,
+    #[value(name = "tab-3")]
+    Tab3,
+    #[value(name = "tab-4")]
+    Tab4,
+    #[value(name = "tab-5")]
+    Tab5,
+    Sort,
+    SortDirection,
+    TierRates,
+    Filter,
+    Freeze,
+    Refresh,
+    Heatmap,
+    Topology,
+    Llcs,
+    Waits,
+    WaitBars,
+    Events,
+    Preempts,
+    RateChart,
+    Group,
+    PinTier,
+    Reset,
+    PauseStats,
+}
+
+impl Action {
+    /// Tab index (0-based) for tab-1 .. tab-5
+    pub fn tab(self) -> Option<usize> {
+        [
+            Action::Tab1,
+            Action::Tab2,
+            Action::Tab3,
+            Action::Tab4,
+            Action::Tab5,
+        ]
+        .iter()
+        .position(|&a| a == self)
+    }
+}
+
+/// Default bindings. A key bound to several actions (h: left on the
+/// Config tab, the heatmap elsewhere) does the first one that applies
+const DEFAULTS: &[(Action, &[&str])] = &[
+    (Action::Left, &["left", "h"]),
+    (Action::Right, &["right", "l"]),
+    (Action::Apply, &["enter"]),
+    (Action::Restore, &["del"]),
+    (Action::Expand, &["enter"]),
+    (Action::Up, &["up", "k"]),
+    (Action::Down, &["down", "j"]),
+    (Action::PageUp, &["pgup"]),
+    (Action::PageDown, &["pgdn"]),
+    (Action::Top, &["home", "gg"]),
+    (Action::Bottom, &["end", "G"]),
+    (Action::Help, &["?"]),
+    (Action::Quit, &["q", "esc"]),
+    (Action::Copy, &["c"]),
+    (Action::Snapshot, &["e"]),
+    (Action::Tasks, &["t"]),
+    (Action::NextTab, &["tab"]),
+    (Action::Tab1, &["1"]),
+    (Action::Tab2, &["2"]),
+    (Action::Tab3, &["3"]),
+    (Action::Tab4, &["4"]),
+    (Action::Tab5, &["5"]),
+    (Action::Sort, &["o"]),
+    (Action::SortDirection, &["O"]),
+    (Action::TierRates, &["d"]),
+    (Action::Filter, &["/"]),
+    (Action::Freeze, &["p"]),
+    (Action::Refresh, &["space"]),
+    (Action::Heatmap, &["h"]),
+    (Action::Topology, &["l"]),
+    (Action::Llcs, &["L"]),
+    (Action::Waits, &["w"]),
+    (Action::WaitBars, &["b"]),
+    (Action::Events, &["v"]),
+    (Action::Preempts, &["x"]),
+    (Action::RateChart, &["a"]),
+    (Action::Group, &["g"]),
+    (Action::PinTier, &["T"]),
+    (Action::Reset, &["r"]),
+    (Action::PauseStats, &["s"]),
+];
+
+/// A [keys] value: one key or a list of them
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum KeyList {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl KeyList {
+    fn keys(&self) -> &[String] {
+        match self {
+            KeyList::One(key) => std::slice::from_ref(key),
+            KeyList::Many(keys) => keys,
+        }
+    }
+}
+
+/// One key by name (left, pgdn, space, ...) or as itself (G, /), or a
+/// two-key sequence of characters (gg)
+fn parse_key(spec: &str) -> Result<Vec<KeyCode>> {
+    let named = match spec.to_lowercase().as_str() {
+        "left" => Some(KeyCode::Left),
+        "right" => Some(KeyCode::Right),
+        "up" => Some(KeyCode::Up),
+        "down" => Some(KeyCode::Down),
+        "pgup" | "pageup" => Some(KeyCode::PageUp),
+        "pgdn" | "pagedown" => Some(KeyCode::PageDown),
+        "home" => Some(KeyCode::Home),
+        "end" => Some(KeyCode::End),
+        "enter" => Some(KeyCode::Enter),
+        "esc" => Some(KeyCode::Esc),
+        "tab" => Some(KeyCode::Tab),
+        "space" => Some(KeyCode::Char(' ')),
+        "del" | "delete" => Some(KeyCode::Delete),
+        _ => None,
+    };
+    if let Some(code) = named {
+        return Ok(vec![code]);
+    }
+    let chars: Vec<KeyCode> = spec.chars().map(KeyCode::Char).collect();
+    match chars.len() {
+        1 | 2 => Ok(chars),
+        _ => bail!("unknown key '{}'", spec),
+    }
+}
+
+/// Key (or two-key sequence) to the actions it may do, in order
+#[derive(Debug, Clone)]
+pub struct Keymap {
+    bindings: HashMap<Vec<KeyCode>, Vec<Action>>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let mut map = Self {
+            bindings: HashMap::new(),
+        };
+        for &(action, keys) in DEFAULTS {
+            for key in keys {
+                map.bind(parse_key(key).expect("default key"), action);
+            }
+        }
+        map
+    }
+}
+
+impl Keymap {
+    /// The defaults with a [keys] table on top: each action named there
+    /// gets exactly the keys listed, which it takes over from any default
+    pub fn with_overrides(overrides: &HashMap<String, KeyList>) -> Result<Self> {
+        let mut map = Self::default();
+        let mut names: Vec<&String> = overrides.keys().collect();
+        names.sort();
+        for name in names {
+            let action =
+                Action::from_str(name, true).map_err(|_| anyhow!("unknown action '{}'", name))?;
+            let keys = overrides[name]
+                .keys()
+                .iter()
+                .map(|k| parse_key(k))
+                .collect::<Result<Vec<_>>>()?;
+            for actions in map.bindings.values_mut() {
+                actions.retain(|&a| a != action);
+            }
+            // A key named in [keys] does only what [keys] says
+            for key in &keys {
+                map.bindings.insert(key.clone(), Vec::new());
+            }
+            for key in keys {
+                map.bind(key, action);
+            }
+        }
+        map.bindings.retain(|_, actions| !actions.is_empty());
+        Ok(map)
+    }
+
+    fn bind(&mut self, key: Vec<KeyCode>, action: Action) {
+        self.bindings.entry(key).or_default().push(action);
+    }
+
+    fn is_prefix(&self, code: KeyCode) -> bool {
+        self.bindings
+            .keys()
+            .any(|key| key.len() == 2 && key[0] == code)
+    }
+
+    fn actions(&self, key: &[KeyCode]) -> Option<&[Action]> {
+        self.bindings.get(key).map(Vec::as_slice)
+    }
+
+    /// Feed one key press. Returns the candidate actions of each key it
+    /// completes: a pending first key that the press does not continue
+    /// acts on its own first. A key that starts a sequence is held in
+    /// `pending` until the next press or expire()
+    pub fn press(&self, code: KeyCode, pending: &mut Option<(KeyCode, Instant)>) -> Vec<&[Action]> {
+        let mut out = Vec::new();
+        if let Some((first, _)) = pending.take() {
+            if let Some(actions) = self.actions(&[first, code]) {
+                return vec![actions];
+            }
+            out.extend(self.actions(&[first]));
+        }
+        match self.is_prefix(code) {
+            true => *pending = Some((code, Instant::now())),
+            false => out.extend(self.actions(&[code])),
+        }
+        out
+    }
+
+    /// A pending first key left alone for SEQ_TIMEOUT acts on its own
+    pub fn expire(&self, pending: &mut Option<(KeyCode, Instant)>) -> Option<&[Action]> {
+        match *pending {
+            Some((code, at)) if at.elapsed() >= SEQ_TIMEOUT => {
+                *pending = None;
+                self.actions(&[code])
+            }
+            _ => None,
+        }
+    }
+
+    /// Bindings that differ from the defaults, as (keys, action) for the help
+    pub fn remapped(&self) -> Vec<(String, Action)> {
+        let defaults = Self::default();
+        let mut out: Vec<(String, Action)> = self
+            .bindings
+            .iter()
+            .flat_map(|(key, actions)| actions.iter().map(move |&a| (key, a)))
+            .filter(|&(key, a)| defaults.actions(key).is_none_or(|d| !d.contains(&a)))
+            .map(|(key, a)| (key_name(key), a))
+            .collect();
+        out.sort_by(|a, b| a.0.cmp(&b.0));
+        out
+    }
+}
+
+fn key_name(key: &[KeyCode]) -> String {
+    key.iter()
+        .map(|code| match code {
+            KeyCode::Char(' ') => "Space".to_string(),
+            KeyCode::Char(c) => c.to_string(),
+            other => format!("{:?}", other),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn overrides(toml_text: &str) -> Result<Keymap> {
+        let table: HashMap<String, KeyList> = toml::from_str(toml_text)?;
+        Keymap::with_overrides(&table)
+    }
+
+    #[test]
+    fn test_sequences_and_fallback() {
+        let keys = Keymap::default();
+        let mut pending = None;
+        assert_eq!(
+            keys.press(KeyCode::Char('h'), &mut pending),
+            [&[Action::Left, Action::Heatmap][..]]
+        );
+
+        // g waits: gg is Top, g then j is Group then Down
+        assert!(keys.press(KeyCode::Char('g'), &mut pending).is_empty());
+        assert_eq!(
+            keys.press(KeyCode::Char('g'), &mut pending),
+            [&[Action::Top][..]]
+        );
+        assert!(pending.is_none());
+        keys.press(KeyCode::Char('g'), &mut pending);
+        assert_eq!(
+            keys.press(KeyCode::Char('j'), &mut pending),
+            [&[Action::Group][..], &[Action::Down][..]]
+        );
+
+        // A lone g acts once the sequence times out
+        keys.press(KeyCode::Char('g'), &mut pending);
+        assert!(keys.expire(&mut pending).is_none());
+        pending = pending.map(|(code, _)| (code, Instant::now() - SEQ_TIMEOUT));
+        assert_eq!(keys.expire(&mut pending), Some(&[Action::Group][..]));
+        assert!(pending.is_none());
+    }
+
+    #[test]
+    fn test_overrides() {
+        let err = overrides("topology = [\"P\", \"F12\"]").unwrap_err();
+        assert!(err.to_string().contains("F12"));
+
+        let keys = overrides("heatmap = \"H\"\nfreeze = [\"z\", \"space\"]").unwrap();
+        let mut pending = None;
+        assert_eq!(
+            keys.press(KeyCode::Char('h'), &mut pending),
+            [&[Action::Left][..]]
+        );
+        assert_eq!(
+            keys.press(KeyCode::Char('H'), &mut pending),
+            [&[Action::Heatmap][..]]
+        );
+        // Space moved from refresh to freeze; p no longer freezes
+        assert_eq!(
+            keys.press(KeyCode::Char(' '), &mut pending),
+            [&[Action::Freeze][..]]
+        );
+        assert!(keys.press(KeyCode::Char('p'), &mut pending).is_empty());
+        assert_eq!(
+            keys.remapped(),
+            [
+                ("H".to_string(), Action::Heatmap),
+                ("Space".to_string(), Action::Freeze),
+                ("z".to_string(), Action::Freeze),
+            ]
+        );
+
+        assert!(overrides("warp = \"w\"").is_err());
+        assert!(overrides("tab-3 = \"\"").is_err());
+    }
+}
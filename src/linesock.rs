@@ -0,0 +1,142 @@
+// SPDX-License-Identifier: GPL-2.0
+// Linesock module - one-line request/reply unix-socket server and client, shared by the control and query sockets
+
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use log::warn;
+
+/// Accept poll period — bounds how long shutdown waits for the server thread
+const ACCEPT_NAP: Duration = Duration::from_millis(200);
+/// A client that connects but never sends a request is dropped after this
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Background thread serving one request line per connection on a unix
+/// socket; removes the socket when dropped
+pub struct LineServer {
+    path: &'static str,
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl LineServer {
+    /// Bind `path` with permissions `mode` and serve it from a thread named
+    /// `name`. `init` runs first on that thread and returns the handler,
+    /// which maps each request line to its reply (newline added here), so
+    /// state that must stay on one thread can live in it.
+    pub fn spawn<F, H>(path: &'static str, mode: u32, name: &str, init: F) -> Result<Self>
+    where
+        F: FnOnce() -> H + Send + 'static,
+        H: FnMut(&str) -> String,
+    {
+        let sock = Path::new(path);
+        if UnixStream::connect(sock).is_ok() {
+            bail!("another scx_cake is serving {}", path);
+        }
+        std::fs::create_dir_all(sock.parent().unwrap())?;
+        let _ = std::fs::remove_file(sock);
+        let listener =
+            UnixListener::bind(sock).with_context(|| format!("Failed to bind {}", path))?;
+        std::fs::set_permissions(sock, std::fs::Permissions::from_mode(mode))?;
+        listener.set_nonblocking(true)?;
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let stop_thread = stop.clone();
+        let handle = std::thread::Builder::new()
+            .name(name.into())
+            .spawn(move || serve(listener, path, init(), stop_thread))?;
+        Ok(Self {
+            path,
+            stop,
+            handle: Some(handle),
+        })
+    }
+}
+
+impl Drop for LineServer {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(h) = self.handle.take() {
+            let _ = h.join();
+        }
+        let _ = std::fs::remove_file(self.path);
+    }
+}
+
+fn serve(
+    listener: UnixListener,
+    path: &str,
+    mut handler: impl FnMut(&str) -> String,
+    stop: Arc<AtomicBool>,
+) {
+    while !stop.load(Ordering::Relaxed) {
+        let stream = match listener.accept() {
+            Ok((stream, _)) => stream,
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                std::thread::sleep(ACCEPT_NAP);
+                continue;
+            }
+            Err(e) => {
+                warn!("{}: accept failed: {}", path, e);
+                std::thread::sleep(ACCEPT_NAP);
+                continue;
+            }
+        };
+        let _ = stream.set_nonblocking(false);
+        let _ = stream.set_read_timeout(Some(CLIENT_TIMEOUT));
+
+        let mut line = String::new();
+        if BufReader::new(&stream).read_line(&mut line).is_err() {
+            continue;
+        }
+        let reply = handler(&line);
+        let _ = (&stream).write_all(format!("{}\n", reply).as_bytes());
+    }
+}
+
+/// Send one request line to the running scheduler at `path` and return its
+/// reply line
+pub fn request(path: &str, line: &str) -> Result<String> {
+    let mut stream = UnixStream::connect(path)
+        .with_context(|| format!("Failed to connect to {} (is scx_cake running?)", path))?;
+    stream.write_all(format!("{}\n", line).as_bytes())?;
+    let mut reply = String::new();
+    BufReader::new(&stream).read_line(&mut reply)?;
+    Ok(reply)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let path: &'static str = Box::leak(
+            std::env::temp_dir()
+                .join(format!("scx_cake_linesock_{}.sock", std::process::id()))
+                .to_string_lossy()
+                .into_owned()
+                .into_boxed_str(),
+        );
+        let server = LineServer::spawn(path, 0o600, "cake-test", || {
+            let mut n = 0;
+            move |line: &str| {
+                n += 1;
+                format!("{} {}", n, line.trim())
+            }
+        })
+        .unwrap();
+        assert_eq!(request(path, "hello").unwrap(), "1 hello\n");
+        assert_eq!(request(path, "again").unwrap(), "2 again\n");
+        assert!(LineServer::spawn(path, 0o600, "cake-test", || |_: &str| String::new()).is_err());
+        drop(server);
+        assert!(!Path::new(path).exists());
+    }
+}
@@ -1,18 +1,53 @@
 // SPDX-License-Identifier: GPL-2.0
 // scx_cake - sched_ext scheduler applying CAKE bufferbloat concepts to CPU scheduling
 
+mod alert;
+mod autotune;
+mod bench;
 mod calibrate;
+mod cgroup;
+mod check;
+mod config;
+mod control;
+mod csvlog;
+mod ctl;
+mod epp;
+mod events;
+mod history;
+mod hotplug;
+mod idle;
+mod irq;
+mod isolation;
+mod keymap;
+mod linesock;
+mod metrics;
+#[cfg(test)]
+mod model;
+#[cfg(feature = "otlp")]
+mod otlp;
+mod overrides;
+mod partial;
+mod powersave;
+mod query;
+mod replay;
+mod rules;
+mod schedule;
 mod stats;
+mod statsd;
+mod theme;
 mod topology;
+mod trace;
 mod tui;
 
 use core::sync::atomic::Ordering;
-use std::os::fd::AsRawFd;
+use std::os::fd::{AsRawFd, BorrowedFd};
+use std::path::{Path, PathBuf};
 use std::sync::atomic::AtomicBool;
 use std::sync::Arc;
+use std::time::Duration;
 
-use anyhow::{Context, Result};
-use clap::{Parser, ValueEnum};
+use anyhow::{anyhow, bail, Context, Result};
+use clap::{ArgGroup, Parser, Subcommand, ValueEnum};
 use log::{info, warn};
 use nix::sys::signal::{SigSet, Signal};
 use nix::sys::signalfd::{SfdFlags, SignalFd};
@@ -28,6 +63,7 @@ mod bpf_skel {
     include!(concat!(env!("OUT_DIR"), "/bpf_skel.rs"));
 }
 use bpf_skel::*;
+use theme::Theme;
 
 /// Scheduler profile presets
 #[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
@@ -40,6 +76,215 @@ pub enum Profile {
     Gaming,
     /// Balanced profile for general desktop use (same as gaming for now)
     Default,
+    /// Shortest slices for every tier: input-to-photon first, throughput second (1ms quantum)
+    Latency,
+    /// Long slices for build servers and batch work; latency still bounded (4ms quantum)
+    Throughput,
+    /// Few context switches and wakeups so cores reach deep idle (4ms quantum)
+    Powersave,
+}
+
+/// Tier names accepted by per-tier CLI options
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Tier {
+    /// T0: IRQ, input, audio, network (<100µs)
+    Critical,
+    /// T1: compositor, physics, AI (<2ms)
+    Interactive,
+    /// T2: game render, encoding (<8ms)
+    Frame,
+    /// T3: compilation, background (≥8ms)
+    Bulk,
+}
+
+/// Statistics display for --verbose
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum UiMode {
+    /// TUI on a terminal, plain text otherwise
+    Auto,
+    /// Interactive terminal UI
+    Tui,
+    /// One summary line per interval
+    Plain,
+    /// One JSON object per interval
+    Json,
+}
+
+/// Concurrency cap for one tier: TIER=N tasks, or TIER=N% of the CPUs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TierCap {
+    tier: Tier,
+    limit: u32,
+    percent: bool,
+}
+
+impl std::str::FromStr for TierCap {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (tier, limit) = s
+            .split_once('=')
+            .ok_or_else(|| format!("expected TIER=N or TIER=N%, got '{}'", s))?;
+        let (limit, percent) = match limit.strip_suffix('%') {
+            Some(pct) => (pct, true),
+            None => (limit, false),
+        };
+        let limit: u32 = limit
+            .trim()
+            .parse()
+            .map_err(|e| format!("bad limit in '{}': {}", s, e))?;
+        if limit == 0 || (percent && limit > 100) {
+            return Err(format!("limit out of range in '{}'", s));
+        }
+        Ok(Self {
+            tier: Tier::from_str(tier.trim(), true)?,
+            limit,
+            percent,
+        })
+    }
+}
+
+impl TierCap {
+    /// Running-task limit on this machine (a percentage never rounds to 0)
+    fn resolve(&self, nr_cpus: usize) -> u32 {
+        match self.percent {
+            true => ((nr_cpus as u32 * self.limit) / 100).max(1),
+            false => self.limit,
+        }
+    }
+}
+
+/// Quantum multiplier override for one tier: TIER=X (1.0 = --quantum)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TierQuantum {
+    tier: Tier,
+    multiplier: f64,
+}
+
+/// Largest multiplier the 12-bit fixed-point field holds (4095/1024)
+const MAX_TIER_MULTIPLIER: f64 = 4.0;
+
+impl std::str::FromStr for TierQuantum {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (tier, mult) = s
+            .split_once('=')
+            .ok_or_else(|| format!("expected TIER=MULTIPLIER, got '{}'", s))?;
+        let multiplier: f64 = mult
+            .trim()
+            .parse()
+            .map_err(|e| format!("bad multiplier in '{}': {}", s, e))?;
+        if !(multiplier > 0.0 && multiplier <= MAX_TIER_MULTIPLIER) {
+            return Err(format!(
+                "multiplier in '{}' must be in (0, {}]",
+                s, MAX_TIER_MULTIPLIER
+            ));
+        }
+        Ok(Self {
+            tier: Tier::from_str(tier.trim(), true)?,
+            multiplier,
+        })
+    }
+}
+
+impl TierQuantum {
+    /// Fixed-point multiplier (1024 = 1.0x), saturated to the 12-bit field
+    fn fixed_point(&self) -> u32 {
+        ((self.multiplier * 1024.0).round() as u32).clamp(1, 0xFFF)
+    }
+}
+
+/// Starvation threshold override for one tier: TIER=MICROSECONDS
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TierStarvation {
+    tier: Tier,
+    us: u64,
+}
+
+/// Largest threshold the 20-bit field holds (0xFFFFF << 10 ns ≈ 1.07s)
+const MAX_TIER_STARVATION_US: u64 = (0xFFFFF << 10) / 1000;
+
+/// Rebalance cadence when --rebalance-imbalance is set without
+/// --rebalance-interval
+const REBALANCE_IMBALANCE_INTERVAL_MS: u64 = 100;
+
+impl std::str::FromStr for TierStarvation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (tier, us) = s
+            .split_once('=')
+            .ok_or_else(|| format!("expected TIER=MICROSECONDS, got '{}'", s))?;
+        let us: u64 = us
+            .trim()
+            .parse()
+            .map_err(|e| format!("bad threshold in '{}': {}", s, e))?;
+        if us == 0 || us > MAX_TIER_STARVATION_US {
+            return Err(format!(
+                "threshold in '{}' must be 1-{}µs",
+                s, MAX_TIER_STARVATION_US
+            ));
+        }
+        Ok(Self {
+            tier: Tier::from_str(tier.trim(), true)?,
+            us,
+        })
+    }
+}
+
+/// Initial tier for a range of nice values: LO..HI=TIER or N=TIER (inclusive)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NiceRange {
+    lo: i32,
+    hi: i32,
+    tier: Tier,
+}
+
+impl std::str::FromStr for NiceRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (range, tier) = s
+            .split_once('=')
+            .ok_or_else(|| format!("expected LO..HI=TIER, got '{}'", s))?;
+        let nice = |v: &str| {
+            v.trim()
+                .parse::<i32>()
+                .map_err(|e| format!("bad nice value in '{}': {}", s, e))
+        };
+        let (lo, hi) = match range.split_once("..") {
+            Some((lo, hi)) => (nice(lo)?, nice(hi)?),
+            None => (nice(range)?, nice(range)?),
+        };
+        if !(-20..=19).contains(&lo) || !(lo..=19).contains(&hi) {
+            return Err(format!(
+                "range in '{}' must be within -20..19, low first",
+                s
+            ));
+        }
+        Ok(Self {
+            lo,
+            hi,
+            tier: Tier::from_str(tier.trim(), true)?,
+        })
+    }
+}
+
+/// Nice → initial tier table indexed by nice + 20 (the nice_tier rodata):
+/// built-in mapping with `ranges` applied in order
+pub fn nice_tiers(ranges: &[NiceRange]) -> [u8; 40] {
+    let mut tiers = std::array::from_fn(|i| match i as i32 - 20 {
+        n if n < 0 => Tier::Critical as u8,
+        n if n > 10 => Tier::Bulk as u8,
+        _ => Tier::Interactive as u8,
+    });
+    for range in ranges {
+        for nice in range.lo..=range.hi {
+            tiers[(nice + 20) as usize] = range.tier as u8;
+        }
+    }
+    tiers
 }
 
 impl Profile {
@@ -54,20 +299,26 @@ impl Profile {
             Profile::Gaming => (2000, 8000, 100000),
             // Default: Same as gaming for now
             Profile::Default => (2000, 8000, 100000),
+            // Latency: esports quantum, but new flows get less head start
+            Profile::Latency => (1000, 2000, 50000),
+            // Throughput: legacy quantum with a long starvation leash
+            Profile::Throughput => (4000, 8000, 400000),
+            // Powersave: long slices, small bonus — fewer preemptions overall
+            Profile::Powersave => (4000, 4000, 200000),
         }
     }
 
     /// Per-tier starvation thresholds in nanoseconds (4 tiers + padding)
     fn starvation_threshold(&self) -> [u64; 8] {
         match self {
-            Profile::Esports => [
+            Profile::Esports | Profile::Latency => [
                 1_500_000,  // T0 Critical: 1.5ms
                 4_000_000,  // T1 Interactive: 4ms
                 20_000_000, // T2 Frame: 20ms
                 50_000_000, // T3 Bulk: 50ms
                 50_000_000, 50_000_000, 50_000_000, 50_000_000, // Padding
             ],
-            Profile::Legacy => [
+            Profile::Legacy | Profile::Powersave => [
                 6_000_000,   // T0 Critical: 6ms
                 16_000_000,  // T1 Interactive: 16ms
                 80_000_000,  // T2 Frame: 80ms
@@ -87,6 +338,31 @@ impl Profile {
                 100_000_000,
                 100_000_000, // Padding
             ],
+            Profile::Throughput => [
+                10_000_000,  // T0 Critical: 10ms
+                40_000_000,  // T1 Interactive: 40ms
+                200_000_000, // T2 Frame: 200ms
+                400_000_000, // T3 Bulk: 400ms
+                400_000_000,
+                400_000_000,
+                400_000_000,
+                400_000_000, // Padding
+            ],
+        }
+    }
+
+    /// Starvation-immune tiers as a bitmask (bit N = tier N)
+    fn starvation_immune_mask(&self) -> u8 {
+        match self {
+            // Critical + Interactive: audio/input must never yield to a Bulk rescue
+            Profile::Esports
+            | Profile::Legacy
+            | Profile::Gaming
+            | Profile::Default
+            | Profile::Latency
+            | Profile::Powersave => (1 << Tier::Critical as u8) | (1 << Tier::Interactive as u8),
+            // Throughput: only Critical (IRQ, audio) is spared by a Bulk rescue
+            Profile::Throughput => 1 << Tier::Critical as u8,
         }
     }
 
@@ -100,20 +376,41 @@ impl Profile {
                 1434, // T3 Bulk: 1.4x
                 1434, 1434, 1434, 1434, // Padding
             ],
+            Profile::Latency => [
+                512,  // T0 Critical: 0.5x
+                768,  // T1 Interactive: 0.75x
+                1024, // T2 Frame: 1.0x
+                1280, // T3 Bulk: 1.25x
+                1280, 1280, 1280, 1280, // Padding
+            ],
+            Profile::Throughput => [
+                1024, // T0 Critical: 1.0x
+                1536, // T1 Interactive: 1.5x
+                2048, // T2 Frame: 2.0x
+                4095, // T3 Bulk: ~4.0x (12-bit max)
+                4095, 4095, 4095, 4095, // Padding
+            ],
+            Profile::Powersave => [
+                1024, // T0 Critical: 1.0x
+                1536, // T1 Interactive: 1.5x
+                2048, // T2 Frame: 2.0x
+                3072, // T3 Bulk: 3.0x
+                3072, 3072, 3072, 3072, // Padding
+            ],
         }
     }
 
     /// Wait budget per tier in nanoseconds — 4 tiers + padding
     fn wait_budget(&self) -> [u64; 8] {
         match self {
-            Profile::Esports => [
+            Profile::Esports | Profile::Latency => [
                 50_000,    // T0 Critical: 50µs
                 1_000_000, // T1 Interactive: 1ms
                 4_000_000, // T2 Frame: 4ms
                 0,         // T3 Bulk: no limit
                 0, 0, 0, 0, // Padding
             ],
-            Profile::Legacy => [
+            Profile::Legacy | Profile::Throughput | Profile::Powersave => [
                 200_000,    // T0 Critical: 200µs
                 4_000_000,  // T1 Interactive: 4ms
                 16_000_000, // T2 Frame: 16ms
@@ -131,9 +428,12 @@ impl Profile {
     }
 
     /// Consolidated tier config - packs quantum/multiplier/budget/starvation into 64-bit per tier.
-    fn tier_configs(&self, quantum_us: u64) -> [u64; 8] {
-        let starvation = self.starvation_threshold();
-        let multiplier = self.tier_multiplier();
+    fn tier_configs(
+        &self,
+        quantum_us: u64,
+        multiplier: [u32; 8],
+        starvation: [u64; 8],
+    ) -> [u64; 8] {
         let budget = self.wait_budget();
 
         let mut configs = [0u64; 8];
@@ -163,17 +463,45 @@ impl Profile {
 ///
 /// EXAMPLES:
 ///   scx_cake                          # Run with gaming profile (default)
+///   scx_cake run -p esports           # Same, spelled as a subcommand
+///   scx_cake monitor                  # Per-tier stats of the running scheduler
+///   scx_cake dump-topology            # Show what topology detection found
 ///   scx_cake -p esports               # Ultra-low-latency for competitive play
 ///   scx_cake --quantum 1500           # Gaming profile with custom quantum
 ///   scx_cake -v                       # Run with live TUI stats display
-#[derive(Parser, Debug)]
+///   scx_cake bench game --mangohud-log mangohud.csv   # Attribute frame spikes
+#[derive(Parser, Debug, Clone)]
 #[command(
     author,
     version,
     about = "🍰 A sched_ext scheduler applying CAKE bufferbloat concepts to CPU scheduling",
-    verbatim_doc_comment
+    verbatim_doc_comment,
+    group(ArgGroup::new("domain").args(["primary_domain", "x3d"]))
 )]
 struct Args {
+    /// Load options from a TOML file; command-line options override it.
+    ///
+    /// Every option can also be set from the environment as
+    /// SCX_CAKE_<OPTION> (e.g. SCX_CAKE_QUANTUM=1500,
+    /// SCX_CAKE_TIER_CAP=bulk=25%). Precedence: command line >
+    /// environment > config file > profile defaults.
+    ///
+    /// Keys are the long option names with underscores, e.g.
+    ///   version = 1
+    ///   profile = "gaming"
+    ///   quantum = 1500
+    ///   interval = 5
+    ///   tier_cap = ["bulk=25%"]
+    /// Files without `version` (or an older one) are migrated to the
+    /// current schema; unknown keys are warned about and ignored.
+    #[arg(
+        long,
+        env = "SCX_CAKE_CONFIG",
+        value_name = "PATH",
+        verbatim_doc_comment
+    )]
+    config: Option<PathBuf>,
+
     /// Scheduler profile preset.
     ///
     /// Profiles configure all tier thresholds, quantum multipliers, and wait budgets.
@@ -190,7 +518,23 @@ struct Args {
     ///
     /// DEFAULT: Balanced profile for general desktop use.
     ///   - Currently same as gaming; will diverge in future versions
-    #[arg(long, short, value_enum, default_value_t = Profile::Gaming, verbatim_doc_comment)]
+    ///
+    /// LATENCY: Shortest slices in every tier, small new-flow bonus.
+    ///   - Quantum: 1000µs, Starvation: 50ms, multipliers 0.5x-1.25x
+    ///
+    /// THROUGHPUT: Build servers and batch work.
+    ///   - Quantum: 4000µs, Starvation: 400ms, multipliers 1.0x-4.0x
+    ///
+    /// POWERSAVE: Fewer preemptions so cores reach deep idle.
+    ///   - Quantum: 4000µs, Starvation: 200ms, multipliers 1.0x-3.0x
+    #[arg(
+        long,
+        short,
+        env = "SCX_CAKE_PROFILE",
+        value_enum,
+        default_value_t = Profile::Gaming,
+        verbatim_doc_comment
+    )]
     profile: Profile,
 
     /// Base scheduling time slice in MICROSECONDS [default: 2000].
@@ -200,7 +544,7 @@ struct Args {
     /// Smaller quantum = more responsive but higher overhead.
     /// Esports: 1000µs | Gaming: 2000µs | Legacy: 4000µs
     /// Recommended range: 1000-8000µs
-    #[arg(long, verbatim_doc_comment)]
+    #[arg(long, env = "SCX_CAKE_QUANTUM", verbatim_doc_comment)]
     quantum: Option<u64>,
 
     /// Bonus time for newly woken tasks in MICROSECONDS [default: 8000].
@@ -210,7 +554,7 @@ struct Args {
     ///
     /// Esports: 4000µs | Gaming: 8000µs
     /// Recommended range: 4000-16000µs
-    #[arg(long, verbatim_doc_comment)]
+    #[arg(long, env = "SCX_CAKE_NEW_FLOW_BONUS", verbatim_doc_comment)]
     new_flow_bonus: Option<u64>,
 
     /// Max run time before forced preemption in MICROSECONDS [default: 100000].
@@ -220,25 +564,921 @@ struct Args {
     ///
     /// Esports: 50000µs (50ms) | Gaming: 100000µs (100ms) | Legacy: 200000µs (200ms)
     /// Recommended range: 50000-200000µs
-    #[arg(long, verbatim_doc_comment)]
+    #[arg(long, env = "SCX_CAKE_STARVATION", verbatim_doc_comment)]
     starvation: Option<u64>,
 
+    /// Shortest slice under load in MICROSECONDS (requires --slice-max).
+    ///
+    /// With both bounds set, each CPU's slice ceiling shrinks as tasks
+    /// queue up: --slice-max / (1 + runnable tasks per CPU), never below
+    /// --slice-min. Tier slices are capped by that ceiling, so a busy
+    /// machine rotates faster and an idle one keeps long slices.
+    ///
+    /// Example: --slice-min 250 --slice-max 8000
+    #[arg(
+        long,
+        env = "SCX_CAKE_SLICE_MIN",
+        requires = "slice_max",
+        verbatim_doc_comment
+    )]
+    slice_min: Option<u64>,
+
+    /// Longest slice when nothing is waiting in MICROSECONDS (requires --slice-min).
+    #[arg(long, env = "SCX_CAKE_SLICE_MAX", requires = "slice_min")]
+    slice_max: Option<u64>,
+
+    /// Tiers immune to starvation preemption by lower tiers [default: critical,interactive].
+    ///
+    /// An immune task past its tier starvation limit is only preempted when
+    /// the waiting task is the same or a higher tier. --starvation remains a
+    /// hard ceiling: immune tasks running longer than it are preempted anyway.
+    ///
+    /// Example: --starvation-immune critical,interactive,frame
+    #[arg(
+        long,
+        env = "SCX_CAKE_STARVATION_IMMUNE",
+        value_enum,
+        value_delimiter = ',',
+        verbatim_doc_comment
+    )]
+    starvation_immune: Option<Vec<Tier>>,
+
+    /// Disable starvation-preemption immunity for all tiers.
+    #[arg(
+        long,
+        env = "SCX_CAKE_NO_STARVATION_IMMUNITY",
+        conflicts_with = "starvation_immune"
+    )]
+    no_starvation_immunity: bool,
+
+    /// Cap how many tasks of a tier run at once: TIER=N or TIER=N% (repeatable).
+    ///
+    /// Enforced at dispatch: tasks of a tier at its cap stay queued
+    /// while other CPUs run higher tiers or idle, keeping caches and
+    /// memory bandwidth free for the foreground. N% is of online CPUs.
+    ///
+    /// Example: --tier-cap bulk=25%
+    #[arg(
+        long,
+        env = "SCX_CAKE_TIER_CAP",
+        value_name = "TIER=N",
+        verbatim_doc_comment
+    )]
+    tier_cap: Vec<TierCap>,
+
+    /// Per-tier quantum multipliers: TIER=X[,TIER=X...] (1.0 = --quantum).
+    ///
+    /// A tier's slice is --quantum times its multiplier. Tiers not
+    /// listed keep the profile value:
+    ///   critical=0.75, interactive=1.0, frame=1.2, bulk=1.4
+    /// Multipliers range over (0, 4.0].
+    ///
+    /// Example: --tier-quantum critical=0.5,bulk=4.0
+    #[arg(
+        long,
+        env = "SCX_CAKE_TIER_QUANTUM",
+        value_name = "TIER=X",
+        value_delimiter = ',',
+        verbatim_doc_comment
+    )]
+    tier_quantum: Vec<TierQuantum>,
+
+    /// Per-tier starvation thresholds in MICROSECONDS: TIER=US[,TIER=US...].
+    ///
+    /// A task running past its tier threshold while others wait on its
+    /// CPU is preempted (subject to --starvation-immune). Tiers not
+    /// listed keep the profile value; gaming uses
+    ///   critical=3000, interactive=8000, frame=40000, bulk=100000
+    /// Maximum 1073740µs. --starvation stays the hard ceiling for
+    /// immune tiers.
+    ///
+    /// Example: --tier-starvation frame=20000,bulk=500000
+    #[arg(
+        long,
+        env = "SCX_CAKE_TIER_STARVATION",
+        value_name = "TIER=US",
+        value_delimiter = ',',
+        verbatim_doc_comment
+    )]
+    tier_starvation: Vec<TierStarvation>,
+
+    /// Initial tier by nice value: LO..HI=TIER[,LO..HI=TIER...].
+    ///
+    /// Sets where a new task starts; avg_runtime reclassification
+    /// takes over after a few runs either way. Ranges are inclusive
+    /// and applied in order over the default
+    ///   -20..-1=critical, 0..10=interactive, 11..19=bulk
+    /// Use -20..19=interactive to keep nice out of tier placement.
+    ///
+    /// Example: --nice-map=-20..-10=critical,-9..-1=interactive
+    #[arg(
+        long,
+        env = "SCX_CAKE_NICE_MAP",
+        value_name = "LO..HI=TIER",
+        value_delimiter = ',',
+        allow_hyphen_values = true,
+        verbatim_doc_comment
+    )]
+    nice_map: Vec<NiceRange>,
+
+    /// Pin tasks to a tier by name: comm=NAME:TIER (repeatable).
+    ///
+    /// NAME is matched against the thread name (comm, 15 bytes) when a
+    /// task is created and again when it execs; a trailing * matches a
+    /// prefix. The first matching rule wins and the task never leaves
+    /// that tier. Up to 32 rules.
+    ///
+    /// Example: --tier-rule comm=pipewire:critical --tier-rule 'comm=cc1*:bulk'
+    #[arg(
+        long,
+        env = "SCX_CAKE_TIER_RULE",
+        value_name = "comm=NAME:TIER",
+        verbatim_doc_comment
+    )]
+    tier_rule: Vec<rules::TierRule>,
+
+    /// Run --primary-tiers only on these CPUs (cpulist, e.g. the X3D CCD).
+    ///
+    /// Wakeups of a primary tier take an idle primary CPU or queue on
+    /// the primary LLC; CPUs outside the domain never pull them. Other
+    /// tiers spill everywhere. Tasks whose affinity excludes the whole
+    /// domain are not restricted.
+    ///
+    /// Example: --primary-domain 0-7,16-23
+    #[arg(
+        long,
+        env = "SCX_CAKE_PRIMARY_DOMAIN",
+        value_name = "LIST",
+        value_parser = parse_cpus,
+        verbatim_doc_comment
+    )]
+    primary_domain: Option<topology::CpuMask>,
+
+    /// Dual-CCD X3D: game tiers on the V-Cache CCD, Bulk on the other.
+    ///
+    /// Finds the CCD whose L3 is larger than the other's and makes it the
+    /// primary domain for --primary-tiers, as --primary-domain would.
+    /// Bulk prefers the frequency CCD: it queues there and takes an idle
+    /// CPU there first, but spills onto the cache CCD rather than wait.
+    /// Ignored (with a warning) when no CCD has the larger L3.
+    #[arg(long, env = "SCX_CAKE_X3D", verbatim_doc_comment)]
+    x3d: bool,
+
+    /// Tiers kept on --primary-domain (or the V-Cache CCD with --x3d).
+    #[arg(
+        long,
+        env = "SCX_CAKE_PRIMARY_TIERS",
+        value_enum,
+        value_delimiter = ',',
+        default_values = ["critical", "interactive", "frame"],
+        requires = "domain"
+    )]
+    primary_tiers: Vec<Tier>,
+
+    /// Keep these tiers off cores whose SMT sibling is busy [bare: all tiers].
+    ///
+    /// Wakeups of a listed tier only take a whole idle core; without one
+    /// they queue until a CPU with an idle sibling dispatches, or until
+    /// they have waited their tier wait budget (so they never starve).
+    /// Trades some throughput for per-thread performance. No effect on
+    /// machines without SMT.
+    ///
+    /// Example: --disable-smt critical,frame
+    #[arg(
+        long,
+        env = "SCX_CAKE_DISABLE_SMT",
+        value_enum,
+        value_name = "TIERS",
+        value_delimiter = ',',
+        num_args = 0..,
+        default_missing_values = ["critical", "interactive", "frame", "bulk"],
+        verbatim_doc_comment
+    )]
+    disable_smt: Option<Vec<Tier>>,
+
+    /// Keep lower tiers off the SMT sibling of these tiers
+    /// [bare: critical,interactive,frame].
+    ///
+    /// While a listed tier runs on a CPU, a lower-tier task on its
+    /// sibling is preempted and the sibling only takes the same tier or
+    /// higher; lower tiers wait until the listed task stops or they have
+    /// waited their tier wait budget. The counterpart of --disable-smt,
+    /// which moves the listed tier itself. No effect on machines without
+    /// SMT.
+    ///
+    /// Example: --smt-guard critical,frame
+    #[arg(
+        long,
+        env = "SCX_CAKE_SMT_GUARD",
+        value_enum,
+        value_name = "TIERS",
+        value_delimiter = ',',
+        num_args = 0..,
+        default_missing_values = ["critical", "interactive", "frame"],
+        verbatim_doc_comment
+    )]
+    smt_guard: Option<Vec<Tier>>,
+
+    /// Put these tiers on the highest-boosting idle core of the LLC
+    /// [bare: critical,interactive,frame].
+    ///
+    /// Ranks cores by CPPC highest_perf (cpufreq/amd_pstate_highest_perf,
+    /// else acpi_cppc/highest_perf), the order the kernel's preferred-core
+    /// (ITMT) logic uses: a wakeup of a listed tier trades the idle CPU
+    /// the kernel picked for a higher-ranked idle one in the same LLC.
+    /// No effect where all cores report the same highest_perf.
+    ///
+    /// Example: --preferred-cores critical,frame
+    #[arg(
+        long,
+        env = "SCX_CAKE_PREFERRED_CORES",
+        value_enum,
+        value_name = "TIERS",
+        value_delimiter = ',',
+        num_args = 0..,
+        default_missing_values = ["critical", "interactive", "frame"],
+        verbatim_doc_comment
+    )]
+    preferred_cores: Option<Vec<Tier>>,
+
+    /// Only place tasks on these CPUs (cpulist, e.g. 0-7,16-23).
+    ///
+    /// Other CPUs never pull from cake's queues and are left to tasks
+    /// that can't run on the managed set (pinned kthreads, affinity-
+    /// restricted tasks) and, with --partial, to the default
+    /// scheduler. Topology detection (LLC DSQs, P/E cores) only sees
+    /// the managed CPUs. CPUs 0-255.
+    #[arg(
+        long,
+        env = "SCX_CAKE_CPUS",
+        value_name = "LIST",
+        value_parser = parse_cpus,
+        verbatim_doc_comment
+    )]
+    cpus: Option<topology::CpuMask>,
+
+    /// Replace detected LLCs, P/E cores and SMT pairs from a TOML file.
+    ///
+    /// For new silicon and VMs where detection gets them wrong. Keys,
+    /// each optional, replace only what they name:
+    ///   llc = ["0-7,16-23", "8-15,24-31"]  # one cpulist per LLC
+    ///   big = "0-7,16-23"                  # P-cores; the rest are E-cores
+    ///   smt = ["0,16", "1,17"]             # SMT sibling pairs
+    #[arg(
+        long,
+        env = "SCX_CAKE_TOPOLOGY_OVERRIDE",
+        value_name = "FILE",
+        verbatim_doc_comment
+    )]
+    topology_override: Option<PathBuf>,
+
+    /// Also place tasks on isolated CPUs.
+    ///
+    /// By default CPUs isolated with isolcpus=, nohz_full= or an
+    /// isolated cpuset partition are left out like CPUs outside --cpus:
+    /// they run only what is pinned there (DPDK, audio, RT threads).
+    #[arg(long, env = "SCX_CAKE_INCLUDE_ISOLATED", verbatim_doc_comment)]
+    include_isolated: bool,
+
+    /// Only schedule tasks that opted into SCHED_EXT; the rest stay on EEVDF.
+    ///
+    /// For cautious rollouts: tasks join with sched_setscheduler(SCHED_EXT)
+    /// (e.g. `chrt --ext 0 <cmd>` with util-linux 2.40+) or by matching
+    /// --partial-comm. Combine with --cpus to keep cake off some CPUs.
+    #[arg(long, env = "SCX_CAKE_PARTIAL", verbatim_doc_comment)]
+    partial: bool,
+
+    /// Thread name prefixes moved into SCHED_EXT under --partial.
+    ///
+    /// /proc is rescanned every 2s; fair-class threads whose comm
+    /// starts with a prefix are switched (RT threads are left alone).
+    /// They return to EEVDF automatically when scx_cake exits.
+    ///
+    /// Example: --partial --partial-comm game.exe,vkd3d,dxvk
+    #[arg(
+        long,
+        env = "SCX_CAKE_PARTIAL_COMM",
+        value_name = "PREFIX",
+        value_delimiter = ',',
+        requires = "partial",
+        verbatim_doc_comment
+    )]
+    partial_comm: Vec<String>,
+
+    /// Game-launch warm-up window in SECONDS (0 = disabled).
+    ///
+    /// A new process that spawns --warmup-threads threads within 2 seconds
+    /// is treated as a launching game: its main thread is held at
+    /// Interactive or better while the loader threads (shader compilation,
+    /// asset streaming) run as Bulk. Normal classification resumes after
+    /// the window. Opt-in: a busy non-game process that spawns threads fast
+    /// would get the same treatment.
+    ///
+    /// Default: 0 (off). Example: --warmup 20
+    #[arg(
+        long,
+        env = "SCX_CAKE_WARMUP",
+        default_value_t = 0,
+        verbatim_doc_comment
+    )]
+    warmup: u64,
+
+    /// Thread spawns within 2 seconds that mark a launch burst (1-32).
+    ///
+    /// Default: 8 threads
+    #[arg(
+        long,
+        env = "SCX_CAKE_WARMUP_THREADS",
+        default_value_t = 8,
+        value_parser = clap::value_parser!(u32).range(1..=32),
+        verbatim_doc_comment
+    )]
+    warmup_threads: u32,
+
+    /// Background rebalance interval in MILLISECONDS (0 = disabled).
+    ///
+    /// A BPF timer periodically compares queued tasks across LLC domains
+    /// (CCDs) and moves low-tier tasks off the busiest one. Only armed on
+    /// multi-LLC systems. --rebalance-imbalance alone runs it every 100ms.
+    ///
+    /// Default: 0 (off). Example: --rebalance-interval 100
+    #[arg(
+        long,
+        env = "SCX_CAKE_REBALANCE_INTERVAL",
+        default_value_t = 0,
+        verbatim_doc_comment
+    )]
+    rebalance_interval: u64,
+
+    /// Queued-task imbalance between LLCs that triggers a rebalance.
+    ///
+    /// The rebalancer acts when the busiest LLC queue holds more than this
+    /// many tasks beyond the idlest one. Lower = more aggressive migration.
+    ///
+    /// Default: 4 tasks
+    #[arg(
+        long,
+        env = "SCX_CAKE_REBALANCE_THRESHOLD",
+        default_value_t = 4,
+        verbatim_doc_comment
+    )]
+    rebalance_threshold: u32,
+
+    /// Balance LLCs by load per CPU, past this PERCENT imbalance.
+    ///
+    /// Instead of the raw --rebalance-threshold queue difference, each
+    /// pass compares queued tasks per managed CPU of every LLC, and only
+    /// when the busiest exceeds the idlest by more than this percentage
+    /// does it move tasks, Bulk only, until both hold the same share.
+    /// Shorter tiers stay where their cache is; idle stealing still
+    /// covers them. 0 = off.
+    ///
+    /// Example: --rebalance-imbalance 25
+    #[arg(
+        long,
+        env = "SCX_CAKE_REBALANCE_IMBALANCE",
+        value_name = "PCT",
+        default_value_t = 0,
+        verbatim_doc_comment
+    )]
+    rebalance_imbalance: u32,
+
+    /// Relax the latency policy after this many MINUTES without input (0 = off).
+    ///
+    /// Watches keyboards, mice and gamepads (/dev/input) plus logind:
+    /// while idle, slices are 4x longer, SMT spreading and LLC
+    /// rebalancing pause so work compacts onto fewer cores, and Bulk
+    /// is promoted to Frame. The first input event restores the
+    /// normal policy. Idle inhibitors (video playback) keep it strict,
+    /// and a machine with no input devices and no graphical session
+    /// never relaxes.
+    ///
+    /// Default: 0 (off). Example: --idle-relax 10
+    #[arg(
+        long,
+        env = "SCX_CAKE_IDLE_RELAX",
+        default_value_t = 0,
+        verbatim_doc_comment
+    )]
+    idle_relax: u64,
+
+    /// Move each CPU's energy_performance_preference with the tiers it runs
+    /// [bare: performance:balance_power].
+    ///
+    /// LATENCY:RELAXED — a CPU gets LATENCY while Critical, Interactive
+    /// or Frame tasks run on it, and RELAXED once only Bulk (or nothing)
+    /// has run there for a second. Needs amd-pstate or intel_pstate in
+    /// active mode; the values found at start are put back on exit.
+    ///
+    /// Example: --epp-hints balance_performance:power
+    #[arg(
+        long,
+        env = "SCX_CAKE_EPP_HINTS",
+        value_name = "LATENCY:RELAXED",
+        num_args = 0..=1,
+        default_missing_value = "performance:balance_power",
+        verbatim_doc_comment
+    )]
+    epp_hints: Option<epp::EppHints>,
+
+    /// Pack Bulk onto as few cores as keep up, E-cores first [bare: 80].
+    ///
+    /// Bulk runs only on a packed set that starts at two CPUs of the
+    /// packing order (E-cores, then one LLC at a time, both SMT threads
+    /// of a core before the next) so the other cores reach deep idle.
+    /// The set grows by a CPU while its CPUs average more than PCT% busy
+    /// and gives one back after a second under half of that. Bulk
+    /// ignores --disable-smt and --preferred-cores; the other tiers
+    /// still use every core.
+    ///
+    /// Example: --powersave 90
+    #[arg(
+        long,
+        env = "SCX_CAKE_POWERSAVE",
+        value_name = "PCT",
+        num_args = 0..=1,
+        default_missing_value = "80",
+        value_parser = clap::value_parser!(u32).range(1..=100),
+        conflicts_with = "x3d",
+        verbatim_doc_comment
+    )]
+    powersave: Option<u32>,
+
+    /// Steer device IRQs away from CPUs running latency-critical tasks.
+    ///
+    /// Every 2s the task iterator finds CPUs where --irq-steer-tiers
+    /// tasks use >=10% of a CPU; matching --irq-devices IRQs are moved
+    /// to the remaining CPUs (a CPU stays protected for 10s). Original
+    /// affinities are restored on exit. Stop irqbalance first or it
+    /// will undo the placement. Kernel-managed IRQs (per-queue NVMe)
+    /// cannot be moved and are skipped.
+    #[arg(long, env = "SCX_CAKE_IRQ_STEER", verbatim_doc_comment)]
+    irq_steer: bool,
+
+    /// IRQ name prefixes (from /proc/interrupts) moved by --irq-steer.
+    #[arg(
+        long,
+        env = "SCX_CAKE_IRQ_DEVICES",
+        value_delimiter = ',',
+        default_values = ["amdgpu", "nvidia", "i915", "xe", "nvme", "enp", "eno", "eth", "wlp", "iwlwifi"]
+    )]
+    irq_devices: Vec<String>,
+
+    /// Tiers whose CPUs --irq-steer keeps free of device IRQs.
+    #[arg(
+        long,
+        env = "SCX_CAKE_IRQ_STEER_TIERS",
+        value_enum,
+        value_delimiter = ',',
+        default_values = ["critical", "frame"]
+    )]
+    irq_steer_tiers: Vec<Tier>,
+
+    /// Switch profile by time of day: PROFILE@HH:MM-HH:MM (repeatable).
+    ///
+    /// Outside every window --profile applies. Windows may wrap past
+    /// midnight; the first matching rule wins. Switching reloads the
+    /// BPF scheduler (a few milliseconds on the default scheduler).
+    /// `scx_cake ctl profile <NAME|auto>` overrides the schedule.
+    ///
+    /// Example: --schedule legacy@01:00-07:00
+    #[arg(
+        long,
+        env = "SCX_CAKE_SCHEDULE",
+        value_name = "RULE",
+        verbatim_doc_comment
+    )]
+    schedule: Vec<schedule::Rule>,
+
+    /// Read --schedule rules from a file (one per line, '#' comments).
+    #[arg(long, env = "SCX_CAKE_SCHEDULE_FILE", value_name = "PATH")]
+    schedule_file: Option<PathBuf>,
+
     /// Enable live TUI (Terminal User Interface) with real-time statistics.
     ///
     /// Shows dispatch counts per tier, tier transitions,
     /// wait time stats, and system topology information.
-    /// Press 'q' to exit TUI mode.
-    #[arg(long, short, verbatim_doc_comment)]
+    /// Press 'q' to exit TUI mode. Without a terminal (systemd,
+    /// nohup, pipes) stats are printed as plain text instead; see --ui.
+    #[arg(long, env = "SCX_CAKE_VERBOSE", short, verbatim_doc_comment)]
     verbose: bool,
 
-    /// Statistics refresh interval in SECONDS (only with --verbose).
+    /// How --verbose shows statistics.
     ///
-    /// How often the TUI updates. Lower values = more responsive but
-    /// higher overhead. Has no effect without --verbose.
+    /// AUTO: TUI on a terminal, plain text otherwise.
+    /// TUI: always the TUI (needs a terminal).
+    /// PLAIN: one summary line per --interval on stdout.
+    /// JSON: one JSON object per --interval on stdout.
+    /// Any value other than auto implies --verbose.
+    #[arg(
+        long,
+        env = "SCX_CAKE_UI",
+        value_enum,
+        default_value_t = UiMode::Auto,
+        verbatim_doc_comment
+    )]
+    ui: UiMode,
+
+    /// Show the TUI, without --verbose (same as --ui tui).
+    ///
+    /// To watch a daemon started elsewhere instead, run
+    /// `scx_cake monitor --tui` against it.
+    #[arg(
+        long,
+        env = "SCX_CAKE_TUI",
+        conflicts_with_all = ["ui", "stats"],
+        verbatim_doc_comment
+    )]
+    tui: bool,
+
+    /// TUI color theme.
+    ///
+    /// DEFAULT: terminal colors tuned for dark backgrounds.
+    /// SOLARIZED: Solarized accents, terminal text color; readable on
+    /// light backgrounds too.
+    /// MONOCHROME: no color, bold and reverse video only.
+    #[arg(
+        long,
+        env = "SCX_CAKE_THEME",
+        value_enum,
+        default_value_t = Theme::Default,
+        verbatim_doc_comment
+    )]
+    theme: Theme,
+
+    /// Disable color in the TUI and startup screen (--theme monochrome).
+    /// Also implied by a non-empty NO_COLOR environment variable.
+    #[arg(long, env = "SCX_CAKE_NO_COLOR")]
+    no_color: bool,
+
+    /// Directory for TUI snapshots ('e'): a text report and a JSON object
+    /// per press, named by unix time. Default: the current directory.
+    #[arg(long, env = "SCX_CAKE_SNAPSHOT_DIR", value_name = "DIR")]
+    snapshot_dir: Option<PathBuf>,
+
+    /// TUI wait alert: TIER.METRIC=LIMIT (repeatable).
+    ///
+    /// METRIC is wait (mean per run), p99 or max, over the last tick;
+    /// LIMIT is in µs, or ms with an ms suffix. Over the limit, the
+    /// tier's wait cells turn red and the status line says so.
+    ///
+    /// Example: --alert frame.max=5ms --alert interactive.p99=2ms
+    #[arg(
+        long,
+        env = "SCX_CAKE_ALERT",
+        value_name = "TIER.METRIC=LIMIT",
+        verbatim_doc_comment
+    )]
+    alert: Vec<alert::TierAlert>,
+
+    /// Ring the terminal bell when an --alert goes over its limit.
+    #[arg(long, env = "SCX_CAKE_ALERT_BELL")]
+    alert_bell: bool,
+
+    /// Append a timestamped line per --alert crossing to this file.
+    #[arg(long, env = "SCX_CAKE_ALERT_LOG", value_name = "PATH")]
+    alert_log: Option<PathBuf>,
+
+    /// TUI key bindings: the defaults plus the --config [keys] table
+    #[arg(skip)]
+    keys: keymap::Keymap,
+
+    /// Print one JSON stats object every SECS on stdout, no TUI.
+    ///
+    /// Same convention as scx_lavd / scx_rusty --stats, so existing
+    /// scx monitoring scripts work unchanged. Shorthand for
+    /// `--ui json --interval SECS`.
+    ///
+    /// Example: scx_cake --stats 2 | jq .tier_dispatches
+    #[arg(
+        long,
+        env = "SCX_CAKE_STATS",
+        value_name = "SECS",
+        conflicts_with_all = ["verbose", "ui", "interval"],
+        verbatim_doc_comment
+    )]
+    stats: Option<u64>,
+
+    /// Count stats with no output of its own, for a later attach.
+    ///
+    /// Meant for a daemon: `scx_cake monitor --tui` (or `scx_cake
+    /// query stats`) can then show live per-tier numbers at any time
+    /// without restarting the scheduler.
+    #[arg(
+        long,
+        env = "SCX_CAKE_HEADLESS_STATS",
+        conflicts_with_all = ["verbose", "ui", "tui", "stats"],
+        verbatim_doc_comment
+    )]
+    headless_stats: bool,
+
+    /// Serve stats on the standard scx_stats socket.
+    ///
+    /// Lets `scx_cake --monitor` and generic scx tooling attach to this
+    /// instance from another shell (/var/run/scx/root/stats). Turns on
+    /// the BPF stat counters, like --verbose or --history-db do.
+    #[arg(long, env = "SCX_CAKE_STATS_SERVER", verbatim_doc_comment)]
+    stats_server: bool,
+
+    /// Push metrics to an OpenTelemetry collector (OTLP/HTTP) every --interval.
+    ///
+    /// Cumulative counters (dispatches, preempts, CPU time, migrations,
+    /// select_cpu placements) labelled by tier, for fleets that collect
+    /// with OpenTelemetry rather than scraping. Needs a build with
+    /// `--features otlp`. Turns on the BPF stat counters.
+    ///
+    /// Example: scx_cake --otlp-endpoint http://collector:4318/v1/metrics
+    #[arg(
+        long,
+        env = "SCX_CAKE_OTLP_ENDPOINT",
+        value_name = "URL",
+        verbatim_doc_comment
+    )]
+    otlp_endpoint: Option<String>,
+
+    /// Send the interval counters to a statsd daemon (UDP) every --interval.
+    ///
+    /// One `scx_cake.<column>:N|c` counter per non-zero --log-stats
+    /// column plus the `scx_cake.user_idle` gauge, for dashboards that
+    /// already read statsd / Graphite. Turns on the BPF stat counters.
+    ///
+    /// Example: scx_cake --statsd 192.168.1.10:8125
+    #[arg(
+        long,
+        env = "SCX_CAKE_STATSD",
+        value_name = "HOST:PORT",
+        verbatim_doc_comment
+    )]
+    statsd: Option<String>,
+
+    /// Print the stats of a running scx_cake every SECS, then exit on Ctrl-C.
+    ///
+    /// Connects to the scx_stats socket of an instance started with
+    /// --stats-server. Does not load a scheduler.
+    #[arg(
+        long,
+        value_name = "SECS",
+        conflicts_with_all = ["stats", "verbose", "stats_server"],
+        verbatim_doc_comment
+    )]
+    monitor: Option<f64>,
+
+    /// Like --monitor, as one key=value line per interval (default 1s).
+    ///
+    /// For grep/awk and status bars (waybar, polybar custom modules):
+    /// dispatch rate, mean and max runnable wait, per-tier dispatches
+    /// in the interval, starvation preempts/s and user idle state.
+    ///
+    /// Example: scx_cake --monitor-oneline 2 | awk -F'[ =]' '{print $4}'
+    #[arg(
+        long,
+        value_name = "SECS",
+        num_args = 0..=1,
+        default_missing_value = "1",
+        conflicts_with_all = ["monitor", "stats", "verbose", "stats_server"],
+        verbatim_doc_comment
+    )]
+    monitor_oneline: Option<f64>,
+
+    /// Statistics refresh interval in SECONDS (--verbose / --history-db).
+    ///
+    /// How often the TUI updates and history snapshots are written.
+    /// Lower values = more responsive but higher overhead.
     ///
     /// Default: 1 second
-    #[arg(long, default_value_t = 1, verbatim_doc_comment)]
+    #[arg(
+        long,
+        env = "SCX_CAKE_INTERVAL",
+        default_value_t = 1,
+        verbatim_doc_comment
+    )]
     interval: u64,
+
+    /// Record interval snapshots into an SQLite database at this path.
+    ///
+    /// One row per --interval with per-tier dispatches, starvation
+    /// preempts, rebalance moves and warm-up dispatches, tagged with
+    /// the active profile. Query with `scx_cake history query`.
+    #[arg(long, env = "SCX_CAKE_HISTORY_DB", verbatim_doc_comment)]
+    history_db: Option<PathBuf>,
+
+    /// Append one CSV row of interval counters to this file every --interval.
+    ///
+    /// Columns: unix timestamp, interval seconds, per-tier dispatches /
+    /// starvation / cap counters, then the global counters and
+    /// user_idle. An existing file is appended to, header kept. Works
+    /// in every UI mode, so a session can be logged with the TUI open.
+    ///
+    /// Example: scx_cake --log-stats /var/log/scx_cake.csv --interval 5
+    #[arg(
+        long,
+        env = "SCX_CAKE_LOG_STATS",
+        value_name = "PATH",
+        verbatim_doc_comment
+    )]
+    log_stats: Option<PathBuf>,
+
+    /// Write a final stats summary to this file when the scheduler exits.
+    ///
+    /// Totals since start: dispatches, per-tier breakdown, starvation
+    /// preempts and per-tier wait percentiles with the highest wait
+    /// bucket reached. Overwritten on every exit (Ctrl+C, SIGTERM or
+    /// a BPF abort). Without it, the summary goes to the log whenever
+    /// stats are being counted.
+    ///
+    /// Example: scx_cake --summary-file /tmp/cake-run.txt
+    #[arg(
+        long,
+        env = "SCX_CAKE_SUMMARY_FILE",
+        value_name = "PATH",
+        verbatim_doc_comment
+    )]
+    summary_file: Option<PathBuf>,
+
+    /// Stream scheduling events from BPF: "log", or a file for JSON lines.
+    ///
+    /// One record per tier change, new task (flow), new-flow bonus
+    /// spent and starvation preempt, with wall-clock time, CPU, pid
+    /// and comm — what counters can't say is *when* it happened. A
+    /// 256KB ring buffer; events are dropped (and counted) rather than
+    /// ever stalling the scheduler. Independent of the stat counters.
+    ///
+    /// Example: scx_cake --events /var/log/scx_cake-events.jsonl
+    #[arg(
+        long,
+        env = "SCX_CAKE_EVENTS",
+        value_name = "log|PATH",
+        verbatim_doc_comment
+    )]
+    events: Option<String>,
+
+    /// Record every enqueue, run, preempt and tier change to a trace file.
+    ///
+    /// Writes Chrome trace-event JSON that ui.perfetto.dev opens: one
+    /// track per thread, each run a slice named after its tier. Times
+    /// are CLOCK_BOOTTIME, like Perfetto's and `trace-cmd -C boot`
+    /// kernel traces, so sched_switch from either lines up. Uses a 16MB
+    /// ring buffer and grows by MBs per second on a busy box — meant
+    /// for short captures. Works alongside --events.
+    ///
+    /// Example: scx_cake --trace out.perfetto
+    #[arg(
+        long,
+        env = "SCX_CAKE_TRACE",
+        value_name = "PATH",
+        verbatim_doc_comment
+    )]
+    trace: Option<PathBuf>,
+
+    /// Days of history kept in --history-db (0 = keep forever).
+    ///
+    /// Older rows are pruned hourly while recording.
+    ///
+    /// Default: 30 days
+    #[arg(
+        long,
+        env = "SCX_CAKE_HISTORY_RETENTION",
+        default_value_t = 30,
+        verbatim_doc_comment
+    )]
+    history_retention: u64,
+
+    /// BPF exit dump buffer size in bytes (0 = kernel default).
+    ///
+    /// When the BPF scheduler aborts, the kernel writes a debug dump
+    /// (backtrace, runqueue and task state) into this buffer and it is
+    /// printed with the exit reason. Raise it if the dump is truncated.
+    #[arg(
+        long,
+        env = "SCX_CAKE_EXIT_DUMP_LEN",
+        default_value_t = 0,
+        verbatim_doc_comment
+    )]
+    exit_dump_len: u32,
+
+    /// Validate the configuration and exit without loading the scheduler.
+    ///
+    /// Parses options (command line, environment, --config), detects
+    /// the topology and opens the BPF skeleton with the resulting
+    /// rodata, then prints the per-tier table and per-CPU placement
+    /// map. Nothing is loaded or attached. Exits non-zero on errors.
+    #[arg(long, env = "SCX_CAKE_CHECK_CONFIG", verbatim_doc_comment)]
+    check_config: bool,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+/// Optional subcommands — bare invocation runs the scheduler
+#[derive(Subcommand, Debug, Clone)]
+enum Command {
+    /// Run the scheduler (same as no subcommand).
+    ///
+    /// Scheduler options may follow it: scx_cake run -p esports
+    #[command(verbatim_doc_comment)]
+    Run,
+
+    /// Print per-tier task counts, CPU and wait of the running scheduler
+    Monitor {
+        /// Seconds between samples
+        #[arg(long, default_value_t = 1)]
+        interval: u64,
+
+        /// Compare against this baseline file, or record it if missing.
+        ///
+        /// Recording writes the mean of every sample until Ctrl-C; a
+        /// later run prints its own mean with the % change per tier,
+        /// e.g. to A/B a --quantum change.
+        #[arg(long, value_name = "FILE", verbatim_doc_comment)]
+        baseline: Option<PathBuf>,
+
+        /// Play a --log-stats CSV back in the TUI instead.
+        ///
+        /// No scheduler needed: step, seek and pause through the
+        /// recorded rows on any machine.
+        #[arg(
+            long,
+            value_name = "FILE",
+            conflicts_with = "baseline",
+            verbatim_doc_comment
+        )]
+        replay: Option<PathBuf>,
+
+        /// With --replay: an --events file from the same session
+        #[arg(long, value_name = "FILE", requires = "replay")]
+        events: Option<PathBuf>,
+
+        /// Attach the TUI to the running scheduler instead.
+        ///
+        /// Polls the query socket every --interval, so a daemon
+        /// started without --verbose can be watched; quitting leaves
+        /// it running. Start it with --headless-stats for numbers.
+        #[arg(
+            long,
+            conflicts_with_all = ["baseline", "replay"],
+            verbatim_doc_comment
+        )]
+        tui: bool,
+    },
+
+    /// Print the detected CPU / LLC / SMT / core-type map and exit
+    DumpTopology {
+        /// Print it as JSON, with every CPU's idle search order (for bug
+        /// reports)
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Run the scheduler while benchmarking a workload
+    #[command(subcommand)]
+    Bench(bench::BenchCommand),
+
+    /// Inspect stats recorded with --history-db
+    #[command(subcommand)]
+    History(history::HistoryCommand),
+
+    /// Inspect the running scheduler
+    #[command(subcommand)]
+    Ctl(ctl::CtlCommand),
+
+    /// Change a tunable of the running scheduler without reloading it.
+    ///
+    /// Talks to the daemon over /run/scx_cake/ctl.sock. Values are in
+    /// MICROSECONDS; "default" restores the startup value. "stats on|off"
+    /// starts or pauses BPF stats counting. Without arguments prints the
+    /// current values. Changes last until the scheduler restarts or
+    /// switches profile.
+    ///
+    /// Example: scx_cake set quantum 1500
+    #[command(verbatim_doc_comment)]
+    Set(control::SetArgs),
+
+    /// Send one request to the JSON query socket and print the reply.
+    ///
+    /// Overlays and scripts can talk to /run/scx_cake/query.sock
+    /// directly: one request line in, one JSON line out. Requests:
+    ///   stats [--tier TIER]                      counters since load
+    ///   tasks [--top N] [--by cpu|wait|runs] [--tier TIER]
+    ///
+    /// Example: scx_cake query tasks --top 10
+    #[command(verbatim_doc_comment)]
+    Query {
+        /// Request words, e.g. stats --tier frame
+        #[arg(required = true, trailing_var_arg = true, allow_hyphen_values = true)]
+        request: Vec<String>,
+    },
+
+    /// Hill-climb quantum / new-flow bonus / starvation for this machine.
+    ///
+    /// Each trial reloads the scheduler with new values and measures the
+    /// p99 per-run wait of the chosen tiers (or a benchmark's score) while
+    /// your workload runs. Prints the best flags when done.
+    #[command(verbatim_doc_comment)]
+    Autotune(autotune::AutotuneArgs),
+}
+
+fn parse_cpus(s: &str) -> Result<topology::CpuMask, String> {
+    topology::parse_cpu_list(s).map_err(|e| e.to_string())
 }
 
 impl Args {
@@ -251,6 +1491,346 @@ impl Args {
             self.starvation.unwrap_or(starv),
         )
     }
+
+    /// --slice-min/--slice-max in nanoseconds (None = fixed tier slices)
+    fn slice_bounds(&self) -> Result<Option<(u64, u64)>> {
+        match (self.slice_min, self.slice_max) {
+            (None, None) => Ok(None),
+            (Some(min), Some(max)) => {
+                if min < 50 || min > max || max > 100_000 {
+                    bail!(
+                        "need 50 <= --slice-min <= --slice-max <= 100000µs, got {}..{}",
+                        min,
+                        max
+                    );
+                }
+                Ok(Some((min * 1000, max * 1000)))
+            }
+            _ => bail!("--slice-min and --slice-max must be set together"),
+        }
+    }
+
+    /// Effective starvation-immune tier mask (profile default unless overridden)
+    fn starvation_immune_mask(&self) -> u8 {
+        if self.no_starvation_immunity {
+            return 0;
+        }
+        match &self.starvation_immune {
+            Some(tiers) => tiers.iter().fold(0, |mask, &t| mask | (1 << t as u8)),
+            None => self.profile.starvation_immune_mask(),
+        }
+    }
+
+    /// Profile tier multipliers with --tier-quantum overrides applied
+    fn tier_multiplier(&self) -> [u32; 8] {
+        let mut multiplier = self.profile.tier_multiplier();
+        for tq in &self.tier_quantum {
+            multiplier[tq.tier as usize] = tq.fixed_point();
+        }
+        multiplier
+    }
+
+    /// Profile tier starvation thresholds (ns) with --tier-starvation overrides
+    fn tier_starvation(&self) -> [u64; 8] {
+        let mut starvation = self.profile.starvation_threshold();
+        for ts in &self.tier_starvation {
+            starvation[ts.tier as usize] = ts.us * 1000;
+        }
+        starvation
+    }
+
+    /// Resolved stats display (None = silent). Auto picks the TUI only when
+    /// both stdin and stdout are terminals — crossterm raw mode needs both.
+    fn stats_ui(&self) -> Option<UiMode> {
+        use std::io::IsTerminal;
+        if self.stats.is_some() {
+            return Some(UiMode::Json);
+        }
+        if self.tui {
+            return Some(UiMode::Tui);
+        }
+        match (self.verbose, self.ui) {
+            (false, UiMode::Auto) => None,
+            (true, UiMode::Auto)
+                if std::io::stdout().is_terminal() && std::io::stdin().is_terminal() =>
+            {
+                Some(UiMode::Tui)
+            }
+            (true, UiMode::Auto) => Some(UiMode::Plain),
+            (_, ui) => Some(ui),
+        }
+    }
+
+    /// Resolved TUI theme: --no-color and NO_COLOR win over --theme
+    fn theme(&self) -> Theme {
+        if self.no_color || theme::no_color_env() {
+            Theme::Monochrome
+        } else {
+            self.theme
+        }
+    }
+
+    /// Stats / history interval in seconds (--stats wins over --interval)
+    fn interval(&self) -> u64 {
+        self.stats.unwrap_or(self.interval).max(1)
+    }
+
+    /// Time-of-day profile rules configured (--schedule / --schedule-file)
+    fn has_schedule(&self) -> bool {
+        !self.schedule.is_empty() || self.schedule_file.is_some()
+    }
+}
+
+/// Detect the topology of the --cpus set less isolated CPUs, then apply
+/// --topology-override
+fn detect_topology(args: &Args) -> Result<topology::TopologyInfo> {
+    let managed = args.cpus.unwrap_or(topology::CpuMask::ALL);
+    let sources = match args.include_isolated {
+        true => Vec::new(),
+        false => isolation::isolated_cpus(),
+    };
+    let mut isolated = sources
+        .iter()
+        .fold(topology::CpuMask::default(), |all, &(_, mask)| all | mask)
+        & managed;
+    let online = hotplug::online_cpus().unwrap_or(topology::CpuMask::ALL);
+    if !isolated.is_empty() && (managed & online & !isolated).is_empty() {
+        warn!(
+            "Every managed CPU is isolated ({}), placing on them anyway",
+            check::mask_list(isolated)
+        );
+        isolated = topology::CpuMask::default();
+    }
+    let mut topo = topology::detect(managed & !isolated)?;
+    topo.isolated_mask = isolated & topo.online_mask;
+    if !topo.isolated_mask.is_empty() {
+        let names: Vec<&str> = sources.iter().map(|&(name, _)| name).collect();
+        info!(
+            "Leaving isolated CPUs {} ({}) to their own tasks",
+            check::mask_list(topo.isolated_mask),
+            names.join(", ")
+        );
+    }
+    if let Some(path) = &args.topology_override {
+        overrides::TopologyOverride::load(path)?.apply(&mut topo)?;
+    }
+    Ok(topo)
+}
+
+/// Open the BPF skeleton and configure its rodata for `args` on `topo`.
+/// Shared by Scheduler::new and --check-config (which never loads it).
+fn open_configured<'a>(
+    args: &Args,
+    topo: &topology::TopologyInfo,
+    open_object: &'a mut std::mem::MaybeUninit<libbpf_rs::OpenObject>,
+) -> Result<OpenBpfSkel<'a>> {
+    use libbpf_rs::skel::SkelBuilder;
+
+    let mut open_skel = BpfSkelBuilder::default()
+        .open(open_object)
+        .context("Failed to open BPF skeleton")?;
+
+    // Populate SCX enum RODATA from kernel BTF (SCX_DSQ_LOCAL_ON, SCX_KICK_PREEMPT, etc.)
+    scx_utils::import_enums!(open_skel);
+
+    // --trace carries a record per scheduling step: size the ring for it
+    if args.trace.is_some() {
+        open_skel
+            .maps
+            .events
+            .set_max_entries(bpf_intf::CAKE_TRACE_RINGBUF_SZ)
+            .context("Failed to size the trace ring buffer")?;
+    }
+
+    // Get effective values (profile + CLI overrides)
+    let (quantum, new_flow_bonus, starvation) = args.effective_values();
+    let slice_bounds = args.slice_bounds()?;
+
+    // Stats counting starts on when anything consumes it; `scx_cake set
+    // stats on|off` or the TUI can flip it later (BSS, not rodata)
+    if let Some(bss) = &mut open_skel.maps.bss_data {
+        bss.enable_stats = (args.stats_ui().is_some()
+            || args.headless_stats
+            || args.command.is_some()
+            || args.history_db.is_some()
+            || args.stats_server
+            || args.otlp_endpoint.is_some()
+            || args.statsd.is_some()
+            || args.log_stats.is_some()
+            || args.summary_file.is_some()) as u32;
+        // --powersave: the pack controller grows this from its minimum
+        bss.pack_width = powersave::MIN_WIDTH.min(topo.managed_mask.count()) as u32;
+    }
+
+    // Configure the scheduler via rodata (read-only data)
+    if let Some(rodata) = &mut open_skel.maps.rodata_data {
+        rodata.quantum_ns = quantum * 1000;
+        rodata.new_flow_bonus_ns = new_flow_bonus * 1000;
+        rodata.tier_configs =
+            args.profile
+                .tier_configs(quantum, args.tier_multiplier(), args.tier_starvation());
+        rodata.starvation_ns = starvation * 1000;
+        rodata.starvation_immune_mask = args.starvation_immune_mask();
+        if let Some((min, max)) = slice_bounds {
+            rodata.has_slice_bounds = true;
+            rodata.slice_min_ns = min;
+            rodata.slice_max_ns = max;
+        }
+        rodata.rebalance_interval_ns = match (args.rebalance_interval, args.rebalance_imbalance) {
+            (0, pct) if pct > 0 => REBALANCE_IMBALANCE_INTERVAL_MS,
+            (ms, _) => ms,
+        } * 1_000_000;
+        rodata.rebalance_threshold = args.rebalance_threshold;
+        rodata.rebalance_imbalance_pct = args.rebalance_imbalance;
+        rodata.warmup_ns = args.warmup * 1_000_000_000;
+        rodata.warmup_threads = args.warmup_threads;
+        rodata.has_tier_caps = !args.tier_cap.is_empty();
+        rodata.has_epp_hints = args.epp_hints.is_some();
+        // --powersave: Bulk stays on the packed set, so it skips the idle
+        // passes that would move it off (whole cores, best-boosting cores)
+        let pack_mask = args.powersave.map_or(0, |_| 1 << Tier::Bulk as u8);
+        // The TUI's event log pane reads the same stream
+        rodata.has_events =
+            args.events.is_some() || args.trace.is_some() || args.stats_ui() == Some(UiMode::Tui);
+        rodata.has_trace = args.trace.is_some();
+        rodata.nice_tier = nice_tiers(&args.nice_map);
+        rodata.nr_tier_rules = args.tier_rule.len().min(rules::MAX_TIER_RULES) as u32;
+        let nr_managed = topo.managed_mask.count();
+        for cap in &args.tier_cap {
+            rodata.tier_cap[cap.tier as usize] = cap.resolve(nr_managed);
+        }
+
+        // Topology: has_hybrid (DVFS scaling in cake_tick, P/E migration
+        // stats), has_smt (heavy spread)
+        rodata.has_hybrid = topo.has_hybrid_cores;
+        rodata.cpu_is_big = topo.cpu_is_big;
+        // Capacity: slices stretch on smaller cores, at most 4x (floor 256)
+        rodata.has_capacity = topo.has_capacity;
+        rodata.cpu_capacity = topo.cpu_capacity.map(|cap| cap.max(256));
+        rodata.has_smt = topo.smt_enabled;
+        if let Some(tiers) = &args.disable_smt {
+            match topo.smt_enabled {
+                true => {
+                    rodata.smt_avoid_mask =
+                        tiers.iter().fold(0, |mask, &t| mask | (1 << t as u8)) & !pack_mask
+                }
+                false => warn!("--disable-smt: no SMT siblings on this machine, ignored"),
+            }
+        }
+        if let Some(tiers) = &args.smt_guard {
+            match topo.smt_enabled {
+                true => {
+                    rodata.smt_guard_mask = tiers.iter().fold(0, |mask, &t| mask | (1 << t as u8))
+                }
+                false => warn!("--smt-guard: no SMT siblings on this machine, ignored"),
+            }
+        }
+        rodata.cpu_sibling = topo.cpu_sibling_map;
+        rodata.has_clusters = topo.has_clusters;
+        rodata.cpu_cluster_mask = topo.cpu_cluster_mask.map(|mask| mask.0);
+        if let Some(tiers) = &args.preferred_cores {
+            match topo.has_preferred_cores {
+                true => {
+                    rodata.prefcore_tier_mask =
+                        tiers.iter().fold(0, |mask, &t| mask | (1 << t as u8)) & !pack_mask;
+                    for (rank, cpu) in topo.perf_order().into_iter().enumerate() {
+                        rodata.cpu_by_rank[rank] = cpu as u8;
+                        rodata.cpu_perf_rank[cpu] = rank as u8;
+                    }
+                }
+                false => warn!("--preferred-cores: cores report no CPPC ranking, ignored"),
+            }
+        }
+
+        // Per-LLC DSQ partitioning: populate CPU→LLC mapping
+        rodata.nr_llcs = topo.nr_llcs as u32;
+        rodata.nr_cpus = topo.nr_cpus.min(topology::MAX_CPUS) as u32; // Rule 39: bounds kick scan loop
+        rodata.nr_online = topo.nr_online() as u32;
+        if topo.nr_llcs > 1 {
+            rodata.has_select_order = true;
+            for cpu in 0..topo.nr_cpus.min(topology::MAX_CPUS) {
+                rodata.cpu_select_order[cpu] = topo.select_order(cpu);
+            }
+        }
+        if args.rebalance_imbalance > 0 {
+            for (llc, &mask) in topo.llc_cpu_mask.iter().enumerate() {
+                rodata.llc_nr_cpus[llc] = (mask & topo.managed_mask).count() as u32;
+            }
+        }
+        rodata.has_cpu_mask = args.cpus.is_some() || !topo.isolated_mask.is_empty();
+        rodata.managed_cpus = topo.managed_mask.0;
+        let vcache = args.x3d.then(|| topo.vcache_llc()).flatten();
+        if args.x3d && vcache.is_none() {
+            warn!("--x3d: no CCD has a larger L3 than the others, ignored");
+        }
+        let primary = args
+            .primary_domain
+            .or(vcache.map(|llc| topo.llc_cpu_mask[llc]));
+        if let Some(primary) = primary {
+            let primary = primary & topo.managed_mask;
+            let llc = topo
+                .primary_llc(primary)
+                .ok_or_else(|| anyhow!("--primary-domain selects no managed CPU"))?;
+            rodata.has_primary = true;
+            rodata.primary_cpus = primary.0;
+            rodata.primary_llc = llc as u32;
+            rodata.primary_tier_mask = args
+                .primary_tiers
+                .iter()
+                .fold(0, |mask, &t| mask | (1 << t as u8));
+        }
+        if let Some(llc) = vcache {
+            // Frequency CCD: the largest LLC without the V-Cache
+            let freq = (0..topo.nr_llcs)
+                .filter(|&l| l != llc)
+                .max_by_key(|&l| (topo.llc_cpu_mask[l].count(), std::cmp::Reverse(l)))
+                .unwrap_or(llc);
+            rodata.has_secondary = true;
+            rodata.secondary_cpus = (topo.managed_mask & !topo.llc_cpu_mask[llc]).0;
+            rodata.secondary_llc = freq as u32;
+            rodata.secondary_tier_mask = 1 << Tier::Bulk as u8;
+        }
+        if pack_mask != 0 {
+            let order = topo.packing_order();
+            rodata.has_powersave = true;
+            rodata.powersave_tier_mask = pack_mask;
+            rodata.cpu_pack_rank = [u8::MAX; topology::MAX_CPUS]; // Unmanaged: never packed
+            for (rank, &cpu) in order.iter().enumerate() {
+                rodata.cpu_by_pack[rank] = cpu as u8;
+                rodata.cpu_pack_rank[cpu] = rank as u8;
+            }
+            rodata.pack_llc = order.first().map_or(0, |&cpu| topo.cpu_llc_id[cpu] as u32);
+        }
+        for (i, &llc_id) in topo.cpu_llc_id.iter().enumerate() {
+            rodata.cpu_llc_id[i] = llc_id as u32;
+        }
+
+        // NUMA: same-node idle scans and stealing before remote nodes
+        rodata.has_numa = topo.has_numa;
+        for (i, &node) in topo.cpu_node_id.iter().enumerate() {
+            rodata.cpu_node_id[i] = node as u32;
+        }
+        for (i, &node) in topo.llc_node_id.iter().enumerate() {
+            rodata.llc_node_id[i] = node as u32;
+        }
+    }
+
+    // Tier rules re-match at exec; without rules the tracepoint isn't loaded
+    open_skel
+        .progs
+        .cake_exec
+        .set_autoload(!args.tier_rule.is_empty());
+
+    // Partial mode: only SCHED_EXT tasks are ours, everything else stays on EEVDF
+    if args.partial {
+        open_skel.struct_ops.cake_ops_mut().flags |= *scx_utils::compat::SCX_OPS_SWITCH_PARTIAL;
+    }
+
+    // Size the UEI dump buffer before load so aborts carry the kernel's debug dump
+    open_skel.struct_ops.cake_ops_mut().exit_dump_len = args.exit_dump_len;
+    scx_utils::uei_set_size!(open_skel, cake_ops, uei);
+
+    Ok(open_skel)
 }
 
 struct Scheduler<'a> {
@@ -258,73 +1838,71 @@ struct Scheduler<'a> {
     args: Args,
     topology: topology::TopologyInfo,
     latency_matrix: Vec<Vec<f64>>,
+    exit_reported: bool,
+    /// The BPF exit asked to be loaded again (SCX_ECODE_ACT_RESTART, e.g.
+    /// the kernel unloading it on CPU hotplug)
+    restart: bool,
+    /// Reset point for "since reset" totals (TUI 'r', SIGUSR2)
+    stats_epoch: stats::StatsEpoch,
 }
 
 impl<'a> Scheduler<'a> {
     fn new(
         args: Args,
         open_object: &'a mut std::mem::MaybeUninit<libbpf_rs::OpenObject>,
+        latency_matrix: Option<Vec<Vec<f64>>>,
     ) -> Result<Self> {
-        use libbpf_rs::skel::{OpenSkel, SkelBuilder};
-
-        // Open and load the BPF skeleton
-        let skel_builder = BpfSkelBuilder::default();
-
-        let mut open_skel = skel_builder
-            .open(open_object)
-            .context("Failed to open BPF skeleton")?;
-
-        // Populate SCX enum RODATA from kernel BTF (SCX_DSQ_LOCAL_ON, SCX_KICK_PREEMPT, etc.)
-        scx_utils::import_enums!(open_skel);
+        use libbpf_rs::skel::OpenSkel;
 
         // Detect system topology (CCDs, P/E cores)
-        let topo = topology::detect()?;
-
-        // Get effective values (profile + CLI overrides)
-        let (quantum, new_flow_bonus, _starvation) = args.effective_values();
+        let topo = detect_topology(&args)?;
 
         // ETD: Empirical Topology Discovery — display-grade measurement
         // Measures inter-core CAS latency for startup heatmap and TUI display
-        info!("Starting ETD calibration...");
-        let latency_matrix = calibrate::calibrate_full_matrix(
-            topo.nr_cpus,
-            &calibrate::EtdConfig::default(),
-            |current, total, is_complete| {
-                tui::render_calibration_progress(current, total, is_complete);
-            },
-        );
+        // Skipped when the caller passes a matrix (profile reloads, autotune trials
+        // pass an empty one) that still fits: CPU hotplug can change the CPU count
+        let latency_matrix = latency_matrix
+            .filter(|m| m.is_empty() || m.len() == topo.nr_cpus)
+            .unwrap_or_else(|| {
+                info!("Starting ETD calibration...");
+                let tty = std::io::IsTerminal::is_terminal(&std::io::stdout());
+                calibrate::calibrate_full_matrix(
+                    topo.nr_cpus,
+                    &calibrate::EtdConfig::default(),
+                    |current, total, is_complete| {
+                        if tty {
+                            tui::render_calibration_progress(
+                                current,
+                                total,
+                                is_complete,
+                                args.theme().has_color(),
+                            );
+                        }
+                    },
+                )
+            });
 
-        // Configure the scheduler via rodata (read-only data)
-        if let Some(rodata) = &mut open_skel.maps.rodata_data {
-            rodata.quantum_ns = quantum * 1000;
-            rodata.new_flow_bonus_ns = new_flow_bonus * 1000;
-            rodata.enable_stats = args.verbose;
-            rodata.tier_configs = args.profile.tier_configs(quantum);
-
-            // Topology: only has_hybrid is live (DVFS scaling in cake_tick)
-            rodata.has_hybrid = topo.has_hybrid_cores;
-
-            // Per-LLC DSQ partitioning: populate CPU→LLC mapping
-            let llc_count = topo.llc_cpu_mask.iter().filter(|&&m| m != 0).count() as u32;
-            rodata.nr_llcs = llc_count.max(1);
-            rodata.nr_cpus = topo.nr_cpus.min(64) as u32; // Rule 39: bounds kick scan loop
-            for (i, &llc_id) in topo.cpu_llc_id.iter().enumerate() {
-                rodata.cpu_llc_id[i] = llc_id as u32;
-            }
-        }
+        let open_skel = open_configured(&args, &topo, open_object)?;
 
         // Load the BPF program
         let skel = open_skel.load().context("Failed to load BPF program")?;
+        // Tier rules must be in place before attach runs init_task on every task
+        rules::install(&skel, &args.tier_rule)?;
 
         Ok(Self {
             skel,
             args,
             topology: topo,
             latency_matrix,
+            exit_reported: false,
+            restart: false,
+            stats_epoch: Default::default(),
         })
     }
 
-    fn run(&mut self, shutdown: Arc<AtomicBool>) -> Result<()> {
+    /// `wake` turns readable when something else set `shutdown` (CPU
+    /// hotplug), so the silent loop stops without waiting out its timeout
+    fn run(&mut self, shutdown: Arc<AtomicBool>, wake: Option<BorrowedFd<'_>>) -> Result<()> {
         // Attach the scheduler
         let _link = self
             .skel
@@ -332,63 +1910,333 @@ impl<'a> Scheduler<'a> {
             .cake_ops
             .attach_struct_ops()
             .context("Failed to attach scheduler")?;
+        let started = std::time::Instant::now();
+        let _exec_link = match self.args.tier_rule.is_empty() {
+            true => None,
+            false => Some(
+                self.skel
+                    .progs
+                    .cake_exec
+                    .attach()
+                    .context("Failed to attach tier-rule exec hook")?,
+            ),
+        };
+
+        // Animated splash is terminal-only: it would spray escape codes into
+        // journald or a pipe
+        if std::io::IsTerminal::is_terminal(&std::io::stdout()) {
+            self.show_startup_splash()?;
+        }
+
+        // Expose the task iterator to `scx_cake ctl` (best-effort: needs bpffs)
+        let _task_pin = match stats::PinnedTaskIter::pin(&self.skel) {
+            Ok(pin) => Some(pin),
+            Err(e) => {
+                warn!("scx_cake ctl unavailable: {:#}", e);
+                None
+            }
+        };
+
+        // IRQ steering samples the pinned iterator from its own thread
+        let _irq = match (&_task_pin, self.args.irq_steer) {
+            (Some(_), true) => Some(irq::IrqSteering::spawn(
+                self.args.irq_devices.clone(),
+                self.args
+                    .irq_steer_tiers
+                    .iter()
+                    .fold(0, |mask, &t| mask | (1 << t as u8)),
+                self.topology.online_mask,
+            )),
+            (None, true) => {
+                warn!("IRQ steering needs the pinned task iterator, disabled");
+                None
+            }
+            _ => None,
+        };
+
+        // Partial mode: opt matching threads into SCHED_EXT
+        let _partial = (self.args.partial && !self.args.partial_comm.is_empty())
+            .then(|| partial::PartialMatcher::spawn(self.args.partial_comm.clone()));
+
+        // User-idle relaxation writes the BPF user_idle flag from its own thread
+        let _idle = match (self.args.idle_relax, &mut self.skel.maps.bss_data) {
+            (0, _) | (_, None) => None,
+            (minutes, Some(bss)) => {
+                // SAFETY: the BSS mmap lives as long as self.skel, and _idle is
+                // dropped (thread joined) before run() returns.
+                let flag = unsafe { idle::IdleFlag::new(std::ptr::addr_of_mut!(bss.user_idle)) };
+                Some(idle::IdleMonitor::spawn(
+                    flag,
+                    Duration::from_secs(minutes * 60),
+                ))
+            }
+        };
+
+        // --epp-hints: EPP follows the tiers each CPU runs, from its own thread
+        let _epp = match (&self.args.epp_hints, self.skel.maps.bss_data.as_ref()) {
+            (Some(hints), Some(bss)) => {
+                // SAFETY: the BSS mmap lives as long as self.skel, and _epp is
+                // dropped (thread joined) before run() returns.
+                let tiers = unsafe { epp::RunTiers::new(bss.mega_mailbox.as_ptr()) };
+                let cpus = self.topology.managed_mask.iter().collect();
+                epp::EppHinter::spawn(hints.clone(), tiers, cpus)
+                    .map_err(|e| warn!("--epp-hints unavailable: {:#}", e))
+                    .ok()
+            }
+            _ => None,
+        };
+
+        // --powersave: the packed set grows with its utilization, from its own thread
+        let _pack = match (self.args.powersave, &mut self.skel.maps.bss_data) {
+            (Some(threshold), Some(bss)) => {
+                // SAFETY: the BSS mmap lives as long as self.skel, and _pack is
+                // dropped (thread joined) before run() returns.
+                let width =
+                    unsafe { powersave::PackWidth::new(std::ptr::addr_of_mut!(bss.pack_width)) };
+                Some(powersave::PackController::spawn(
+                    width,
+                    self.topology.packing_order(),
+                    threshold,
+                ))
+            }
+            _ => None,
+        };
+
+        // Live tunables and stats on/off for `scx_cake set` (best-effort, like the ctl pin)
+        let (q, nfb, starv) = self.args.effective_values();
+        let _control = match self.skel.maps.bss_data.as_mut() {
+            Some(bss) => {
+                // SAFETY: the BSS mmap lives as long as self.skel, and _control
+                // is dropped (thread joined) before run() returns.
+                let live = unsafe {
+                    control::LiveTunables::new(
+                        std::ptr::addr_of_mut!(bss.live_quantum_ns),
+                        std::ptr::addr_of_mut!(bss.live_new_flow_bonus_ns),
+                        std::ptr::addr_of_mut!(bss.live_starvation_ns),
+                        std::ptr::addr_of_mut!(bss.enable_stats),
+                    )
+                };
+                control::ControlServer::spawn(live, [q, nfb, starv])
+                    .map_err(|e| warn!("scx_cake set unavailable: {:#}", e))
+                    .ok()
+            }
+            None => None,
+        };
+
+        // Read-only JSON queries for overlays and scripts (best-effort)
+        let _query = match self.stats_source() {
+            Some(source) => query::QueryServer::spawn(source)
+                .map_err(|e| warn!("Query socket unavailable: {:#}", e))
+                .ok(),
+            None => None,
+        };
+
+        // scx_stats socket for `scx_cake --monitor` and scx tooling
+        let _stats_service = match (self.args.stats_server, self.stats_source()) {
+            (true, Some(source)) => metrics::StatsService::launch(source)
+                .map_err(|e| warn!("scx_stats server unavailable: {:#}", e))
+                .ok(),
+            _ => None,
+        };
+
+        // --otlp-endpoint: the SDK's reader thread samples and pushes
+        #[cfg(feature = "otlp")]
+        let _otlp = match (&self.args.otlp_endpoint, self.stats_source()) {
+            (Some(url), Some(source)) => {
+                otlp::OtlpExporter::start(url, source, Duration::from_secs(self.args.interval()))
+                    .map_err(|e| warn!("OTLP export unavailable: {:#}", e))
+                    .ok()
+            }
+            _ => None,
+        };
+
+        // --statsd: UDP packets from their own thread
+        let _statsd = match (&self.args.statsd, self.stats_source()) {
+            (Some(target), Some(source)) => statsd::StatsdSender::spawn(
+                target,
+                source,
+                Duration::from_secs(self.args.interval()),
+            )
+            .map_err(|e| warn!("statsd export unavailable: {:#}", e))
+            .ok(),
+            _ => None,
+        };
 
-        self.show_startup_splash()?;
+        // --events / --trace / TUI log pane: ring buffer consumer on its own map handle
+        let feed = (self.args.stats_ui() == Some(UiMode::Tui)).then(events::EventFeed::default);
+        let _events = match (&self.args.events, &self.args.trace, &feed) {
+            (None, None, None) => None,
+            (events, trace, feed) => Some(events::EventStream::spawn(
+                libbpf_rs::MapHandle::try_from(&self.skel.maps.events)?,
+                events.as_deref(),
+                trace.as_deref(),
+                feed.clone(),
+            )?),
+        };
 
-        if self.args.verbose {
+        // --log-stats: CSV rows from their own thread, whatever the UI mode
+        let _csv_log = match (&self.args.log_stats, self.stats_source()) {
+            (Some(path), Some(source)) => Some(csvlog::CsvLogger::spawn(
+                path,
+                source,
+                Duration::from_secs(self.args.interval()),
+            )?),
+            _ => None,
+        };
+
+        let profile_str = format!("{:?}", self.args.profile);
+        let mut history = match &self.args.history_db {
+            Some(path) => Some(history::Recorder::open(
+                path,
+                &profile_str,
+                self.args.history_retention,
+            )?),
+            None => None,
+        };
+
+        if let Some(Command::Bench(bench::BenchCommand::Game(game))) = &self.args.command {
+            // Benchmark capture: sample stats, then correlate with MangoHud
+            bench::run_game(&self.skel, shutdown.clone(), game)?;
+        } else if self.args.stats_ui() == Some(UiMode::Tui) {
             // Run TUI mode
             tui::run_tui(
                 &mut self.skel,
                 shutdown.clone(),
-                self.args.interval,
                 self.topology.clone(),
+                history.as_mut(),
+                &mut self.stats_epoch,
+                tui::TuiParams {
+                    interval_secs: self.args.interval,
+                    theme: self.args.theme(),
+                    snapshot_dir: self.args.snapshot_dir.as_deref().unwrap_or(Path::new(".")),
+                    feed: feed.as_ref(),
+                    alerts: &self.args.alert,
+                    alert_bell: self.args.alert_bell,
+                    alert_log: self.args.alert_log.as_deref(),
+                    keys: &self.args.keys,
+                },
             )?;
         } else {
             // Event-based silent mode - block on signalfd, poll with 60s timeout for UEI check
 
-            // Block SIGINT and SIGTERM from normal delivery
-            let mut mask = SigSet::empty();
-            mask.add(Signal::SIGINT);
-            mask.add(Signal::SIGTERM);
-            mask.thread_block().context("Failed to block signals")?;
-
-            // Create signalfd to receive signals as readable events
-            let sfd = SignalFd::with_flags(&mask, SfdFlags::SFD_NONBLOCK)
+            // main() blocked these in every thread; read them as events.
+            // SIGUSR1 dumps stats, SIGUSR2 resets them
+            let sfd = SignalFd::with_flags(&blocked_signals(), SfdFlags::SFD_NONBLOCK)
                 .context("Failed to create signalfd")?;
 
             use nix::poll::{poll, PollFd, PollFlags};
-            use std::os::fd::BorrowedFd;
+
+            // Plain/JSON stats output (--ui, or --verbose without a terminal)
+            let report = self.args.stats_ui();
+            if report == Some(UiMode::Plain) && self.args.ui == UiMode::Auto {
+                info!("No terminal for the TUI - printing plain stats every --interval");
+            }
+            let mut report_prev = stats::aggregate_stats(&self.skel);
+            let mut report_waits = stats::wait_histograms(&self.skel);
+            let mut report_depth = stats::dsq_depth(&self.skel);
+            let mut report_at = std::time::Instant::now();
+            let report_every = Duration::from_secs(self.args.interval());
+
+            // With --history-db or stats output, wake every --interval;
+            // with a profile schedule, wake often enough to notice a switch
+            let mut timeout_ms = match (&history, report) {
+                (None, None) => 60_000,
+                _ => (self.args.interval() * 1000).min(60_000),
+            };
+            if self.args.has_schedule() {
+                timeout_ms = timeout_ms.min(5_000);
+            }
+            let timeout_ms = timeout_ms as u16;
 
             loop {
-                // Block for up to 60 seconds, then check UEI
+                // Block until signal or timeout, then snapshot history and check UEI
                 // poll() returns: >0 = readable, 0 = timeout, -1 = error
                 // SAFETY: sfd is valid for the duration of this loop
                 let poll_fd = unsafe {
                     PollFd::new(BorrowedFd::borrow_raw(sfd.as_raw_fd()), PollFlags::POLLIN)
                 };
-                let mut fds = [poll_fd];
-                let result = poll(&mut fds, nix::poll::PollTimeout::from(60_000u16)); // 60 seconds
+                let mut fds: Vec<PollFd> = std::iter::once(poll_fd)
+                    .chain(wake.map(|fd| PollFd::new(fd, PollFlags::POLLIN)))
+                    .collect();
+                let result = poll(&mut fds, nix::poll::PollTimeout::from(timeout_ms));
+                if fds[1..].iter().any(|f| f.any().unwrap_or(false)) {
+                    break;
+                }
 
                 match result {
                     Ok(n) if n > 0 => {
-                        // Signal received - read it to clear and exit
-                        if let Ok(Some(siginfo)) = sfd.read_signal() {
-                            info!("Received signal {} - shutting down", siginfo.ssi_signo);
-                            shutdown.store(true, Ordering::Relaxed);
+                        // Signal received - read it to clear; SIGUSR1 dumps stats,
+                        // SIGUSR2 resets them, others exit
+                        match sfd.read_signal() {
+                            Ok(Some(siginfo)) if siginfo.ssi_signo == Signal::SIGUSR1 as u32 => {
+                                self.log_stats_snapshot(started.elapsed());
+                                continue;
+                            }
+                            Ok(Some(siginfo)) if siginfo.ssi_signo == Signal::SIGUSR2 as u32 => {
+                                self.stats_epoch.reset(&self.skel);
+                                info!("Stats reset (epoch {})", self.stats_epoch.epoch);
+                                continue;
+                            }
+                            Ok(Some(siginfo)) => {
+                                info!("Received signal {} - shutting down", siginfo.ssi_signo);
+                                shutdown.store(true, Ordering::Relaxed);
+                            }
+                            _ => {}
                         }
                         break;
                     }
                     Ok(_) => {
-                        // Timeout - check UEI
-                        if scx_utils::uei_exited!(&self.skel, uei) {
-                            match scx_utils::uei_report!(&self.skel, uei) {
-                                Ok(reason) => {
-                                    warn!("BPF scheduler exited: {:?}", reason);
-                                }
-                                Err(e) => {
-                                    warn!("BPF scheduler exited (failed to get reason: {})", e);
-                                }
+                        // Timeout - a profile switch may have requested shutdown
+                        if shutdown.load(Ordering::Relaxed) {
+                            break;
+                        }
+
+                        if let Some(h) = &mut history {
+                            if let Err(e) = h.record(&stats::aggregate_stats(&self.skel)) {
+                                warn!("History snapshot failed: {}", e);
                             }
+                        }
+
+                        if report.is_some() && report_at.elapsed() >= report_every {
+                            let cur = stats::aggregate_stats(&self.skel);
+                            let secs = report_at.elapsed().as_secs_f64();
+                            let user_idle = self
+                                .skel
+                                .maps
+                                .bss_data
+                                .as_ref()
+                                .is_some_and(|b| b.user_idle != 0);
+                            let line = match report {
+                                Some(UiMode::Json) => {
+                                    let waits = stats::wait_histograms(&self.skel);
+                                    let interval = stats::wait_hist_delta(&waits, &report_waits);
+                                    report_waits = waits;
+                                    let depth = stats::dsq_depth(&self.skel);
+                                    let mean_depth = stats::QueueDepth::between(
+                                        &depth,
+                                        &report_depth,
+                                        self.topology.nr_cpus,
+                                    );
+                                    report_depth = depth;
+                                    stats::format_json(
+                                        &cur,
+                                        &report_prev,
+                                        &interval,
+                                        &mean_depth,
+                                        secs,
+                                        self.topology.nr_online(),
+                                        user_idle,
+                                    )
+                                }
+                                _ => stats::format_plain(&cur, &report_prev, secs, user_idle),
+                            };
+                            println!("{}", line);
+                            report_prev = cur;
+                            report_at = std::time::Instant::now();
+                        }
+
+                        // Timeout - check UEI
+                        if self.report_exit() {
                             break;
                         }
                     }
@@ -406,10 +2254,116 @@ impl<'a> Scheduler<'a> {
             }
         }
 
+        // TUI / bench return on their own; the BPF side may have aborted under them
+        self.report_exit();
+        self.write_summary(started.elapsed());
+        if let Some(bss) = self.skel.maps.bss_data.as_ref() {
+            if bss.nr_events_dropped > 0 {
+                warn!(
+                    "{} scheduling events dropped (ring buffer full)",
+                    bss.nr_events_dropped
+                );
+            }
+        }
+
         info!("scx_cake scheduler shutting down");
         Ok(())
     }
 
+    /// Per-CPU stats pointers for threads that sample counters themselves
+    fn stats_source(&self) -> Option<metrics::StatsSource> {
+        let bss = self.skel.maps.bss_data.as_ref()?;
+        let wait_hist = libbpf_rs::MapHandle::try_from(&self.skel.maps.wait_hist).ok()?;
+        // SAFETY: the BSS mmap lives as long as self.skel, and every reader
+        // thread is joined before run() returns.
+        Some(unsafe {
+            metrics::StatsSource::new(
+                bss.global_stats.as_ptr(),
+                bss.global_stats.len(),
+                std::ptr::addr_of!(bss.user_idle),
+                wait_hist,
+            )
+        })
+    }
+
+    /// Whether BPF is counting stats right now (`scx_cake set stats` can flip it)
+    fn stats_counting(&self) -> bool {
+        self.skel
+            .maps
+            .bss_data
+            .as_ref()
+            .is_some_and(|bss| bss.enable_stats != 0)
+    }
+
+    /// SIGUSR1 in silent mode: log the same report the TUI copies with 'c'.
+    /// With stats off there is nothing to report, so start counting instead
+    fn log_stats_snapshot(&mut self, uptime: Duration) {
+        if !self.stats_counting() {
+            if let Some(bss) = self.skel.maps.bss_data.as_mut() {
+                bss.enable_stats = 1;
+            }
+            warn!("SIGUSR1: stats were off, counting from now; send SIGUSR1 again for a report");
+            return;
+        }
+        let stats = self.stats_epoch.totals(&self.skel);
+        let text = tui::format_stats_for_clipboard(&stats, &tui::format_uptime(uptime));
+        if self.stats_epoch.epoch > 0 {
+            info!(
+                "Counting since stats reset {} ({} ago)",
+                self.stats_epoch.epoch,
+                tui::format_uptime(self.stats_epoch.since.elapsed())
+            );
+        }
+        for line in text.lines().filter(|l| !l.is_empty()) {
+            info!("{}", line);
+        }
+    }
+
+    /// Final counters on the way out (signal or BPF exit): to --summary-file
+    /// if set, otherwise to the log when stats were counted at any point
+    fn write_summary(&self, uptime: Duration) {
+        let stats = self.stats_epoch.totals(&self.skel);
+        if stats.nr_runs == 0 {
+            return;
+        }
+        let waits = self.stats_epoch.waits(&stats::wait_histograms(&self.skel));
+        let text = format!(
+            "{}\n{}",
+            tui::format_stats_for_clipboard(&stats, &tui::format_uptime(uptime)),
+            stats::format_wait_summary(&waits)
+        );
+        match &self.args.summary_file {
+            Some(path) => match std::fs::write(path, &text) {
+                Ok(()) => info!("Wrote stats summary to {}", path.display()),
+                Err(e) => warn!("Failed to write {}: {}", path.display(), e),
+            },
+            None => {
+                for line in text.lines().filter(|l| !l.is_empty()) {
+                    info!("{}", line);
+                }
+            }
+        }
+    }
+
+    /// If the BPF scheduler exited, print its reason, message and exit dump
+    /// (sized by --exit-dump-len). Returns true once it has exited.
+    fn report_exit(&mut self) -> bool {
+        if self.exit_reported || !scx_utils::uei_exited!(&self.skel, uei) {
+            return self.exit_reported;
+        }
+        self.exit_reported = true;
+        match scx_utils::uei_report!(&self.skel, uei) {
+            Ok(reason) => {
+                warn!("BPF scheduler exited: {:?}", reason);
+                self.restart = reason.should_restart();
+            }
+            Err(e) => {
+                warn!("BPF scheduler exited (failed to get reason: {})", e);
+            }
+        }
+        true
+    }
+
     fn show_startup_splash(&self) -> Result<()> {
         let (q, _nfb, starv) = self.args.effective_values();
         let profile_str = format!("{:?}", self.args.profile).to_uppercase();
@@ -420,30 +2374,163 @@ impl<'a> Scheduler<'a> {
             profile: &profile_str,
             quantum: q,
             starvation: starv,
+            theme: self.args.theme(),
         })
     }
 }
 
+/// SIGINT, SIGTERM, SIGUSR1 (stats dump) and SIGUSR2 (stats reset): blocked
+/// process-wide by main(), read by the silent loop's signalfd
+fn blocked_signals() -> SigSet {
+    let mut mask = SigSet::empty();
+    mask.add(Signal::SIGINT);
+    mask.add(Signal::SIGTERM);
+    mask.add(Signal::SIGUSR1);
+    mask.add(Signal::SIGUSR2);
+    mask
+}
+
+/// SIGINT/SIGTERM handling for every mode: the signals are blocked, so wait
+/// for them on a thread, set `shutdown` and pass the signal on to the main
+/// thread, whose signalfd the silent loop may be waiting on
+fn spawn_shutdown_waiter(shutdown: Arc<AtomicBool>) -> Result<()> {
+    let mut mask = SigSet::empty();
+    mask.add(Signal::SIGINT);
+    mask.add(Signal::SIGTERM);
+    // SAFETY: pthread_self has no preconditions
+    let main_thread = unsafe { libc::pthread_self() };
+    std::thread::Builder::new()
+        .name("cake-signals".into())
+        .spawn(move || loop {
+            let Ok(sig) = mask.wait() else {
+                continue;
+            };
+            info!("Received shutdown signal");
+            shutdown.store(true, Ordering::Relaxed);
+            // SAFETY: the main thread lives as long as the process
+            unsafe { libc::pthread_kill(main_thread, sig as libc::c_int) };
+        })
+        .context("Failed to start signal thread")?;
+    Ok(())
+}
+
 fn main() -> Result<()> {
     env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
 
-    let args = Args::parse();
+    let args = config::load()?;
 
-    // Set up signal handler
+    // Offline subcommands: no BPF, no root
+    match &args.command {
+        Some(Command::History(cmd)) => return history::run(cmd),
+        Some(Command::Ctl(cmd)) => return ctl::run(cmd),
+        Some(Command::Set(cmd)) => return control::run_set(cmd),
+        Some(Command::Query { request }) => return query::run_query(request),
+        Some(Command::Monitor {
+            replay: Some(path),
+            events,
+            ..
+        }) => {
+            let session = replay::Session::load(path, events.as_deref())?;
+            return tui::run_replay(session, args.theme());
+        }
+        Some(Command::Monitor {
+            interval,
+            tui: true,
+            ..
+        }) => return tui::run_attach(Duration::from_secs((*interval).max(1)), args.theme()),
+        Some(Command::Monitor {
+            interval, baseline, ..
+        }) => return ctl::monitor(*interval, baseline.as_deref()),
+        Some(Command::DumpTopology { json }) => return check::dump_topology(&args, json),
+        _ => {}
+    }
+
+    if args.otlp_endpoint.is_some() && !cfg!(feature = "otlp") {
+        bail!("--otlp-endpoint needs scx_cake built with --features otlp");
+    }
+
+    if args.check_config {
+        return check::run(&args);
+    }
+
+    // Block the signals before any thread starts so every thread inherits
+    // the mask; otherwise SIGUSR1/SIGUSR2 can land on a helper thread and
+    // their default action kills the daemon
+    blocked_signals()
+        .thread_block()
+        .context("Failed to block signals")?;
     let shutdown = Arc::new(AtomicBool::new(false));
-    let shutdown_clone = shutdown.clone();
+    spawn_shutdown_waiter(shutdown.clone())?;
+
+    if let Some(secs) = args.monitor {
+        return metrics::monitor(Duration::from_secs_f64(secs.max(0.1)), false, shutdown);
+    }
+    if let Some(secs) = args.monitor_oneline {
+        return metrics::monitor(Duration::from_secs_f64(secs.max(0.1)), true, shutdown);
+    }
+
+    if let Some(Command::Autotune(opts)) = &args.command {
+        return autotune::run(&args, opts, shutdown);
+    }
 
-    ctrlc::set_handler(move || {
-        info!("Received shutdown signal");
-        shutdown_clone.store(true, Ordering::Relaxed);
-    })?;
+    // Profile schedule only applies to the plain daemon, not bench runs
+    let rules = if args.command.is_none() {
+        schedule::load_rules(&args.schedule, args.schedule_file.as_deref())?
+    } else {
+        Vec::new()
+    };
+    let base_profile = args.profile;
+    let mut profile = match rules.is_empty() {
+        true => base_profile,
+        false => schedule::desired_profile(&rules, base_profile),
+    };
+    let mut latency_matrix = None;
 
-    // Create open object for BPF - needs to outlive scheduler
-    let mut open_object = std::mem::MaybeUninit::uninit();
+    loop {
+        // Create open object for BPF - needs to outlive scheduler
+        let mut open_object = std::mem::MaybeUninit::uninit();
 
-    // Create and run the scheduler
-    let mut scheduler = Scheduler::new(args, &mut open_object)?;
-    scheduler.run(shutdown)?;
+        // Create and run the scheduler
+        let mut run_args = args.clone();
+        run_args.profile = profile;
+        let mut scheduler = Scheduler::new(run_args, &mut open_object, latency_matrix.take())?;
+
+        let watcher = (!rules.is_empty()).then(|| {
+            schedule::ScheduleWatcher::spawn(rules.clone(), base_profile, profile, shutdown.clone())
+        });
+        let hotplug = args.command.is_none().then(|| {
+            hotplug::HotplugWatcher::spawn(scheduler.topology.online_mask, shutdown.clone())
+        });
+        let result = scheduler.run(
+            shutdown.clone(),
+            hotplug.as_ref().and_then(hotplug::HotplugWatcher::wake_fd),
+        );
+        let next = watcher.and_then(schedule::ScheduleWatcher::finish);
+        let replugged = hotplug.and_then(hotplug::HotplugWatcher::finish);
+        result?;
+
+        // Reload with the scheduled profile, or on the topology left by CPU
+        // hotplug, reusing the ETD measurement when the CPU count held.
+        // A restart the exit asked for is skipped if the user quit.
+        if let Some((before, after)) = replugged {
+            info!(
+                "CPU hotplug: online CPUs {} -> {}, reloading",
+                check::mask_list(before),
+                check::mask_list(after)
+            );
+        } else if scheduler.restart && !shutdown.load(Ordering::Relaxed) {
+            info!("BPF scheduler asked to be restarted, reloading");
+        } else if next.is_none() {
+            break;
+        }
+        if let Some(next) = next {
+            info!("Profile schedule: switching {:?} -> {:?}", profile, next);
+            profile = next;
+        }
+        latency_matrix = Some(std::mem::take(&mut scheduler.latency_matrix));
+        drop(scheduler);
+        shutdown.store(false, Ordering::Relaxed);
+    }
 
     Ok(())
 }
@@ -0,0 +1,346 @@
+// SPDX-License-Identifier: GPL-2.0
+// Metrics module - scx_stats server on the standard scx socket and the --monitor clients
+
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use libbpf_rs::MapHandle;
+use scx_stats::prelude::*;
+use scx_stats_derive::Stats;
+use serde::{Deserialize, Serialize};
+
+use crate::bpf_skel::types::cake_stats;
+use crate::stats::{self, WaitHist};
+
+/// How long the responder waits for a request before checking for shutdown
+const POLL: Duration = Duration::from_millis(500);
+
+/// Interval deltas served to `scx_cake --monitor` and generic scx tooling
+#[derive(Clone, Debug, Default, Serialize, Deserialize, Stats)]
+#[stat(top)]
+pub struct Metrics {
+    #[stat(desc = "Seconds covered by this sample")]
+    pub interval_s: f64,
+    #[stat(desc = "Critical tier dispatches")]
+    pub dispatch_critical: u64,
+    #[stat(desc = "Interactive tier dispatches")]
+    pub dispatch_interactive: u64,
+    #[stat(desc = "Frame tier dispatches")]
+    pub dispatch_frame: u64,
+    #[stat(desc = "Bulk tier dispatches")]
+    pub dispatch_bulk: u64,
+    #[stat(desc = "Dispatches from new-flow")]
+    pub new_flow: u64,
+    #[stat(desc = "Dispatches from old-flow")]
+    pub old_flow: u64,
+    #[stat(desc = "Starvation preemptions, all tiers")]
+    pub starvation_preempts: u64,
+    #[stat(desc = "Starvation preemptions skipped by immunity")]
+    pub starvation_immune: u64,
+    #[stat(desc = "Dispatch passes that left a capped tier queued")]
+    pub cap_deferrals: u64,
+    #[stat(desc = "Heavy wakeups moved off a heavy SMT sibling")]
+    pub smt_spread: u64,
+    #[stat(desc = "Heavy wakeups left next to a heavy SMT sibling")]
+    pub smt_colocated: u64,
+    #[stat(desc = "Tasks moved between LLC DSQs by the rebalancer")]
+    pub rebalance_moves: u64,
+    #[stat(desc = "Dispatches with the warm-up tier override")]
+    pub warmup_dispatches: u64,
+    #[stat(desc = "Bulk enqueues promoted to Frame while the user is idle")]
+    pub idle_promotions: u64,
+    #[stat(desc = "1 while the user is idle (--idle-relax)")]
+    pub user_idle: u64,
+    #[stat(desc = "Runs ended")]
+    pub runs: u64,
+    #[stat(desc = "Mean runnable wait per run, microseconds")]
+    pub wait_avg_us: f64,
+    #[stat(desc = "Upper bound of the highest wait bucket hit, microseconds")]
+    pub wait_max_us: u64,
+}
+
+/// Raw counters the scheduler hands the stats server per request
+pub struct Snapshot {
+    pub stats: cake_stats,
+    pub waits: WaitHist,
+    pub user_idle: bool,
+    pub at: Instant,
+}
+
+impl Metrics {
+    fn delta(cur: &Snapshot, prev: &Snapshot) -> Self {
+        let d = |f: fn(&cake_stats) -> u64| f(&cur.stats).saturating_sub(f(&prev.stats));
+        let tier = |t: usize| {
+            cur.stats.nr_tier_dispatches[t].saturating_sub(prev.stats.nr_tier_dispatches[t])
+        };
+        let runs = d(|s| s.nr_runs);
+        Self {
+            interval_s: cur.at.duration_since(prev.at).as_secs_f64(),
+            dispatch_critical: tier(0),
+            dispatch_interactive: tier(1),
+            dispatch_frame: tier(2),
+            dispatch_bulk: tier(3),
+            new_flow: d(|s| s.nr_new_flow_dispatches),
+            old_flow: d(|s| s.nr_old_flow_dispatches),
+            starvation_preempts: d(|s| s.nr_starvation_preempts_tier.iter().sum()),
+            starvation_immune: d(|s| s.nr_starvation_immune_tier.iter().sum()),
+            cap_deferrals: d(|s| s.nr_cap_deferrals.iter().sum()),
+            smt_spread: d(|s| s.nr_smt_spread),
+            smt_colocated: d(|s| s.nr_smt_colocated),
+            rebalance_moves: d(|s| s.nr_rebalance_moves),
+            warmup_dispatches: d(|s| s.nr_warmup_dispatches),
+            idle_promotions: d(|s| s.nr_idle_promotions),
+            user_idle: cur.user_idle as u64,
+            runs,
+            wait_avg_us: d(|s| s.wait_ns) as f64 / runs.max(1) as f64 / 1000.0,
+            wait_max_us: stats::wait_max_us(&stats::wait_hist_delta(&cur.waits, &prev.waits)),
+        }
+    }
+
+    /// One line per sample, rates per second like `--ui plain`
+    fn format<W: Write>(&self, w: &mut W) -> Result<()> {
+        let rate = |n: u64| n as f64 / self.interval_s.max(0.001);
+        writeln!(
+            w,
+            "dispatch/s: Critical {:.0}, Interactive {:.0}, Frame {:.0}, Bulk {:.0} | \
+             starvation/s {:.1} (immune {:.1}) | smt spread/s {:.1} | \
+             rebalance moves/s {:.1} | warmup/s {:.1} | user {}",
+            rate(self.dispatch_critical),
+            rate(self.dispatch_interactive),
+            rate(self.dispatch_frame),
+            rate(self.dispatch_bulk),
+            rate(self.starvation_preempts),
+            rate(self.starvation_immune),
+            rate(self.smt_spread),
+            rate(self.rebalance_moves),
+            rate(self.warmup_dispatches),
+            if self.user_idle != 0 {
+                "idle"
+            } else {
+                "active"
+            }
+        )?;
+        Ok(())
+    }
+
+    /// `--monitor-oneline`: one `key=value` line for grep/awk and status
+    /// bars (waybar, polybar); tier counts are for the interval
+    fn format_oneline<W: Write>(&self, w: &mut W) -> Result<()> {
+        let rate = |n: u64| n as f64 / self.interval_s.max(0.001);
+        let dispatches = self.dispatch_critical
+            + self.dispatch_interactive
+            + self.dispatch_frame
+            + self.dispatch_bulk;
+        writeln!(
+            w,
+            "dispatch_s={:.0} wait_avg_us={:.1} wait_max_us={} critical={} interactive={} \
+             frame={} bulk={} starvation_s={:.1} user={}",
+            rate(dispatches),
+            self.wait_avg_us,
+            self.wait_max_us,
+            self.dispatch_critical,
+            self.dispatch_interactive,
+            self.dispatch_frame,
+            self.dispatch_bulk,
+            rate(self.starvation_preempts),
+            if self.user_idle != 0 {
+                "idle"
+            } else {
+                "active"
+            }
+        )?;
+        Ok(())
+    }
+}
+
+/// Each client gets deltas since its own previous read
+fn server_data() -> StatsServerData<(), Snapshot> {
+    let open: Box<dyn StatsOpener<(), Snapshot>> = Box::new(move |(req_ch, res_ch)| {
+        req_ch.send(())?;
+        let mut prev = res_ch.recv()?;
+
+        let read: Box<dyn StatsReader<(), Snapshot>> = Box::new(move |_args, (req_ch, res_ch)| {
+            req_ch.send(())?;
+            let cur = res_ch.recv()?;
+            let metrics = Metrics::delta(&cur, &prev);
+            prev = cur;
+            metrics.to_json()
+        });
+        Ok(read)
+    });
+
+    StatsServerData::new()
+        .add_meta(Metrics::meta())
+        .add_ops("top", StatsOps { open, close: None })
+}
+
+/// Pointers to the BPF per-CPU stats array and `user_idle` BSS variable,
+/// plus a handle on the wait_hist map
+pub struct StatsSource {
+    stats: *const cake_stats,
+    len: usize,
+    user_idle: *const u32,
+    wait_hist: MapHandle,
+}
+
+// SAFETY: the BSS mmap outlives the reader threads (StatsService, CsvLogger,
+// StatsdSender, OtlpExporter and QueryServer stop theirs on drop and are
+// dropped before the skeleton); only reads are done through it.
+unsafe impl Send for StatsSource {}
+
+impl StatsSource {
+    /// # Safety
+    /// `stats` (`len` entries) and `user_idle` must point into the skeleton's
+    /// mmap'd BSS and stay valid until the thread using them is joined.
+    pub unsafe fn new(
+        stats: *const cake_stats,
+        len: usize,
+        user_idle: *const u32,
+        wait_hist: MapHandle,
+    ) -> Self {
+        Self {
+            stats,
+            len,
+            user_idle,
+            wait_hist,
+        }
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        // SAFETY: see StatsSource::new
+        let (per_cpu, user_idle) = unsafe {
+            (
+                std::slice::from_raw_parts(self.stats, self.len),
+                std::ptr::read_volatile(self.user_idle),
+            )
+        };
+        Snapshot {
+            stats: stats::sum_stats(per_cpu),
+            waits: stats::read_wait_histograms(&self.wait_hist),
+            user_idle: user_idle != 0,
+            at: Instant::now(),
+        }
+    }
+}
+
+/// The scx_stats socket plus the thread answering its snapshot requests.
+/// The server thread only holds channels, so once this is dropped late
+/// clients get an error instead of reading a freed skeleton.
+pub struct StatsService {
+    _server: StatsServer<(), Snapshot>,
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl StatsService {
+    pub fn launch(source: StatsSource) -> Result<Self> {
+        let server = StatsServer::new(server_data())
+            .launch()
+            .context("Failed to start the scx_stats server")?;
+        let (res_ch, req_ch) = server.channels();
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let stop_thread = stop.clone();
+        let handle = std::thread::Builder::new()
+            .name("cake-stats".into())
+            .spawn(move || {
+                while !stop_thread.load(Ordering::Relaxed) {
+                    match req_ch.recv_timeout(POLL) {
+                        Ok(()) => {
+                            if res_ch.send(source.snapshot()).is_err() {
+                                break;
+                            }
+                        }
+                        Err(e) if e.is_disconnected() => break,
+                        Err(_) => {}
+                    }
+                }
+            })
+            .context("Failed to start the stats responder thread")?;
+
+        Ok(Self {
+            _server: server,
+            stop,
+            handle: Some(handle),
+        })
+    }
+}
+
+impl Drop for StatsService {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+/// `--monitor SECS` / `--monitor-oneline SECS`: print a running instance's
+/// metrics from its stats socket
+pub fn monitor(interval: Duration, oneline: bool, shutdown: Arc<AtomicBool>) -> Result<()> {
+    scx_utils::monitor_stats::<Metrics>(
+        &vec![],
+        interval,
+        || shutdown.load(Ordering::Relaxed),
+        |metrics| match oneline {
+            true => metrics.format_oneline(&mut std::io::stdout()),
+            false => metrics.format(&mut std::io::stdout()),
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_delta_and_format() {
+        let at = Instant::now();
+        let mut prev = Snapshot {
+            stats: cake_stats::default(),
+            waits: Default::default(),
+            user_idle: false,
+            at,
+        };
+        prev.stats.nr_tier_dispatches = [100, 0, 0, 0];
+        let mut cur = Snapshot {
+            stats: prev.stats,
+            waits: Default::default(),
+            user_idle: true,
+            at: at + Duration::from_secs(2),
+        };
+        cur.stats.nr_tier_dispatches = [300, 0, 0, 40];
+        cur.stats.nr_starvation_preempts_tier = [0, 1, 0, 3];
+        cur.stats.nr_runs = 200;
+        cur.stats.wait_ns = 5_000_000;
+        cur.waits[1][4] = 1;
+        cur.waits[3][9] = 2;
+
+        let m = Metrics::delta(&cur, &prev);
+        assert_eq!(m.dispatch_critical, 200);
+        assert_eq!(m.dispatch_bulk, 40);
+        assert_eq!(m.starvation_preempts, 4);
+        assert_eq!(m.user_idle, 1);
+
+        let mut line = Vec::new();
+        m.format(&mut line).unwrap();
+        let line = String::from_utf8(line).unwrap();
+        assert!(line.starts_with("dispatch/s: Critical 100,"), "{}", line);
+        assert!(line.contains("Bulk 20 |"), "{}", line);
+        assert!(line.trim_end().ends_with("user idle"), "{}", line);
+
+        let mut line = Vec::new();
+        m.format_oneline(&mut line).unwrap();
+        assert_eq!(
+            String::from_utf8(line).unwrap(),
+            "dispatch_s=120 wait_avg_us=25.0 wait_max_us=512 critical=200 interactive=0 \
+             frame=0 bulk=40 starvation_s=2.0 user=idle\n"
+        );
+        // A counter reset (scheduler reload) never goes negative
+        assert_eq!(Metrics::delta(&prev, &cur).dispatch_critical, 0);
+    }
+}
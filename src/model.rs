@@ -0,0 +1,432 @@
+// SPDX-License-Identifier: GPL-2.0
+// Policy model - pure-Rust mirror of the cake.bpf.c tier/DRR++ logic for root-free unit tests
+
+use crate::bpf_intf;
+
+/// Flow flag: task still holds its new-flow bonus (mirrors CAKE_FLOW_NEW)
+const FLOW_NEW: u8 = 1 << 0;
+
+/// Per-tier graduated backoff recheck masks (mirrors tier_recheck_mask[])
+const TIER_RECHECK_MASK: [u16; 4] = [1023, 127, 31, 15];
+
+/// Tier gates in µs, shared with the BPF side through the generated bindings
+const GATES: [u16; 3] = [
+    bpf_intf::TIER_GATE_T0 as u16,
+    bpf_intf::TIER_GATE_T1 as u16,
+    bpf_intf::TIER_GATE_T2 as u16,
+];
+
+/// Userspace copy of the per-task state kept in cake_task_ctx
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TaskModel {
+    pub next_slice_ns: u64,
+    pub deficit_us: u16,
+    pub avg_runtime_us: u16,
+    pub tier: u8,
+    pub stable: u8,
+    pub flags: u8,
+    pub reclass_counter: u16,
+}
+
+impl TaskModel {
+    pub fn is_new_flow(&self) -> bool {
+        self.flags & FLOW_NEW != 0
+    }
+}
+
+/// Scheduling policy decisions, one method per BPF callback that makes them
+pub trait SchedPolicy {
+    /// alloc_task_ctx_cold: initial state from static_prio (120 = nice 0)
+    fn init_task(&self, static_prio: u32) -> TaskModel;
+
+    /// reclassify_task_cold: EWMA + deficit + tier update after one bout
+    fn on_stop(&self, task: &mut TaskModel, runtime_ns: u32);
+
+    /// cake_enqueue: tier-encoded vtime for the per-LLC DSQ
+    fn enqueue_vtime(&self, task: &TaskModel, now_ns: u64) -> u64;
+
+    /// cake_tick: should the running task be kicked for starvation?
+    /// `waiter_tier` is the tier at the head of the LLC DSQ, if any.
+    fn starvation_preempt(
+        &self,
+        task: &TaskModel,
+        runtime_ns: u64,
+        waiter_tier: Option<u8>,
+    ) -> bool;
+}
+
+/// Model of the shipped policy, parameterized like the BPF rodata
+pub struct CakeModel {
+    pub quantum_ns: u64,
+    pub new_flow_bonus_ns: u64,
+    pub tier_configs: [u64; 8],
+    pub starvation_ns: u64,
+    pub starvation_immune_mask: u8,
+    /// Initial tier by nice + 20 (mirrors the nice_tier rodata table)
+    pub nice_tiers: [u8; 40],
+    /// Mirrors the BSS user_idle flag (set by the idle monitor)
+    pub user_idle: bool,
+}
+
+impl CakeModel {
+    /// Model configured exactly as Scheduler::new would load the given profile
+    pub fn from_profile(profile: crate::Profile) -> Self {
+        let (quantum_us, nfb_us, starvation_us) = profile.values();
+        Self {
+            quantum_ns: quantum_us * 1000,
+            new_flow_bonus_ns: nfb_us * 1000,
+            tier_configs: profile.tier_configs(
+                quantum_us,
+                profile.tier_multiplier(),
+                profile.starvation_threshold(),
+            ),
+            starvation_ns: starvation_us * 1000,
+            starvation_immune_mask: profile.starvation_immune_mask(),
+            nice_tiers: crate::nice_tiers(&[]),
+            user_idle: false,
+        }
+    }
+
+    fn multiplier(&self, tier: u8) -> u64 {
+        self.tier_configs[(tier & 7) as usize] & 0xFFF
+    }
+
+    fn starvation_threshold_ns(&self, tier: u8) -> u64 {
+        (self.tier_configs[(tier & 7) as usize] >> 44) << 10
+    }
+
+    /// Hysteresis-adjusted classification: promotion needs 10% below the gate
+    fn classify(avg_us: u16, old_tier: u8) -> u8 {
+        let gate = |idx: usize| {
+            let g = GATES[idx];
+            if old_tier as usize <= idx {
+                g
+            } else {
+                g - g / 10
+            }
+        };
+        if avg_us < gate(0) {
+            0
+        } else if avg_us < gate(1) {
+            1
+        } else if avg_us < gate(2) {
+            2
+        } else {
+            3
+        }
+    }
+}
+
+impl SchedPolicy for CakeModel {
+    fn init_task(&self, static_prio: u32) -> TaskModel {
+        let tier = match static_prio.wrapping_sub(100) as usize {
+            idx if idx < self.nice_tiers.len() => self.nice_tiers[idx],
+            _ => 1,
+        };
+        TaskModel {
+            next_slice_ns: self.quantum_ns,
+            deficit_us: ((self.quantum_ns + self.new_flow_bonus_ns) >> 10) as u16,
+            avg_runtime_us: 0,
+            tier,
+            stable: 0,
+            flags: FLOW_NEW,
+            reclass_counter: 0,
+        }
+    }
+
+    fn on_stop(&self, task: &mut TaskModel, runtime_ns: u32) {
+        let runtime_us = runtime_ns >> 10;
+        let rt = runtime_us.min(0xFFFF) as u16;
+
+        let new_avg = task.avg_runtime_us - (task.avg_runtime_us >> 3) + (rt >> 3);
+        let deficit = task.deficit_us.saturating_sub(rt);
+
+        if task.stable == 3 {
+            // Graduated backoff: EWMA + deficit only, spot-check the tier
+            task.avg_runtime_us = new_avg;
+            task.deficit_us = deficit;
+            task.reclass_counter = task.reclass_counter.wrapping_add(1);
+            if task.reclass_counter & TIER_RECHECK_MASK[(task.tier & 3) as usize] != 0 {
+                if Self::classify(new_avg, task.tier) != task.tier {
+                    task.stable = 0;
+                    task.reclass_counter = 0;
+                }
+                return;
+            }
+        }
+
+        let deficit_exhausted = deficit == 0 && task.is_new_flow();
+        task.avg_runtime_us = new_avg;
+        task.deficit_us = deficit;
+
+        let new_tier = Self::classify(new_avg, task.tier);
+        let tier_changed = new_tier != task.tier;
+        task.stable = if tier_changed {
+            0
+        } else {
+            (task.stable + 1).min(3)
+        };
+        if deficit_exhausted {
+            task.flags &= !FLOW_NEW;
+        }
+        if tier_changed {
+            task.tier = new_tier;
+            task.next_slice_ns = (self.quantum_ns * self.multiplier(new_tier)) >> 10;
+            task.reclass_counter = 0;
+        }
+    }
+
+    fn enqueue_vtime(&self, task: &TaskModel, now_ns: u64) -> u64 {
+        // User idle: Bulk drains alongside Frame
+        let tier = if self.user_idle && task.tier == 3 {
+            2
+        } else {
+            task.tier & 3
+        };
+        let mut vtime = ((tier as u64) << 56) | (now_ns & 0x00FF_FFFF_FFFF_FFFF);
+        if task.is_new_flow() {
+            vtime -= self.new_flow_bonus_ns;
+        }
+        vtime
+    }
+
+    fn starvation_preempt(
+        &self,
+        task: &TaskModel,
+        runtime_ns: u64,
+        waiter_tier: Option<u8>,
+    ) -> bool {
+        if runtime_ns <= self.starvation_threshold_ns(task.tier) {
+            return false;
+        }
+        if self.starvation_immune_mask & (1 << (task.tier & 3)) == 0 {
+            return true;
+        }
+        // Immune: hard ceiling, then only same-or-higher tier waiters preempt
+        runtime_ns > self.starvation_ns || waiter_tier.is_some_and(|w| w <= task.tier)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Profile;
+
+    const NICE_0: u32 = 120;
+
+    fn gaming() -> CakeModel {
+        CakeModel::from_profile(Profile::Gaming)
+    }
+
+    /// Run `n` identical bouts of `runtime_us` through the model
+    fn run(model: &CakeModel, task: &mut TaskModel, runtime_us: u32, n: usize) {
+        for _ in 0..n {
+            model.on_stop(task, runtime_us << 10);
+        }
+    }
+
+    #[test]
+    fn test_init_tier_from_nice() {
+        let m = gaming();
+        assert_eq!(m.init_task(100).tier, 0, "nice -20 starts Critical");
+        assert_eq!(m.init_task(NICE_0).tier, 1, "nice 0 starts Interactive");
+        assert_eq!(m.init_task(139).tier, 3, "nice 19 starts Bulk");
+        assert!(m.init_task(NICE_0).is_new_flow());
+    }
+
+    #[test]
+    fn test_nice_map_overrides_init_tier() {
+        let mut m = gaming();
+        m.nice_tiers = crate::nice_tiers(&["-20..19=interactive".parse().unwrap()]);
+        assert_eq!(m.init_task(100).tier, 1, "nice ignored for placement");
+        assert_eq!(m.init_task(139).tier, 1);
+
+        m.nice_tiers = crate::nice_tiers(&[
+            "-20..-10=critical".parse().unwrap(),
+            "-9..-1=interactive".parse().unwrap(),
+        ]);
+        assert_eq!(m.init_task(110).tier, 0, "nice -10 still Critical");
+        assert_eq!(m.init_task(111).tier, 1, "nice -9 now Interactive");
+        assert_eq!(m.init_task(139).tier, 3, "unlisted nice keeps the default");
+        assert!("5..-5=bulk".parse::<crate::NiceRange>().is_err());
+        assert!("-21=bulk".parse::<crate::NiceRange>().is_err());
+    }
+
+    #[test]
+    fn test_new_flow_bonus_expires_with_deficit() {
+        let m = gaming();
+        let mut t = m.init_task(NICE_0);
+        let credit = t.deficit_us as u32;
+
+        // Just short of the credit: still a new flow
+        run(&m, &mut t, credit - 1, 1);
+        assert!(t.is_new_flow());
+        assert_eq!(t.deficit_us, 1);
+
+        // Exhausting the deficit clears the bonus
+        run(&m, &mut t, 1, 1);
+        assert_eq!(t.deficit_us, 0);
+        assert!(!t.is_new_flow());
+    }
+
+    #[test]
+    fn test_new_flow_drains_before_established_same_tier() {
+        let m = gaming();
+        let fresh = m.init_task(NICE_0);
+        let mut old = fresh;
+        old.flags = 0;
+
+        // Established task enqueued first still sorts after the new flow
+        assert!(m.enqueue_vtime(&fresh, 5_000_000) < m.enqueue_vtime(&old, 1_000_000));
+    }
+
+    #[test]
+    fn test_tier_dominates_enqueue_order() {
+        let m = gaming();
+        let mut critical = m.init_task(NICE_0);
+        critical.tier = 0;
+        critical.flags = 0;
+        let mut bulk = critical;
+        bulk.tier = 3;
+
+        // T0 enqueued an hour later still drains before T3
+        let hour = 3_600_000_000_000;
+        assert!(m.enqueue_vtime(&critical, hour) < m.enqueue_vtime(&bulk, 0));
+    }
+
+    #[test]
+    fn test_user_idle_promotes_bulk_to_frame() {
+        let mut m = gaming();
+        let mut bulk = m.init_task(139);
+        bulk.flags = 0;
+        let mut frame = bulk;
+        frame.tier = 2;
+
+        assert!(m.enqueue_vtime(&bulk, 0) > m.enqueue_vtime(&frame, 1_000_000));
+        m.user_idle = true;
+        assert!(
+            m.enqueue_vtime(&bulk, 0) < m.enqueue_vtime(&frame, 1_000_000),
+            "idle: earlier Bulk enqueue drains first"
+        );
+    }
+
+    #[test]
+    fn test_demotion_uses_standard_gate() {
+        let m = gaming();
+        let mut t = m.init_task(NICE_0);
+        t.avg_runtime_us = 2100;
+        t.stable = 2;
+
+        run(&m, &mut t, 2100, 1);
+        assert_eq!(t.tier, 2, "avg above the T1 gate demotes immediately");
+        assert_eq!(t.stable, 0);
+    }
+
+    #[test]
+    fn test_promotion_needs_hysteresis_margin() {
+        let m = gaming();
+        let mut t = m.init_task(NICE_0);
+        t.tier = 2;
+        t.avg_runtime_us = 1900;
+
+        // Inside the 10% deadband (1800..2000): stays Frame
+        run(&m, &mut t, 1900, 1);
+        assert_eq!(t.tier, 2);
+
+        // Clearly below the promote gate: back to Interactive
+        t.avg_runtime_us = 1750;
+        run(&m, &mut t, 1750, 1);
+        assert_eq!(t.tier, 1);
+    }
+
+    #[test]
+    fn test_tier_change_recomputes_slice() {
+        let m = gaming();
+        let mut t = m.init_task(NICE_0);
+        t.avg_runtime_us = 9000;
+
+        run(&m, &mut t, 9000, 1);
+        assert_eq!(t.tier, 3);
+        assert_eq!(t.next_slice_ns, (m.quantum_ns * m.multiplier(3)) >> 10);
+    }
+
+    #[test]
+    fn test_stable_task_spot_check_catches_change() {
+        let m = gaming();
+        let mut t = m.init_task(NICE_0);
+        run(&m, &mut t, 500, 32);
+        assert_eq!(t.tier, 1);
+        assert_eq!(t.stable, 3);
+
+        // Behaviour shifts to long bursts: backoff path must notice
+        run(&m, &mut t, 20_000, 16);
+        assert_eq!(t.tier, 3);
+    }
+
+    #[test]
+    fn test_starvation_below_threshold_never_preempts() {
+        let m = gaming();
+        let mut t = m.init_task(NICE_0);
+        t.tier = 3;
+        assert!(!m.starvation_preempt(&t, m.starvation_threshold_ns(3), Some(0)));
+    }
+
+    #[test]
+    fn test_starvation_immunity_ordering() {
+        let m = gaming();
+        let mut t = m.init_task(NICE_0);
+        t.tier = 0;
+        let over = m.starvation_threshold_ns(0) + 1;
+
+        assert!(
+            !m.starvation_preempt(&t, over, Some(3)),
+            "Bulk rescue spares T0"
+        );
+        assert!(
+            !m.starvation_preempt(&t, over, None),
+            "local-only waiters spare T0"
+        );
+        assert!(
+            m.starvation_preempt(&t, over, Some(0)),
+            "same tier may preempt"
+        );
+        assert!(
+            m.starvation_preempt(&t, m.starvation_ns + 1, Some(3)),
+            "hard ceiling overrides immunity"
+        );
+    }
+
+    #[test]
+    fn test_non_immune_tier_preempted_by_any_waiter() {
+        let m = gaming();
+        let mut t = m.init_task(NICE_0);
+        t.tier = 2;
+        let over = m.starvation_threshold_ns(2) + 1;
+        assert!(m.starvation_preempt(&t, over, Some(3)));
+    }
+
+    #[test]
+    fn test_profile_slices_order_latency_to_throughput() {
+        let slice = |p: Profile, tier: u8| {
+            let m = CakeModel::from_profile(p);
+            (m.quantum_ns * m.multiplier(tier)) >> 10
+        };
+        for tier in 0..4 {
+            assert!(slice(Profile::Latency, tier) < slice(Profile::Gaming, tier));
+            assert!(slice(Profile::Gaming, tier) < slice(Profile::Throughput, tier));
+        }
+        assert!(slice(Profile::Powersave, 3) > slice(Profile::Gaming, 3));
+    }
+
+    #[test]
+    fn test_throughput_immunity_covers_critical_only() {
+        let m = CakeModel::from_profile(Profile::Throughput);
+        let mut t = m.init_task(NICE_0);
+        t.tier = 1;
+        let over = m.starvation_threshold_ns(1) + 1;
+        assert!(m.starvation_preempt(&t, over, Some(3)));
+        t.tier = 0;
+        let over = m.starvation_threshold_ns(0) + 1;
+        assert!(!m.starvation_preempt(&t, over, Some(3)));
+    }
+}
@@ -0,0 +1,182 @@
+// SPDX-License-Identifier: GPL-2.0
+// OTLP module - --otlp-endpoint pushes the BPF counters to an OpenTelemetry collector (feature "otlp")
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use log::{info, warn};
+use opentelemetry::metrics::{Meter, MeterProvider as _};
+use opentelemetry::KeyValue;
+use opentelemetry_otlp::{MetricExporter, WithExportConfig};
+use opentelemetry_sdk::metrics::{PeriodicReader, SdkMeterProvider};
+use opentelemetry_sdk::Resource;
+
+use crate::bpf_skel::types::cake_stats;
+use crate::metrics::{Snapshot, StatsSource};
+use crate::stats::{SELECT_RUNGS, TIER_NAMES};
+
+/// Per-tier counters: (metric name, description, field)
+const TIER_COUNTERS: [(&str, &str, fn(&cake_stats) -> [u64; 4]); 5] = [
+    ("scx_cake.dispatches", "Tasks dispatched", |s| {
+        s.nr_tier_dispatches
+    }),
+    (
+        "scx_cake.starvation_preempts",
+        "Runs cut short for a starving waiter",
+        |s| s.nr_starvation_preempts_tier,
+    ),
+    (
+        "scx_cake.starvation_immune",
+        "Starvation preempts skipped by immunity",
+        |s| s.nr_starvation_immune_tier,
+    ),
+    (
+        "scx_cake.runtime_ns",
+        "CPU time consumed, by the tier it ran in",
+        |s| s.tier_runtime_ns,
+    ),
+    (
+        "scx_cake.migrate_cross_llc",
+        "Runs that landed on another LLC",
+        |s| s.nr_migrate_cross_llc,
+    ),
+];
+
+/// Scheduler-wide counters: (metric name, description, field)
+const COUNTERS: [(&str, &str, fn(&cake_stats) -> u64); 10] = [
+    ("scx_cake.runs", "Runs ended", |s| s.nr_runs),
+    ("scx_cake.wait_ns", "Runnable wait before those runs", |s| {
+        s.wait_ns
+    }),
+    (
+        "scx_cake.smt_spread",
+        "Heavy wakeups moved off a heavy SMT sibling",
+        |s| s.nr_smt_spread,
+    ),
+    (
+        "scx_cake.rebalance_moves",
+        "Tasks moved between LLC DSQs",
+        |s| s.nr_rebalance_moves,
+    ),
+    (
+        "scx_cake.idle_promotions",
+        "Bulk enqueues promoted while the user is idle",
+        |s| s.nr_idle_promotions,
+    ),
+    (
+        "scx_cake.warmup_dispatches",
+        "Dispatches with the warm-up tier override",
+        |s| s.nr_warmup_dispatches,
+    ),
+    (
+        "scx_cake.migrate_local",
+        "Runs on a new CPU in the same LLC",
+        |s| s.nr_migrate_local,
+    ),
+    (
+        "scx_cake.migrate_p_to_e",
+        "Hybrid big → little core moves",
+        |s| s.nr_migrate_p_to_e,
+    ),
+    (
+        "scx_cake.migrate_e_to_p",
+        "Hybrid little → big core moves",
+        |s| s.nr_migrate_e_to_p,
+    ),
+    (
+        "scx_cake.migrate_cross_node",
+        "Runs that landed on another NUMA node",
+        |s| s.nr_migrate_cross_node,
+    ),
+];
+
+/// Meter provider whose periodic reader samples the BPF counters and pushes
+/// them over OTLP/HTTP. The reader thread is the only user of the
+/// StatsSource; shutting the provider down on drop joins it.
+pub struct OtlpExporter {
+    provider: SdkMeterProvider,
+}
+
+impl OtlpExporter {
+    pub fn start(endpoint: &str, source: StatsSource, interval: Duration) -> Result<Self> {
+        let exporter = MetricExporter::builder()
+            .with_http()
+            .with_endpoint(endpoint)
+            .build()
+            .context("Failed to build the OTLP metric exporter")?;
+        let reader = PeriodicReader::builder(exporter)
+            .with_interval(interval)
+            .build();
+        let provider = SdkMeterProvider::builder()
+            .with_reader(reader)
+            .with_resource(Resource::builder().with_service_name("scx_cake").build())
+            .build();
+
+        register(&provider.meter("scx_cake"), Arc::new(Mutex::new(source)));
+        info!(
+            "Exporting metrics to {} every {}s",
+            endpoint,
+            interval.as_secs()
+        );
+        Ok(Self { provider })
+    }
+}
+
+impl Drop for OtlpExporter {
+    fn drop(&mut self) {
+        if let Err(e) = self.provider.shutdown() {
+            warn!("OTLP exporter shutdown: {}", e);
+        }
+    }
+}
+
+/// A poisoned lock only means another callback panicked; the pointers are fine
+fn read(source: &Mutex<StatsSource>) -> Snapshot {
+    source.lock().unwrap_or_else(|e| e.into_inner()).snapshot()
+}
+
+/// Observable instruments: every collection re-reads the per-CPU counters,
+/// so the collector gets cumulative totals and computes its own rates
+fn register(meter: &Meter, source: Arc<Mutex<StatsSource>>) {
+    for (name, desc, field) in TIER_COUNTERS {
+        let src = source.clone();
+        meter
+            .u64_observable_counter(name)
+            .with_description(desc)
+            .with_callback(move |obs| {
+                let values = field(&read(&src).stats);
+                for (tier, v) in TIER_NAMES.iter().zip(values) {
+                    obs.observe(v, &[KeyValue::new("tier", tier.to_lowercase())]);
+                }
+            })
+            .build();
+    }
+
+    for (name, desc, field) in COUNTERS {
+        let src = source.clone();
+        meter
+            .u64_observable_counter(name)
+            .with_description(desc)
+            .with_callback(move |obs| obs.observe(field(&read(&src).stats), &[]))
+            .build();
+    }
+
+    let src = source.clone();
+    meter
+        .u64_observable_counter("scx_cake.select")
+        .with_description("select_cpu placements by rung")
+        .with_callback(move |obs| {
+            let stats = read(&src).stats;
+            for (rung, v) in SELECT_RUNGS.iter().zip(stats.nr_select) {
+                obs.observe(v, &[KeyValue::new("rung", *rung)]);
+            }
+        })
+        .build();
+
+    meter
+        .u64_observable_gauge("scx_cake.user_idle")
+        .with_description("1 while the user is idle (--idle-relax)")
+        .with_callback(move |obs| obs.observe(read(&source).user_idle as u64, &[]))
+        .build();
+}
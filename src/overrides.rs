@@ -0,0 +1,173 @@
+// SPDX-License-Identifier: GPL-2.0
+// Overrides module - --topology-override file replacing detected LLCs, P/E cores and SMT pairs
+
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use log::info;
+use serde::Deserialize;
+
+use crate::check::mask_list;
+use crate::topology::{parse_cpu_list, CpuMask, TopologyInfo};
+
+/// --topology-override file; every key is optional and replaces only
+/// what it names:
+///
+/// ```toml
+/// llc = ["0-7,16-23", "8-15,24-31"]  # one cpulist per LLC
+/// big = "0-7,16-23"                  # P-cores; the rest are E-cores
+/// smt = ["0,16", "1,17"]             # SMT sibling pairs
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FileOverride {
+    llc: Option<Vec<String>>,
+    big: Option<String>,
+    smt: Option<Vec<String>>,
+}
+
+/// Parsed and checked --topology-override
+#[derive(Debug, Default, PartialEq)]
+pub struct TopologyOverride {
+    pub llcs: Option<Vec<CpuMask>>,
+    pub big: Option<CpuMask>,
+    pub smt: Option<Vec<(usize, usize)>>,
+}
+
+impl TopologyOverride {
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read topology override {}", path.display()))?;
+        parse(&text).with_context(|| format!("Invalid topology override {}", path.display()))
+    }
+
+    /// Replace what the file names in `topo`. Every online CPU must be in
+    /// one of the listed LLCs; SMT pairs with an offline CPU are dropped.
+    pub fn apply(&self, topo: &mut TopologyInfo) -> Result<()> {
+        if let Some(llcs) = &self.llcs {
+            let listed = llcs
+                .iter()
+                .fold(CpuMask::default(), |all, &mask| all | mask);
+            let missing = topo.online_mask & !listed;
+            if !missing.is_empty() {
+                bail!(
+                    "topology override: CPUs {} are in no LLC",
+                    mask_list(missing)
+                );
+            }
+            topo.set_llcs(llcs);
+        }
+        if let Some(big) = self.big {
+            topo.set_big(big);
+        }
+        if let Some(smt) = &self.smt {
+            let online = topo.online_mask;
+            let pairs: Vec<(usize, usize)> = smt
+                .iter()
+                .copied()
+                .filter(|&(a, b)| online.test(a) && online.test(b))
+                .collect();
+            topo.set_smt_pairs(&pairs);
+        }
+        info!(
+            "Topology override: {}",
+            [
+                self.llcs.as_ref().map(|l| format!("{} LLC(s)", l.len())),
+                self.big.map(|b| format!("P-cores {}", mask_list(b))),
+                self.smt
+                    .as_ref()
+                    .map(|s| format!("{} SMT pair(s)", s.len())),
+            ]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>()
+            .join(", ")
+        );
+        Ok(())
+    }
+}
+
+fn parse(text: &str) -> Result<TopologyOverride> {
+    let file: FileOverride = toml::from_str(text)?;
+    let mut out = TopologyOverride::default();
+
+    if let Some(lists) = file.llc {
+        let mut seen = CpuMask::default();
+        let mut llcs = Vec::with_capacity(lists.len());
+        for list in &lists {
+            let mask = parse_cpu_list(list).with_context(|| format!("llc \"{}\"", list))?;
+            if !(mask & seen).is_empty() {
+                bail!("llc: CPUs {} are in two LLCs", mask_list(mask & seen));
+            }
+            seen |= mask;
+            llcs.push(mask);
+        }
+        if llcs.is_empty() {
+            bail!("llc: needs at least one cpulist");
+        }
+        out.llcs = Some(llcs);
+    }
+
+    if let Some(list) = file.big {
+        out.big = Some(parse_cpu_list(&list).context("big")?);
+    }
+
+    if let Some(lists) = file.smt {
+        let mut seen = CpuMask::default();
+        let mut pairs = Vec::with_capacity(lists.len());
+        for list in &lists {
+            let mask = parse_cpu_list(list).with_context(|| format!("smt \"{}\"", list))?;
+            let cpus: Vec<usize> = mask.iter().collect();
+            let &[a, b] = cpus.as_slice() else {
+                bail!("smt \"{}\": a pair names exactly two CPUs", list);
+            };
+            if !(mask & seen).is_empty() {
+                bail!("smt: CPUs {} are in two pairs", mask_list(mask & seen));
+            }
+            seen |= mask;
+            pairs.push((a, b));
+        }
+        out.smt = Some(pairs);
+    }
+
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let o = parse(
+            r#"
+            llc = ["0-3,8-11", "4-7,12-15"]
+            big = "0-7"
+            smt = ["0,8", "1-1,9"]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(o.llcs, Some(vec![0x0f0f.into(), 0xf0f0.into()]));
+        assert_eq!(o.big, Some(0xff.into()));
+        assert_eq!(o.smt, Some(vec![(0, 8), (1, 9)]));
+
+        // Keys are independent; an empty file overrides nothing
+        assert_eq!(parse("").unwrap(), TopologyOverride::default());
+        assert_eq!(parse("big = \"2\"").unwrap().big, Some(CpuMask::cpu(2)));
+        // Pairs past the first 64 CPUs (2-socket EPYC)
+        let o = parse("smt = [\"100,228\"]").unwrap();
+        assert_eq!(o.smt, Some(vec![(100, 228)]));
+
+        assert!(
+            parse("llc = [\"0-3\", \"3-7\"]").is_err(),
+            "overlapping LLCs"
+        );
+        assert!(parse("llc = []").is_err());
+        assert!(parse("smt = [\"0,1,2\"]").is_err(), "not a pair");
+        assert!(
+            parse("smt = [\"0,1\", \"1,2\"]").is_err(),
+            "CPU in two pairs"
+        );
+        assert!(parse("cores = 4").is_err(), "unknown key");
+    }
+}
@@ -0,0 +1,147 @@
+// SPDX-License-Identifier: GPL-2.0
+// Partial module - moves threads matching --partial-comm into SCHED_EXT under --partial
+
+use std::collections::HashSet;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use log::{debug, info, warn};
+
+/// Not yet exported by libc
+const SCHED_EXT: libc::c_int = 7;
+
+/// How often /proc is rescanned for new matching threads
+const SCAN_PERIOD: Duration = Duration::from_secs(2);
+
+/// Thread ids under `proc_root` whose comm starts with one of `prefixes`
+fn matching_tids(proc_root: &Path, prefixes: &[String]) -> Vec<i32> {
+    let mut tids = Vec::new();
+    let Ok(procs) = std::fs::read_dir(proc_root) else {
+        return tids;
+    };
+    for proc in procs.flatten() {
+        if proc
+            .file_name()
+            .to_str()
+            .and_then(|s| s.parse::<i32>().ok())
+            .is_none()
+        {
+            continue;
+        }
+        let Ok(tasks) = std::fs::read_dir(proc.path().join("task")) else {
+            continue;
+        };
+        for task in tasks.flatten() {
+            let Some(tid) = task
+                .file_name()
+                .to_str()
+                .and_then(|s| s.parse::<i32>().ok())
+            else {
+                continue;
+            };
+            let Ok(comm) = std::fs::read_to_string(task.path().join("comm")) else {
+                continue;
+            };
+            let comm = comm.trim_end();
+            if prefixes.iter().any(|p| comm.starts_with(p.as_str())) {
+                tids.push(tid);
+            }
+        }
+    }
+    tids
+}
+
+/// Switch a fair-class thread to SCHED_EXT; RT and deadline threads are left alone
+fn switch_to_ext(tid: i32) -> bool {
+    // SAFETY: plain syscalls on a tid; a vanished thread just returns ESRCH
+    unsafe {
+        match libc::sched_getscheduler(tid) {
+            libc::SCHED_OTHER | libc::SCHED_BATCH | libc::SCHED_IDLE => {
+                let param = libc::sched_param { sched_priority: 0 };
+                libc::sched_setscheduler(tid, SCHED_EXT, &param) == 0
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Background thread opting matching threads into cake. The kernel returns
+/// SCHED_EXT threads to the fair class when the scheduler unloads.
+pub struct PartialMatcher {
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl PartialMatcher {
+    pub fn spawn(prefixes: Vec<String>) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let stop_thread = stop.clone();
+        let handle = std::thread::Builder::new()
+            .name("cake-partial".into())
+            .spawn(move || scan(prefixes, stop_thread))
+            .ok();
+        if handle.is_none() {
+            warn!("Failed to start partial-mode matcher thread");
+        }
+        Self { stop, handle }
+    }
+}
+
+impl Drop for PartialMatcher {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(h) = self.handle.take() {
+            let _ = h.join();
+        }
+    }
+}
+
+fn scan(prefixes: Vec<String>, stop: Arc<AtomicBool>) {
+    info!("Partial mode: opting in threads matching {:?}", prefixes);
+    let mut switched: HashSet<i32> = HashSet::new();
+    while !stop.load(Ordering::Relaxed) {
+        let tids = matching_tids(Path::new("/proc"), &prefixes);
+        for &tid in &tids {
+            if !switched.contains(&tid) && switch_to_ext(tid) {
+                debug!("Partial mode: tid {} -> SCHED_EXT", tid);
+                switched.insert(tid);
+            }
+        }
+        // Forget exited threads so a reused tid is checked again
+        let live: HashSet<i32> = tids.into_iter().collect();
+        switched.retain(|tid| live.contains(tid));
+
+        let deadline = Instant::now() + SCAN_PERIOD;
+        while Instant::now() < deadline && !stop.load(Ordering::Relaxed) {
+            std::thread::sleep(Duration::from_millis(100));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matching_tids_by_comm_prefix() {
+        let root = std::env::temp_dir().join(format!("cake-partial-{}", std::process::id()));
+        let task = |pid: &str, tid: &str, comm: &str| {
+            let dir = root.join(pid).join("task").join(tid);
+            std::fs::create_dir_all(&dir).unwrap();
+            std::fs::write(dir.join("comm"), format!("{}\n", comm)).unwrap();
+        };
+        task("100", "100", "game.exe");
+        task("100", "101", "vkd3d_queue");
+        task("200", "200", "make");
+        std::fs::create_dir_all(root.join("self")).unwrap();
+
+        let prefixes = vec!["game".to_string(), "vkd3d".to_string()];
+        let mut tids = matching_tids(&root, &prefixes);
+        tids.sort();
+        std::fs::remove_dir_all(&root).unwrap();
+        assert_eq!(tids, vec![100, 101]);
+    }
+}
@@ -0,0 +1,214 @@
+// SPDX-License-Identifier: GPL-2.0
+// Powersave module - grows and shrinks the --powersave packed CPU set with its utilization
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use log::{debug, info, warn};
+
+use crate::topology::MAX_CPUS;
+
+/// How often the packed CPUs' busy time is sampled from /proc/stat
+const SAMPLE_PERIOD: Duration = Duration::from_millis(100);
+
+/// Samples in a row under half the threshold before the set gives up a
+/// CPU (1s), so a bursty build doesn't flap it every sample
+const SHRINK_AFTER: u32 = 10;
+
+/// The packed set never gets smaller than this
+pub const MIN_WIDTH: usize = 2;
+
+/// Pointer to the BPF pack_width global
+pub struct PackWidth(*mut u32);
+
+// SAFETY: the BSS mmap outlives the controller (PackController joins on
+// drop and is dropped before the skeleton); a u32 volatile store is the
+// only access.
+unsafe impl Send for PackWidth {}
+
+impl PackWidth {
+    /// # Safety
+    /// `ptr` must point into the skeleton's mmap'd BSS and stay valid until
+    /// the PackController holding it is dropped.
+    pub unsafe fn new(ptr: *mut u32) -> Self {
+        Self(ptr)
+    }
+
+    fn set(&self, width: usize) {
+        // SAFETY: see PackWidth::new
+        unsafe { std::ptr::write_volatile(self.0, width as u32) }
+    }
+}
+
+/// Background thread widening the packed set while its CPUs run hot
+pub struct PackController {
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl PackController {
+    /// `order` is the packing order; the BPF side starts on its first
+    /// MIN_WIDTH CPUs. `threshold` is the --powersave busy percentage.
+    pub fn spawn(width: PackWidth, order: Vec<usize>, threshold: u32) -> Self {
+        info!(
+            "Powersave: packing Bulk onto {} of {} CPUs, growing past {}% busy",
+            MIN_WIDTH.min(order.len()),
+            order.len(),
+            threshold
+        );
+        let stop = Arc::new(AtomicBool::new(false));
+        let stop_thread = stop.clone();
+        let handle = std::thread::Builder::new()
+            .name("cake-pack".into())
+            .spawn(move || control(width, order, threshold, stop_thread))
+            .ok();
+        if handle.is_none() {
+            warn!("Failed to start powersave pack thread");
+        }
+        Self { stop, handle }
+    }
+}
+
+impl Drop for PackController {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(h) = self.handle.take() {
+            let _ = h.join();
+        }
+    }
+}
+
+fn control(pack: PackWidth, order: Vec<usize>, threshold: u32, stop: Arc<AtomicBool>) {
+    let max = order.len();
+    let mut width = MIN_WIDTH.min(max);
+    let mut calm = 0;
+    let mut prev = read_proc_stat();
+
+    while !stop.load(Ordering::Relaxed) {
+        std::thread::sleep(SAMPLE_PERIOD);
+        let now = read_proc_stat();
+        let busy = busy_pct(&prev, &now, &order[..width]);
+        prev = now;
+
+        let next = next_width(width, busy, threshold, &mut calm, max);
+        if next != width {
+            debug!(
+                "Powersave: {}% busy on {} CPUs, packing onto {}",
+                busy, width, next
+            );
+            width = next;
+            pack.set(width);
+        }
+    }
+}
+
+/// Cumulative (busy, total) jiffies per CPU
+type CpuTimes = [(u64, u64); MAX_CPUS];
+
+fn read_proc_stat() -> CpuTimes {
+    std::fs::read_to_string("/proc/stat")
+        .map(|text| parse_proc_stat(&text))
+        .unwrap_or([(0, 0); MAX_CPUS])
+}
+
+/// Per-CPU lines of /proc/stat: user nice system idle iowait irq softirq
+/// steal ...; idle and iowait are the only time a CPU isn't busy. Guest
+/// time is already counted in user and nice.
+fn parse_proc_stat(text: &str) -> CpuTimes {
+    let mut times = [(0, 0); MAX_CPUS];
+    for line in text.lines() {
+        let mut fields = line.split_whitespace();
+        let Some(cpu) = fields
+            .next()
+            .and_then(|name| name.strip_prefix("cpu"))
+            .and_then(|n| n.parse::<usize>().ok())
+            .filter(|&cpu| cpu < MAX_CPUS)
+        else {
+            continue;
+        };
+        let ticks: Vec<u64> = fields.take(8).filter_map(|f| f.parse().ok()).collect();
+        let total: u64 = ticks.iter().sum();
+        let idle = ticks.get(3).unwrap_or(&0) + ticks.get(4).unwrap_or(&0);
+        times[cpu] = (total - idle, total);
+    }
+    times
+}
+
+/// Average busy percentage of `cpus` between two samples
+fn busy_pct(prev: &CpuTimes, now: &CpuTimes, cpus: &[usize]) -> u32 {
+    let (busy, total) = cpus.iter().fold((0, 0), |(busy, total), &cpu| {
+        (
+            busy + now[cpu].0.saturating_sub(prev[cpu].0),
+            total + now[cpu].1.saturating_sub(prev[cpu].1),
+        )
+    });
+    match total {
+        0 => 0,
+        _ => (busy * 100 / total) as u32,
+    }
+}
+
+/// One controller step: grow by a CPU when the packed CPUs average more
+/// than `threshold`% busy, shrink by one after SHRINK_AFTER samples in a
+/// row under half of it, never below MIN_WIDTH nor above `max`
+fn next_width(width: usize, busy_pct: u32, threshold: u32, calm: &mut u32, max: usize) -> usize {
+    if busy_pct > threshold {
+        *calm = 0;
+        return (width + 1).min(max);
+    }
+    if busy_pct * 2 >= threshold {
+        *calm = 0;
+        return width;
+    }
+    *calm += 1;
+    if *calm < SHRINK_AFTER {
+        return width;
+    }
+    *calm = 0;
+    width.saturating_sub(1).max(MIN_WIDTH.min(max))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_proc_stat() {
+        let text = "cpu  100 0 50 800 50 0 0 0 0 0\n\
+                    cpu0 60 0 30 400 10 0 0 0 0 0\n\
+                    cpu1 40 0 20 400 40 0 0 0 0 0\n\
+                    intr 12345\n";
+        let times = parse_proc_stat(text);
+        assert_eq!(times[0], (90, 500));
+        assert_eq!(times[1], (60, 500));
+        assert_eq!(times[2], (0, 0), "no such CPU");
+
+        let mut later = times;
+        later[0] = (190, 600);
+        later[1] = (60, 600);
+        assert_eq!(busy_pct(&times, &later, &[0]), 100);
+        assert_eq!(busy_pct(&times, &later, &[0, 1]), 50);
+        assert_eq!(busy_pct(&times, &times, &[0]), 0, "no time passed");
+    }
+
+    #[test]
+    fn test_next_width() {
+        let mut calm = 0;
+        assert_eq!(next_width(2, 95, 80, &mut calm, 8), 3, "hot: grow");
+        assert_eq!(next_width(8, 95, 80, &mut calm, 8), 8, "all CPUs packed");
+        assert_eq!(next_width(3, 60, 80, &mut calm, 8), 3, "in band: hold");
+        for _ in 1..SHRINK_AFTER {
+            assert_eq!(next_width(3, 10, 80, &mut calm, 8), 3);
+        }
+        assert_eq!(next_width(3, 10, 80, &mut calm, 8), 2, "calm: shrink");
+        for _ in 0..SHRINK_AFTER {
+            assert_eq!(next_width(2, 0, 80, &mut calm, 8), MIN_WIDTH);
+        }
+        // A busy sample restarts the calm count
+        calm = SHRINK_AFTER - 1;
+        next_width(4, 50, 80, &mut calm, 8);
+        assert_eq!(next_width(4, 10, 80, &mut calm, 8), 4);
+    }
+}
@@ -0,0 +1,264 @@
+// SPDX-License-Identifier: GPL-2.0
+// Query module - read-only JSON stats/tasks protocol on a unix socket
+
+use std::time::Instant;
+
+use anyhow::{anyhow, bail, Context, Result};
+use clap::ValueEnum;
+use serde_json::{json, Value};
+
+use crate::bpf_skel::types::cake_stats;
+use crate::linesock::{self, LineServer};
+use crate::metrics::{Snapshot, StatsSource};
+use crate::stats::{self, TaskRate, TaskSampler, TopBy, TIER_NAMES, WAIT_PERCENTILES};
+use crate::Tier;
+
+/// Listening socket of the running scheduler. Any local user may connect:
+/// the protocol only reads, and overlays rarely run as root.
+pub const SOCKET_PATH: &str = "/run/scx_cake/query.sock";
+
+/// `tasks` without --top
+const DEFAULT_TOP: usize = 20;
+
+/// One request line: `stats [--tier T]` or `tasks [--top N] [--by K] [--tier T]`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Request {
+    Stats {
+        tier: Option<u8>,
+    },
+    Tasks {
+        top: usize,
+        by: TopBy,
+        tier: Option<u8>,
+    },
+}
+
+fn parse_request(line: &str) -> Result<Request> {
+    let mut words = line.split_whitespace();
+    let what = words.next().ok_or_else(|| anyhow!("empty request"))?;
+    let (mut tier, mut top, mut by) = (None, DEFAULT_TOP, TopBy::Cpu);
+    while let Some(flag) = words.next() {
+        let value = words
+            .next()
+            .ok_or_else(|| anyhow!("{} needs a value", flag))?;
+        match (what, flag) {
+            (_, "--tier") => {
+                tier = Some(Tier::from_str(value, true).map_err(|e| anyhow!(e))? as u8)
+            }
+            ("tasks", "--top") => top = value.parse().context("--top")?,
+            ("tasks", "--by") => by = TopBy::from_str(value, true).map_err(|e| anyhow!(e))?,
+            _ => bail!("unknown option '{}' for '{}'", flag, what),
+        }
+    }
+    match what {
+        "stats" => Ok(Request::Stats { tier }),
+        "tasks" => Ok(Request::Tasks { top, by, tier }),
+        _ => bail!("expected 'stats' or 'tasks', got '{}'", what),
+    }
+}
+
+/// Counters since the scheduler loaded (monotonic: diff two replies for
+/// rates), per tier or for one tier
+fn stats_reply(snap: &Snapshot, tier: Option<u8>, uptime_s: f64) -> Value {
+    let s = &snap.stats;
+    let tiers: Vec<Value> = (0..TIER_NAMES.len())
+        .filter(|&t| tier.is_none_or(|tier| tier as usize == t))
+        .map(|t| {
+            let pcts: serde_json::Map<String, Value> = WAIT_PERCENTILES
+                .iter()
+                .map(|&p| {
+                    (
+                        format!("wait_p{}_us", p as u32),
+                        json!(stats::wait_percentile(&snap.waits[t], p)),
+                    )
+                })
+                .collect();
+            let mut v = json!({
+                "tier": TIER_NAMES[t],
+                "dispatches": s.nr_tier_dispatches[t],
+                "runtime_ns": s.tier_runtime_ns[t],
+                "runs": snap.waits[t].iter().sum::<u64>(),
+                "starvation_preempts": s.nr_starvation_preempts_tier[t],
+                "cross_llc_migrations": s.nr_migrate_cross_llc[t],
+            });
+            v.as_object_mut().unwrap().extend(pcts);
+            v
+        })
+        .collect();
+    json!({
+        "uptime_s": uptime_s,
+        "user_idle": snap.user_idle,
+        "runs": s.nr_runs,
+        "wait_ns": s.wait_ns,
+        "wait_avg_us": s.wait_ns as f64 / s.nr_runs.max(1) as f64 / 1000.0,
+        "tiers": tiers,
+    })
+}
+
+/// The counters a full `stats` reply carries, back as a cake_stats (the
+/// rest stay 0), and the user-idle flag; None for anything else
+pub fn stats_from_reply(reply: &Value) -> Option<(cake_stats, bool)> {
+    let mut s = cake_stats::default();
+    s.nr_runs = reply["runs"].as_u64()?;
+    s.wait_ns = reply["wait_ns"].as_u64()?;
+    let tiers = reply["tiers"].as_array()?;
+    if tiers.len() != TIER_NAMES.len() {
+        return None;
+    }
+    for (t, tier) in tiers.iter().enumerate() {
+        s.nr_tier_dispatches[t] = tier["dispatches"].as_u64()?;
+        s.tier_runtime_ns[t] = tier["runtime_ns"].as_u64()?;
+        s.nr_starvation_preempts_tier[t] = tier["starvation_preempts"].as_u64()?;
+        s.nr_migrate_cross_llc[t] = tier["cross_llc_migrations"].as_u64()?;
+    }
+    Some((s, reply["user_idle"].as_bool()?))
+}
+
+/// Busiest threads over `window_s`, the time since the previous `tasks`
+fn tasks_reply(top: &[&TaskRate], window_s: f64) -> Value {
+    let tasks: Vec<Value> = top
+        .iter()
+        .map(|t| {
+            json!({
+                "pid": t.pid,
+                "comm": t.comm,
+                "tier": TIER_NAMES[(t.tier & 3) as usize],
+                "cpu_pct": t.cpu_pct,
+                "wait_ms_per_s": t.wait_ms_per_s,
+                "runs_per_s": t.runs_per_s,
+                "avg_runtime_us": t.avg_runtime_us,
+            })
+        })
+        .collect();
+    json!({ "window_s": window_s, "tasks": tasks })
+}
+
+/// Background thread serving SOCKET_PATH; removes the socket when dropped
+pub struct QueryServer(LineServer);
+
+impl QueryServer {
+    pub fn spawn(source: StatsSource) -> Result<Self> {
+        LineServer::spawn(SOCKET_PATH, 0o666, "cake-query", move || {
+            let started = Instant::now();
+            // Opened here: the link stays on this thread. Missing without the
+            // ctl pin, in which case `tasks` reports the error.
+            let mut sampler = TaskSampler::open_pinned();
+            if let Ok(sampler) = &mut sampler {
+                // Prime the baselines so the first `tasks` has a window
+                let _ = sampler.sample();
+            }
+            let mut last_tasks = Instant::now();
+
+            move |line: &str| {
+                let reply = parse_request(line).and_then(|req| match req {
+                    Request::Stats { tier } => Ok(stats_reply(
+                        &source.snapshot(),
+                        tier,
+                        started.elapsed().as_secs_f64(),
+                    )),
+                    Request::Tasks { top, by, tier } => {
+                        let sampler = sampler.as_mut().map_err(|e| anyhow!("{:#}", e))?;
+                        let procs = sampler.sample()?;
+                        let window_s = last_tasks.elapsed().as_secs_f64();
+                        last_tasks = Instant::now();
+                        Ok(tasks_reply(
+                            &stats::top_tasks(&procs, by, tier, top),
+                            window_s,
+                        ))
+                    }
+                });
+                reply
+                    .unwrap_or_else(|e| json!({ "error": format!("{:#}", e) }))
+                    .to_string()
+            }
+        })
+        .map(Self)
+    }
+}
+
+/// Send one request line to the running scheduler and return its reply
+pub fn request(line: &str) -> Result<Value> {
+    let reply = linesock::request(SOCKET_PATH, line)?;
+    let value: Value = serde_json::from_str(&reply).context("unexpected reply")?;
+    if let Some(err) = value.get("error").and_then(Value::as_str) {
+        bail!("{}", err);
+    }
+    Ok(value)
+}
+
+/// `scx_cake query REQUEST...`: send one request and print the JSON reply
+pub fn run_query(request: &[String]) -> Result<()> {
+    let value = self::request(&request.join(" "))?;
+    println!("{}", serde_json::to_string_pretty(&value)?);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_request() {
+        assert_eq!(
+            parse_request("stats\n").unwrap(),
+            Request::Stats { tier: None }
+        );
+        assert_eq!(
+            parse_request("stats --tier Frame").unwrap(),
+            Request::Stats { tier: Some(2) }
+        );
+        assert_eq!(
+            parse_request("tasks --top 10 --by wait").unwrap(),
+            Request::Tasks {
+                top: 10,
+                by: TopBy::Wait,
+                tier: None
+            }
+        );
+        for bad in [
+            "",
+            "reload",
+            "stats --top 3",
+            "tasks --top",
+            "tasks --tier Gaming",
+        ] {
+            assert!(parse_request(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_stats_reply() {
+        let mut snap = Snapshot {
+            stats: cake_stats::default(),
+            waits: Default::default(),
+            user_idle: false,
+            at: Instant::now(),
+        };
+        snap.stats.nr_runs = 4;
+        snap.stats.wait_ns = 8_000;
+        snap.stats.nr_tier_dispatches = [1, 2, 3, 4];
+        snap.waits[3][5] = 4;
+
+        let all = stats_reply(&snap, None, 1.5);
+        assert_eq!(all["wait_avg_us"], 2.0);
+        assert_eq!(all["tiers"].as_array().unwrap().len(), 4);
+
+        let bulk = stats_reply(&snap, Some(3), 1.5);
+        let tiers = bulk["tiers"].as_array().unwrap();
+        assert_eq!(tiers.len(), 1);
+        assert_eq!(tiers[0]["tier"], "Bulk");
+        assert_eq!(tiers[0]["dispatches"], 4);
+        assert_eq!(tiers[0]["runs"], 4);
+        assert!(tiers[0]["wait_p99_us"].as_f64().unwrap() > 16.0);
+
+        snap.stats.tier_runtime_ns = [5, 6, 7, 8];
+        snap.user_idle = true;
+        let (back, idle) = stats_from_reply(&stats_reply(&snap, None, 1.5)).unwrap();
+        assert!(idle);
+        assert_eq!((back.nr_runs, back.wait_ns), (4, 8_000));
+        assert_eq!(back.nr_tier_dispatches, [1, 2, 3, 4]);
+        assert_eq!(back.tier_runtime_ns, [5, 6, 7, 8]);
+        // A one-tier reply can't stand in for the whole scheduler
+        assert!(stats_from_reply(&bulk).is_none());
+    }
+}
@@ -0,0 +1,176 @@
+// SPDX-License-Identifier: GPL-2.0
+// Replay module - a recorded --log-stats CSV (and --events file) for `monitor --replay`,
+// or rows sampled live for `monitor --tui`
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+
+use crate::bpf_skel::types::cake_stats;
+use crate::csvlog::{counter_columns, counters, stats_from_counters};
+use crate::events::Event;
+
+/// One --log-stats row: the counter movement over one interval
+#[derive(Debug, Clone)]
+pub struct Frame {
+    /// Unix seconds at the end of the interval
+    pub ts: u64,
+    pub interval_s: f64,
+    /// In counter_columns() order
+    counters: Vec<u64>,
+    pub user_idle: bool,
+}
+
+impl Frame {
+    /// The movement from `prev` to `cur`, like a --log-stats row (a
+    /// counter that went backwards counts from zero)
+    pub fn between(
+        ts: u64,
+        interval_s: f64,
+        cur: &cake_stats,
+        prev: &cake_stats,
+        user_idle: bool,
+    ) -> Self {
+        Self {
+            ts,
+            interval_s,
+            counters: counters(cur)
+                .iter()
+                .zip(counters(prev))
+                .map(|(&now, before)| if now >= before { now - before } else { now })
+                .collect(),
+            user_idle,
+        }
+    }
+
+    pub fn delta(&self) -> cake_stats {
+        stats_from_counters(&self.counters)
+    }
+}
+
+/// A recorded session: stats rows, and the events logged alongside them
+#[derive(Debug)]
+pub struct Session {
+    /// File name, for the header
+    pub name: String,
+    pub frames: Vec<Frame>,
+    /// Oldest first
+    pub events: Vec<Event>,
+}
+
+impl Session {
+    pub fn load(stats: &Path, events: Option<&Path>) -> Result<Self> {
+        let text = std::fs::read_to_string(stats)
+            .with_context(|| format!("Failed to read {}", stats.display()))?;
+        let frames = parse_csv(&text).with_context(|| format!("{}", stats.display()))?;
+        let mut events = match events {
+            Some(path) => std::fs::read_to_string(path)
+                .with_context(|| format!("Failed to read {}", path.display()))?
+                .lines()
+                .filter_map(Event::from_json)
+                .collect(),
+            None => Vec::new(),
+        };
+        events.sort_by(|a: &Event, b| a.time.total_cmp(&b.time));
+        Ok(Self {
+            name: stats.file_name().map_or_else(
+                || stats.display().to_string(),
+                |n| n.to_string_lossy().into(),
+            ),
+            frames,
+            events,
+        })
+    }
+
+    /// Counters summed from the first row through `pos`
+    pub fn totals(&self, pos: usize) -> cake_stats {
+        let mut sum = vec![0u64; counter_columns().len()];
+        for frame in &self.frames[..=pos.min(self.frames.len() - 1)] {
+            for (s, &c) in sum.iter_mut().zip(&frame.counters) {
+                *s = s.saturating_add(c);
+            }
+        }
+        stats_from_counters(&sum)
+    }
+
+    /// Events logged up to the end of row `pos`
+    pub fn events_until(&self, pos: usize) -> &[Event] {
+        let end = self.frames[pos].ts as f64;
+        &self.events[..self.events.partition_point(|e| e.time <= end)]
+    }
+}
+
+/// Rows of a --log-stats file, matched to the columns by header name so an
+/// older log with fewer counters still loads (the missing ones read 0)
+fn parse_csv(text: &str) -> Result<Vec<Frame>> {
+    let mut lines = text.lines().filter(|l| !l.trim().is_empty());
+    let header: HashMap<&str, usize> = lines
+        .next()
+        .context("empty stats log")?
+        .split(',')
+        .enumerate()
+        .map(|(i, name)| (name.trim(), i))
+        .collect();
+    let (Some(&ts_col), Some(&interval_col)) = (header.get("timestamp"), header.get("interval_s"))
+    else {
+        bail!("not a --log-stats CSV (no timestamp,interval_s header)");
+    };
+    let cols: Vec<Option<usize>> = counter_columns()
+        .iter()
+        .map(|name| header.get(name.as_str()).copied())
+        .collect();
+    let idle_col = header.get("user_idle").copied();
+
+    let mut frames = Vec::new();
+    for (n, line) in lines.enumerate() {
+        let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+        let field = |col: usize| fields.get(col).copied().unwrap_or("");
+        let bad = || format!("bad row {}: {}", n + 2, line);
+        frames.push(Frame {
+            ts: field(ts_col).parse::<u64>().with_context(bad)?,
+            interval_s: field(interval_col).parse::<f64>().with_context(bad)?,
+            counters: cols
+                .iter()
+                .map(|col| col.map_or(Ok(0), |c| field(c).parse::<u64>()))
+                .collect::<Result<Vec<_>, _>>()
+                .with_context(bad)?,
+            user_idle: idle_col.is_some_and(|c| field(c) == "1"),
+        });
+    }
+    if frames.is_empty() {
+        bail!("no stats rows to replay");
+    }
+    Ok(frames)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_csv_by_header_name() {
+        // An older log: fewer counters, in a different order
+        let text = "timestamp,interval_s,runs,dispatch_critical,user_idle\n\
+                    1700000000,1.000,40,7,0\n\
+                    1700000001,1.000,60,3,1\n";
+        let frames = parse_csv(text).unwrap();
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[1].ts, 1_700_000_001);
+        assert!(frames[1].user_idle);
+        let delta = frames[0].delta();
+        assert_eq!((delta.nr_runs, delta.nr_tier_dispatches[0]), (40, 7));
+
+        let session = Session {
+            name: "s.csv".into(),
+            frames,
+            events: Vec::new(),
+        };
+        let totals = session.totals(1);
+        assert_eq!((totals.nr_runs, totals.nr_tier_dispatches[0]), (100, 10));
+
+        assert!(parse_csv("a,b\n1,2\n").is_err());
+        assert!(parse_csv("timestamp,interval_s\n").is_err());
+        assert!(parse_csv("timestamp,interval_s\nx,1\n").is_err());
+    }
+}
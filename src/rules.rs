@@ -0,0 +1,216 @@
+// SPDX-License-Identifier: GPL-2.0
+// Rules module - --tier-rule process-name to tier pins, installed into the tier_rules BPF map,
+// and manual per-TID pins from the TUI (tier_pins map)
+
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use anyhow::{bail, Context, Result};
+use clap::ValueEnum;
+use libbpf_rs::{MapCore, MapFlags};
+
+use crate::bpf_skel::BpfSkel;
+use crate::Tier;
+
+/// Max rules the BPF map holds (CAKE_MAX_TIER_RULES)
+pub const MAX_TIER_RULES: usize = 32;
+
+/// Max manual TID pins the BPF map holds (CAKE_MAX_TIER_PINS)
+pub const MAX_TIER_PINS: usize = 256;
+
+/// comm is TASK_COMM_LEN (16) including the NUL
+const MAX_COMM_LEN: usize = 15;
+
+/// Pin tasks by name: comm=NAME:TIER, NAME may end in * for a prefix match
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TierRule {
+    comm: String,
+    prefix: bool,
+    tier: Tier,
+}
+
+impl FromStr for TierRule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, tier) = s
+            .strip_prefix("comm=")
+            .and_then(|rest| rest.rsplit_once(':'))
+            .ok_or_else(|| format!("expected comm=NAME:TIER, got '{}'", s))?;
+        let (comm, prefix) = match name.strip_suffix('*') {
+            Some(stem) => (stem, true),
+            None => (name, false),
+        };
+        if comm.is_empty() || comm.len() > MAX_COMM_LEN || comm.contains('*') {
+            return Err(format!(
+                "name in '{}' must be 1-{} bytes with an optional trailing *",
+                s, MAX_COMM_LEN
+            ));
+        }
+        Ok(Self {
+            comm: comm.to_string(),
+            prefix,
+            tier: Tier::from_str(tier.trim(), true)?,
+        })
+    }
+}
+
+impl std::fmt::Display for TierRule {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let glob = if self.prefix { "*" } else { "" };
+        write!(f, "{}{} -> {:?}", self.comm, glob, self.tier)
+    }
+}
+
+impl TierRule {
+    /// struct cake_tier_rule: comm[16], len, prefix, tier, _pad[5]
+    fn encode(&self) -> [u8; 24] {
+        let mut rec = [0u8; 24];
+        rec[..self.comm.len()].copy_from_slice(self.comm.as_bytes());
+        rec[16] = self.comm.len() as u8;
+        rec[17] = self.prefix as u8;
+        rec[18] = self.tier as u8;
+        rec
+    }
+}
+
+/// Write `rules` into the loaded skeleton's tier_rules map (first match wins)
+pub fn install(skel: &BpfSkel, rules: &[TierRule]) -> Result<()> {
+    if rules.len() > MAX_TIER_RULES {
+        bail!("at most {} --tier-rule entries", MAX_TIER_RULES);
+    }
+    for (i, rule) in rules.iter().enumerate() {
+        skel.maps
+            .tier_rules
+            .update(&(i as u32).to_ne_bytes(), &rule.encode(), MapFlags::ANY)
+            .with_context(|| format!("Failed to install tier rule {:?}", rule))?;
+    }
+    Ok(())
+}
+
+/// Next tier in the TUI pin cycle: unpinned → Critical → … → Bulk → unpinned
+pub fn next_pin(cur: Option<Tier>) -> Option<Tier> {
+    match cur {
+        None => Some(Tier::Critical),
+        Some(Tier::Critical) => Some(Tier::Interactive),
+        Some(Tier::Interactive) => Some(Tier::Frame),
+        Some(Tier::Frame) => Some(Tier::Bulk),
+        Some(Tier::Bulk) => None,
+    }
+}
+
+/// Manual TID → tier pins, mirrored into the tier_pins BPF map
+#[derive(Debug, Default)]
+pub struct TierPins {
+    pins: HashMap<u32, Tier>,
+}
+
+impl TierPins {
+    pub fn get(&self, pid: u32) -> Option<Tier> {
+        self.pins.get(&pid).copied()
+    }
+
+    /// Forget pins whose thread exited: cake_exit_task deletes the map
+    /// entry so a reused TID starts unpinned, and the count BPF gates the
+    /// stop-path lookup on follows
+    pub fn prune(&mut self, skel: &mut BpfSkel) {
+        let before = self.pins.len();
+        self.pins.retain(|pid, _| {
+            matches!(
+                skel.maps
+                    .tier_pins
+                    .lookup(&pid.to_ne_bytes(), MapFlags::ANY),
+                Ok(Some(_))
+            )
+        });
+        if self.pins.len() != before {
+            if let Some(bss) = skel.maps.bss_data.as_mut() {
+                bss.nr_tier_pins = self.pins.len() as u32;
+            }
+        }
+    }
+
+    /// Pin every TID in `pids` to `tier`, or unpin them with None
+    pub fn set(&mut self, skel: &mut BpfSkel, pids: &[u32], tier: Option<Tier>) -> Result<()> {
+        self.prune(skel);
+        if let Some(tier) = tier {
+            let new = pids.iter().filter(|p| !self.pins.contains_key(p)).count();
+            if self.pins.len() + new > MAX_TIER_PINS {
+                bail!("at most {} pinned threads", MAX_TIER_PINS);
+            }
+            for &pid in pids {
+                skel.maps
+                    .tier_pins
+                    .update(&pid.to_ne_bytes(), &[tier as u8], MapFlags::ANY)
+                    .with_context(|| format!("Failed to pin {} to {:?}", pid, tier))?;
+                self.pins.insert(pid, tier);
+            }
+        } else {
+            for &pid in pids {
+                if self.pins.remove(&pid).is_some() {
+                    // Already gone if the BPF side never saw it; nothing to undo
+                    let _ = skel.maps.tier_pins.delete(&pid.to_ne_bytes());
+                }
+            }
+        }
+        if let Some(bss) = skel.maps.bss_data.as_mut() {
+            bss.nr_tier_pins = self.pins.len() as u32;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_encode() {
+        let rule: TierRule = "comm=cc1*:bulk".parse().unwrap();
+        let rec = rule.encode();
+        assert_eq!(&rec[..4], b"cc1\0");
+        assert_eq!(rec[16..19], [3, 1, Tier::Bulk as u8]);
+        assert_eq!(rule.to_string(), "cc1* -> Bulk");
+
+        let exact: TierRule = "comm=pipewire:Critical".parse().unwrap();
+        assert!(!exact.prefix);
+        assert_eq!(exact.tier, Tier::Critical);
+        // The name itself may contain ':' — the tier follows the last one
+        let colon: TierRule = "comm=a:b:frame".parse().unwrap();
+        assert_eq!(colon.comm, "a:b");
+    }
+
+    #[test]
+    fn test_rejects_bad_rules() {
+        for s in [
+            "pipewire:critical",
+            "comm=:critical",
+            "comm=*:bulk",
+            "comm=a*b:bulk",
+            "comm=this_name_is_too_long:bulk",
+            "comm=cc1:batch",
+        ] {
+            assert!(s.parse::<TierRule>().is_err(), "{}", s);
+        }
+    }
+
+    #[test]
+    fn test_next_pin_cycles_back_to_unpinned() {
+        let mut pin = None;
+        let mut seen = Vec::new();
+        for _ in 0..5 {
+            pin = next_pin(pin);
+            seen.push(pin);
+        }
+        assert_eq!(
+            seen,
+            [
+                Some(Tier::Critical),
+                Some(Tier::Interactive),
+                Some(Tier::Frame),
+                Some(Tier::Bulk),
+                None
+            ]
+        );
+    }
+}
@@ -0,0 +1,263 @@
+// SPDX-License-Identifier: GPL-2.0
+// Schedule module - time-of-day profile rules and the `ctl profile` override
+
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, bail, Context, Result};
+use clap::ValueEnum;
+use log::warn;
+
+use crate::Profile;
+
+/// Written by `scx_cake ctl profile`, read by the daemon's schedule watcher
+pub const OVERRIDE_PATH: &str = "/run/scx_cake/profile-override";
+
+/// How often the watcher re-evaluates rules and the override file
+const CHECK_PERIOD: Duration = Duration::from_secs(5);
+
+/// `PROFILE@HH:MM-HH:MM` — active from start (inclusive) to end (exclusive),
+/// wrapping past midnight when end < start
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rule {
+    pub profile: Profile,
+    pub start: u16,
+    pub end: u16,
+}
+
+fn parse_hhmm(s: &str) -> Result<u16> {
+    let (h, m) = s
+        .split_once(':')
+        .ok_or_else(|| anyhow!("expected HH:MM, got '{}'", s))?;
+    let (h, m): (u16, u16) = (h.parse()?, m.parse()?);
+    if h > 24 || m > 59 || (h == 24 && m != 0) {
+        bail!("time out of range: '{}'", s);
+    }
+    Ok(h * 60 + m)
+}
+
+impl FromStr for Rule {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (profile, range) = s
+            .split_once(['@', ' ', '\t'])
+            .ok_or_else(|| anyhow!("expected PROFILE@HH:MM-HH:MM, got '{}'", s))?;
+        let (start, end) = range
+            .trim()
+            .split_once(['-', '–'])
+            .ok_or_else(|| anyhow!("expected HH:MM-HH:MM, got '{}'", range))?;
+        Ok(Self {
+            profile: Profile::from_str(profile.trim(), true).map_err(|e| anyhow!(e))?,
+            start: parse_hhmm(start.trim())?,
+            end: parse_hhmm(end.trim())?,
+        })
+    }
+}
+
+impl Rule {
+    fn contains(&self, minute: u16) -> bool {
+        if self.start <= self.end {
+            (self.start..self.end).contains(&minute)
+        } else {
+            minute >= self.start || minute < self.end
+        }
+    }
+}
+
+/// CLI rules followed by rules from a file (one per line, '#' comments)
+pub fn load_rules(cli: &[Rule], file: Option<&Path>) -> Result<Vec<Rule>> {
+    let mut rules = cli.to_vec();
+    if let Some(path) = file {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        for (n, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            rules.push(
+                line.parse()
+                    .with_context(|| format!("{}:{}", path.display(), n + 1))?,
+            );
+        }
+    }
+    Ok(rules)
+}
+
+/// First rule covering `minute` wins; otherwise the --profile baseline
+pub fn active_profile(rules: &[Rule], base: Profile, minute: u16) -> Profile {
+    rules
+        .iter()
+        .find(|r| r.contains(minute))
+        .map(|r| r.profile)
+        .unwrap_or(base)
+}
+
+/// Minutes since local midnight
+fn local_minute() -> u16 {
+    // SAFETY: time/localtime_r only write the provided tm
+    unsafe {
+        let t = libc::time(std::ptr::null_mut());
+        let mut tm: libc::tm = std::mem::zeroed();
+        libc::localtime_r(&t, &mut tm);
+        (tm.tm_hour * 60 + tm.tm_min) as u16
+    }
+}
+
+/// Manual override from `ctl profile` (None = follow the schedule)
+pub fn read_override() -> Option<Profile> {
+    let text = std::fs::read_to_string(OVERRIDE_PATH).ok()?;
+    Profile::from_str(text.trim(), true).ok()
+}
+
+/// Set or clear the manual override
+pub fn write_override(profile: Option<Profile>) -> Result<()> {
+    match profile {
+        Some(p) => {
+            std::fs::create_dir_all(Path::new(OVERRIDE_PATH).parent().unwrap())?;
+            let name = p.to_possible_value().unwrap();
+            std::fs::write(OVERRIDE_PATH, name.get_name())
+                .with_context(|| format!("Failed to write {}", OVERRIDE_PATH))
+        }
+        None => match std::fs::remove_file(OVERRIDE_PATH) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        },
+    }
+}
+
+/// Profile the daemon should be running right now
+pub fn desired_profile(rules: &[Rule], base: Profile) -> Profile {
+    read_override().unwrap_or_else(|| active_profile(rules, base, local_minute()))
+}
+
+/// Watches the clock and override file; stops the running scheduler (via the
+/// shutdown flag) when a different profile is due
+pub struct ScheduleWatcher {
+    stop: Arc<AtomicBool>,
+    next: Arc<Mutex<Option<Profile>>>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl ScheduleWatcher {
+    pub fn spawn(
+        rules: Vec<Rule>,
+        base: Profile,
+        current: Profile,
+        shutdown: Arc<AtomicBool>,
+    ) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let next = Arc::new(Mutex::new(None));
+        let (stop_t, next_t) = (stop.clone(), next.clone());
+
+        let handle = std::thread::Builder::new()
+            .name("cake-schedule".into())
+            .spawn(move || {
+                let running =
+                    || !stop_t.load(Ordering::Relaxed) && !shutdown.load(Ordering::Relaxed);
+                let mut next_check = Instant::now();
+                while running() {
+                    if Instant::now() >= next_check {
+                        let want = desired_profile(&rules, base);
+                        if want != current {
+                            *next_t.lock().unwrap() = Some(want);
+                            shutdown.store(true, Ordering::Relaxed);
+                            break;
+                        }
+                        next_check = Instant::now() + CHECK_PERIOD;
+                    }
+                    // Short naps so finish() doesn't stall shutdown
+                    std::thread::sleep(Duration::from_millis(200));
+                }
+            })
+            .map_err(|e| warn!("Failed to start schedule watcher: {}", e))
+            .ok();
+
+        Self { stop, next, handle }
+    }
+
+    /// Stop the watcher; Some(profile) if it requested the switch
+    pub fn finish(mut self) -> Option<Profile> {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(h) = self.handle.take() {
+            let _ = h.join();
+        }
+        self.next.lock().unwrap().take()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(s: &str) -> Rule {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_parse_rule_forms() {
+        let r = rule("legacy@01:00-07:00");
+        assert_eq!(
+            r,
+            Rule {
+                profile: Profile::Legacy,
+                start: 60,
+                end: 420
+            }
+        );
+        assert_eq!(rule("Esports 18:30–23:00").start, 18 * 60 + 30);
+        assert!("legacy@25:00-07:00".parse::<Rule>().is_err());
+        assert!("turbo@01:00-02:00".parse::<Rule>().is_err());
+        assert!("legacy".parse::<Rule>().is_err());
+    }
+
+    #[test]
+    fn test_active_profile_window_and_fallback() {
+        let rules = [rule("legacy@01:00-07:00")];
+        assert_eq!(active_profile(&rules, Profile::Gaming, 60), Profile::Legacy);
+        assert_eq!(
+            active_profile(&rules, Profile::Gaming, 419),
+            Profile::Legacy
+        );
+        assert_eq!(
+            active_profile(&rules, Profile::Gaming, 420),
+            Profile::Gaming
+        );
+        assert_eq!(active_profile(&rules, Profile::Gaming, 0), Profile::Gaming);
+    }
+
+    #[test]
+    fn test_rule_wraps_midnight() {
+        let rules = [rule("legacy@22:00-06:00")];
+        assert_eq!(
+            active_profile(&rules, Profile::Gaming, 23 * 60),
+            Profile::Legacy
+        );
+        assert_eq!(
+            active_profile(&rules, Profile::Gaming, 5 * 60),
+            Profile::Legacy
+        );
+        assert_eq!(
+            active_profile(&rules, Profile::Gaming, 12 * 60),
+            Profile::Gaming
+        );
+    }
+
+    #[test]
+    fn test_first_matching_rule_wins() {
+        let rules = [rule("esports@19:00-23:00"), rule("legacy@00:00-24:00")];
+        assert_eq!(
+            active_profile(&rules, Profile::Gaming, 20 * 60),
+            Profile::Esports
+        );
+        assert_eq!(
+            active_profile(&rules, Profile::Gaming, 10 * 60),
+            Profile::Legacy
+        );
+    }
+}
@@ -1,6 +1,20 @@
 // SPDX-License-Identifier: GPL-2.0
 // Statistics module for scx_cake - utilities for reading/formatting scheduler stats from BPF maps
 
+use std::collections::HashMap;
+use std::io::Read;
+use std::mem::size_of;
+use std::time::Instant;
+
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use libbpf_rs::{Iter, Link, MapCore, MapFlags};
+use serde::{Deserialize, Serialize};
+
+use crate::bpf_intf::cake_task_rec;
+use crate::bpf_skel::types::{cake_dsq_depth, cake_stats};
+use crate::bpf_skel::BpfSkel;
+
 /// Priority tier names (4-tier system classified by avg_runtime)
 pub const TIER_NAMES: [&str; 4] = [
     "Critical",    // T0: <100µs
@@ -8,3 +22,1656 @@ pub const TIER_NAMES: [&str; 4] = [
     "Frame",       // T2: <8ms
     "Bulk",        // T3: ≥8ms
 ];
+
+/// select_cpu outcomes in nr_select order (enum cake_select_rung)
+pub const SELECT_RUNGS: [&str; 9] = [
+    "sync",    // Waker's CPU (SCX_WAKE_SYNC)
+    "prev",    // Idle prev_cpu
+    "sibling", // prev_cpu's SMT sibling
+    "cluster", // Another core sharing prev_cpu's L2 (E-core module)
+    "llc",     // Another CPU in prev_cpu's LLC
+    "node",    // Another LLC on prev_cpu's NUMA node
+    "big",     // Big core in another LLC (hybrid)
+    "other",   // Any other idle CPU
+    "queued",  // Nothing idle: left for enqueue
+];
+
+/// "prev 61.2% | llc 20.0% | ..." — each select_cpu rung's share of wakeups
+pub fn format_select_outcomes(stats: &cake_stats) -> String {
+    let total: u64 = stats.nr_select.iter().sum();
+    SELECT_RUNGS
+        .iter()
+        .zip(stats.nr_select)
+        .map(|(name, n)| match total {
+            0 => format!("{} 0%", name),
+            t => format!("{} {:.1}%", name, n as f64 * 100.0 / t as f64),
+        })
+        .collect::<Vec<_>>()
+        .join(" | ")
+}
+
+/// Preempt matrix rows: the waiting tier, or "Local" for a waiter already
+/// in the CPU's local DSQ (its tier isn't visible to the tick)
+pub const PREEMPT_SOURCES: [&str; 5] = ["Critical", "Interactive", "Frame", "Bulk", "Local"];
+
+/// Starvation preempts as a waiter × victim table (rows: who waited,
+/// columns: whose run was cut short)
+pub fn format_preempt_matrix(stats: &cake_stats) -> String {
+    let mut out = format!("{:14}", "Waiter\\Victim");
+    for name in TIER_NAMES {
+        out.push_str(&format!(" {:>11}", name));
+    }
+    out.push('\n');
+    for (name, row) in PREEMPT_SOURCES.iter().zip(stats.preempt_matrix) {
+        out.push_str(&format!("{:14}", name));
+        for n in row {
+            out.push_str(&format!(" {:>11}", n));
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// Mean capped-tier queue length seen by dispatch passes that hit the cap
+pub fn cap_queue_len(stats: &cake_stats, tier: usize) -> f64 {
+    match stats.nr_cap_deferrals[tier] {
+        0 => 0.0,
+        n => stats.nr_cap_queued[tier] as f64 / n as f64,
+    }
+}
+
+/// Sum the per-CPU BSS stats array into a single snapshot
+pub fn aggregate_stats(skel: &BpfSkel) -> cake_stats {
+    match &skel.maps.bss_data {
+        Some(bss) => sum_stats(&bss.global_stats),
+        None => Default::default(),
+    }
+}
+
+/// Apply `op(acc, s)` to every counter of `acc`, field by field
+fn combine(acc: &mut cake_stats, s: &cake_stats, op: fn(u64, u64) -> u64) {
+    let f = |a: &mut u64, b: u64| *a = op(*a, b);
+    f(&mut acc.nr_new_flow_dispatches, s.nr_new_flow_dispatches);
+    f(&mut acc.nr_old_flow_dispatches, s.nr_old_flow_dispatches);
+    f(&mut acc.nr_smt_spread, s.nr_smt_spread);
+    f(&mut acc.nr_smt_colocated, s.nr_smt_colocated);
+    f(&mut acc.nr_rebalance_passes, s.nr_rebalance_passes);
+    f(&mut acc.nr_rebalance_moves, s.nr_rebalance_moves);
+    f(&mut acc.nr_warmup_launches, s.nr_warmup_launches);
+    f(&mut acc.nr_warmup_dispatches, s.nr_warmup_dispatches);
+    f(&mut acc.nr_idle_promotions, s.nr_idle_promotions);
+    f(&mut acc.nr_runs, s.nr_runs);
+    f(&mut acc.wait_ns, s.wait_ns);
+    f(&mut acc.nr_migrate_local, s.nr_migrate_local);
+    f(&mut acc.nr_migrate_p_to_e, s.nr_migrate_p_to_e);
+    f(&mut acc.nr_migrate_e_to_p, s.nr_migrate_e_to_p);
+    f(&mut acc.nr_migrate_cross_node, s.nr_migrate_cross_node);
+
+    for i in 0..TIER_NAMES.len() {
+        f(&mut acc.nr_tier_dispatches[i], s.nr_tier_dispatches[i]);
+        f(
+            &mut acc.nr_starvation_preempts_tier[i],
+            s.nr_starvation_preempts_tier[i],
+        );
+        f(
+            &mut acc.nr_starvation_immune_tier[i],
+            s.nr_starvation_immune_tier[i],
+        );
+        f(&mut acc.nr_cap_deferrals[i], s.nr_cap_deferrals[i]);
+        f(&mut acc.nr_cap_queued[i], s.nr_cap_queued[i]);
+        f(&mut acc.tier_runtime_ns[i], s.tier_runtime_ns[i]);
+        f(&mut acc.nr_migrate_cross_llc[i], s.nr_migrate_cross_llc[i]);
+    }
+    for (a, n) in acc.nr_select.iter_mut().zip(s.nr_select) {
+        f(a, n);
+    }
+    for (row, src) in acc.preempt_matrix.iter_mut().zip(s.preempt_matrix) {
+        for (a, n) in row.iter_mut().zip(src) {
+            f(a, n);
+        }
+    }
+}
+
+/// Sum per-CPU stats entries into one
+pub fn sum_stats(per_cpu: &[cake_stats]) -> cake_stats {
+    let mut total: cake_stats = Default::default();
+    for s in per_cpu {
+        combine(&mut total, s, u64::wrapping_add);
+    }
+    total
+}
+
+/// Counter movement since `base`, field by field (never negative)
+pub fn diff_stats(cur: &cake_stats, base: &cake_stats) -> cake_stats {
+    let mut out = *cur;
+    combine(&mut out, base, u64::saturating_sub);
+    out
+}
+
+/// Stats reset by epoch. Zeroing the BPF counters from userspace races the
+/// per-CPU increments (a store can land between a CPU's load and its
+/// write-back), so the counters only ever grow; a reset starts a new epoch
+/// by recording them, and "since reset" totals are read against that. Every
+/// interval consumer (rates, --log-stats, history, exporters) keeps seeing
+/// monotonic counters.
+#[derive(Debug, Clone)]
+pub struct StatsEpoch {
+    /// Resets so far (0 = counting since start)
+    pub epoch: u64,
+    pub since: Instant,
+    per_cpu: Vec<cake_stats>,
+    total: cake_stats,
+    waits: WaitHist,
+}
+
+impl Default for StatsEpoch {
+    fn default() -> Self {
+        Self {
+            epoch: 0,
+            since: Instant::now(),
+            per_cpu: Vec::new(),
+            total: Default::default(),
+            waits: [[0; WAIT_BUCKETS]; 4],
+        }
+    }
+}
+
+impl StatsEpoch {
+    /// Start a new epoch at these counter values
+    pub fn advance(&mut self, per_cpu: &[cake_stats], waits: WaitHist) {
+        self.epoch += 1;
+        self.since = Instant::now();
+        self.per_cpu = per_cpu.to_vec();
+        self.total = sum_stats(per_cpu);
+        self.waits = waits;
+    }
+
+    /// Start a new epoch at the scheduler's current counters (TUI 'r', SIGUSR2)
+    pub fn reset(&mut self, skel: &BpfSkel) {
+        if let Some(bss) = &skel.maps.bss_data {
+            self.advance(&bss.global_stats, wait_histograms(skel));
+        }
+    }
+
+    /// Summed counters since the epoch began
+    pub fn since(&self, total: &cake_stats) -> cake_stats {
+        diff_stats(total, &self.total)
+    }
+
+    /// Per-CPU counters since the epoch began
+    pub fn per_cpu(&self, per_cpu: &[cake_stats]) -> Vec<cake_stats> {
+        per_cpu
+            .iter()
+            .enumerate()
+            .map(|(i, s)| match self.per_cpu.get(i) {
+                Some(base) => diff_stats(s, base),
+                None => *s,
+            })
+            .collect()
+    }
+
+    /// Wait histograms since the epoch began
+    pub fn waits(&self, cur: &WaitHist) -> WaitHist {
+        wait_hist_delta(cur, &self.waits)
+    }
+
+    /// The scheduler's summed counters since the epoch began
+    pub fn totals(&self, skel: &BpfSkel) -> cake_stats {
+        self.since(&aggregate_stats(skel))
+    }
+}
+
+/// Mean runnable wait before a run, in µs
+pub fn avg_wait_us(stats: &cake_stats) -> f64 {
+    match stats.nr_runs {
+        0 => 0.0,
+        n => stats.wait_ns as f64 / n as f64 / 1000.0,
+    }
+}
+
+/// Percent of all CPUs' time each tier ran for between two snapshots
+pub fn cpu_share(cur: &cake_stats, prev: &cake_stats, secs: f64, nr_cpus: usize) -> [f64; 4] {
+    let capacity = secs.max(0.001) * 1e9 * nr_cpus.max(1) as f64;
+    std::array::from_fn(|i| {
+        cur.tier_runtime_ns[i].saturating_sub(prev.tier_runtime_ns[i]) as f64 * 100.0 / capacity
+    })
+}
+
+/// Each tier's percent of the CPU time cake has scheduled so far
+pub fn runtime_split(stats: &cake_stats) -> [f64; 4] {
+    let total: u64 = stats.tier_runtime_ns.iter().sum();
+    std::array::from_fn(|i| match total {
+        0 => 0.0,
+        t => stats.tier_runtime_ns[i] as f64 * 100.0 / t as f64,
+    })
+}
+
+/// One CPU's (or one LLC's) share of the per-CPU stats
+#[derive(Debug, Clone, PartialEq)]
+pub struct CpuLoad {
+    /// "cpu 3" or "llc 1"
+    pub label: String,
+    pub llc: u8,
+    pub dispatches: u64,
+    pub preempts: u64,
+    pub runs: u64,
+    pub avg_wait_us: f64,
+    /// Mean local DSQ depth over the last interval (summed for an LLC)
+    pub queued: f64,
+    /// cpufreq current frequency, MHz (mean for an LLC; None without cpufreq)
+    pub freq_mhz: Option<u32>,
+}
+
+impl CpuLoad {
+    fn new(label: String, llc: u8, s: &cake_stats, queued: f64, freq_mhz: Option<u32>) -> Self {
+        Self {
+            label,
+            llc,
+            dispatches: s.nr_tier_dispatches.iter().sum(),
+            preempts: s.nr_starvation_preempts_tier.iter().sum(),
+            runs: s.nr_runs,
+            avg_wait_us: avg_wait_us(s),
+            queued,
+            freq_mhz,
+        }
+    }
+}
+
+/// Per-CPU rows, then one total per LLC — a starved CCD shows up as an LLC
+/// with long waits next to one with few dispatches
+pub fn cpu_breakdown(
+    per_cpu: &[cake_stats],
+    cpu_llc: &[u8],
+    depth: &[f64],
+    freq_mhz: &[Option<u32>],
+) -> Vec<CpuLoad> {
+    let queued = |cpu: usize| depth.get(cpu).copied().unwrap_or(0.0);
+    let freq = |cpu: usize| freq_mhz.get(cpu).copied().flatten();
+    let mut rows: Vec<CpuLoad> = per_cpu
+        .iter()
+        .zip(cpu_llc)
+        .enumerate()
+        .map(|(cpu, (s, &llc))| {
+            CpuLoad::new(format!("cpu {}", cpu), llc, s, queued(cpu), freq(cpu))
+        })
+        .collect();
+
+    let mut llcs: Vec<u8> = cpu_llc[..per_cpu.len().min(cpu_llc.len())].to_vec();
+    llcs.sort_unstable();
+    llcs.dedup();
+    if llcs.len() > 1 {
+        for llc in llcs {
+            let members: Vec<cake_stats> = per_cpu
+                .iter()
+                .zip(cpu_llc)
+                .filter(|&(_, &l)| l == llc)
+                .map(|(s, _)| *s)
+                .collect();
+            let llc_queued = (0..per_cpu.len())
+                .filter(|&cpu| cpu_llc[cpu] == llc)
+                .map(queued)
+                .sum();
+            let freqs: Vec<u32> = (0..per_cpu.len())
+                .filter(|&cpu| cpu_llc[cpu] == llc)
+                .filter_map(freq)
+                .collect();
+            let llc_freq = match freqs.len() {
+                0 => None,
+                n => Some(freqs.iter().sum::<u32>() / n as u32),
+            };
+            rows.push(CpuLoad::new(
+                format!("llc {}", llc),
+                llc,
+                &sum_stats(&members),
+                llc_queued,
+                llc_freq,
+            ));
+        }
+    }
+    rows
+}
+
+/// Current frequency of each CPU from cpufreq, MHz. None where the CPU has
+/// no cpufreq policy (VMs, some ARM boards) or the read fails.
+pub fn cpu_freq_mhz(nr_cpus: usize) -> Vec<Option<u32>> {
+    (0..nr_cpus)
+        .map(|cpu| {
+            let path = format!(
+                "/sys/devices/system/cpu/cpu{}/cpufreq/scaling_cur_freq",
+                cpu
+            );
+            parse_khz(&std::fs::read_to_string(path).ok()?)
+        })
+        .collect()
+}
+
+/// scaling_cur_freq (kHz) to MHz
+fn parse_khz(text: &str) -> Option<u32> {
+    text.trim().parse::<u32>().ok().map(|khz| khz / 1000)
+}
+
+/// One LLC (CCD), or one NUMA node, over the last tick
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LlcLoad {
+    /// LLC index, or node index from node_breakdown()
+    pub llc: u8,
+    pub nr_cpus: usize,
+    /// Mean busy % of its CPUs
+    pub busy_pct: f64,
+    pub dispatches_s: f64,
+    /// Share of all dispatches, %
+    pub dispatch_pct: f64,
+    pub runs_s: f64,
+    pub avg_wait_us: f64,
+    /// Runs that arrived from another LLC, per second
+    pub cross_llc_in_s: f64,
+    /// Runs that arrived from another NUMA node, per second
+    pub cross_node_in_s: f64,
+}
+
+/// Per-NUMA-node rates: llc_breakdown() grouped by `cpu_node` instead
+pub fn node_breakdown(
+    cur: &[cake_stats],
+    prev: &[cake_stats],
+    cpu_node: &[u8],
+    busy: &[f64],
+    secs: f64,
+) -> Vec<LlcLoad> {
+    llc_breakdown(cur, prev, cpu_node, busy, secs)
+}
+
+/// Per-LLC rates between two per-CPU snapshots, for checking how evenly
+/// work spreads across the CCDs of a dual-CCD part
+pub fn llc_breakdown(
+    cur: &[cake_stats],
+    prev: &[cake_stats],
+    cpu_llc: &[u8],
+    busy: &[f64],
+    secs: f64,
+) -> Vec<LlcLoad> {
+    let n = cur.len().min(cpu_llc.len());
+    let mut llcs: Vec<u8> = cpu_llc[..n].to_vec();
+    llcs.sort_unstable();
+    llcs.dedup();
+    let delta: Vec<cake_stats> = (0..n)
+        .map(|cpu| prev.get(cpu).map_or(cur[cpu], |p| diff_stats(&cur[cpu], p)))
+        .collect();
+    let all_dispatches: u64 = delta
+        .iter()
+        .map(|s| s.nr_tier_dispatches.iter().sum::<u64>())
+        .sum();
+    llcs.into_iter()
+        .map(|llc| {
+            let cpus: Vec<usize> = (0..n).filter(|&cpu| cpu_llc[cpu] == llc).collect();
+            let members: Vec<cake_stats> = cpus.iter().map(|&cpu| delta[cpu]).collect();
+            let s = sum_stats(&members);
+            let dispatches: u64 = s.nr_tier_dispatches.iter().sum();
+            LlcLoad {
+                llc,
+                nr_cpus: cpus.len(),
+                busy_pct: cpus
+                    .iter()
+                    .map(|&cpu| busy.get(cpu).copied().unwrap_or(0.0))
+                    .sum::<f64>()
+                    / cpus.len().max(1) as f64,
+                dispatches_s: per_sec(dispatches, 0, secs),
+                dispatch_pct: dispatches as f64 * 100.0 / all_dispatches.max(1) as f64,
+                runs_s: per_sec(s.nr_runs, 0, secs),
+                avg_wait_us: avg_wait_us(&s),
+                cross_llc_in_s: per_sec(s.nr_migrate_cross_llc.iter().sum(), 0, secs),
+                cross_node_in_s: per_sec(s.nr_migrate_cross_node, 0, secs),
+            }
+        })
+        .collect()
+}
+
+/// SCX_ECODE_ACT_RESTART: set in a UEI exit code that wants a reload
+const ECODE_ACT_RESTART: i64 = 1 << 48;
+
+/// BPF-side state for the TUI status line
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Health {
+    /// The BPF scheduler's exit reason and message, once it has exited
+    pub exited: Option<String>,
+    /// The exit asks the loader to load cake again (CPU hotplug)
+    pub restart: bool,
+    /// /sys/kernel/sched_ext/state and root/ops, e.g. "enabled" and "cake"
+    pub state: String,
+    pub ops: String,
+    /// Tasks in the ext class right now (cake_enable minus cake_disable)
+    pub nr_tasks: u64,
+}
+
+impl Health {
+    pub fn read(skel: &BpfSkel) -> Self {
+        let sysfs = |name: &str| {
+            std::fs::read_to_string(format!("/sys/kernel/sched_ext/{}", name))
+                .map_or_else(|_| "?".to_string(), |s| s.trim().to_string())
+        };
+        let exit = match scx_utils::uei_exited!(skel, uei) {
+            true => skel.maps.data_data.as_ref().map(|data| &data.uei),
+            false => None,
+        };
+        Self {
+            exited: exit.map(|uei| exit_text(&uei.reason, &uei.msg)),
+            restart: exit.is_some_and(|uei| uei.exit_code & ECODE_ACT_RESTART != 0),
+            state: sysfs("state"),
+            ops: sysfs("root/ops"),
+            nr_tasks: skel.maps.bss_data.as_ref().map_or(0, |bss| bss.nr_tasks),
+        }
+    }
+
+    /// The kernel is running cake's struct_ops: not exited, sched_ext
+    /// enabled with cake as the root scheduler
+    pub fn attached(&self) -> bool {
+        self.exited.is_none() && self.state == "enabled" && self.ops.starts_with("cake")
+    }
+}
+
+/// UEI reason and message (NUL-terminated C strings) as one line
+fn exit_text(reason: &[std::ffi::c_char], msg: &[std::ffi::c_char]) -> String {
+    let text = |chars: &[std::ffi::c_char]| -> String {
+        let bytes: Vec<u8> = chars
+            .iter()
+            .take_while(|&&c| c != 0)
+            .map(|&c| c as u8)
+            .collect();
+        String::from_utf8_lossy(&bytes).trim().to_string()
+    };
+    let (reason, msg) = (text(reason), text(msg));
+    match (reason.is_empty(), msg.is_empty()) {
+        (true, _) => "unknown reason".to_string(),
+        (false, true) => reason,
+        (false, false) => format!("{} ({})", reason, msg.lines().next().unwrap_or("")),
+    }
+}
+
+/// CPU time each CPU spent running cake tasks (all tiers), cumulative
+pub fn cpu_runtime_ns(per_cpu: &[cake_stats]) -> Vec<u64> {
+    per_cpu
+        .iter()
+        .map(|s| s.tier_runtime_ns.iter().sum())
+        .collect()
+}
+
+/// Percent busy per CPU between two cpu_runtime_ns reads (a CPU missing
+/// from `prev` reads as idle)
+pub fn cpu_busy_pct(cur: &[u64], prev: &[u64], secs: f64) -> Vec<f64> {
+    cur.iter()
+        .enumerate()
+        .map(|(cpu, &ns)| {
+            let ns0 = prev.get(cpu).copied().unwrap_or(ns);
+            (ns.saturating_sub(ns0) as f64 * 100.0 / (secs.max(0.001) * 1e9)).min(100.0)
+        })
+        .collect()
+}
+
+/// Latest dsq_depth gauges (zeroed without a BSS map)
+pub fn dsq_depth(skel: &BpfSkel) -> cake_dsq_depth {
+    skel.maps
+        .bss_data
+        .as_ref()
+        .map(|bss| bss.dsq_depth)
+        .unwrap_or_default()
+}
+
+/// Mean run queue depth between two dsq_depth reads: per tier across the
+/// LLC DSQs and per CPU in its local DSQ. Without a sample in between
+/// (sampler not armed yet) the latest sample stands in.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct QueueDepth {
+    pub tier: [f64; 4],
+    pub cpu: Vec<f64>,
+}
+
+impl QueueDepth {
+    pub fn between(cur: &cake_dsq_depth, prev: &cake_dsq_depth, nr_cpus: usize) -> Self {
+        let n = cur.nr_samples.saturating_sub(prev.nr_samples);
+        let mean = |sum: u64, prev_sum: u64, latest: u32| match n {
+            0 => latest as f64,
+            n => sum.saturating_sub(prev_sum) as f64 / n as f64,
+        };
+        let nr_cpus = nr_cpus.min(cur.cpu.len());
+        Self {
+            tier: std::array::from_fn(|t| mean(cur.tier_sum[t], prev.tier_sum[t], cur.tier[t])),
+            cpu: (0..nr_cpus)
+                .map(|c| mean(cur.cpu_sum[c], prev.cpu_sum[c], cur.cpu[c]))
+                .collect(),
+        }
+    }
+}
+
+/// log2 wait histogram buckets per tier (CAKE_WAIT_BUCKETS)
+pub const WAIT_BUCKETS: usize = 32;
+
+/// Per-tier wait histograms: [tier][bucket] run counts
+pub type WaitHist = [[u64; WAIT_BUCKETS]; 4];
+
+/// Sum the per-CPU wait_hist map (tiers that can't be read stay zero)
+pub fn wait_histograms(skel: &BpfSkel) -> WaitHist {
+    read_wait_histograms(&skel.maps.wait_hist)
+}
+
+/// wait_hist through any handle, e.g. a MapHandle held by a reader thread
+pub fn read_wait_histograms(map: &impl MapCore) -> WaitHist {
+    let mut hist = [[0; WAIT_BUCKETS]; 4];
+    for (tier, h) in hist.iter_mut().enumerate() {
+        let key = (tier as u32).to_ne_bytes();
+        let Ok(Some(per_cpu)) = map.lookup_percpu(&key, MapFlags::ANY) else {
+            continue;
+        };
+        for val in per_cpu {
+            for (b, word) in val.chunks_exact(8).take(WAIT_BUCKETS).enumerate() {
+                h[b] += u64::from_ne_bytes(word.try_into().unwrap());
+            }
+        }
+    }
+    hist
+}
+
+/// Histogram movement between two reads
+pub fn wait_hist_delta(cur: &WaitHist, prev: &WaitHist) -> WaitHist {
+    let mut d = *cur;
+    for (t, tier) in d.iter_mut().enumerate() {
+        for (b, n) in tier.iter_mut().enumerate() {
+            *n = n.saturating_sub(prev[t][b]);
+        }
+    }
+    d
+}
+
+/// Wait in µs below which `pct`% of the runs fall, interpolated linearly
+/// inside the log2 bucket (0 without samples)
+pub fn wait_percentile(hist: &[u64; WAIT_BUCKETS], pct: f64) -> f64 {
+    let total: u64 = hist.iter().sum();
+    let rank = total as f64 * pct / 100.0;
+    let mut seen = 0.0;
+    for (b, &n) in hist.iter().enumerate() {
+        if n == 0 {
+            continue;
+        }
+        if seen + n as f64 >= rank {
+            let (lo, hi) = match b {
+                0 => (0.0, 1.0),
+                _ => ((1u64 << (b - 1)) as f64, (1u64 << b) as f64),
+            };
+            return lo + (hi - lo) * (rank - seen) / n as f64;
+        }
+        seen += n as f64;
+    }
+    0.0
+}
+
+/// Upper bound in µs of the highest bucket any tier's runs landed in
+/// (0 without samples)
+pub fn wait_max_us(hist: &WaitHist) -> u64 {
+    hist.iter().map(tier_wait_max_us).max().unwrap_or(0)
+}
+
+/// wait_max_us for one tier's histogram
+pub fn tier_wait_max_us(hist: &[u64; WAIT_BUCKETS]) -> u64 {
+    hist.iter().rposition(|&n| n > 0).map_or(0, |b| 1 << b)
+}
+
+/// Mean wait in µs of one tier's histogram, each run counted at the middle
+/// of its bucket (0 without samples)
+pub fn wait_mean_us(hist: &[u64; WAIT_BUCKETS]) -> f64 {
+    let total: u64 = hist.iter().sum();
+    if total == 0 {
+        return 0.0;
+    }
+    let sum: f64 = hist
+        .iter()
+        .enumerate()
+        .map(|(b, &n)| match b {
+            0 => n as f64 * 0.5,
+            _ => n as f64 * 0.75 * (1u64 << b) as f64,
+        })
+        .sum();
+    sum / total as f64
+}
+
+/// Percentiles shown in the TUI and --ui json
+pub const WAIT_PERCENTILES: [f64; 3] = [50.0, 95.0, 99.0];
+
+fn format_us(us: u64) -> String {
+    match us {
+        0..=999 => format!("{}µs", us),
+        1000..=999_999 => format!("{:.1}ms", us as f64 / 1000.0),
+        _ => format!("{:.1}s", us as f64 / 1_000_000.0),
+    }
+}
+
+/// Wait range a histogram bucket covers, e.g. "<1µs", "64-128µs", "≥1073.7s"
+pub fn wait_bucket_label(b: usize) -> String {
+    match b {
+        0 => "<1µs".to_string(),
+        _ if b >= WAIT_BUCKETS - 1 => format!("≥{}", format_us(1 << (WAIT_BUCKETS - 2))),
+        _ => format!("{}-{}", format_us(1 << (b - 1)), format_us(1 << b)),
+    }
+}
+
+/// Bucket's upper bound in µs, short enough for a bar chart axis:
+/// "<1", "512", "2k", "1M"
+pub fn wait_bucket_tick(b: usize) -> String {
+    let short = |us: u64| match us {
+        0..=999 => us.to_string(),
+        1000..=999_999 => format!("{}k", us / 1000),
+        _ => format!("{}M", us / 1_000_000),
+    };
+    match b {
+        0 => "<1".to_string(),
+        _ if b >= WAIT_BUCKETS - 1 => format!("≥{}", short(1 << (WAIT_BUCKETS - 2))),
+        _ => short(1 << b),
+    }
+}
+
+/// Per-tier wait table for the shutdown summary: runs, percentiles and the
+/// highest bucket any run landed in
+pub fn format_wait_summary(waits: &WaitHist) -> String {
+    let mut out =
+        String::from("Wait           Runs         p50         p95         p99    Max bucket\n");
+    for (name, hist) in TIER_NAMES.iter().zip(waits) {
+        let pcts: Vec<String> = WAIT_PERCENTILES
+            .iter()
+            .map(|&p| format!("{:>10.1}µs", wait_percentile(hist, p)))
+            .collect();
+        let max = hist
+            .iter()
+            .rposition(|&n| n > 0)
+            .map_or("—".to_string(), wait_bucket_label);
+        out.push_str(&format!(
+            "{:12} {:>6} {}    {}\n",
+            name,
+            hist.iter().sum::<u64>(),
+            pcts.concat(),
+            max
+        ));
+    }
+    out
+}
+
+fn per_sec(cur: u64, prev: u64, secs: f64) -> f64 {
+    cur.saturating_sub(prev) as f64 / secs.max(0.001)
+}
+
+/// Per-second movement between two snapshots — what the TUI and JSON show
+/// next to totals that stop meaning much after an hour of uptime
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Rates {
+    pub dispatches: [f64; 4],
+    pub preempts: [f64; 4],
+    pub immune: [f64; 4],
+    pub cap_deferrals: [f64; 4],
+    pub promotions: f64,
+    /// Runs that landed on a new CPU: same LLC, another LLC by tier, P↔E
+    pub migrate_local: f64,
+    pub migrate_cross_llc: [f64; 4],
+    pub migrate_p_to_e: f64,
+    pub migrate_e_to_p: f64,
+    pub migrate_cross_node: f64,
+}
+
+impl Rates {
+    pub fn between(cur: &cake_stats, prev: &cake_stats, secs: f64) -> Self {
+        let tiers = |c: &[u64; 4], p: &[u64; 4]| std::array::from_fn(|i| per_sec(c[i], p[i], secs));
+        Self {
+            dispatches: tiers(&cur.nr_tier_dispatches, &prev.nr_tier_dispatches),
+            preempts: tiers(
+                &cur.nr_starvation_preempts_tier,
+                &prev.nr_starvation_preempts_tier,
+            ),
+            immune: tiers(
+                &cur.nr_starvation_immune_tier,
+                &prev.nr_starvation_immune_tier,
+            ),
+            cap_deferrals: tiers(&cur.nr_cap_deferrals, &prev.nr_cap_deferrals),
+            promotions: per_sec(cur.nr_idle_promotions, prev.nr_idle_promotions, secs),
+            migrate_local: per_sec(cur.nr_migrate_local, prev.nr_migrate_local, secs),
+            migrate_cross_llc: tiers(&cur.nr_migrate_cross_llc, &prev.nr_migrate_cross_llc),
+            migrate_p_to_e: per_sec(cur.nr_migrate_p_to_e, prev.nr_migrate_p_to_e, secs),
+            migrate_e_to_p: per_sec(cur.nr_migrate_e_to_p, prev.nr_migrate_e_to_p, secs),
+            migrate_cross_node: per_sec(
+                cur.nr_migrate_cross_node,
+                prev.nr_migrate_cross_node,
+                secs,
+            ),
+        }
+    }
+}
+
+/// One-line interval summary for `--ui plain` (rates per second)
+pub fn format_plain(cur: &cake_stats, prev: &cake_stats, secs: f64, user_idle: bool) -> String {
+    let tiers: Vec<String> = TIER_NAMES
+        .iter()
+        .enumerate()
+        .map(|(i, name)| {
+            format!(
+                "{} {:.0}",
+                name,
+                per_sec(cur.nr_tier_dispatches[i], prev.nr_tier_dispatches[i], secs)
+            )
+        })
+        .collect();
+    let sum = |f: fn(&cake_stats) -> u64| per_sec(f(cur), f(prev), secs);
+    format!(
+        "dispatch/s: {} | starvation/s {:.1} (immune {:.1}) | smt spread/s {:.1} | \
+         rebalance moves/s {:.1} | warmup/s {:.1} | migrations/s {:.1} in-LLC, {:.1} cross-LLC, \
+         {:.1} cross-node | user {}",
+        tiers.join(", "),
+        sum(|s| s.nr_starvation_preempts_tier.iter().sum()),
+        sum(|s| s.nr_starvation_immune_tier.iter().sum()),
+        sum(|s| s.nr_smt_spread),
+        sum(|s| s.nr_rebalance_moves),
+        sum(|s| s.nr_warmup_dispatches),
+        sum(|s| s.nr_migrate_local),
+        sum(|s| s.nr_migrate_cross_llc.iter().sum()),
+        sum(|s| s.nr_migrate_cross_node),
+        if user_idle { "idle" } else { "active" }
+    )
+}
+
+/// One JSON object per interval for `--ui json` (raw interval deltas;
+/// `waits` is the interval's histogram movement, reported as percentiles)
+pub fn format_json(
+    cur: &cake_stats,
+    prev: &cake_stats,
+    waits: &WaitHist,
+    depth: &QueueDepth,
+    secs: f64,
+    nr_cpus: usize,
+    user_idle: bool,
+) -> String {
+    let d = |c: u64, p: u64| c.saturating_sub(p);
+    let tiers = |c: &[u64; 4], p: &[u64; 4]| {
+        let v: Vec<String> = (0..4).map(|i| d(c[i], p[i]).to_string()).collect();
+        format!("[{}]", v.join(","))
+    };
+    let rates = Rates::between(cur, prev, secs);
+    let share = cpu_share(cur, prev, secs, nr_cpus);
+    let per_tier = |v: &[f64; 4]| {
+        let v: Vec<String> = v.iter().map(|r| format!("{:.1}", r)).collect();
+        format!("[{}]", v.join(","))
+    };
+    let percentiles: Vec<String> = WAIT_PERCENTILES
+        .iter()
+        .map(|&pct| {
+            let v: Vec<String> = waits
+                .iter()
+                .map(|h| format!("{:.1}", wait_percentile(h, pct)))
+                .collect();
+            format!("\"wait_p{}_us\":[{}],", pct, v.join(","))
+        })
+        .collect();
+    format!(
+        "{{\"interval_s\":{:.3},\"tier_dispatches\":{},\"new_flow\":{},\"old_flow\":{},\
+         \"starvation_preempts\":{},\"starvation_immune\":{},\"cap_deferrals\":{},\
+         \"smt_spread\":{},\"smt_colocated\":{},\"rebalance_moves\":{},\
+         \"warmup_dispatches\":{},\"idle_promotions\":{},\"dispatches_per_s\":{},\
+         \"preempts_per_s\":{},\"promotions_per_s\":{:.1},\"tier_runtime_ns\":{},\
+         \"cpu_share_pct\":{},\"migrate_local\":{},\"migrate_cross_llc\":{},\
+         \"migrate_p_to_e\":{},\"migrate_e_to_p\":{},\"migrate_cross_node\":{},\
+         \"select_outcomes\":{{{}}},\"queue_depth\":{},\"local_queue_depth\":[{}],\
+         \"preempt_matrix\":[{}],{}\
+         \"user_idle\":{}}}",
+        secs,
+        tiers(&cur.nr_tier_dispatches, &prev.nr_tier_dispatches),
+        d(cur.nr_new_flow_dispatches, prev.nr_new_flow_dispatches),
+        d(cur.nr_old_flow_dispatches, prev.nr_old_flow_dispatches),
+        tiers(
+            &cur.nr_starvation_preempts_tier,
+            &prev.nr_starvation_preempts_tier
+        ),
+        tiers(
+            &cur.nr_starvation_immune_tier,
+            &prev.nr_starvation_immune_tier
+        ),
+        tiers(&cur.nr_cap_deferrals, &prev.nr_cap_deferrals),
+        d(cur.nr_smt_spread, prev.nr_smt_spread),
+        d(cur.nr_smt_colocated, prev.nr_smt_colocated),
+        d(cur.nr_rebalance_moves, prev.nr_rebalance_moves),
+        d(cur.nr_warmup_dispatches, prev.nr_warmup_dispatches),
+        d(cur.nr_idle_promotions, prev.nr_idle_promotions),
+        per_tier(&rates.dispatches),
+        per_tier(&rates.preempts),
+        rates.promotions,
+        tiers(&cur.tier_runtime_ns, &prev.tier_runtime_ns),
+        per_tier(&share),
+        d(cur.nr_migrate_local, prev.nr_migrate_local),
+        tiers(&cur.nr_migrate_cross_llc, &prev.nr_migrate_cross_llc),
+        d(cur.nr_migrate_p_to_e, prev.nr_migrate_p_to_e),
+        d(cur.nr_migrate_e_to_p, prev.nr_migrate_e_to_p),
+        d(cur.nr_migrate_cross_node, prev.nr_migrate_cross_node),
+        SELECT_RUNGS
+            .iter()
+            .enumerate()
+            .map(|(i, name)| format!("\"{}\":{}", name, d(cur.nr_select[i], prev.nr_select[i])))
+            .collect::<Vec<_>>()
+            .join(","),
+        per_tier(&depth.tier),
+        depth
+            .cpu
+            .iter()
+            .map(|q| format!("{:.1}", q))
+            .collect::<Vec<_>>()
+            .join(","),
+        (0..PREEMPT_SOURCES.len())
+            .map(|src| tiers(&cur.preempt_matrix[src], &prev.preempt_matrix[src]))
+            .collect::<Vec<_>>()
+            .join(","),
+        percentiles.concat(),
+        user_idle
+    )
+}
+
+/// One task as seen by the dump_tasks iterator
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TaskStat {
+    pub pid: u32,
+    pub tgid: u32,
+    pub cgid: u64,
+    pub comm: String,
+    pub tier: u8,
+    pub cpu: u32,
+    pub avg_runtime_us: u16,
+    pub runtime_ns: u64,
+    pub wait_ns: u64,
+    pub nr_runs: u64,
+}
+
+impl TaskStat {
+    fn from_rec(rec: &cake_task_rec) -> Self {
+        let comm: Vec<u8> = rec
+            .comm
+            .iter()
+            .take_while(|&&c| c != 0)
+            .map(|&c| c as u8)
+            .collect();
+        Self {
+            pid: rec.pid,
+            tgid: rec.tgid,
+            cgid: rec.cgid,
+            comm: String::from_utf8_lossy(&comm).into_owned(),
+            tier: rec.tier,
+            cpu: rec.cpu,
+            avg_runtime_us: rec.avg_runtime_us,
+            runtime_ns: rec.runtime_ns,
+            wait_ns: rec.wait_ns,
+            nr_runs: rec.nr_runs,
+        }
+    }
+}
+
+/// A task's activity over the last sampling interval
+#[derive(Debug, Clone, PartialEq)]
+pub struct TaskRate {
+    pub pid: u32,
+    pub cgid: u64,
+    pub comm: String,
+    pub tier: u8,
+    /// CPU it last ran on
+    pub cpu: u32,
+    pub avg_runtime_us: u16,
+    /// Runtime as % of one CPU
+    pub cpu_pct: f64,
+    /// Runnable-but-waiting time per second (ms)
+    pub wait_ms_per_s: f64,
+    /// Times run on a CPU per second
+    pub runs_per_s: f64,
+}
+
+impl TaskRate {
+    /// Mean runnable wait before each run, in µs (0 without runs)
+    pub fn avg_wait_us(&self) -> f64 {
+        if self.runs_per_s > 0.0 {
+            self.wait_ms_per_s * 1000.0 / self.runs_per_s
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Per-process totals with the threads that make them up
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProcessStat {
+    pub tgid: u32,
+    pub comm: String,
+    pub cpu_pct: f64,
+    pub wait_ms_per_s: f64,
+    pub tier_counts: [u32; 4],
+    /// Busiest first
+    pub threads: Vec<TaskRate>,
+}
+
+/// Turn two iterator snapshots into per-process rates, busiest process first.
+/// Tasks missing from `prev` (new since the last sample) count from zero.
+pub fn group_by_process(
+    tasks: &[TaskStat],
+    prev: &HashMap<u32, TaskStat>,
+    interval_ns: u64,
+) -> Vec<ProcessStat> {
+    let interval = interval_ns.max(1) as f64;
+    let mut procs: HashMap<u32, ProcessStat> = HashMap::new();
+
+    for t in tasks {
+        let (run0, wait0, runs0) = prev
+            .get(&t.pid)
+            .map(|p| (p.runtime_ns, p.wait_ns, p.nr_runs))
+            .unwrap_or((t.runtime_ns, t.wait_ns, t.nr_runs));
+        let rate = TaskRate {
+            pid: t.pid,
+            cgid: t.cgid,
+            comm: t.comm.clone(),
+            tier: t.tier,
+            cpu: t.cpu,
+            avg_runtime_us: t.avg_runtime_us,
+            cpu_pct: t.runtime_ns.saturating_sub(run0) as f64 * 100.0 / interval,
+            wait_ms_per_s: t.wait_ns.saturating_sub(wait0) as f64 * 1000.0 / interval,
+            runs_per_s: t.nr_runs.saturating_sub(runs0) as f64 * 1e9 / interval,
+        };
+
+        let proc = procs.entry(t.tgid).or_insert_with(|| ProcessStat {
+            tgid: t.tgid,
+            comm: t.comm.clone(),
+            cpu_pct: 0.0,
+            wait_ms_per_s: 0.0,
+            tier_counts: [0; 4],
+            threads: Vec::new(),
+        });
+        // The group leader names the process
+        if t.pid == t.tgid {
+            proc.comm = t.comm.clone();
+        }
+        proc.cpu_pct += rate.cpu_pct;
+        proc.wait_ms_per_s += rate.wait_ms_per_s;
+        proc.tier_counts[(t.tier & 3) as usize] += 1;
+        proc.threads.push(rate);
+    }
+
+    let mut out: Vec<ProcessStat> = procs.into_values().collect();
+    for p in &mut out {
+        p.threads
+            .sort_by(|a, b| b.cpu_pct.total_cmp(&a.cpu_pct).then(a.pid.cmp(&b.pid)));
+    }
+    out.sort_by(|a, b| b.cpu_pct.total_cmp(&a.cpu_pct).then(a.tgid.cmp(&b.tgid)));
+    out
+}
+
+/// Per-tier totals behind `scx_cake monitor`, and its --baseline file
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct TierSummary {
+    pub tasks: [f64; 4],
+    pub cpu_pct: [f64; 4],
+    pub wait_ms_per_s: [f64; 4],
+    pub runs_per_s: [f64; 4],
+}
+
+impl TierSummary {
+    pub fn from_procs(procs: &[ProcessStat]) -> Self {
+        let mut s = Self::default();
+        for t in procs.iter().flat_map(|p| &p.threads) {
+            let i = (t.tier & 3) as usize;
+            s.tasks[i] += 1.0;
+            s.cpu_pct[i] += t.cpu_pct;
+            s.wait_ms_per_s[i] += t.wait_ms_per_s;
+            s.runs_per_s[i] += t.runs_per_s;
+        }
+        s
+    }
+
+    /// Fold `sample` into a running mean that already covers `n - 1` samples
+    pub fn average_in(&mut self, sample: &Self, n: u32) {
+        let n = n.max(1) as f64;
+        let fold = |mean: &mut [f64; 4], v: &[f64; 4]| {
+            for i in 0..4 {
+                mean[i] += (v[i] - mean[i]) / n;
+            }
+        };
+        fold(&mut self.tasks, &sample.tasks);
+        fold(&mut self.cpu_pct, &sample.cpu_pct);
+        fold(&mut self.wait_ms_per_s, &sample.wait_ms_per_s);
+        fold(&mut self.runs_per_s, &sample.runs_per_s);
+    }
+}
+
+/// Per-tier thread count, CPU%, wait (ms/s) and runs/s table for `scx_cake monitor`
+pub fn format_tier_summary(procs: &[ProcessStat]) -> String {
+    let s = TierSummary::from_procs(procs);
+    let mut out = String::from("Tier         Tasks    CPU%  Wait ms/s    Runs/s\n");
+    for (i, name) in TIER_NAMES.iter().enumerate() {
+        out += &format!(
+            "{:<12} {:>5} {:>7.1} {:>10.2} {:>9.0}\n",
+            name, s.tasks[i], s.cpu_pct[i], s.wait_ms_per_s[i], s.runs_per_s[i]
+        );
+    }
+    out
+}
+
+/// Relative change for `monitor --baseline`; "new" when the baseline had none
+fn pct_change(cur: f64, base: f64) -> String {
+    if base.abs() < f64::EPSILON {
+        if cur.abs() < f64::EPSILON {
+            "=".into()
+        } else {
+            "new".into()
+        }
+    } else {
+        format!("{:+.1}%", (cur - base) * 100.0 / base)
+    }
+}
+
+/// `monitor --baseline`: each tier's mean over `samples` next to its change
+/// against the recorded baseline
+pub fn format_tier_diff(cur: &TierSummary, base: &TierSummary, samples: u32) -> String {
+    let mut out = format!(
+        "vs baseline (mean of {} sample{})\n{:<12} {:>16} {:>16} {:>18} {:>16}\n",
+        samples,
+        if samples == 1 { "" } else { "s" },
+        "Tier",
+        "Tasks",
+        "CPU%",
+        "Wait ms/s",
+        "Runs/s"
+    );
+    for (i, name) in TIER_NAMES.iter().enumerate() {
+        out += &format!(
+            "{:<12} {:>7.1} {:>8} {:>7.1} {:>8} {:>9.2} {:>8} {:>7.0} {:>8}\n",
+            name,
+            cur.tasks[i],
+            pct_change(cur.tasks[i], base.tasks[i]),
+            cur.cpu_pct[i],
+            pct_change(cur.cpu_pct[i], base.cpu_pct[i]),
+            cur.wait_ms_per_s[i],
+            pct_change(cur.wait_ms_per_s[i], base.wait_ms_per_s[i]),
+            cur.runs_per_s[i],
+            pct_change(cur.runs_per_s[i], base.runs_per_s[i]),
+        );
+    }
+    out
+}
+
+/// Sort key for `ctl top` and the TUI task and tier tables
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum TopBy {
+    /// CPU share
+    Cpu,
+    /// Runnable-but-waiting time
+    Wait,
+    /// Times run (dispatches)
+    Runs,
+}
+
+impl TopBy {
+    pub fn key(self, t: &TaskRate) -> f64 {
+        match self {
+            TopBy::Cpu => t.cpu_pct,
+            TopBy::Wait => t.wait_ms_per_s,
+            TopBy::Runs => t.runs_per_s,
+        }
+    }
+
+    /// The same key for a whole process
+    pub fn process_key(self, p: &ProcessStat) -> f64 {
+        match self {
+            TopBy::Cpu => p.cpu_pct,
+            TopBy::Wait => p.wait_ms_per_s,
+            TopBy::Runs => p.threads.iter().map(|t| t.runs_per_s).sum(),
+        }
+    }
+
+    /// Next key for the TUI's sort cycle; None keeps the natural order
+    pub fn cycle(sort: Option<TopBy>) -> Option<TopBy> {
+        match sort {
+            None => Some(TopBy::Cpu),
+            Some(TopBy::Cpu) => Some(TopBy::Wait),
+            Some(TopBy::Wait) => Some(TopBy::Runs),
+            Some(TopBy::Runs) => None,
+        }
+    }
+}
+
+/// TUI task filter: a number matches that pid exactly, anything else a
+/// case-insensitive substring of the comm; empty matches everything
+pub fn task_filter_matches(filter: &str, pid: u32, comm: &str) -> bool {
+    let filter = filter.trim();
+    if filter.is_empty() {
+        return true;
+    }
+    match filter.parse::<u32>() {
+        Ok(n) => n == pid,
+        Err(_) => comm.to_lowercase().contains(&filter.to_lowercase()),
+    }
+}
+
+/// The `n` top threads by `by` across all processes, only those currently
+/// in `tier` if given
+pub fn top_tasks(procs: &[ProcessStat], by: TopBy, tier: Option<u8>, n: usize) -> Vec<&TaskRate> {
+    let mut tasks: Vec<&TaskRate> = procs
+        .iter()
+        .flat_map(|p| &p.threads)
+        .filter(|t| tier.is_none_or(|tier| t.tier & 3 == tier))
+        .collect();
+    tasks.sort_by(|a, b| by.key(b).total_cmp(&by.key(a)).then(a.pid.cmp(&b.pid)));
+    tasks.truncate(n);
+    tasks
+}
+
+/// `ctl top` table
+pub fn format_top(tasks: &[&TaskRate]) -> String {
+    let mut out = String::from(
+        "    PID  COMM             TIER           CPU%  Wait ms/s   Runs/s  AvgRun µs\n",
+    );
+    for t in tasks {
+        out += &format!(
+            "{:>7}  {:<16} {:<12} {:>6.1} {:>10.2} {:>8.0} {:>10}\n",
+            t.pid,
+            t.comm,
+            TIER_NAMES[(t.tier & 3) as usize],
+            t.cpu_pct,
+            t.wait_ms_per_s,
+            t.runs_per_s,
+            t.avg_runtime_us
+        );
+    }
+    out
+}
+
+/// bpffs pin of the task iterator link, read by `scx_cake ctl`
+pub const TASK_ITER_PIN: &str = "/sys/fs/bpf/scx_cake_tasks";
+
+/// Keeps the task iterator pinned while the scheduler runs; unpins on drop
+pub struct PinnedTaskIter(Link);
+
+impl PinnedTaskIter {
+    pub fn pin(skel: &BpfSkel) -> Result<Self> {
+        // A stale pin from a crashed run would make pin() fail with EEXIST
+        let _ = std::fs::remove_file(TASK_ITER_PIN);
+        let mut link = skel
+            .progs
+            .dump_tasks
+            .attach()
+            .context("Failed to attach task iterator")?;
+        link.pin(TASK_ITER_PIN)
+            .with_context(|| format!("Failed to pin {}", TASK_ITER_PIN))?;
+        Ok(Self(link))
+    }
+}
+
+impl Drop for PinnedTaskIter {
+    fn drop(&mut self) {
+        let _ = self.0.unpin();
+    }
+}
+
+/// Samples the dump_tasks iterator and keeps the previous snapshot for rates
+pub struct TaskSampler {
+    link: Link,
+    prev: HashMap<u32, TaskStat>,
+    last: Instant,
+}
+
+impl TaskSampler {
+    pub fn new(skel: &BpfSkel) -> Result<Self> {
+        let link = skel
+            .progs
+            .dump_tasks
+            .attach()
+            .context("Failed to attach task iterator")?;
+        Ok(Self::from_link(link))
+    }
+
+    /// Sampler over the link pinned by a running scheduler
+    pub fn open_pinned() -> Result<Self> {
+        let link = Link::open(TASK_ITER_PIN)
+            .with_context(|| format!("Failed to open {} (is scx_cake running?)", TASK_ITER_PIN))?;
+        Ok(Self::from_link(link))
+    }
+
+    fn from_link(link: Link) -> Self {
+        Self {
+            link,
+            prev: HashMap::new(),
+            last: Instant::now(),
+        }
+    }
+
+    /// Read every cake-managed task
+    pub fn snapshot(&self) -> Result<Vec<TaskStat>> {
+        let mut buf = Vec::new();
+        Iter::new(&self.link)
+            .context("Failed to create task iterator")?
+            .read_to_end(&mut buf)
+            .context("Failed to read task iterator")?;
+
+        Ok(buf
+            .chunks_exact(size_of::<cake_task_rec>())
+            .map(|chunk| {
+                // SAFETY: chunk is exactly one record written by dump_tasks
+                let rec: cake_task_rec =
+                    unsafe { std::ptr::read_unaligned(chunk.as_ptr() as *const _) };
+                TaskStat::from_rec(&rec)
+            })
+            .collect())
+    }
+
+    /// Per-process rates since the previous sample
+    pub fn sample(&mut self) -> Result<Vec<ProcessStat>> {
+        let tasks = self.snapshot()?;
+        let interval_ns = self.last.elapsed().as_nanos() as u64;
+        self.last = Instant::now();
+
+        let procs = group_by_process(&tasks, &self.prev, interval_ns);
+        self.prev = tasks.into_iter().map(|t| (t.pid, t)).collect();
+        Ok(procs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task(pid: u32, tgid: u32, comm: &str, tier: u8, runtime_ns: u64) -> TaskStat {
+        TaskStat {
+            pid,
+            tgid,
+            cgid: 0,
+            comm: comm.to_string(),
+            tier,
+            cpu: 0,
+            avg_runtime_us: 0,
+            runtime_ns,
+            wait_ns: 0,
+            nr_runs: 0,
+        }
+    }
+
+    #[test]
+    fn test_threads_roll_up_to_process() {
+        let prev: HashMap<u32, TaskStat> = [
+            task(100, 100, "game", 2, 0),
+            task(101, 100, "RenderThread", 2, 0),
+        ]
+        .into_iter()
+        .map(|t| (t.pid, t))
+        .collect();
+        let now = [
+            task(101, 100, "RenderThread", 2, 400_000_000),
+            task(100, 100, "game", 1, 100_000_000),
+            task(200, 200, "kwin", 1, 0),
+        ];
+
+        let procs = group_by_process(&now, &prev, 1_000_000_000);
+        assert_eq!(procs.len(), 2);
+        assert_eq!(procs[0].comm, "game", "leader names the process");
+        assert_eq!(procs[0].cpu_pct, 50.0);
+        assert_eq!(procs[0].tier_counts, [0, 1, 1, 0]);
+        assert_eq!(procs[0].threads[0].pid, 101, "busiest thread first");
+    }
+
+    #[test]
+    fn test_tier_summary_sums_threads() {
+        let prev: HashMap<u32, TaskStat> = [task(1, 1, "a", 3, 0), task(2, 1, "b", 3, 0)]
+            .into_iter()
+            .map(|t| (t.pid, t))
+            .collect();
+        let now = [
+            task(1, 1, "a", 3, 250_000_000),
+            task(2, 1, "b", 3, 500_000_000),
+        ];
+        let table = format_tier_summary(&group_by_process(&now, &prev, 1_000_000_000));
+        let bulk = table.lines().nth(4).unwrap();
+        assert!(bulk.starts_with("Bulk"), "{}", bulk);
+        assert!(bulk.contains("    2    75.0"), "{}", bulk);
+        assert!(table.lines().nth(1).unwrap().contains("    0     0.0"));
+    }
+
+    #[test]
+    fn test_epoch_reads_against_reset_point() {
+        let mut cpu0 = cake_stats::default();
+        cpu0.nr_tier_dispatches = [10, 0, 0, 5];
+        cpu0.preempt_matrix[4][3] = 2;
+        let mut cpu1 = cake_stats::default();
+        cpu1.nr_runs = 7;
+
+        let mut epoch = StatsEpoch::default();
+        assert_eq!(epoch.since(&sum_stats(&[cpu0, cpu1])).nr_runs, 7);
+
+        let mut waits = [[0; WAIT_BUCKETS]; 4];
+        waits[1][3] = 4;
+        epoch.advance(&[cpu0, cpu1], waits);
+        assert_eq!(epoch.epoch, 1);
+
+        cpu0.nr_tier_dispatches[0] += 3;
+        cpu0.preempt_matrix[4][3] += 1;
+        cpu1.nr_runs += 1;
+        let since = epoch.since(&sum_stats(&[cpu0, cpu1]));
+        assert_eq!(since.nr_tier_dispatches, [3, 0, 0, 0]);
+        assert_eq!(since.preempt_matrix[4][3], 1);
+        assert_eq!(since.nr_runs, 1);
+
+        let per_cpu = epoch.per_cpu(&[cpu0, cpu1, cpu1]);
+        assert_eq!(per_cpu[0].nr_tier_dispatches[0], 3);
+        assert_eq!(per_cpu[1].nr_runs, 1);
+        // A CPU the baseline didn't cover counts from zero
+        assert_eq!(per_cpu[2].nr_runs, 8);
+
+        waits[1][3] = 9;
+        assert_eq!(epoch.waits(&waits)[1][3], 5);
+    }
+
+    #[test]
+    fn test_tier_diff_against_baseline() {
+        let base = TierSummary {
+            cpu_pct: [10.0, 0.0, 40.0, 50.0],
+            wait_ms_per_s: [1.0, 0.0, 2.0, 4.0],
+            ..Default::default()
+        };
+        let mut mean = TierSummary::default();
+        let mut sample = base;
+        sample.cpu_pct = [12.0, 0.0, 40.0, 30.0];
+        mean.average_in(&sample, 1);
+        sample.cpu_pct = [14.0, 5.0, 40.0, 50.0];
+        mean.average_in(&sample, 2);
+        assert_eq!(mean.cpu_pct, [13.0, 2.5, 40.0, 40.0]);
+
+        let diff = format_tier_diff(&mean, &base, 2);
+        assert!(
+            diff.starts_with("vs baseline (mean of 2 samples)"),
+            "{}",
+            diff
+        );
+        let row = |name: &str| diff.lines().find(|l| l.starts_with(name)).unwrap();
+        assert!(row("Critical").contains("13.0   +30.0%"), "{}", diff);
+        assert!(row("Interactive").contains("2.5      new"), "{}", diff);
+        assert!(row("Frame").contains("40.0    +0.0%"), "{}", diff);
+        assert!(row("Bulk").contains("40.0   -20.0%"), "{}", diff);
+    }
+
+    #[test]
+    fn test_top_tasks_by_metric_and_tier() {
+        let mut a = task(1, 1, "game", 2, 0);
+        let mut b = task(2, 2, "cc1", 3, 0);
+        let prev: HashMap<u32, TaskStat> = [a.clone(), b.clone()]
+            .into_iter()
+            .map(|t| (t.pid, t))
+            .collect();
+        a.runtime_ns = 800_000_000;
+        a.nr_runs = 100;
+        b.runtime_ns = 200_000_000;
+        b.wait_ns = 50_000_000;
+        b.nr_runs = 5;
+        let procs = group_by_process(&[a, b], &prev, 1_000_000_000);
+
+        let by_cpu = top_tasks(&procs, TopBy::Cpu, None, 10);
+        assert_eq!(by_cpu[0].comm, "game");
+        assert_eq!(by_cpu[0].runs_per_s, 100.0);
+        assert_eq!(top_tasks(&procs, TopBy::Wait, None, 1)[0].comm, "cc1");
+        let bulk = top_tasks(&procs, TopBy::Cpu, Some(3), 10);
+        assert_eq!(bulk.len(), 1);
+        assert!(format_top(&bulk).lines().nth(1).unwrap().contains("cc1"));
+
+        // Process keys: pid 2 is its own process with 5 runs/s
+        let cc1 = procs.iter().find(|p| p.tgid == 2).unwrap();
+        assert_eq!(TopBy::Runs.process_key(cc1), 5.0);
+        assert_eq!(TopBy::Wait.process_key(cc1), cc1.wait_ms_per_s);
+        assert_eq!(TopBy::cycle(Some(TopBy::Runs)), None);
+    }
+
+    #[test]
+    fn test_task_filter_matches() {
+        assert!(task_filter_matches("", 7, "anything"));
+        assert!(task_filter_matches("cs2", 40, "CS2"));
+        assert!(task_filter_matches(" render", 41, "RenderThread"));
+        assert!(task_filter_matches("41", 41, "RenderThread"));
+        // A number is a pid, not a comm substring
+        assert!(!task_filter_matches("2", 40, "cs2"));
+        assert!(!task_filter_matches("kwin", 41, "RenderThread"));
+    }
+
+    #[test]
+    fn test_cpu_breakdown_totals_llcs() {
+        let mut per_cpu = [cake_stats::default(); 4];
+        for (i, s) in per_cpu.iter_mut().enumerate() {
+            s.nr_tier_dispatches = [10 * i as u64, 0, 0, 0];
+            s.nr_runs = 10;
+            s.wait_ns = 1_000_000 * i as u64;
+        }
+        let freq = [Some(4000), Some(3000), None, Some(5000)];
+        let rows = cpu_breakdown(&per_cpu, &[0, 0, 1, 1], &[1.0, 0.5, 2.0], &freq);
+        assert_eq!(rows.len(), 6);
+        assert_eq!(rows[3].label, "cpu 3");
+        assert_eq!(rows[3].avg_wait_us, 300.0);
+        assert_eq!(rows[4].label, "llc 0");
+        assert_eq!(rows[4].dispatches, 10);
+        assert_eq!(rows[5].dispatches, 50);
+        assert_eq!(rows[5].avg_wait_us, 250.0);
+        assert_eq!(rows[4].queued, 1.5);
+        // A CPU past the sampled depths reads as an empty queue
+        assert_eq!(rows[5].queued, 2.0);
+        // LLC frequency: mean of the CPUs that report one
+        assert_eq!(rows[4].freq_mhz, Some(3500));
+        assert_eq!(rows[5].freq_mhz, Some(5000));
+        assert_eq!(rows[2].freq_mhz, None);
+        assert_eq!(parse_khz("3600000\n"), Some(3600));
+        assert_eq!(parse_khz("<unknown>"), None);
+
+        // Single LLC: no totals, they would repeat the summary line
+        assert_eq!(cpu_breakdown(&per_cpu[..2], &[0, 0], &[], &[]).len(), 2);
+    }
+
+    #[test]
+    fn test_llc_breakdown_over_tick() {
+        let prev = [cake_stats::default(); 4];
+        let mut cur = prev;
+        for (i, s) in cur.iter_mut().enumerate() {
+            s.nr_tier_dispatches = [10 * (i as u64 + 1), 0, 0, 0];
+            s.nr_runs = 10;
+            s.wait_ns = 1_000_000;
+            s.nr_migrate_cross_llc = [0, i as u64, 0, 0];
+            s.nr_migrate_cross_node = i as u64;
+        }
+        let llcs = llc_breakdown(&cur, &prev, &[0, 0, 1, 1], &[50.0, 100.0, 0.0], 2.0);
+        assert_eq!(llcs.len(), 2);
+        assert_eq!((llcs[0].llc, llcs[0].nr_cpus), (0, 2));
+        assert_eq!(llcs[0].busy_pct, 75.0);
+        // A CPU past the busy samples reads idle
+        assert_eq!(llcs[1].busy_pct, 0.0);
+        assert_eq!(llcs[0].dispatches_s, 15.0);
+        assert_eq!(llcs[1].dispatch_pct, 70.0);
+        assert_eq!((llcs[1].runs_s, llcs[1].avg_wait_us), (10.0, 100.0));
+        assert_eq!(llcs[1].cross_llc_in_s, 2.5);
+
+        // NUMA nodes group the same way: CPUs 0-2 on node 0, CPU 3 on node 1
+        let nodes = node_breakdown(&cur, &prev, &[0, 0, 0, 1], &[], 2.0);
+        assert_eq!((nodes[0].nr_cpus, nodes[1].nr_cpus), (3, 1));
+        assert_eq!(nodes[0].dispatch_pct, 60.0);
+        assert_eq!(nodes[1].cross_node_in_s, 1.5);
+
+        // Movement only: the previous snapshot is subtracted
+        let again = llc_breakdown(&cur, &cur, &[0, 0, 1, 1], &[], 1.0);
+        assert_eq!(again[0].dispatches_s, 0.0);
+    }
+
+    #[test]
+    fn test_cpu_busy_pct() {
+        let mut per_cpu = [cake_stats::default(); 3];
+        per_cpu[0].tier_runtime_ns = [100_000_000, 0, 0, 400_000_000];
+        per_cpu[1].tier_runtime_ns = [0, 0, 3_000_000_000, 0];
+        let cur = cpu_runtime_ns(&per_cpu);
+        assert_eq!(cur, [500_000_000, 3_000_000_000, 0]);
+        // Half a second of a one-second tick; clamped at 100; new CPU idle
+        assert_eq!(cpu_busy_pct(&cur, &[0, 0], 1.0), [50.0, 100.0, 0.0]);
+    }
+
+    #[test]
+    fn test_wait_percentiles_interpolate() {
+        let mut hist = [0; WAIT_BUCKETS];
+        hist[1] = 50; // 1-2µs
+        hist[8] = 50; // 128-256µs: the tail an average would hide
+        assert_eq!(wait_percentile(&hist, 50.0), 2.0);
+        assert!((wait_percentile(&hist, 99.0) - 253.44).abs() < 1e-9);
+        assert_eq!(wait_percentile(&[0; WAIT_BUCKETS], 99.0), 0.0);
+
+        let prev = [hist; 4];
+        let mut cur = prev;
+        cur[2][8] += 5;
+        let d = wait_hist_delta(&cur, &prev);
+        assert_eq!(d[2][8], 5);
+        assert_eq!(d[0].iter().sum::<u64>(), 0);
+    }
+
+    #[test]
+    fn test_wait_mean_and_max() {
+        let mut hist = [0; WAIT_BUCKETS];
+        assert_eq!((wait_mean_us(&hist), tier_wait_max_us(&hist)), (0.0, 0));
+        hist[1] = 3; // 1-2µs, counted at 1.5
+        hist[4] = 1; // 8-16µs, counted at 12
+        assert_eq!(wait_mean_us(&hist), 4.125);
+        assert_eq!(tier_wait_max_us(&hist), 16);
+        assert_eq!(
+            wait_max_us(&[[0; WAIT_BUCKETS], hist, [0; WAIT_BUCKETS], hist]),
+            16
+        );
+    }
+
+    #[test]
+    fn test_wait_bucket_labels() {
+        assert_eq!(wait_bucket_label(0), "<1µs");
+        assert_eq!(wait_bucket_label(1), "1-2µs");
+        assert_eq!(wait_bucket_label(8), "128-256µs");
+        assert_eq!(wait_bucket_label(11), "1.0ms-2.0ms");
+        assert!(wait_bucket_label(WAIT_BUCKETS - 1).starts_with('≥'));
+        assert_eq!(wait_bucket_tick(0), "<1");
+        assert_eq!(wait_bucket_tick(9), "512");
+        assert_eq!(wait_bucket_tick(11), "2k");
+        assert_eq!(wait_bucket_tick(20), "1M");
+        assert_eq!(wait_bucket_tick(WAIT_BUCKETS - 1), "≥1073M");
+
+        let mut waits = [[0; WAIT_BUCKETS]; 4];
+        waits[3][1] = 4;
+        waits[3][12] = 1;
+        let summary = format_wait_summary(&waits);
+        let bulk = summary.lines().find(|l| l.starts_with("Bulk")).unwrap();
+        assert!(bulk.contains("     5 "), "{}", bulk);
+        assert!(bulk.ends_with("2.0ms-4.1ms"), "{}", bulk);
+        assert!(summary.lines().nth(1).unwrap().ends_with('—'));
+    }
+
+    #[test]
+    fn test_interval_output_uses_deltas() {
+        let prev = cake_stats::default();
+        let mut cur = cake_stats::default();
+        cur.nr_tier_dispatches = [200, 0, 0, 40];
+        cur.nr_smt_spread = 3;
+
+        let plain = format_plain(&cur, &prev, 2.0, false);
+        assert!(plain.contains("Critical 100"), "{}", plain);
+        assert!(plain.contains("Bulk 20"), "{}", plain);
+        assert!(plain.ends_with("user active"));
+
+        let mut waits = [[0; WAIT_BUCKETS]; 4];
+        waits[0][1] = 10;
+        cur.tier_runtime_ns = [0, 0, 1_000_000_000, 3_000_000_000];
+        cur.nr_migrate_cross_llc = [0, 0, 6, 0];
+        cur.nr_select = [0, 6, 0, 0, 2, 0, 0, 0, 2];
+        cur.preempt_matrix[0][3] = 9;
+        cur.preempt_matrix[4][3] = 1;
+        let depth = QueueDepth {
+            tier: [0.0, 0.5, 0.0, 12.0],
+            cpu: vec![1.0, 0.0],
+        };
+        let json = format_json(&cur, &prev, &waits, &depth, 2.0, 4, true);
+        assert!(
+            json.contains("\"tier_dispatches\":[200,0,0,40]"),
+            "{}",
+            json
+        );
+        assert!(json.contains("\"smt_spread\":3"));
+        assert!(
+            json.contains("\"dispatches_per_s\":[100.0,0.0,0.0,20.0]"),
+            "{}",
+            json
+        );
+        assert!(json.ends_with("\"user_idle\":true}"));
+        // A counter reset (scheduler reload) never goes negative
+        assert!(
+            json.contains("\"wait_p50_us\":[1.5,0.0,0.0,0.0]"),
+            "{}",
+            json
+        );
+        assert!(
+            json.contains("\"cpu_share_pct\":[0.0,0.0,12.5,37.5]"),
+            "{}",
+            json
+        );
+        assert_eq!(runtime_split(&cur), [0.0, 0.0, 25.0, 75.0]);
+        assert!(json.contains("\"migrate_cross_llc\":[0,0,6,0]"), "{}", json);
+        assert!(
+            json.contains("\"select_outcomes\":{\"sync\":0,\"prev\":6,\"sibling\":0,"),
+            "{}",
+            json
+        );
+        assert_eq!(
+            format_select_outcomes(&cur),
+            "sync 0.0% | prev 60.0% | sibling 0.0% | cluster 0.0% | llc 20.0% | node 0.0% | \
+             big 0.0% | other 0.0% | queued 20.0%"
+        );
+        assert!(format_select_outcomes(&prev).starts_with("sync 0% |"));
+        assert!(
+            json.contains("\"preempt_matrix\":[[0,0,0,9],[0,0,0,0],[0,0,0,0],[0,0,0,0],[0,0,0,1]]"),
+            "{}",
+            json
+        );
+        let matrix = format_preempt_matrix(&cur);
+        assert_eq!(matrix.lines().count(), 6);
+        assert!(matrix.lines().nth(1).unwrap().ends_with("          9"));
+        assert!(matrix.lines().last().unwrap().starts_with("Local"));
+        assert!(
+            json.contains("\"queue_depth\":[0.0,0.5,0.0,12.0],\"local_queue_depth\":[1.0,0.0]"),
+            "{}",
+            json
+        );
+        assert!(format_json(&prev, &cur, &waits, &depth, 1.0, 4, false).contains("[0,0,0,0]"));
+    }
+
+    #[test]
+    fn test_rates_cover_every_tier_counter() {
+        let prev = cake_stats::default();
+        let mut cur = cake_stats::default();
+        cur.nr_starvation_immune_tier = [0, 8, 0, 0];
+        cur.nr_cap_deferrals = [0, 0, 0, 30];
+        let rates = Rates::between(&cur, &prev, 2.0);
+        assert_eq!(rates.immune, [0.0, 4.0, 0.0, 0.0]);
+        assert_eq!(rates.cap_deferrals, [0.0, 0.0, 0.0, 15.0]);
+        // Reversed snapshots (counter reset) clamp to zero
+        assert_eq!(Rates::between(&prev, &cur, 2.0), Rates::default());
+    }
+
+    #[test]
+    fn test_new_task_counts_from_zero() {
+        let procs = group_by_process(
+            &[task(300, 300, "cc1", 3, 5_000_000_000)],
+            &HashMap::new(),
+            1_000_000_000,
+        );
+        assert_eq!(procs[0].cpu_pct, 0.0);
+    }
+
+    #[test]
+    fn test_exit_text_from_c_strings() {
+        let c = |s: &str, len: usize| -> Vec<std::ffi::c_char> {
+            let mut v: Vec<_> = s.bytes().map(|b| b as std::ffi::c_char).collect();
+            v.resize(len, 0);
+            v
+        };
+        assert_eq!(
+            exit_text(
+                &c("runnable task stall", 40),
+                &c("cake[42] failed\nmore", 32)
+            ),
+            "runnable task stall (cake[42] failed)"
+        );
+        assert_eq!(exit_text(&c("unregistered", 16), &c("", 8)), "unregistered");
+        assert_eq!(exit_text(&c("", 8), &c("", 8)), "unknown reason");
+    }
+}
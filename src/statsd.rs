@@ -0,0 +1,190 @@
+// SPDX-License-Identifier: GPL-2.0
+// statsd module - --statsd sends the interval counters as statsd UDP packets
+
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use anyhow::{anyhow, Context, Result};
+use log::{info, warn};
+
+use crate::csvlog::{counter_columns, counters};
+use crate::metrics::{Snapshot, StatsSource};
+
+/// Shutdown latency bound for the sender thread
+const POLL: Duration = Duration::from_millis(250);
+
+/// Payload limit that fits one datagram on a 1500 MTU path without IP
+/// fragmentation (the statsd/etsy recommendation)
+const MAX_PACKET: usize = 1432;
+
+/// Every metric is namespaced under this prefix
+const PREFIX: &str = "scx_cake";
+
+/// Interval movement as statsd lines: one `|c` counter per non-zero
+/// --log-stats column plus the user_idle gauge, packed into datagrams of at
+/// most MAX_PACKET bytes. Resets leave the BPF counters alone, but a counter
+/// that went backwards counts from zero.
+fn packets(cur: &Snapshot, prev: &Snapshot) -> Vec<String> {
+    let mut lines: Vec<String> = counter_columns()
+        .iter()
+        .zip(counters(&cur.stats).iter().zip(counters(&prev.stats)))
+        .filter_map(|(name, (&now, before))| {
+            let d = if now >= before { now - before } else { now };
+            (d > 0).then(|| format!("{}.{}:{}|c", PREFIX, name, d))
+        })
+        .collect();
+    lines.push(format!("{}.user_idle:{}|g", PREFIX, cur.user_idle as u8));
+
+    let mut out = vec![String::new()];
+    for line in lines {
+        let last = out.last_mut().unwrap();
+        if !last.is_empty() && last.len() + 1 + line.len() > MAX_PACKET {
+            out.push(line);
+        } else {
+            if !last.is_empty() {
+                last.push('\n');
+            }
+            last.push_str(&line);
+        }
+    }
+    out
+}
+
+/// Background thread sending one batch of statsd packets every interval.
+/// UDP is fire-and-forget: a collector that is down costs nothing but a
+/// warning, and the scheduler never waits on it.
+pub struct StatsdSender {
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl StatsdSender {
+    pub fn spawn(target: &str, source: StatsSource, interval: Duration) -> Result<Self> {
+        let addr = target
+            .to_socket_addrs()
+            .with_context(|| format!("Failed to resolve statsd address {}", target))?
+            .next()
+            .ok_or_else(|| anyhow!("statsd address {} resolved to nothing", target))?;
+        let bind: SocketAddr = if addr.is_ipv4() {
+            "0.0.0.0:0".parse()?
+        } else {
+            "[::]:0".parse()?
+        };
+        let socket = UdpSocket::bind(bind).context("Failed to open the statsd socket")?;
+        socket
+            .connect(addr)
+            .with_context(|| format!("Failed to set statsd peer {}", addr))?;
+        info!(
+            "Sending statsd metrics to {} every {}s",
+            addr,
+            interval.as_secs()
+        );
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let stop_thread = stop.clone();
+        let handle = std::thread::Builder::new()
+            .name("cake-statsd".into())
+            .spawn(move || send_loop(socket, source, interval, stop_thread))
+            .context("Failed to start the statsd thread")?;
+        Ok(Self {
+            stop,
+            handle: Some(handle),
+        })
+    }
+}
+
+impl Drop for StatsdSender {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(h) = self.handle.take() {
+            let _ = h.join();
+        }
+    }
+}
+
+fn send_loop(socket: UdpSocket, source: StatsSource, interval: Duration, stop: Arc<AtomicBool>) {
+    let mut prev = source.snapshot();
+    // Warn once per outage, not once per interval
+    let mut failing = false;
+    while !stop.load(Ordering::Relaxed) {
+        std::thread::sleep(POLL);
+        if prev.at.elapsed() < interval {
+            continue;
+        }
+        let cur = source.snapshot();
+        let sent = packets(&cur, &prev)
+            .iter()
+            .try_for_each(|p| socket.send(p.as_bytes()).map(|_| ()));
+        match sent {
+            Err(e) if !failing => {
+                warn!("statsd send failed, will keep trying: {}", e);
+                failing = true;
+            }
+            Ok(()) if failing => {
+                info!("statsd sends recovered");
+                failing = false;
+            }
+            _ => {}
+        }
+        prev = cur;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bpf_skel::types::cake_stats;
+    use std::time::Instant;
+
+    #[test]
+    fn test_packets() {
+        let at = Instant::now();
+        let mut prev = Snapshot {
+            stats: cake_stats::default(),
+            waits: Default::default(),
+            user_idle: false,
+            at,
+        };
+        prev.stats.nr_tier_dispatches = [10, 0, 0, 50];
+        let mut cur = Snapshot {
+            stats: prev.stats,
+            waits: Default::default(),
+            user_idle: true,
+            at: at + Duration::from_secs(1),
+        };
+        cur.stats.nr_tier_dispatches = [25, 0, 0, 7];
+
+        let p = packets(&cur, &prev);
+        assert_eq!(p.len(), 1);
+        // Unchanged counters are left out; Bulk was reset, so it reports 7
+        assert_eq!(
+            p[0],
+            "scx_cake.dispatch_critical:15|c\nscx_cake.dispatch_bulk:7|c\nscx_cake.user_idle:1|g"
+        );
+
+        // Every counter moving needs several datagrams, none over the limit
+        let mut busy = cur.stats;
+        busy.nr_select = [u64::MAX / 2; 9];
+        busy.tier_runtime_ns = [u64::MAX / 2; 4];
+        busy.nr_cap_queued = [u64::MAX / 2; 4];
+        busy.nr_starvation_immune_tier = [u64::MAX / 2; 4];
+        busy.nr_migrate_cross_llc = [u64::MAX / 2; 4];
+        busy.nr_starvation_preempts_tier = [u64::MAX / 2; 4];
+        busy.nr_cap_deferrals = [u64::MAX / 2; 4];
+        let p = packets(
+            &Snapshot {
+                stats: busy,
+                waits: Default::default(),
+                user_idle: false,
+                at,
+            },
+            &prev,
+        );
+        assert!(p.len() > 1);
+        assert!(p.iter().all(|pkt| pkt.len() <= MAX_PACKET));
+        assert!(p.last().unwrap().ends_with("scx_cake.user_idle:0|g"));
+    }
+}
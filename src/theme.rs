@@ -0,0 +1,112 @@
+// SPDX-License-Identifier: GPL-2.0
+// Theme module - TUI color themes (--theme, --no-color / NO_COLOR)
+
+use clap::ValueEnum;
+use ratatui::buffer::Buffer;
+use ratatui::style::{Color, Modifier};
+
+/// TUI palette. Views draw with the named terminal colors; a theme recolors
+/// the finished frame, so every view and the startup screen follow one table.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Theme {
+    /// Terminal colors as drawn, tuned for dark backgrounds
+    #[default]
+    Default,
+    /// Solarized accents with the terminal's own text color: readable on
+    /// light and dark backgrounds
+    Solarized,
+    /// No color, only bold and reverse video (what NO_COLOR selects)
+    Monochrome,
+}
+
+/// Solarized accent for a named color; white and gray text fall back to the
+/// terminal foreground, which is what breaks on light backgrounds
+fn solarized(c: Color) -> Color {
+    match c {
+        Color::White | Color::Gray => Color::Reset,
+        Color::Black => Color::Rgb(0x00, 0x2b, 0x36), // base03
+        Color::DarkGray => Color::Rgb(0x58, 0x6e, 0x75), // base01
+        Color::Red => Color::Rgb(0xdc, 0x32, 0x2f),
+        Color::LightRed => Color::Rgb(0xcb, 0x4b, 0x16), // orange
+        Color::Green | Color::LightGreen => Color::Rgb(0x85, 0x99, 0x00),
+        Color::Yellow | Color::LightYellow => Color::Rgb(0xb5, 0x89, 0x00),
+        Color::Blue | Color::LightBlue => Color::Rgb(0x26, 0x8b, 0xd2),
+        Color::Magenta => Color::Rgb(0xd3, 0x36, 0x82),
+        Color::LightMagenta => Color::Rgb(0x6c, 0x71, 0xc4), // violet
+        Color::Cyan | Color::LightCyan => Color::Rgb(0x2a, 0xa1, 0x98),
+        other => other,
+    }
+}
+
+impl Theme {
+    /// Recolor a drawn frame in place. A dark-gray background (selected row,
+    /// idle heatmap cell) becomes reverse video, which shows on any background.
+    pub fn apply(self, buf: &mut Buffer) {
+        if self == Theme::Default {
+            return;
+        }
+        for cell in buf.content.iter_mut() {
+            let highlight = match self {
+                Theme::Monochrome => cell.bg != Color::Reset,
+                _ => cell.bg == Color::DarkGray,
+            };
+            let (fg, bg) = match self {
+                Theme::Monochrome => (Color::Reset, Color::Reset),
+                _ if highlight => (solarized(cell.fg), Color::Reset),
+                _ => (solarized(cell.fg), solarized(cell.bg)),
+            };
+            cell.fg = fg;
+            cell.bg = bg;
+            if highlight {
+                cell.modifier.insert(Modifier::REVERSED);
+            }
+        }
+    }
+
+    /// Whether plain ANSI output (calibration progress) may use color
+    pub fn has_color(self) -> bool {
+        self != Theme::Monochrome
+    }
+}
+
+/// NO_COLOR convention (no-color.org): set and non-empty disables color
+pub fn no_color_env() -> bool {
+    std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::layout::Rect;
+    use ratatui::style::Style;
+
+    fn frame() -> Buffer {
+        let mut buf = Buffer::empty(Rect::new(0, 0, 3, 1));
+        buf[(0, 0)].set_style(Style::default().fg(Color::White));
+        buf[(1, 0)].set_style(Style::default().fg(Color::Cyan).bg(Color::DarkGray));
+        buf[(2, 0)].set_style(Style::default().fg(Color::Black).bg(Color::Red));
+        buf
+    }
+
+    #[test]
+    fn test_themes_recolor_frame() {
+        let mut buf = frame();
+        Theme::Default.apply(&mut buf);
+        assert_eq!(buf, frame());
+
+        Theme::Solarized.apply(&mut buf);
+        assert_eq!(buf[(0, 0)].fg, Color::Reset);
+        assert_eq!(buf[(1, 0)].bg, Color::Reset);
+        assert!(buf[(1, 0)].modifier.contains(Modifier::REVERSED));
+        assert_eq!(buf[(2, 0)].bg, Color::Rgb(0xdc, 0x32, 0x2f));
+
+        let mut buf = frame();
+        Theme::Monochrome.apply(&mut buf);
+        assert!(buf
+            .content
+            .iter()
+            .all(|c| c.fg == Color::Reset && c.bg == Color::Reset));
+        assert!(!buf[(0, 0)].modifier.contains(Modifier::REVERSED));
+        assert!(buf[(2, 0)].modifier.contains(Modifier::REVERSED));
+    }
+}
@@ -1,126 +1,713 @@
 // SPDX-License-Identifier: GPL-2.0
-// Topology detection - CPUs, CCDs, P/E cores. Results passed to BPF as const volatile.
+// Topology detection - CPUs, CCDs, NUMA nodes, P/E cores. Results passed to BPF as const volatile.
 
-use anyhow::Result;
+use std::ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign, Not};
+
+use anyhow::{anyhow, bail, Result};
 use scx_utils::{CoreType, Topology};
 
 /// Maximum supported CPUs (matches BPF array sizes)
-pub const MAX_CPUS: usize = 64;
-/// Maximum supported LLCs (matches BPF array sizes)
-pub const MAX_LLCS: usize = 8;
+pub const MAX_CPUS: usize = 256;
+/// u64 words in a CpuMask (matches CAKE_MASK_WORDS)
+pub const MASK_WORDS: usize = MAX_CPUS / 64;
+
+/// Set of CPUs below MAX_CPUS, laid out like the BPF side's
+/// u64[CAKE_MASK_WORDS] masks (CPU n is bit n % 64 of word n / 64)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CpuMask(pub [u64; MASK_WORDS]);
+
+impl CpuMask {
+    /// Every CPU below MAX_CPUS
+    pub const ALL: Self = Self([u64::MAX; MASK_WORDS]);
+
+    /// Just `cpu` (empty past MAX_CPUS)
+    pub fn cpu(cpu: usize) -> Self {
+        let mut mask = Self::default();
+        mask.set(cpu);
+        mask
+    }
+
+    pub fn test(&self, cpu: usize) -> bool {
+        cpu < MAX_CPUS && self.0[cpu / 64] & (1u64 << (cpu % 64)) != 0
+    }
+
+    /// Add `cpu`; CPUs from MAX_CPUS up are left out
+    pub fn set(&mut self, cpu: usize) {
+        if cpu < MAX_CPUS {
+            self.0[cpu / 64] |= 1u64 << (cpu % 64);
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.iter().all(|&word| word == 0)
+    }
+
+    pub fn count(&self) -> usize {
+        self.0.iter().map(|word| word.count_ones() as usize).sum()
+    }
+
+    /// Lowest CPU in the mask
+    pub fn first(&self) -> Option<usize> {
+        self.iter().next()
+    }
+
+    /// CPUs in the mask, lowest first
+    pub fn iter(&self) -> impl Iterator<Item = usize> {
+        let mask = *self;
+        (0..MAX_CPUS).filter(move |&cpu| mask.test(cpu))
+    }
+}
+
+/// CPUs 0-63 from a single word
+impl From<u64> for CpuMask {
+    fn from(word: u64) -> Self {
+        let mut mask = Self::default();
+        mask.0[0] = word;
+        mask
+    }
+}
+
+impl FromIterator<usize> for CpuMask {
+    fn from_iter<I: IntoIterator<Item = usize>>(cpus: I) -> Self {
+        let mut mask = Self::default();
+        for cpu in cpus {
+            mask.set(cpu);
+        }
+        mask
+    }
+}
+
+impl BitAnd for CpuMask {
+    type Output = Self;
+    fn bitand(mut self, rhs: Self) -> Self {
+        self &= rhs;
+        self
+    }
+}
+
+impl BitAndAssign for CpuMask {
+    fn bitand_assign(&mut self, rhs: Self) {
+        for (a, b) in self.0.iter_mut().zip(rhs.0) {
+            *a &= b;
+        }
+    }
+}
+
+impl BitOr for CpuMask {
+    type Output = Self;
+    fn bitor(mut self, rhs: Self) -> Self {
+        self |= rhs;
+        self
+    }
+}
+
+impl BitOrAssign for CpuMask {
+    fn bitor_assign(&mut self, rhs: Self) {
+        for (a, b) in self.0.iter_mut().zip(rhs.0) {
+            *a |= b;
+        }
+    }
+}
+
+impl Not for CpuMask {
+    type Output = Self;
+    fn not(self) -> Self {
+        Self(self.0.map(|word| !word))
+    }
+}
 
 /// Detected topology information
 #[derive(Debug, Clone)]
 pub struct TopologyInfo {
-    /// Number of online CPUs
+    /// Highest online CPU id + 1: the span per-CPU maps and BPF loops
+    /// cover. CPUs below it may be offline (firmware-disabled SMT threads,
+    /// parked cores), so check online_mask; nr_online() counts them.
     pub nr_cpus: usize,
 
+    /// Number of LLC domains with at least one managed CPU below MAX_CPUS
+    pub nr_llcs: usize,
+
+    /// Number of NUMA nodes with at least one managed CPU below MAX_CPUS
+    pub nr_nodes: usize,
+
+    /// True if managed CPUs span more than one NUMA node
+    pub has_numa: bool,
+
+    /// CPUs below MAX_CPUS that were online at detection
+    pub online_mask: CpuMask,
+
+    /// CPUs cake places tasks on (--cpus); all bits set when unrestricted
+    pub managed_mask: CpuMask,
+
+    /// Online CPUs left out of managed_mask because they are isolated
+    /// (isolcpus=, nohz_full=, isolated cpuset partition); set by the loader
+    pub isolated_mask: CpuMask,
+
     /// True if system has multiple L3 cache domains (CCDs)
     pub has_dual_ccd: bool,
 
+    /// L3 size in KiB per LLC (0 if sysfs doesn't say), nr_llcs entries
+    pub llc_cache_kb: Vec<u32>,
+
     /// True if system has hybrid P/E cores (Intel hybrid or similar)
     pub has_hybrid_cores: bool,
 
+    /// sysfs cpu_capacity per CPU scaled so the largest managed core is
+    /// 1024; 1024 everywhere unless has_capacity
+    pub cpu_capacity: [u16; MAX_CPUS],
+
+    /// True if every managed CPU reports a cpu_capacity and they differ
+    /// (ARM big.LITTLE / DynamIQ, hybrid x86 on recent kernels)
+    pub has_capacity: bool,
+
+    /// CPPC highest_perf per CPU (amd-pstate or ACPI CPPC), 0 where the
+    /// kernel doesn't expose it
+    pub cpu_perf: [u32; MAX_CPUS],
+
+    /// True if every managed CPU reports highest_perf and they differ:
+    /// the firmware ranks some cores as boosting higher (preferred cores)
+    pub has_preferred_cores: bool,
+
     /// SMT enabled status
     pub smt_enabled: bool,
     /// Map of CPU ID -> Sibling CPU ID (or self if none/disabled)
     pub cpu_sibling_map: [u8; MAX_CPUS],
 
+    /// True if some managed CPUs share an L2 with other cores but not the
+    /// whole LLC (Intel E-core modules)
+    pub has_clusters: bool,
+    /// Managed CPUs sharing each CPU's L2 cluster, empty where the cluster is
+    /// just the CPU's core or its whole LLC
+    pub cpu_cluster_mask: [CpuMask; MAX_CPUS],
+
     // BPF Maps
     pub cpu_llc_id: [u8; MAX_CPUS],
+    pub cpu_node_id: [u8; MAX_CPUS],
+    /// NUMA node of each LLC, nr_llcs entries
+    pub llc_node_id: Vec<u8>,
     pub cpu_is_big: [u8; MAX_CPUS],
     pub cpu_core_id: [u8; MAX_CPUS],
     pub cpu_thread_bit: [u8; MAX_CPUS],
     pub cpu_dsq_id: [u32; MAX_CPUS],
-    /// Pre-computed 64-bit mask of all CPUs in a physical core
-    pub core_cpu_mask: [u64; 32],
+    /// Pre-computed mask of all CPUs in a physical core, by core id
+    pub core_cpu_mask: [CpuMask; MAX_CPUS],
     /// Bitmask requirement for a core to be "fully idle" (e.g. 0x3 for dual SMT)
-    pub core_thread_mask: [u8; 32],
-    pub llc_cpu_mask: [u64; MAX_LLCS],
-    pub big_cpu_mask: u64,
+    pub core_thread_mask: [u8; MAX_CPUS],
+    /// Managed CPUs of each LLC, nr_llcs entries
+    pub llc_cpu_mask: Vec<CpuMask>,
+    pub big_cpu_mask: CpuMask,
 
     // Info
     pub cpus_per_ccd: u32,
 }
 
-pub fn detect() -> Result<TopologyInfo> {
-    // robustly detect topology using scx_utils
-    let topo = Topology::new()?;
+impl TopologyInfo {
+    /// LLC index for --primary-domain queues: the LLC holding most of
+    /// `primary` (first on a tie). None if no managed CPU is primary.
+    pub fn primary_llc(&self, primary: CpuMask) -> Option<usize> {
+        primary_first_llc(&self.llc_cpu_mask, primary)
+    }
 
-    let nr_cpus = topo.all_cpus.len();
-    let nr_llcs = topo.all_llcs.len();
+    /// LLC carrying 3D V-Cache on a multi-CCD X3D part: the one LLC whose
+    /// L3 is larger than every other's. None if sizes are unknown or equal.
+    pub fn vcache_llc(&self) -> Option<usize> {
+        vcache_llc(&self.llc_cache_kb)
+    }
 
-    // Get sibling map directly from scx_utils
-    let siblings = topo.sibling_cpus();
-    let mut cpu_sibling_map = [0u8; MAX_CPUS];
+    /// Distinct L2 clusters (E-core modules), lowest CPU first
+    pub fn clusters(&self) -> Vec<CpuMask> {
+        let mut clusters: Vec<CpuMask> = self
+            .cpu_cluster_mask
+            .iter()
+            .copied()
+            .filter(|mask| !mask.is_empty())
+            .collect();
+        clusters.sort_by_key(|mask| mask.first());
+        clusters.dedup();
+        clusters
+    }
+
+    /// Replace the detected LLCs with `llcs`, one CPU mask each, in order
+    /// (--topology-override). Masks without a managed CPU get no LLC.
+    pub fn set_llcs(&mut self, llcs: &[CpuMask]) {
+        self.cpu_llc_id = [0; MAX_CPUS];
+        self.llc_cpu_mask.clear();
+        self.llc_node_id.clear();
+        self.llc_cache_kb.clear();
+        for &mask in llcs {
+            let managed = mask & self.managed_mask;
+            let Some(first) = managed.first() else {
+                continue;
+            };
+            for cpu in mask.iter() {
+                self.cpu_llc_id[cpu] = self.llc_cpu_mask.len() as u8;
+            }
+            self.push_llc(managed, first);
+        }
+        self.finish_llcs();
+    }
+
+    /// Append an LLC of the managed CPUs `mask`, `first` the lowest of them
+    fn push_llc(&mut self, mask: CpuMask, first: usize) {
+        self.llc_cpu_mask.push(mask);
+        self.llc_node_id.push(self.cpu_node_id[first]);
+        self.llc_cache_kb.push(read_l3_kb(first).unwrap_or(0));
+    }
 
-    // Default to self-mapping
-    for (i, sibling) in cpu_sibling_map.iter_mut().enumerate().take(MAX_CPUS) {
-        *sibling = i as u8;
+    /// Count the LLCs pushed. With none (no managed CPU reports one), LLC 0
+    /// stands empty so every CPU's llc id still has an entry.
+    fn finish_llcs(&mut self) {
+        self.nr_llcs = self.llc_cpu_mask.len().max(1);
+        self.llc_cpu_mask.resize(self.nr_llcs, CpuMask::default());
+        self.llc_node_id.resize(self.nr_llcs, 0);
+        self.llc_cache_kb.resize(self.nr_llcs, 0);
+        self.has_dual_ccd = self.nr_llcs > 1;
+        self.cpus_per_ccd = self.llc_cpu_mask[0].count() as u32;
     }
 
-    // Populate with detected siblings
-    for (cpu, &sibling) in siblings.iter().enumerate() {
-        if cpu < MAX_CPUS && sibling >= 0 {
-            let sib = sibling as usize;
-            if sib < MAX_CPUS {
-                cpu_sibling_map[cpu] = sib as u8;
+    /// Replace the detected P-cores with `big`; every other CPU is an E-core
+    pub fn set_big(&mut self, big: CpuMask) {
+        for (cpu, is_big) in self.cpu_is_big.iter_mut().enumerate() {
+            *is_big = big.test(cpu) as u8;
+        }
+        self.big_cpu_mask = big & self.managed_mask;
+        self.has_hybrid_cores =
+            !self.big_cpu_mask.is_empty() && self.big_cpu_mask != self.managed_mask;
+    }
+
+    /// Distinct capacities of the managed CPUs, largest first, with the
+    /// CPUs of each class (one class unless has_capacity)
+    pub fn capacity_classes(&self) -> Vec<(u16, CpuMask)> {
+        let mut classes: Vec<(u16, CpuMask)> = Vec::new();
+        for cpu in self.managed_mask.iter() {
+            let cap = self.cpu_capacity[cpu];
+            match classes.iter_mut().find(|(c, _)| *c == cap) {
+                Some((_, mask)) => mask.set(cpu),
+                None => classes.push((cap, CpuMask::cpu(cpu))),
             }
         }
+        classes.sort_by_key(|&(cap, _)| std::cmp::Reverse(cap));
+        classes
     }
 
+    /// Replace the detected SMT siblings with `pairs` and rebuild the core
+    /// maps: each pair is one core, every other CPU a core of its own
+    pub fn set_smt_pairs(&mut self, pairs: &[(usize, usize)]) {
+        self.cpu_sibling_map = std::array::from_fn(|cpu| cpu as u8);
+        for &(a, b) in pairs {
+            self.cpu_sibling_map[a] = b as u8;
+            self.cpu_sibling_map[b] = a as u8;
+        }
+        self.smt_enabled = !pairs.is_empty();
+
+        self.cpu_core_id = [0; MAX_CPUS];
+        self.cpu_thread_bit = [0; MAX_CPUS];
+        self.core_cpu_mask = [CpuMask::default(); MAX_CPUS];
+        self.core_thread_mask = [0; MAX_CPUS];
+        let mut core_id = 0;
+        for cpu in self.online_mask.iter() {
+            let sibling = self.cpu_sibling_map[cpu] as usize;
+            if sibling < cpu {
+                continue; // Core already numbered from its first thread
+            }
+            let threads = [cpu, sibling];
+            let threads = &threads[..if sibling == cpu { 1 } else { 2 }];
+            for (thread_idx, &thread) in threads.iter().enumerate() {
+                self.cpu_core_id[thread] = core_id as u8;
+                self.cpu_thread_bit[thread] = 1 << thread_idx;
+                self.core_cpu_mask[core_id].set(thread);
+            }
+            self.core_thread_mask[core_id] = (1u8 << threads.len()) - 1;
+            core_id += 1;
+        }
+    }
+
+    /// Online CPUs, which is fewer than nr_cpus when the numbering has gaps
+    pub fn nr_online(&self) -> usize {
+        self.online_mask.count()
+    }
+
+    /// Managed CPUs best first by CPPC highest_perf (lower CPU id first on a
+    /// tie), the order --preferred-cores tries them in
+    pub fn perf_order(&self) -> Vec<usize> {
+        perf_order(
+            &self.cpu_perf[..self.nr_cpus.min(MAX_CPUS)],
+            self.managed_mask,
+        )
+    }
+
+    /// Managed CPUs in the order --powersave grows its packed set: little
+    /// cores first (hybrid only), then LLC by LLC, both SMT threads of a
+    /// core before the next core so whole cores stay idle
+    pub fn packing_order(&self) -> Vec<usize> {
+        let n = self.nr_cpus.min(MAX_CPUS);
+        packing_order(
+            self.managed_mask,
+            &self.cpu_llc_id[..n],
+            &self.cpu_core_id[..n],
+            self.has_hybrid_cores.then_some(self.big_cpu_mask),
+        )
+    }
+
+    /// Managed CPUs in the order cake_select_cpu looks for an idle one when
+    /// a task last ran on `cpu`, by rung (SELECT_RUNGS prev..other): the CPU
+    /// itself, its SMT sibling, the rest of its L2 cluster (clusters only),
+    /// the rest of its LLC, other LLCs on its NUMA node (multi-node only),
+    /// big cores elsewhere (hybrid only), then everything else
+    pub fn preference(&self, cpu: usize) -> [Vec<usize>; 7] {
+        let n = self.nr_cpus.min(MAX_CPUS);
+        cpu_preference(
+            cpu,
+            self.managed_mask,
+            &self.cpu_sibling_map[..n],
+            self.has_clusters.then_some(&self.cpu_cluster_mask[..n]),
+            &self.cpu_llc_id[..n],
+            self.has_numa.then_some(&self.cpu_node_id[..n]),
+            self.has_hybrid_cores.then_some(self.big_cpu_mask),
+        )
+    }
+
+    /// preference(cpu) flattened for the BPF cpu_select_order table
+    pub fn select_order(&self, cpu: usize) -> [u16; MAX_CPUS] {
+        select_order(&self.preference(cpu))
+    }
+}
+
+/// Flattened preference past the CPU itself, padded with u16::MAX: the
+/// order cake_select_cpu walks when the kernel's idle pick left its LLC
+fn select_order(rungs: &[Vec<usize>; 7]) -> [u16; MAX_CPUS] {
+    let mut order = [u16::MAX; MAX_CPUS];
+    for (slot, &cpu) in order.iter_mut().zip(rungs[1..].iter().flatten()) {
+        *slot = cpu as u16;
+    }
+    order
+}
+
+/// CPU -> SMT sibling from scx_utils' sibling list (-1 = none), each CPU
+/// its own sibling where the other thread is offline or past MAX_CPUS
+fn sibling_map(siblings: &[i32], online: CpuMask) -> [u8; MAX_CPUS] {
+    let mut map: [u8; MAX_CPUS] = std::array::from_fn(|cpu| cpu as u8);
+    for (cpu, &sib) in siblings.iter().enumerate().take(MAX_CPUS) {
+        let Ok(sib) = usize::try_from(sib) else {
+            continue;
+        };
+        if online.test(cpu) && online.test(sib) {
+            map[cpu] = sib as u8;
+        }
+    }
+    map
+}
+
+fn cpu_preference(
+    cpu: usize,
+    managed: CpuMask,
+    sibling: &[u8],
+    cluster: Option<&[CpuMask]>,
+    llc: &[u8],
+    node: Option<&[u8]>,
+    big: Option<CpuMask>,
+) -> [Vec<usize>; 7] {
+    let mut rungs: [Vec<usize>; 7] = Default::default();
+    for c in managed.iter().take_while(|&c| c < llc.len()) {
+        let rung = if c == cpu {
+            0
+        } else if c == sibling[cpu] as usize {
+            1
+        } else if cluster.is_some_and(|cluster| cluster[cpu].test(c)) {
+            2
+        } else if llc[c] == llc[cpu] {
+            3
+        } else if node.is_some_and(|node| node[c] == node[cpu]) {
+            4
+        } else if big.is_some_and(|mask| mask.test(c)) {
+            5
+        } else {
+            6
+        };
+        rungs[rung].push(c);
+    }
+    rungs
+}
+
+fn perf_order(perf: &[u32], managed: CpuMask) -> Vec<usize> {
+    let mut cpus: Vec<usize> = managed.iter().take_while(|&c| c < perf.len()).collect();
+    cpus.sort_by_key(|&c| (std::cmp::Reverse(perf[c]), c));
+    cpus
+}
+
+fn packing_order(managed: CpuMask, llc: &[u8], core: &[u8], big: Option<CpuMask>) -> Vec<usize> {
+    let mut cpus: Vec<usize> = managed.iter().take_while(|&c| c < llc.len()).collect();
+    cpus.sort_by_key(|&c| {
+        let is_big = big.is_some_and(|mask| mask.test(c));
+        (is_big, llc[c], core[c], c)
+    });
+    cpus
+}
+
+/// CPUs sharing `cpu`'s L2, from sysfs cluster_cpus_list (Linux 5.16+)
+fn read_cluster_cpus(cpu: usize) -> Option<CpuMask> {
+    let list = std::fs::read_to_string(format!(
+        "/sys/devices/system/cpu/cpu{}/topology/cluster_cpus_list",
+        cpu
+    ))
+    .ok()?;
+    parse_cpu_list(&list).ok()
+}
+
+/// A cluster worth its own rung: more than the CPU's `core`, less than its
+/// `llc`. Most x86 cores report the core itself and some ARM parts the LLC.
+fn cluster_rung_mask(cluster: CpuMask, core: CpuMask, llc: CpuMask) -> CpuMask {
+    match !(cluster & !core).is_empty() && (cluster & !llc).is_empty() && cluster != llc {
+        true => cluster,
+        false => CpuMask::default(),
+    }
+}
+
+/// Capacities of the managed CPUs in `raw` (sysfs cpu_capacity, 0 where
+/// unreadable) scaled to 1024 for the largest; None when any is missing
+/// or they are all equal
+fn scale_capacity(raw: &[u32], managed: CpuMask) -> Option<[u16; MAX_CPUS]> {
+    let cpus = || managed.iter().take_while(|&c| c < raw.len());
+    let max = cpus().map(|c| raw[c]).max()?;
+    if cpus().any(|c| raw[c] == 0) || cpus().all(|c| raw[c] == max) {
+        return None;
+    }
+    let mut cap = [1024; MAX_CPUS];
+    for c in cpus() {
+        cap[c] = (raw[c] as u64 * 1024 / max as u64) as u16;
+    }
+    Some(cap)
+}
+
+/// Big cores by capacity: within 3/4 of the largest, so a three-class
+/// DynamIQ part counts its middle cores with the prime ones
+fn capacity_big(cap: &[u16], managed: CpuMask) -> CpuMask {
+    managed
+        .iter()
+        .take_while(|&c| c < cap.len())
+        .filter(|&c| cap[c] as u32 * 4 >= 1024 * 3)
+        .collect()
+}
+
+/// sysfs cpu_capacity of `cpu` (asymmetric-capacity kernels, 1024 = largest)
+fn read_capacity(cpu: usize) -> Option<u32> {
+    std::fs::read_to_string(format!("/sys/devices/system/cpu/cpu{}/cpu_capacity", cpu))
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+}
+
+/// CPPC highest_perf of `cpu`: amd-pstate's copy, else ACPI CPPC's
+fn read_highest_perf(cpu: usize) -> Option<u32> {
+    ["cpufreq/amd_pstate_highest_perf", "acpi_cppc/highest_perf"]
+        .iter()
+        .find_map(|file| {
+            std::fs::read_to_string(format!("/sys/devices/system/cpu/cpu{}/{}", cpu, file))
+                .ok()?
+                .trim()
+                .parse()
+                .ok()
+        })
+}
+
+/// L3 size of `cpu` in KiB, from sysfs ("98304K")
+fn read_l3_kb(cpu: usize) -> Option<u32> {
+    let size = std::fs::read_to_string(format!(
+        "/sys/devices/system/cpu/cpu{}/cache/index3/size",
+        cpu
+    ))
+    .ok()?;
+    parse_cache_kb(&size)
+}
+
+fn parse_cache_kb(size: &str) -> Option<u32> {
+    let size = size.trim();
+    match size.strip_suffix('K') {
+        Some(kb) => kb.parse().ok(),
+        None => match size.strip_suffix('M') {
+            Some(mb) => mb.parse::<u32>().ok()?.checked_mul(1024),
+            None => size.parse::<u32>().ok().map(|b| b / 1024),
+        },
+    }
+}
+
+fn vcache_llc(cache_kb: &[u32]) -> Option<usize> {
+    if cache_kb.len() < 2 || cache_kb.contains(&0) {
+        return None;
+    }
+    let max = *cache_kb.iter().max()?;
+    let mut largest = cache_kb.iter().enumerate().filter(|&(_, &kb)| kb == max);
+    let (llc, _) = largest.next()?;
+    largest.next().is_none().then_some(llc)
+}
+
+fn primary_first_llc(llc_cpu_mask: &[CpuMask], primary: CpuMask) -> Option<usize> {
+    llc_cpu_mask
+        .iter()
+        .enumerate()
+        .map(|(llc, &mask)| (llc, (mask & primary).count()))
+        .filter(|&(_, n)| n > 0)
+        .max_by_key(|&(llc, n)| (n, std::cmp::Reverse(llc)))
+        .map(|(llc, _)| llc)
+}
+
+/// Parse a cpulist (`0-7,16-23`) into a CPU bitmask. CPUs must be below MAX_CPUS.
+pub fn parse_cpu_list(s: &str) -> Result<CpuMask> {
+    let mut mask = CpuMask::default();
+    for part in s.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        let (lo, hi) = match part.split_once('-') {
+            Some((lo, hi)) => (lo.trim().parse::<usize>()?, hi.trim().parse::<usize>()?),
+            None => {
+                let cpu = part.parse::<usize>()?;
+                (cpu, cpu)
+            }
+        };
+        if lo > hi || hi >= MAX_CPUS {
+            bail!("bad CPU range '{}' (CPUs 0-{})", part, MAX_CPUS - 1);
+        }
+        for cpu in lo..=hi {
+            mask.set(cpu);
+        }
+    }
+    if mask.is_empty() {
+        return Err(anyhow!("empty CPU list"));
+    }
+    Ok(mask)
+}
+
+/// Mask of a sysfs cpulist (`0-3,6`, empty for none). CPUs from MAX_CPUS
+/// up are left out, as detection leaves them out of placement. None if the
+/// list doesn't parse.
+pub fn sysfs_cpu_mask(text: &str) -> Option<CpuMask> {
+    let mut mask = CpuMask::default();
+    for part in text.trim().split(',').filter(|p| !p.is_empty()) {
+        let (lo, hi) = match part.split_once('-') {
+            Some((lo, hi)) => (lo.parse::<usize>().ok()?, hi.parse::<usize>().ok()?),
+            None => {
+                let cpu = part.parse::<usize>().ok()?;
+                (cpu, cpu)
+            }
+        };
+        for cpu in lo..=hi.min(MAX_CPUS - 1) {
+            mask.set(cpu);
+        }
+    }
+    Some(mask)
+}
+
+/// Detect topology; `managed` restricts LLC and P/E-core maps to a subset of CPUs
+pub fn detect(managed: CpuMask) -> Result<TopologyInfo> {
+    // robustly detect topology using scx_utils
+    let topo = Topology::new()?;
+
+    // CPU ids can have gaps (SMT disabled in firmware, offline cores):
+    // size maps by the highest id, and trust the kernel's online list
+    // over whatever scx_utils enumerated
+    let nr_cpus = topo.all_cpus.keys().max().map_or(0, |&cpu| cpu + 1);
+    let online = topo.all_cpus.keys().copied().collect::<CpuMask>()
+        & crate::hotplug::online_cpus().unwrap_or(CpuMask::ALL);
+    let managed = managed & online;
+    if managed.is_empty() {
+        bail!("--cpus selects no online CPU");
+    }
+
+    // Get sibling map directly from scx_utils
+    let cpu_sibling_map = sibling_map(&topo.sibling_cpus(), online);
+
     let mut info = TopologyInfo {
         nr_cpus,
-        has_dual_ccd: nr_llcs > 1,
+        nr_llcs: 0, // Counted below
+        nr_nodes: 0,
+        has_numa: false, // Set from nr_nodes below
+        online_mask: online,
+        managed_mask: managed,
+        isolated_mask: CpuMask::default(),
+        has_dual_ccd: false,     // Set from nr_llcs below
         has_hybrid_cores: false, // Will detect below
-        smt_enabled: topo.smt_enabled,
+        cpu_capacity: [1024; MAX_CPUS],
+        has_capacity: false, // Set from cpu_capacity below
+        llc_cache_kb: Vec::new(),
+        cpu_perf: [0; MAX_CPUS],
+        has_preferred_cores: false, // Set from cpu_perf below
+        // SMT only counts with both threads of some core online
+        smt_enabled: topo.smt_enabled
+            && (0..MAX_CPUS).any(|cpu| cpu_sibling_map[cpu] as usize != cpu),
         cpu_sibling_map,
+        has_clusters: false, // Set from cpu_cluster_mask below
+        cpu_cluster_mask: [CpuMask::default(); MAX_CPUS],
         cpu_llc_id: [0; MAX_CPUS],
+        cpu_node_id: [0; MAX_CPUS],
+        llc_node_id: Vec::new(),
         cpu_is_big: [1; MAX_CPUS], // Default to 1 (Big) to be safe
         cpu_core_id: [0; MAX_CPUS],
         cpu_thread_bit: [0; MAX_CPUS],
         cpu_dsq_id: [0; MAX_CPUS],
-        core_cpu_mask: [0; 32],
-        core_thread_mask: [0; 32],
-        llc_cpu_mask: [0; MAX_LLCS],
-        big_cpu_mask: 0,
+        core_cpu_mask: [CpuMask::default(); MAX_CPUS],
+        core_thread_mask: [0; MAX_CPUS],
+        llc_cpu_mask: Vec::new(),
+        big_cpu_mask: CpuMask::default(),
         cpus_per_ccd: 0,
     };
 
-    // 1. Map LLCs
-    // Note: topo.all_llcs keys are arbitrary kernel IDs. We must map them to 0..MAX_LLCS-1.
-    // We'll just use a simple counter 0,1,2... as we iterate.
-    let mut llc_idx = 0;
+    if nr_cpus > MAX_CPUS {
+        log::warn!(
+            "CPU ids reach {}, only the first {} get topology-aware placement",
+            nr_cpus - 1,
+            MAX_CPUS
+        );
+    }
 
-    for llc in topo.all_llcs.values() {
-        if llc_idx >= MAX_LLCS {
-            break;
+    // 1. Map NUMA nodes, densely like the LLCs below. Nodes without a
+    // managed CPU keep node 0: nothing is placed there.
+    let mut node_idx = 0;
+    for node in topo.nodes.values() {
+        let cpus: Vec<usize> = node
+            .all_cpus
+            .keys()
+            .copied()
+            .filter(|&cpu| cpu < MAX_CPUS)
+            .collect();
+        if !cpus.iter().any(|&cpu| managed.test(cpu)) {
+            continue;
         }
-
-        let mut mask = 0u64;
-        let mut core_count = 0;
-
-        for cpu_id in llc.all_cpus.keys() {
-            let cpu = *cpu_id;
-            if cpu < MAX_CPUS {
-                info.cpu_llc_id[cpu] = llc_idx as u8;
-                mask |= 1u64 << cpu;
-                core_count += 1;
-            }
+        for &cpu in &cpus {
+            info.cpu_node_id[cpu] = node_idx as u8;
         }
+        node_idx += 1;
+    }
+    info.nr_nodes = node_idx.max(1);
+    info.has_numa = info.nr_nodes > 1;
 
-        info.llc_cpu_mask[llc_idx] = mask;
-        if info.cpus_per_ccd == 0 {
-            info.cpus_per_ccd = core_count;
-        } // Estimate
+    // 2. Map LLCs
+    // Note: topo.all_llcs keys are arbitrary kernel IDs. We must map them to 0..nr_llcs-1.
+    // Dense counter over LLCs that own a supported CPU, so DSQ ids have no gaps.
+    // CAKE_MAX_LLCS == CAKE_MAX_CPUS: every supported CPU's LLC gets an index, none are merged.
+    // With --cpus, LLCs without a managed CPU get no DSQ: their CPUs keep
+    // llc id 0 so wakeups they issue still land in a DSQ that is drained.
+    for llc in topo.all_llcs.values() {
+        let cpus: Vec<usize> = llc
+            .all_cpus
+            .keys()
+            .copied()
+            .filter(|&cpu| cpu < MAX_CPUS)
+            .collect();
+        let mask = cpus.iter().copied().collect::<CpuMask>() & managed;
+        let Some(first) = mask.first() else {
+            continue;
+        };
 
-        llc_idx += 1;
+        for &cpu in &cpus {
+            info.cpu_llc_id[cpu] = info.llc_cpu_mask.len() as u8;
+        }
+        info.push_llc(mask, first);
     }
+    info.finish_llcs();
 
-    // 2. Identify P-cores vs E-cores
+    // 3. Identify P-cores vs E-cores
     // Reset defaults to recalculate based on CoreType
     info.cpu_is_big = [0; MAX_CPUS];
-    info.big_cpu_mask = 0;
+    info.big_cpu_mask = CpuMask::default();
 
     let mut p_cores_found = 0;
     let mut e_cores_found = 0;
@@ -136,22 +723,29 @@ pub fn detect() -> Result<TopologyInfo> {
             _ => 1,
         };
 
-        if is_big == 1 {
+        // Only managed cores decide whether the scheduler sees a hybrid CPU
+        let is_managed = core.cpus.keys().any(|&cpu| managed.test(cpu));
+        if is_managed && is_big == 1 {
             p_cores_found += 1;
-        } else {
+        } else if is_managed {
             e_cores_found += 1;
         }
 
-        // Calculate SMT requirement mask for this core
-        if core_id < 32 {
-            info.core_thread_mask[core_id] = ((1u16 << core.cpus.len()) - 1) as u8;
-        }
-
-        // Iterate over CPUs in this core
+        // Iterate over the online CPUs in this core: an offline sibling
+        // is not a thread the core can run
         let mut thread_idx = 0;
-        let mut sorted_cpus: Vec<_> = core.cpus.keys().collect();
+        let mut sorted_cpus: Vec<_> = core
+            .cpus
+            .keys()
+            .filter(|&&cpu| cpu >= MAX_CPUS || online.test(cpu))
+            .collect();
         sorted_cpus.sort();
 
+        // Calculate SMT requirement mask for this core
+        if core_id < MAX_CPUS && !sorted_cpus.is_empty() {
+            info.core_thread_mask[core_id] = ((1u16 << sorted_cpus.len()) - 1) as u8;
+        }
+
         for cpu_id in sorted_cpus {
             let cpu = *cpu_id;
             if cpu < MAX_CPUS {
@@ -160,18 +754,20 @@ pub fn detect() -> Result<TopologyInfo> {
                 info.cpu_thread_bit[cpu] = 1 << thread_idx;
                 info.cpu_dsq_id[cpu] = 1000 /* CAKE_DSQ_LC_BASE */ + cpu as u32;
 
-                if core_id < 32 {
-                    info.core_cpu_mask[core_id] |= 1u64 << cpu;
+                if core_id < MAX_CPUS {
+                    info.core_cpu_mask[core_id].set(cpu);
                 }
 
                 if is_big == 1 {
-                    info.big_cpu_mask |= 1u64 << cpu;
+                    info.big_cpu_mask.set(cpu);
                 }
                 thread_idx += 1;
             }
         }
     }
 
+    info.big_cpu_mask &= managed;
+
     // Update hybrid flag
     if p_cores_found > 0 && e_cores_found > 0 {
         info.has_hybrid_cores = true;
@@ -184,18 +780,276 @@ pub fn detect() -> Result<TopologyInfo> {
         }
     }
 
+    // 3b. Capacity: where the kernel reports asymmetric cpu_capacity (ARM
+    // big.LITTLE, which has no core type), it decides the big cores
+    let mut raw = [0u32; MAX_CPUS];
+    for &cpu in topo.all_cpus.keys().filter(|&&cpu| cpu < MAX_CPUS) {
+        raw[cpu] = read_capacity(cpu).unwrap_or(0);
+    }
+    if let Some(cap) = scale_capacity(&raw, managed) {
+        info.cpu_capacity = cap;
+        info.has_capacity = true;
+        info.set_big(capacity_big(&cap, managed));
+    }
+
+    // 4. L2 clusters (E-core modules): between the SMT core and the LLC
+    for &cpu in topo.all_cpus.keys().filter(|&&cpu| cpu < MAX_CPUS) {
+        if !managed.test(cpu) {
+            continue;
+        }
+        let core = match info.core_cpu_mask[info.cpu_core_id[cpu] as usize] {
+            core if !core.is_empty() => core,
+            _ => CpuMask::cpu(cpu),
+        };
+        let cluster = read_cluster_cpus(cpu).unwrap_or_default() & managed;
+        info.cpu_cluster_mask[cpu] = cluster_rung_mask(
+            cluster,
+            core,
+            info.llc_cpu_mask[info.cpu_llc_id[cpu] as usize],
+        );
+    }
+    info.has_clusters = info.cpu_cluster_mask.iter().any(|mask| !mask.is_empty());
+
+    // 5. CPPC ranking: preferred cores report a higher highest_perf
+    for &cpu in topo.all_cpus.keys().filter(|&&cpu| cpu < MAX_CPUS) {
+        info.cpu_perf[cpu] = read_highest_perf(cpu).unwrap_or(0);
+    }
+    let managed_perf: Vec<u32> = managed.iter().map(|cpu| info.cpu_perf[cpu]).collect();
+    info.has_preferred_cores =
+        !managed_perf.contains(&0) && managed_perf.iter().any(|&perf| perf != managed_perf[0]);
+
     // Log detected topology (debug level - use RUST_LOG=debug to see)
     log::debug!("Topology detected:");
     log::debug!("  CPUs:          {}", info.nr_cpus);
+    if managed != online {
+        log::debug!("    Managed:     {:x?}", managed.0);
+    }
     log::debug!("  SMT Enabled:   {}", info.smt_enabled);
     log::debug!("  Dual CCD:      {}", info.has_dual_ccd);
+    log::debug!("  NUMA nodes:    {}", info.nr_nodes);
+    log::debug!("  LLCs:          {}", info.nr_llcs);
     if info.has_dual_ccd {
-        log::debug!("    Masks:       {:x?}", &info.llc_cpu_mask[..llc_idx]);
+        log::debug!("    Masks:       {:x?}", info.llc_cpu_mask);
+    }
+    if let Some(llc) = info.vcache_llc() {
+        log::debug!("    V-Cache:     LLC {}", llc);
     }
     log::debug!("  Hybrid cores:  {}", info.has_hybrid_cores);
     if info.has_hybrid_cores {
-        log::debug!("    P-core mask: {:016x}", info.big_cpu_mask);
+        log::debug!("    P-core mask: {:x?}", info.big_cpu_mask.0);
+    }
+    log::debug!("  Capacity:      {}", info.has_capacity);
+    if info.has_capacity {
+        log::debug!("    Classes:     {:x?}", info.capacity_classes());
+    }
+    log::debug!("  L2 clusters:   {}", info.has_clusters);
+    log::debug!("  Preferred:     {}", info.has_preferred_cores);
+    if info.has_preferred_cores {
+        log::debug!("    Order:       {:?}", info.perf_order());
     }
 
     Ok(info)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn m(bits: u64) -> CpuMask {
+        bits.into()
+    }
+
+    #[test]
+    fn test_parse_cpu_list() {
+        assert_eq!(parse_cpu_list("0-3,8").unwrap(), m(0x10f));
+        assert_eq!(parse_cpu_list(" 2 , 5-6 ").unwrap(), m(0x64));
+        assert_eq!(parse_cpu_list("63").unwrap(), m(1u64 << 63));
+        // Past the first word: Threadripper / EPYC CPU ids
+        let wide = parse_cpu_list("0,63-64,255").unwrap();
+        assert_eq!(wide.0, [1 | 1 << 63, 1, 0, 1 << 63]);
+        assert_eq!(wide.iter().collect::<Vec<_>>(), [0, 63, 64, 255]);
+        assert_eq!((wide.count(), wide.first()), (4, Some(0)));
+        assert!(parse_cpu_list("256").is_err());
+        assert!(parse_cpu_list("7-3").is_err());
+        assert!(parse_cpu_list("").is_err());
+        assert!(parse_cpu_list("a-b").is_err());
+    }
+
+    #[test]
+    fn test_sysfs_cpu_mask() {
+        assert_eq!(sysfs_cpu_mask("0-3,6\n"), Some(m(0x4f)));
+        assert_eq!(sysfs_cpu_mask("0"), Some(m(1)));
+        // CPUs past MAX_CPUS are not placed on, so they don't count
+        assert_eq!(
+            sysfs_cpu_mask("254-257,300"),
+            Some([254, 255].into_iter().collect())
+        );
+        assert_eq!(sysfs_cpu_mask("0-x"), None);
+        assert_eq!(sysfs_cpu_mask("\n"), Some(CpuMask::default()));
+    }
+
+    #[test]
+    fn test_primary_llc() {
+        // 9950X3D-style: CCD0 = 0-7,16-23 (V-Cache), CCD1 = 8-15,24-31
+        let llcs = [m(0x00ff_00ff), m(0xff00_ff00)];
+        assert_eq!(primary_first_llc(&llcs, m(0x00ff_00ff)), Some(0));
+        assert_eq!(primary_first_llc(&llcs, m(0x0000_ff01)), Some(1));
+        assert_eq!(
+            primary_first_llc(&llcs, m(0x0000_0101)),
+            Some(0),
+            "tie: first LLC"
+        );
+        assert_eq!(primary_first_llc(&llcs, m(1u64 << 40)), None);
+    }
+
+    #[test]
+    fn test_vcache_llc() {
+        assert_eq!(parse_cache_kb("98304K\n"), Some(98304));
+        assert_eq!(parse_cache_kb("32M"), Some(32768));
+        assert_eq!(parse_cache_kb("big"), None);
+        // 9950X3D: 96M on CCD0, 32M on CCD1
+        assert_eq!(vcache_llc(&[98304, 32768]), Some(0));
+        assert_eq!(vcache_llc(&[32768, 98304]), Some(1));
+        // 9950X: equal CCDs; 9800X3D: a single LLC; unreadable sizes
+        assert_eq!(vcache_llc(&[32768, 32768]), None);
+        assert_eq!(vcache_llc(&[98304]), None);
+        assert_eq!(vcache_llc(&[98304, 0]), None);
+    }
+
+    #[test]
+    fn test_capacity() {
+        // Three-class DynamIQ: prime core 7, A7xx 4-6, A5xx 0-3
+        let raw = [325, 325, 325, 325, 870, 870, 870, 1024];
+        let cap = scale_capacity(&raw, m(0xff)).unwrap();
+        assert_eq!(&cap[..8], &[325, 325, 325, 325, 870, 870, 870, 1024]);
+        assert_eq!(capacity_big(&cap, m(0xff)), m(0xf0));
+        // --cpus without the prime core: the A7xx become the 1024 class
+        let cap = scale_capacity(&raw, m(0x7f)).unwrap();
+        assert_eq!((cap[0], cap[4]), (382, 1024));
+
+        assert_eq!(scale_capacity(&[1024; 4], m(0xf)), None, "symmetric");
+        assert_eq!(
+            scale_capacity(&[1024, 0, 512, 512], m(0xf)),
+            None,
+            "missing"
+        );
+        assert_eq!(scale_capacity(&[0; 4], m(0xf)), None, "not exposed");
+    }
+
+    #[test]
+    fn test_perf_order() {
+        // 7950X-style: CPUs 2 and 5 boost highest, CPU 7 left out by --cpus
+        let perf = [196, 201, 231, 196, 211, 231, 166, 236];
+        assert_eq!(perf_order(&perf, m(0x7f)), [2, 5, 4, 1, 0, 3, 6]);
+    }
+
+    #[test]
+    fn test_sibling_map() {
+        // 4 cores, SMT pairs 0/4 1/5 2/6 3/7, threads 5 and 7 offline
+        let siblings = [4, 5, 6, 7, 0, 1, 2, 3];
+        let map = sibling_map(&siblings, m(0x5f));
+        assert_eq!(&map[..8], &[4, 1, 6, 3, 0, 5, 2, 7]);
+        // No SMT: scx_utils reports -1; CPUs past the list map to themselves
+        let map = sibling_map(&[-1, -1], m(0b11));
+        assert_eq!((map[0], map[1], map[9]), (0, 1, 9));
+    }
+
+    #[test]
+    fn test_packing_order() {
+        // Two 2-core SMT LLCs: 0/4 1/5 in LLC 0, 2/6 3/7 in LLC 1
+        let llc = [0, 0, 1, 1, 0, 0, 1, 1];
+        let core = [0, 1, 2, 3, 0, 1, 2, 3];
+        assert_eq!(
+            packing_order(m(0xff), &llc, &core, None),
+            [0, 4, 1, 5, 2, 6, 3, 7]
+        );
+        // Hybrid: LLC 0 are big cores, so LLC 1's fill first; CPU 7 left out
+        assert_eq!(
+            packing_order(m(0x7f), &llc, &core, Some(m(0x33))),
+            [2, 6, 3, 0, 4, 1, 5]
+        );
+    }
+
+    #[test]
+    fn test_cpu_preference() {
+        // Two 2-core SMT LLCs: 0/4 1/5 in LLC 0, 2/6 3/7 in LLC 1
+        let sibling = [4, 5, 6, 7, 0, 1, 2, 3];
+        let llc = [0, 0, 1, 1, 0, 0, 1, 1];
+        let rungs = cpu_preference(1, m(0xff), &sibling, None, &llc, None, None);
+        assert_eq!(
+            rungs,
+            [
+                vec![1],
+                vec![5],
+                vec![],
+                vec![0, 4],
+                vec![],
+                vec![],
+                vec![2, 3, 6, 7]
+            ]
+        );
+        // Hybrid: LLC 1 are big cores; --cpus left CPU 7 out
+        let rungs = cpu_preference(1, m(0x7f), &sibling, None, &llc, None, Some(m(0xcc)));
+        assert_eq!(rungs[5], vec![2, 3, 6]);
+        assert!(rungs[6].is_empty());
+    }
+
+    #[test]
+    fn test_cpu_preference_numa() {
+        // Two nodes of two SMT-pair LLCs: LLCs 0,1 on node 0, LLCs 2,3 on node 1
+        let sibling = [1, 0, 3, 2, 5, 4, 7, 6];
+        let llc = [0, 0, 1, 1, 2, 2, 3, 3];
+        let node = [0, 0, 0, 0, 1, 1, 1, 1];
+        let rungs = cpu_preference(0, m(0xff), &sibling, None, &llc, Some(&node), None);
+        assert_eq!(
+            rungs,
+            [
+                vec![0],
+                vec![1],
+                vec![],
+                vec![],
+                vec![2, 3],
+                vec![],
+                vec![4, 5, 6, 7]
+            ]
+        );
+        // What cake_select_cpu walks: same node before the remote one
+        let order = select_order(&rungs);
+        assert_eq!(order[..7], [1, 2, 3, 4, 5, 6, 7]);
+        assert_eq!(order[7], u16::MAX);
+        // Remote big cores still beat remote little ones
+        let rungs = cpu_preference(0, m(0xff), &sibling, None, &llc, Some(&node), Some(m(0x30)));
+        assert_eq!(
+            (rungs[5].clone(), rungs[6].clone()),
+            (vec![4, 5], vec![6, 7])
+        );
+    }
+
+    #[test]
+    fn test_cpu_preference_clusters() {
+        // 12600K-style LLC: P-cores 0/1 and 2/3, E-core module 4-7
+        let (p_core, module, llc) = (m(0x3), m(0xf0), m(0xff));
+        let none = CpuMask::default();
+        assert_eq!(cluster_rung_mask(module, CpuMask::cpu(5), llc), module);
+        assert_eq!(
+            cluster_rung_mask(p_core, p_core, llc),
+            none,
+            "cluster = core"
+        );
+        assert_eq!(
+            cluster_rung_mask(llc, CpuMask::cpu(5), llc),
+            none,
+            "cluster = LLC"
+        );
+
+        let sibling = [1, 0, 3, 2, 4, 5, 6, 7];
+        let cluster = [none, none, none, none, module, module, module, module];
+        let llc = [0; 8];
+        let rungs = cpu_preference(5, m(0xff), &sibling, Some(&cluster), &llc, None, None);
+        assert_eq!(rungs[2], vec![4, 6, 7]);
+        assert_eq!(rungs[3], vec![0, 1, 2, 3]);
+        // P-cores have no cluster rung
+        let rungs = cpu_preference(0, m(0xff), &sibling, Some(&cluster), &llc, None, None);
+        assert!(rungs[2].is_empty());
+    }
+}
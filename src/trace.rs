@@ -0,0 +1,164 @@
+// SPDX-License-Identifier: GPL-2.0
+// Trace module - --trace writer: scheduling events as a Perfetto-loadable trace
+
+use std::collections::HashSet;
+use std::io::{self, Write};
+
+use crate::events::{tier_name, Event, EV_ENQUEUE, EV_RUN, EV_STOP};
+
+/// Chrome trace-event JSON, which ui.perfetto.dev (and chrome://tracing)
+/// opens directly: one track per thread, grouped under its process. Each run
+/// is a slice named after the tier it ran in; enqueues, preemptions and tier
+/// changes are instants on the same track. Timestamps are CLOCK_BOOTTIME µs
+/// — the clock Perfetto's ftrace source and `trace-cmd record -C boot` use —
+/// so they line up with a kernel sched trace taken alongside.
+pub struct TraceWriter<W: Write> {
+    out: W,
+    /// Threads whose thread_name metadata has been written
+    named: HashSet<u32>,
+    /// Threads with an open run slice; a stop without one (tracing began
+    /// mid-run) must not emit an unmatched end
+    running: HashSet<u32>,
+    first: bool,
+    finished: bool,
+}
+
+impl<W: Write> TraceWriter<W> {
+    pub fn new(mut out: W) -> io::Result<Self> {
+        out.write_all(b"{\"displayTimeUnit\":\"ns\",\"traceEvents\":[\n")?;
+        Ok(Self {
+            out,
+            named: HashSet::new(),
+            running: HashSet::new(),
+            first: true,
+            finished: false,
+        })
+    }
+
+    fn emit(&mut self, line: &str) -> io::Result<()> {
+        if !self.first {
+            self.out.write_all(b",\n")?;
+        }
+        self.first = false;
+        self.out.write_all(line.as_bytes())
+    }
+
+    pub fn record(&mut self, ev: &Event) -> io::Result<()> {
+        if self.named.insert(ev.pid) {
+            let meta = format!(
+                "{{\"ph\":\"M\",\"name\":\"thread_name\",\"pid\":{},\"tid\":{},\"args\":{{\"name\":{}}}}}",
+                ev.tgid,
+                ev.pid,
+                serde_json::to_string(&ev.comm).unwrap_or_default()
+            );
+            self.emit(&meta)?;
+        }
+
+        let head = format!(
+            "\"pid\":{},\"tid\":{},\"ts\":{:.3}",
+            ev.tgid,
+            ev.pid,
+            ev.ts_ns as f64 / 1e3
+        );
+        let line = match ev.kind {
+            EV_RUN => {
+                self.running.insert(ev.pid);
+                format!(
+                    "{{\"ph\":\"B\",\"name\":\"{}\",{},\"args\":{{\"cpu\":{}}}}}",
+                    tier_name(ev.to),
+                    head,
+                    ev.cpu
+                )
+            }
+            EV_STOP => {
+                if self.running.remove(&ev.pid) {
+                    self.emit(&format!("{{\"ph\":\"E\",{}}}", head))?;
+                }
+                // Blocked ends the run; still runnable means preempted or slice expired
+                if ev.to == 0 {
+                    return Ok(());
+                }
+                format!(
+                    "{{\"ph\":\"i\",\"s\":\"t\",\"name\":\"preempt\",{},\"args\":{{\"cpu\":{},\"tier\":\"{}\"}}}}",
+                    head,
+                    ev.cpu,
+                    tier_name(ev.from)
+                )
+            }
+            EV_ENQUEUE => format!(
+                "{{\"ph\":\"i\",\"s\":\"t\",\"name\":\"enqueue\",{},\"args\":{{\"cpu\":{},\"tier\":\"{}\"}}}}",
+                head,
+                ev.cpu,
+                tier_name(ev.to)
+            ),
+            _ => format!(
+                "{{\"ph\":\"i\",\"s\":\"t\",\"name\":\"{}\",{},\"args\":{{\"cpu\":{},\"from\":\"{}\",\"to\":\"{}\"}}}}",
+                ev.kind_name(),
+                head,
+                ev.cpu,
+                tier_name(ev.from),
+                tier_name(ev.to)
+            ),
+        };
+        self.emit(&line)
+    }
+
+    /// Close the JSON array; runs still open are left for the viewer to end
+    pub fn finish(&mut self) -> io::Result<()> {
+        if !self.finished {
+            self.finished = true;
+            self.out.write_all(b"\n]}\n")?;
+        }
+        self.out.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::EV_TIER_CHANGE;
+
+    fn event(kind: u8, from: u8, to: u8, ts_ns: u64) -> Event {
+        Event {
+            time: 0.0,
+            ts_ns,
+            pid: 812,
+            tgid: 800,
+            cpu: 3,
+            kind,
+            from,
+            to,
+            comm: "kwin\"x".into(),
+        }
+    }
+
+    #[test]
+    fn test_trace_is_valid_json() {
+        let mut w = TraceWriter::new(Vec::new()).unwrap();
+        for ev in [
+            // Stop before any run: no orphan end
+            event(EV_STOP, 2, 0, 500),
+            event(EV_ENQUEUE, 1, 1, 1_000),
+            event(EV_RUN, 1, 1, 2_500),
+            event(EV_STOP, 1, 1, 7_000),
+            event(EV_TIER_CHANGE, 1, 2, 7_000),
+        ] {
+            w.record(&ev).unwrap();
+        }
+        w.finish().unwrap();
+
+        let trace: serde_json::Value = serde_json::from_slice(&w.out).unwrap();
+        let events = trace["traceEvents"].as_array().unwrap();
+        let phases: Vec<&str> = events.iter().map(|e| e["ph"].as_str().unwrap()).collect();
+        assert_eq!(phases, ["M", "i", "B", "E", "i", "i"]);
+        assert_eq!(events[0]["args"]["name"], "kwin\"x");
+        assert_eq!(events[2]["name"], "Interactive");
+        assert_eq!(events[2]["ts"], 2.5);
+        assert_eq!(
+            (events[2]["pid"].as_u64(), events[2]["tid"].as_u64()),
+            (Some(800), Some(812))
+        );
+        assert_eq!(events[4]["name"], "preempt");
+        assert_eq!(events[5]["args"]["to"], "Frame");
+    }
+}
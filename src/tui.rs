@@ -1,16 +1,23 @@
 // SPDX-License-Identifier: GPL-2.0
 // TUI module - ratatui-based terminal UI for real-time scheduler statistics
 
+use std::collections::{HashSet, VecDeque};
+use std::fs::OpenOptions;
 use std::io::{self, Stdout, Write};
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use anyhow::{Context, Result};
 use arboard::Clipboard;
+use clap::ValueEnum;
 use crossterm::{
     cursor::{Hide, MoveTo, Show},
-    event::{self, Event, KeyCode, KeyEventKind},
+    event::{
+        self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEventKind, MouseButton,
+        MouseEvent, MouseEventKind,
+    },
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
     ExecutableCommand,
@@ -18,32 +25,119 @@ use crossterm::{
 use ratatui::{
     buffer::Buffer,
     prelude::*,
-    widgets::{Block, BorderType, Borders, Cell, Padding, Paragraph, Row, Table, Widget},
+    widgets::{
+        Axis, Bar, BarChart, BarGroup, Block, BorderType, Borders, Cell, Chart, Clear, Dataset,
+        GraphType, Padding, Paragraph, Row, Sparkline, Table, TableState, Tabs, Widget,
+    },
 };
 use tachyonfx::{fx, EffectManager};
 
-use crate::bpf_skel::types::cake_stats;
+use crate::alert::{AlertMetric, Alerts, TierAlert};
+use crate::bpf_skel::types::{cake_dsq_depth, cake_stats};
 use crate::bpf_skel::BpfSkel;
-use crate::stats::TIER_NAMES;
-use crate::topology::TopologyInfo;
-
-fn aggregate_stats(skel: &BpfSkel) -> cake_stats {
-    let mut total: cake_stats = Default::default();
+use crate::cgroup::{self, CgroupNode};
+use crate::check;
+use crate::control::Tunable;
+use crate::events::{self, EventFeed};
+use crate::history::Recorder;
+use crate::keymap::{Action, Keymap, SEQ_TIMEOUT};
+use crate::query;
+use crate::replay::{self, Session};
+use crate::rules::{self, TierPins};
+use crate::stats::{
+    aggregate_stats, avg_wait_us, cap_queue_len, cpu_breakdown, cpu_busy_pct, cpu_freq_mhz,
+    cpu_runtime_ns, cpu_share, dsq_depth, format_json, format_preempt_matrix,
+    format_select_outcomes, llc_breakdown, node_breakdown, runtime_split, task_filter_matches,
+    tier_wait_max_us, wait_bucket_label, wait_bucket_tick, wait_hist_delta, wait_histograms,
+    wait_mean_us, wait_percentile, CpuLoad, Health, LlcLoad, ProcessStat, QueueDepth, Rates,
+    StatsEpoch, TaskSampler, TopBy, WaitHist, PREEMPT_SOURCES, SELECT_RUNGS, TIER_NAMES,
+    WAIT_BUCKETS, WAIT_PERCENTILES,
+};
+use crate::theme::Theme;
+use crate::topology::{TopologyInfo, MAX_CPUS};
+
+/// Which panel fills the main area
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum View {
+    Overview,
+    Tiers,
+    Tasks,
+    Cgroups,
+    Cpus,
+    Heatmap,
+    Topology,
+    Llcs,
+    Waits,
+    WaitBars,
+    Preempts,
+    RateChart,
+    Events,
+    Config,
+}
 
-    if let Some(bss) = &skel.maps.bss_data {
-        for s in &bss.global_stats {
-            // Sum all fields
-            total.nr_new_flow_dispatches += s.nr_new_flow_dispatches;
-            total.nr_old_flow_dispatches += s.nr_old_flow_dispatches;
-
-            for i in 0..crate::stats::TIER_NAMES.len() {
-                total.nr_tier_dispatches[i] += s.nr_tier_dispatches[i];
-                total.nr_starvation_preempts_tier[i] += s.nr_starvation_preempts_tier[i];
+impl View {
+    fn tab(self) -> Tab {
+        match self {
+            View::Overview | View::Events => Tab::Overview,
+            View::Tiers | View::Waits | View::WaitBars | View::Preempts | View::RateChart => {
+                Tab::Tiers
             }
+            View::Tasks | View::Cgroups => Tab::Tasks,
+            View::Cpus | View::Heatmap | View::Topology | View::Llcs => Tab::Cpus,
+            View::Config => Tab::Config,
+        }
+    }
+}
+
+/// What a left click does, recorded per screen area while drawing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Click {
+    Tab(Tab),
+    Sort(TopBy),
+}
+
+/// Top-level tabs, each grouping related views; Tab cycles, 1-5 pick
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Tab {
+    Overview,
+    Tiers,
+    Tasks,
+    Cpus,
+    Config,
+}
+
+impl Tab {
+    const ORDER: [Tab; 5] = [
+        Tab::Overview,
+        Tab::Tiers,
+        Tab::Tasks,
+        Tab::Cpus,
+        Tab::Config,
+    ];
+    const TITLES: [&str; 5] = ["1 Overview", "2 Tiers", "3 Tasks", "4 CPUs", "5 Config"];
+
+    fn next(self) -> Tab {
+        let i = Tab::ORDER.iter().position(|&t| t == self).unwrap_or(0);
+        Tab::ORDER[(i + 1) % Tab::ORDER.len()]
+    }
+
+    /// View the tab opens on
+    fn view(self) -> View {
+        match self {
+            Tab::Overview => View::Overview,
+            Tab::Tiers => View::Tiers,
+            Tab::Tasks => View::Tasks,
+            Tab::Cpus => View::Cpus,
+            Tab::Config => View::Config,
         }
     }
+}
 
-    total
+/// One visible line of the task view: a process, or one of its threads
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TaskLine {
+    Process(usize),
+    Thread(usize, usize),
 }
 
 /// TUI Application state
@@ -51,6 +145,149 @@ pub struct TuiApp {
     start_time: Instant,
     status_message: Option<(String, Instant)>,
     topology: TopologyInfo,
+    view: View,
+    /// Latest per-process sample (busiest first)
+    processes: Vec<ProcessStat>,
+    /// Group threads under their process (false = flat thread list)
+    grouped: bool,
+    /// Expanded processes, by tgid
+    expanded: HashSet<u32>,
+    /// Selected line and scroll offset of the task view
+    task_table: TableState,
+    /// Selected row and scroll offset of the CPU view
+    cpu_table: TableState,
+    /// Selected CPU of the topology view
+    topo_table: TableState,
+    /// Rows the scrolled table fit last frame: the PgUp/PgDn step
+    page: usize,
+    /// Sort of the task and tier tables (None = busiest process / tier
+    /// order), smallest first when `sort_asc`
+    sort: Option<TopBy>,
+    sort_asc: bool,
+    /// Tier table counters as per-second rates over the last tick (`d`)
+    /// instead of totals since the last reset
+    tier_rates: bool,
+    /// `/` filter on the task view (comm substring or pid), and whether the
+    /// prompt is taking keys
+    filter: String,
+    filter_editing: bool,
+    /// `?` help overlay is up
+    show_help: bool,
+    /// Click targets of the last frame (tabs, sortable headers)
+    clicks: Vec<(Rect, Click)>,
+    /// Display frozen on the last read (`p`), and a one-off read requested
+    /// (space) that works frozen or not
+    frozen: bool,
+    refresh: bool,
+    /// Cgroup tree built from the latest sample
+    cgroups: Vec<CgroupNode>,
+    /// Per-CPU rows then per-LLC totals (CPU view only)
+    cpus: Vec<CpuLoad>,
+    /// cpufreq MHz per CPU, read each tick while the CPU view is open
+    cpu_freq: Vec<Option<u32>>,
+    /// Percent busy per CPU over the last tick, and the runtimes it starts from
+    cpu_busy: Vec<f64>,
+    cpu_runtime_prev: Vec<u64>,
+    /// Per-LLC rates over the last tick, and the per-CPU counters they start from
+    llcs: Vec<LlcLoad>,
+    /// Per-NUMA-node rates, multi-node machines only
+    nodes: Vec<LlcLoad>,
+    llc_prev: Vec<cake_stats>,
+    /// Per-tier wait histograms (wait view only)
+    waits: WaitHist,
+    /// Loaded configuration (config tab)
+    config: String,
+    /// Live tunables in Tunable::ALL order, µs: as loaded, as running (the
+    /// control socket and this tab write them), and an edit not yet applied
+    /// to the selected row
+    tune_loaded: [u64; 3],
+    tune_live: [u64; 3],
+    tune_edit: Option<u64>,
+    tune_table: TableState,
+    /// BPF stats counting on (`s`, `scx_cake set stats`)
+    stats_on: bool,
+    /// BPF attach / exit state and managed task count for the status line
+    health: Health,
+    /// --config [keys] bindings that differ from the defaults (help popup)
+    remapped: Vec<(String, Action)>,
+    /// Threads pinned to a tier with `T` in the task view
+    pins: TierPins,
+    /// --alert thresholds, and which the last tick went over
+    alerts: Alerts,
+    /// Event log pane: the feed's latest events, oldest first; the
+    /// selection follows the newest unless scrolled up
+    events: Vec<events::Event>,
+    event_table: TableState,
+    /// Per-tier wait history for the sparklines, and the histograms it
+    /// continues from
+    wait_trend: WaitTrend,
+    wait_prev: WaitHist,
+    /// Wait histogram movement over the last tick (bar chart view)
+    wait_tick: WaitHist,
+    /// Per-tier dispatch rates for the rate chart
+    rate_trend: RateTrend,
+    /// BPF user_idle flag (latency policy relaxed)
+    user_idle: bool,
+    /// Per-second rates over the last tick, and the snapshot they start from
+    rates: Rates,
+    /// Percent of all CPUs' time each tier used over the last tick
+    cpu_share: [f64; 4],
+    /// Mean run queue depth over the last tick, and the gauges it starts from
+    depth: QueueDepth,
+    depth_prev: cake_dsq_depth,
+    rate_prev: cake_stats,
+    rate_at: Instant,
+}
+
+/// Ticks of wait history the sparklines keep
+const WAIT_HISTORY: usize = 240;
+
+/// Per-tier mean and max wait (µs) of each of the last WAIT_HISTORY ticks,
+/// oldest first
+#[derive(Default)]
+struct WaitTrend {
+    avg: [VecDeque<u64>; 4],
+    max: [VecDeque<u64>; 4],
+}
+
+impl WaitTrend {
+    /// Append one tick from the histogram movement over it
+    fn push(&mut self, delta: &WaitHist) {
+        for (t, hist) in delta.iter().enumerate() {
+            for (series, us) in [
+                (&mut self.avg[t], wait_mean_us(hist).round() as u64),
+                (&mut self.max[t], tier_wait_max_us(hist)),
+            ] {
+                if series.len() == WAIT_HISTORY {
+                    series.pop_front();
+                }
+                series.push_back(us);
+            }
+        }
+    }
+}
+
+/// Seconds of dispatch rates the rate chart spans
+const RATE_WINDOW_S: f64 = 300.0;
+
+/// Per-tier dispatches/s of each tick in the last RATE_WINDOW_S, keyed by
+/// seconds since the TUI started, oldest first
+#[derive(Default)]
+struct RateTrend {
+    points: VecDeque<(f64, [f64; 4])>,
+}
+
+impl RateTrend {
+    fn push(&mut self, at: f64, dispatches: [f64; 4]) {
+        self.points.push_back((at, dispatches));
+        while self
+            .points
+            .front()
+            .is_some_and(|&(t, _)| t < at - RATE_WINDOW_S)
+        {
+            self.points.pop_front();
+        }
+    }
 }
 
 impl TuiApp {
@@ -59,20 +296,289 @@ impl TuiApp {
             start_time: Instant::now(),
             status_message: None,
             topology,
+            view: View::Overview,
+            processes: Vec::new(),
+            grouped: true,
+            expanded: HashSet::new(),
+            task_table: TableState::default().with_selected(Some(0)),
+            cpu_table: TableState::default().with_selected(Some(0)),
+            topo_table: TableState::default().with_selected(Some(0)),
+            page: 1,
+            sort: None,
+            sort_asc: false,
+            tier_rates: false,
+            filter: String::new(),
+            filter_editing: false,
+            show_help: false,
+            clicks: Vec::new(),
+            frozen: false,
+            refresh: false,
+            cgroups: Vec::new(),
+            cpus: Vec::new(),
+            cpu_freq: Vec::new(),
+            cpu_busy: Vec::new(),
+            cpu_runtime_prev: Vec::new(),
+            llcs: Vec::new(),
+            nodes: Vec::new(),
+            llc_prev: Vec::new(),
+            waits: [[0; WAIT_BUCKETS]; 4],
+            config: String::new(),
+            tune_loaded: [0; 3],
+            tune_live: [0; 3],
+            tune_edit: None,
+            tune_table: TableState::default().with_selected(Some(0)),
+            stats_on: false,
+            health: Health::default(),
+            remapped: Vec::new(),
+            pins: TierPins::default(),
+            alerts: Alerts::default(),
+            events: Vec::new(),
+            event_table: TableState::default(),
+            wait_trend: WaitTrend::default(),
+            wait_prev: [[0; WAIT_BUCKETS]; 4],
+            wait_tick: [[0; WAIT_BUCKETS]; 4],
+            rate_trend: RateTrend::default(),
+            user_idle: false,
+            rates: Rates::default(),
+            cpu_share: [0.0; 4],
+            depth: QueueDepth::default(),
+            depth_prev: Default::default(),
+            rate_prev: Default::default(),
+            rate_at: Instant::now(),
         }
     }
 
-    /// Format uptime as "Xm Ys" or "Xh Ym"
-    fn format_uptime(&self) -> String {
-        let elapsed = self.start_time.elapsed();
-        let secs = elapsed.as_secs();
-        if secs < 3600 {
-            format!("{}m {}s", secs / 60, secs % 60)
+    /// Store a fresh task sample (and rebuild the cgroup tree if it is shown)
+    fn update_tasks(&mut self, procs: Vec<ProcessStat>) {
+        self.processes = procs;
+        let len = self.task_lines().len();
+        self.task_table
+            .select(Some(self.selected().min(len.saturating_sub(1))));
+        if self.view == View::Cgroups {
+            let paths = cgroup::cgroup_paths(std::path::Path::new(cgroup::CGROUP_ROOT));
+            self.cgroups = cgroup::build_tree(&self.processes, &paths);
+        }
+    }
+
+    /// Whether a thread passes the filter, by its own pid/comm or its
+    /// process's (so `/game` keeps all of the game's threads)
+    fn thread_visible(&self, pi: usize, ti: usize) -> bool {
+        let p = &self.processes[pi];
+        let t = &p.threads[ti];
+        task_filter_matches(&self.filter, t.pid, &t.comm)
+            || task_filter_matches(&self.filter, p.tgid, &p.comm)
+    }
+
+    /// Lines shown in the task view, in display order
+    fn task_lines(&self) -> Vec<TaskLine> {
+        let key = |by: TopBy, l: &TaskLine| match *l {
+            TaskLine::Thread(pi, ti) => by.key(&self.processes[pi].threads[ti]),
+            TaskLine::Process(pi) => by.process_key(&self.processes[pi]),
+        };
+        let mut lines = Vec::new();
+        if self.grouped {
+            let mut procs: Vec<usize> = (0..self.processes.len())
+                .filter(|&pi| {
+                    (0..self.processes[pi].threads.len()).any(|ti| self.thread_visible(pi, ti))
+                })
+                .collect();
+            if let Some(by) = self.sort {
+                let proc_key = |pi: usize| by.process_key(&self.processes[pi]);
+                procs.sort_by(|&a, &b| sort_cmp(proc_key(a), proc_key(b), self.sort_asc));
+            }
+            for pi in procs {
+                lines.push(TaskLine::Process(pi));
+                if self.expanded.contains(&self.processes[pi].tgid) {
+                    let mut threads: Vec<TaskLine> = (0..self.processes[pi].threads.len())
+                        .filter(|&ti| self.thread_visible(pi, ti))
+                        .map(|ti| TaskLine::Thread(pi, ti))
+                        .collect();
+                    if let Some(by) = self.sort {
+                        threads.sort_by(|a, b| sort_cmp(key(by, a), key(by, b), self.sort_asc));
+                    }
+                    lines.extend(threads);
+                }
+            }
         } else {
-            format!("{}h {}m", secs / 3600, (secs % 3600) / 60)
+            for (pi, p) in self.processes.iter().enumerate() {
+                lines.extend(
+                    (0..p.threads.len())
+                        .filter(|&ti| self.thread_visible(pi, ti))
+                        .map(|ti| TaskLine::Thread(pi, ti)),
+                );
+            }
+            // Flat view: busiest thread first regardless of process
+            let (by, asc) = match self.sort {
+                Some(by) => (by, self.sort_asc),
+                None => (TopBy::Cpu, false),
+            };
+            lines.sort_by(|a, b| sort_cmp(key(by, a), key(by, b), asc));
+        }
+        lines
+    }
+
+    /// One keypress at the `/` prompt: Enter keeps the filter, Esc drops it
+    fn edit_filter(&mut self, code: KeyCode) {
+        match code {
+            KeyCode::Enter => self.filter_editing = false,
+            KeyCode::Esc => {
+                self.filter.clear();
+                self.filter_editing = false;
+            }
+            KeyCode::Backspace => {
+                self.filter.pop();
+            }
+            KeyCode::Char(c) => self.filter.push(c),
+            _ => return,
+        }
+        self.task_table.select(Some(0));
+    }
+
+    /// Column header, marked when the tables are sorted by `by`
+    fn sort_header(&self, name: &str, by: TopBy) -> String {
+        match self.sort == Some(by) {
+            true => format!("{} {}", name, if self.sort_asc { "▲" } else { "▼" }),
+            false => name.to_string(),
+        }
+    }
+
+    /// Selected line in the task view
+    fn selected(&self) -> usize {
+        self.task_table.selected().unwrap_or(0)
+    }
+
+    /// Move the selection of the task or CPU table by `delta` rows, clamped
+    /// to the table; the render scrolls to keep it visible
+    fn scroll(&mut self, delta: isize) {
+        let len = match self.view {
+            View::Tasks => self.task_lines().len(),
+            View::Cpus => self.cpus.len(),
+            View::Topology => self.topology.nr_cpus.min(MAX_CPUS),
+            View::Config => Tunable::ALL.len(),
+            View::Events => self.events.len(),
+            _ => return,
+        };
+        // A pending edit belongs to the row it was made on
+        if self.view == View::Config {
+            self.tune_edit = None;
+        }
+        let state = match self.view {
+            View::Tasks => &mut self.task_table,
+            View::Topology => &mut self.topo_table,
+            View::Config => &mut self.tune_table,
+            View::Events => &mut self.event_table,
+            _ => &mut self.cpu_table,
+        };
+        let cur = state.selected().unwrap_or(0) as isize;
+        let last = len.saturating_sub(1) as isize;
+        state.select(Some(cur.saturating_add(delta).clamp(0, last) as usize));
+    }
+
+    /// Move the selected tunable's pending value by `steps` arrow-key steps
+    fn nudge_tunable(&mut self, steps: i64) {
+        let row = self.tune_table.selected().unwrap_or(0);
+        let us = self.tune_edit.unwrap_or(self.tune_live[row]);
+        self.tune_edit = Some(Tunable::ALL[row].nudge(us, steps));
+    }
+
+    /// Wheel scrolls the task / CPU table; a left click picks a tab, or sorts
+    /// by a column header (the sorted one again flips the direction)
+    fn mouse(&mut self, mouse: MouseEvent, tasks: &mut Option<TaskSampler>) {
+        match mouse.kind {
+            MouseEventKind::Down(_) if self.show_help => self.show_help = false,
+            _ if self.filter_editing || self.show_help => {}
+            MouseEventKind::ScrollUp => self.scroll(-3),
+            MouseEventKind::ScrollDown => self.scroll(3),
+            MouseEventKind::Down(MouseButton::Left) => {
+                let at = Position::new(mouse.column, mouse.row);
+                let click = self.clicks.iter().find(|(area, _)| area.contains(at));
+                match click.map(|&(_, click)| click) {
+                    Some(Click::Tab(tab)) => self.show(tab.view(), tasks),
+                    Some(Click::Sort(by)) if self.sort == Some(by) => {
+                        self.sort_asc = !self.sort_asc
+                    }
+                    Some(Click::Sort(by)) => {
+                        self.sort = Some(by);
+                        self.sort_asc = false;
+                    }
+                    None => {}
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// TIDs under the selected line: the thread, or every thread of a process
+    fn selected_pids(&self) -> Vec<u32> {
+        match self.task_lines().get(self.selected()) {
+            Some(TaskLine::Thread(pi, ti)) => vec![self.processes[*pi].threads[*ti].pid],
+            Some(TaskLine::Process(pi)) => {
+                self.processes[*pi].threads.iter().map(|t| t.pid).collect()
+            }
+            None => Vec::new(),
+        }
+    }
+
+    /// Expand/collapse the process under (or owning) the selected line
+    fn toggle_expand(&mut self) {
+        let lines = self.task_lines();
+        let pi = match lines.get(self.selected()) {
+            Some(TaskLine::Process(pi)) | Some(TaskLine::Thread(pi, _)) => *pi,
+            None => return,
+        };
+        let tgid = self.processes[pi].tgid;
+        if !self.expanded.remove(&tgid) {
+            self.expanded.insert(tgid);
+        }
+        // Keep the cursor on the process row after collapsing
+        if let Some(pos) = self
+            .task_lines()
+            .iter()
+            .position(|l| *l == TaskLine::Process(pi))
+        {
+            self.task_table.select(Some(pos));
+        }
+    }
+
+    /// Switch views, sampling tasks right away when the new one lists them
+    fn show(&mut self, view: View, tasks: &mut Option<TaskSampler>) {
+        self.view = view;
+        match tasks {
+            _ if !matches!(view, View::Tasks | View::Cgroups) => {}
+            Some(sampler) => {
+                if let Ok(procs) = sampler.sample() {
+                    self.update_tasks(procs);
+                }
+            }
+            None => self.set_status("✗ Task iterator not available"),
+        }
+    }
+
+    /// Open `view`, or go back to the overview if it is already open
+    /// Whether `action` does anything in the current view; a key bound to
+    /// several actions does the first one that does
+    fn applies(&self, action: Action) -> bool {
+        match action {
+            Action::Left | Action::Right | Action::Apply | Action::Restore => {
+                self.view == View::Config
+            }
+            Action::Expand => self.view == View::Tasks && self.grouped,
+            Action::Group | Action::PinTier => self.view == View::Tasks,
+            _ => true,
         }
     }
 
+    fn toggle(&mut self, view: View) {
+        self.view = match self.view == view {
+            true => View::Overview,
+            false => view,
+        };
+    }
+
+    fn format_uptime(&self) -> String {
+        format_uptime(self.start_time.elapsed())
+    }
+
     /// Set a temporary status message that disappears after 2 seconds
     fn set_status(&mut self, msg: &str) {
         self.status_message = Some((msg.to_string(), Instant::now()));
@@ -93,6 +599,9 @@ fn setup_terminal() -> Result<Terminal<CrosstermBackend<Stdout>>> {
     io::stdout()
         .execute(EnterAlternateScreen)
         .context("Failed to enter alternate screen")?;
+    io::stdout()
+        .execute(EnableMouseCapture)
+        .context("Failed to enable mouse capture")?;
     let backend = CrosstermBackend::new(io::stdout());
     Terminal::new(backend).context("Failed to create terminal")
 }
@@ -100,6 +609,9 @@ fn setup_terminal() -> Result<Terminal<CrosstermBackend<Stdout>>> {
 /// Restore terminal to normal mode
 fn restore_terminal() -> Result<()> {
     disable_raw_mode().context("Failed to disable raw mode")?;
+    io::stdout()
+        .execute(DisableMouseCapture)
+        .context("Failed to disable mouse capture")?;
     io::stdout()
         .execute(LeaveAlternateScreen)
         .context("Failed to leave alternate screen")?;
@@ -108,7 +620,7 @@ fn restore_terminal() -> Result<()> {
 
 /// Render a progress gauge inline for calibration progress
 /// Updates a single line in-place, no newlines until complete
-pub fn render_calibration_progress(current: usize, total: usize, is_complete: bool) {
+pub fn render_calibration_progress(current: usize, total: usize, is_complete: bool, color: bool) {
     use std::io::Write;
 
     if total == 0 {
@@ -117,11 +629,12 @@ pub fn render_calibration_progress(current: usize, total: usize, is_complete: bo
 
     let percent = ((current as f64 / total as f64) * 100.0) as u16;
 
-    // ANSI colors
-    let cyan = "\x1b[36m";
-    let green = "\x1b[32m";
-    let bold = "\x1b[1m";
-    let reset = "\x1b[0m";
+    // ANSI colors (all empty under --no-color / NO_COLOR)
+    let ansi = |seq: &'static str| if color { seq } else { "" };
+    let cyan = ansi("\x1b[36m");
+    let green = ansi("\x1b[32m");
+    let bold = ansi("\x1b[1m");
+    let reset = ansi("\x1b[0m");
 
     // Build progress bar (40 chars wide)
     let bar_width = 40;
@@ -173,6 +686,7 @@ pub struct StartupParams<'a> {
     pub profile: &'a str,
     pub quantum: u64,
     pub starvation: u64,
+    pub theme: Theme,
 }
 
 /// Render a beautiful one-time startup screen using Ratatui
@@ -216,6 +730,7 @@ pub fn render_startup_screen(params: StartupParams) -> Result<()> {
 
         let t_duration = tachyonfx::Duration::from_millis(elapsed_ms);
         fx_manager.process_effects(t_duration, &mut buffer, area);
+        params.theme.apply(&mut buffer);
 
         // Print frame starting from top of alternate screen
         execute!(io::stdout(), MoveTo(0, 0))?;
@@ -262,6 +777,7 @@ pub fn render_startup_screen(params: StartupParams) -> Result<()> {
     // Ensure animation is at 100% completion for final print
     let final_duration = tachyonfx::Duration::from_millis(duration_ms);
     fx_manager.process_effects(final_duration, &mut buffer, area);
+    params.theme.apply(&mut buffer);
 
     for y in 0..total_height {
         let mut last_style = Style::default();
@@ -330,6 +846,9 @@ impl ToAnsi for Style {
         if self.add_modifier.contains(Modifier::DIM) {
             seq.push_str(";2");
         }
+        if self.add_modifier.contains(Modifier::REVERSED) {
+            seq.push_str(";7");
+        }
 
         seq.push('m');
         seq
@@ -428,7 +947,7 @@ fn render_startup_widgets(
     let hardware_rows = vec![
         Row::new(vec![
             Cell::from("CPUs").style(Style::default().fg(Color::Cyan)),
-            Cell::from(params.topology.nr_cpus.to_string()),
+            Cell::from(params.topology.nr_online().to_string()),
         ]),
         Row::new(vec![
             Cell::from("SMT").style(Style::default().fg(Color::Cyan)),
@@ -529,24 +1048,20 @@ fn render_startup_widgets(
 
 /// Compact CPU topology schematic for Left Column
 fn build_cpu_topology_grid_compact(topology: &TopologyInfo) -> Paragraph<'static> {
-    let nr_cpus = topology.nr_cpus.min(64);
+    let nr_cpus = topology.nr_cpus.min(MAX_CPUS);
     let mut lines = Vec::new();
 
     lines.push(Line::from(""));
 
     let mut current_line = Vec::new();
     for cpu in 0..nr_cpus {
-        // Dot indicator for core type
-        let symbol = if topology.cpu_is_big.get(cpu).copied().unwrap_or(0) != 0 {
-            "◆" // P-core
-        } else {
-            "◇" // E-core/Uniform
-        };
-
-        let color = if topology.cpu_is_big.get(cpu).copied().unwrap_or(0) != 0 {
-            Color::Magenta
+        // Dot indicator for core type; offline ids keep their slot
+        let (symbol, color) = if !topology.online_mask.test(cpu) {
+            ("·", Color::DarkGray)
+        } else if topology.cpu_is_big.get(cpu).copied().unwrap_or(0) != 0 {
+            ("◆", Color::Magenta) // P-core
         } else {
-            Color::Cyan
+            ("◇", Color::Cyan) // E-core/Uniform
         };
 
         current_line.push(Span::styled(
@@ -771,7 +1286,33 @@ impl<'a> Widget for LatencyTable<'a> {
 }
 
 /// Format stats as a copyable text string
-fn format_stats_for_clipboard(stats: &cake_stats, uptime: &str) -> String {
+/// Format uptime as "Xm Ys" or "Xh Ym"
+pub fn format_uptime(elapsed: Duration) -> String {
+    let secs = elapsed.as_secs();
+    if secs < 3600 {
+        format!("{}m {}s", secs / 60, secs % 60)
+    } else {
+        format!("{}h {}m", secs / 3600, (secs % 3600) / 60)
+    }
+}
+
+/// Write the 'e' snapshot: the text report and a JSON object side by side,
+/// named by unix time so repeated snapshots never overwrite each other
+fn write_snapshot(dir: &Path, text: &str, json: &str) -> Result<PathBuf> {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    let base = dir.join(format!("scx_cake-{}", secs));
+    for (ext, body) in [("txt", text), ("json", json)] {
+        let path = base.with_extension(ext);
+        std::fs::write(&path, format!("{}\n", body.trim_end()))
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+    }
+    Ok(base)
+}
+
+/// Plain-text stats report: the TUI's 'c' copy, 'e' snapshot and the SIGUSR1 log dump
+pub fn format_stats_for_clipboard(stats: &cake_stats, uptime: &str) -> String {
     let total_dispatches = stats.nr_new_flow_dispatches + stats.nr_old_flow_dispatches;
     let new_pct = if total_dispatches > 0 {
         (stats.nr_new_flow_dispatches as f64 / total_dispatches as f64) * 100.0
@@ -789,29 +1330,150 @@ fn format_stats_for_clipboard(stats: &cake_stats, uptime: &str) -> String {
         total_dispatches, new_pct
     ));
 
-    output.push_str("Tier           Dispatches    StarvPreempt\n");
-    output.push_str("───────────────────────────────────────────\n");
+    let split: Vec<String> = TIER_NAMES
+        .iter()
+        .zip(runtime_split(stats))
+        .map(|(name, pct)| format!("{} {:.1}%", name, pct))
+        .collect();
+    output.push_str(&format!("CPU time by tier: {}\n\n", split.join(", ")));
+
+    output.push_str(&format!(
+        "SMT spread: {} (co-located: {})\nRebalance: {} passes, {} tasks moved\n\
+         Launch warm-ups: {} ({} dispatches)\nIdle promotions: {}\n\
+         Migrations: {} in-LLC, {} cross-LLC, {} cross-node, {} P→E, {} E→P\n\
+         Wakeup placement: {}\n\n",
+        stats.nr_smt_spread,
+        stats.nr_smt_colocated,
+        stats.nr_rebalance_passes,
+        stats.nr_rebalance_moves,
+        stats.nr_warmup_launches,
+        stats.nr_warmup_dispatches,
+        stats.nr_idle_promotions,
+        stats.nr_migrate_local,
+        stats.nr_migrate_cross_llc.iter().sum::<u64>(),
+        stats.nr_migrate_cross_node,
+        stats.nr_migrate_p_to_e,
+        stats.nr_migrate_e_to_p,
+        format_select_outcomes(stats)
+    ));
+
+    output.push_str(
+        "Tier           Dispatches    StarvPreempt    ImmuneSkip    CapDefer    CapQueue\n",
+    );
+    output.push_str(
+        "───────────────────────────────────────────────────────────────────────────────\n",
+    );
     for (i, name) in TIER_NAMES.iter().enumerate() {
         output.push_str(&format!(
-            "{:12}   {:>10}    {:>12}\n",
-            name, stats.nr_tier_dispatches[i], stats.nr_starvation_preempts_tier[i]
+            "{:12}   {:>10}    {:>12}    {:>10}    {:>8}    {:>8.1}\n",
+            name,
+            stats.nr_tier_dispatches[i],
+            stats.nr_starvation_preempts_tier[i],
+            stats.nr_starvation_immune_tier[i],
+            stats.nr_cap_deferrals[i],
+            cap_queue_len(stats, i)
         ));
     }
 
+    output.push_str("\nStarvation preempts (waiter tier × preempted tier):\n");
+    output.push_str(&format_preempt_matrix(stats));
+
     output
 }
 
+/// Below this many rows or columns the tabbed layout gives way to
+/// draw_compact rather than cutting widgets off mid-render
+const COMPACT_ROWS: u16 = 30;
+const COMPACT_COLS: u16 = 80;
+
+/// Small-terminal layout: one summary line, one borderless tier table, the
+/// status line and the keys. Views still switch underneath and come back
+/// once the terminal is large enough
+fn draw_compact(frame: &mut Frame, app: &TuiApp) {
+    let layout = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(1),
+            Constraint::Min(2),
+            Constraint::Length(1),
+            Constraint::Length(1),
+        ])
+        .split(frame.area());
+
+    let summary = Paragraph::new(format!(
+        " scx_cake  {}  │  {:.0} disp/s  │  {} tasks{}",
+        app.format_uptime(),
+        app.rates.dispatches.iter().sum::<f64>(),
+        app.health.nr_tasks,
+        if app.frozen { "  │  ⏸ FROZEN" } else { "" }
+    ))
+    .style(
+        Style::default()
+            .fg(Color::Cyan)
+            .add_modifier(Modifier::BOLD),
+    );
+    frame.render_widget(summary, layout[0]);
+
+    let header = ["Tier", "CPU%", "Disp/s", "Wait µs", "p99 µs"].map(|h| {
+        Cell::from(h).style(
+            Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(Modifier::BOLD),
+        )
+    });
+    let rows: Vec<Row> =
+        (0..TIER_NAMES.len())
+            .map(|t| {
+                Row::new(vec![
+                    Cell::from(TIER_NAMES[t]).style(tier_style(t)),
+                    Cell::from(format!("{:.1}", app.cpu_share[t])),
+                    Cell::from(format!("{:.0}", app.rates.dispatches[t])),
+                    Cell::from(format!("{:.0}", wait_mean_us(&app.wait_tick[t])))
+                        .style(alert_style(app, t, Some(AlertMetric::Wait))),
+                    Cell::from(format!("{:.0}", wait_percentile(&app.wait_tick[t], 99.0)))
+                        .style(alert_style(app, t, Some(AlertMetric::P99))),
+                ])
+            })
+            .collect();
+    let table = Table::new(rows, [11, 6, 8, 8, 8].map(Constraint::Length)).header(Row::new(header));
+    frame.render_widget(table, layout[1]);
+
+    draw_health(frame, app, layout[2]);
+
+    let keys = match app.get_status() {
+        Some(status) => format!(" {}", status),
+        None => format!(
+            " [q] Quit  [?] Help  [p] Freeze  (all views from {}x{})",
+            COMPACT_COLS, COMPACT_ROWS
+        ),
+    };
+    frame.render_widget(
+        Paragraph::new(keys).style(Style::default().fg(Color::DarkGray)),
+        layout[3],
+    );
+}
+
 /// Draw the UI
-fn draw_ui(frame: &mut Frame, app: &TuiApp, stats: &cake_stats) {
+fn draw_ui(frame: &mut Frame, app: &mut TuiApp, stats: &cake_stats) {
     let area = frame.area();
+    app.clicks.clear();
+
+    if area.height < COMPACT_ROWS || area.width < COMPACT_COLS {
+        draw_compact(frame, app);
+        if app.show_help {
+            draw_help(frame, &app.remapped);
+        }
+        return;
+    }
 
-    // Create main layout: header, stats table, footer
+    // Create main layout: header, stats table, status line, footer
     let layout = Layout::default()
         .direction(Direction::Vertical)
         .constraints([
             Constraint::Length(3), // Header
-            Constraint::Min(10),   // Stats table
-            Constraint::Length(5), // Summary
+            Constraint::Length(1), // Tabs
+            Constraint::Min(10),   // Active view
+            Constraint::Length(1), // Status line
             Constraint::Length(3), // Footer
         ])
         .split(area);
@@ -827,7 +1489,7 @@ fn draw_ui(frame: &mut Frame, app: &TuiApp, stats: &cake_stats) {
     // Build topology info string
     let topo_info = format!(
         "CPUs: {} {}{}{}",
-        app.topology.nr_cpus,
+        app.topology.nr_online(),
         if app.topology.has_dual_ccd {
             "[Dual-CCD]"
         } else {
@@ -846,11 +1508,16 @@ fn draw_ui(frame: &mut Frame, app: &TuiApp, stats: &cake_stats) {
     );
 
     let header_text = format!(
-        " {}  │  Dispatches: {} ({:.1}% new)  │  Uptime: {}",
+        " {}  │  Dispatches: {} ({:.1}% new)  │  Uptime: {}{}",
         topo_info,
         total_dispatches,
         new_pct,
-        app.format_uptime()
+        app.format_uptime(),
+        if app.frozen {
+            "  │  ⏸ FROZEN ([p] resume, [Space] refresh)"
+        } else {
+            ""
+        }
     );
     let header = Paragraph::new(header_text).block(
         Block::default()
@@ -865,52 +1532,306 @@ fn draw_ui(frame: &mut Frame, app: &TuiApp, stats: &cake_stats) {
     );
     frame.render_widget(header, layout[0]);
 
-    // --- Stats Table ---
-    let header_cells = ["Tier", "Dispatches", "StarvPreempt"].iter().map(|h| {
-        Cell::from(*h).style(
+    // --- Tabs ---
+    let tab = app.view.tab();
+    let tabs = Tabs::new(Tab::TITLES)
+        .select(Tab::ORDER.iter().position(|&t| t == tab))
+        .style(Style::default().fg(Color::DarkGray))
+        .highlight_style(
+            Style::default()
+                .fg(Color::Cyan)
+                .add_modifier(Modifier::BOLD | Modifier::REVERSED),
+        );
+    frame.render_widget(tabs, layout[1]);
+    // Each tab draws as " title " with a one-cell divider after it
+    let mut x = layout[1].x;
+    for (tab, title) in Tab::ORDER.into_iter().zip(Tab::TITLES) {
+        let width = title.chars().count() as u16 + 2;
+        let area = Rect::new(x, layout[1].y, width, 1).intersection(layout[1]);
+        app.clicks.push((area, Click::Tab(tab)));
+        x = x.saturating_add(width + 1);
+    }
+
+    // --- Active view ---
+    match app.view {
+        View::Overview => draw_overview(frame, app, stats, layout[2]),
+        View::Tiers => draw_tier_table(frame, app, stats, layout[2]),
+        View::Tasks => draw_task_view(frame, app, layout[2]),
+        View::Cgroups => draw_cgroup_view(frame, app, layout[2]),
+        View::Cpus => draw_cpu_view(frame, app, layout[2]),
+        View::Heatmap => draw_heatmap_view(frame, app, layout[2]),
+        View::Topology => draw_topology_view(frame, app, layout[2]),
+        View::Llcs => draw_llc_view(frame, app, layout[2]),
+        View::Waits => draw_wait_view(frame, app, layout[2]),
+        View::WaitBars => draw_wait_bars(frame, app, layout[2]),
+        View::Preempts => draw_preempt_view(frame, stats, layout[2]),
+        View::RateChart => draw_rate_chart(frame, app, layout[2]),
+        View::Events => draw_event_view(frame, &app.events, &mut app.event_table, layout[2]),
+        View::Config => draw_config_view(frame, app, layout[2]),
+    }
+
+    draw_health(frame, app, layout[3]);
+
+    // --- Footer (key bindings + status) ---
+    let keys = match app.view {
+        View::Overview => {
+            "[p] Freeze  [Space] Refresh  [c] Copy  [e] Snapshot  [r] Reset  [s] Pause"
+        }
+        View::Tiers => {
+            "[o/O] Sort  [d] Rates/Totals  [w] Waits  [x] Preempts  [a] Rate chart  [r] Reset  [s] Pause"
+        }
+        View::Tasks => {
+            "[↑↓/PgUp/PgDn] Scroll  [Enter] Expand  [g] Group  [o/O] Sort  [/] Filter  [T] Pin tier  [t] Cgroups"
+        }
+        View::Cgroups => "[t] Overview",
+        View::Cpus => "[↑↓/PgUp/PgDn] Scroll  [h] Heatmap  [l] Topology  [L] LLCs  [r] Reset",
+        View::Heatmap => "[h] Overview",
+        View::Topology => "[↑↓/PgUp/PgDn] Select CPU  [l] Overview",
+        View::Llcs => "[L] Overview  [p] Freeze",
+        View::Waits => "[w] Overview  [b] Bar chart  [r] Reset",
+        View::WaitBars => "[b] Overview  [w] Table",
+        View::Preempts => "[x] Overview  [r] Reset",
+        View::RateChart => "[a] Overview  [p] Freeze",
+        View::Events => "[↑↓/PgUp/PgDn] Scroll  [End] Follow newest  [p] Freeze  [v] Overview",
+        View::Config => "[↑↓] Select  [←→] Adjust  [Enter] Apply  [Del] Loaded value  [s] Pause",
+    };
+    let keys = match app.filter_editing {
+        true => format!(" Filter: /{}▏  [Enter] Keep  [Esc] Clear", app.filter),
+        false => format!(" [q] Quit  [?] Help  [Tab/1-5] Tabs  {}", keys),
+    };
+    let footer_text = match app.get_status() {
+        Some(status) => format!("{}  │  {}", keys, status),
+        None => keys,
+    };
+    let (fg_color, border_color) = if app.get_status().is_some() {
+        (Color::Green, Color::Green)
+    } else {
+        (Color::DarkGray, Color::DarkGray)
+    };
+    let footer = Paragraph::new(footer_text)
+        .style(Style::default().fg(fg_color))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(border_color)),
+        );
+    frame.render_widget(footer, layout[4]);
+
+    if app.show_help {
+        draw_help(frame, &app.remapped);
+    }
+}
+
+/// `?` overlay: every key, then what each tab and tier means
+const HELP: &[(&str, &str)] = &[
+    ("Tab / 1-5", "Next tab / jump to a tab"),
+    ("t", "Tasks, then cgroups, then back to the overview"),
+    ("h  l", "CPU heatmap / detected topology"),
+    ("L", "Per-LLC (CCD) busy %, dispatches and wait"),
+    ("w  x", "Wait histograms / starvation preempt matrix"),
+    ("b", "Wait histograms as live bar charts"),
+    ("a", "Dispatch rates, total and per tier, last 5 min"),
+    ("v", "Event log: tier changes, new tasks, starvation"),
+    ("↑↓ j k", "Move the selection (tasks, CPUs, topology)"),
+    ("PgUp PgDn Home End", "Scroll a page / to either end"),
+    ("gg G", "First / last row, like Home / End"),
+    ("Enter", "Expand or collapse the selected process"),
+    ("g", "Group threads under processes, or list them flat"),
+    ("T", "Pin the selected thread or process to the next tier"),
+    ("o  O", "Sort by CPU, wait, runs / flip the direction"),
+    ("d", "Tier table: per-second rates or totals"),
+    ("/", "Filter tasks by name or PID (Esc clears)"),
+    ("r", "Reset stats (new epoch, BPF untouched)"),
+    ("s", "Pause or resume BPF stats counting"),
+    ("p", "Freeze or unfreeze the display"),
+    ("Space", "Refresh now, frozen or not"),
+    (
+        "←→ h l Enter Del",
+        "Config tab: adjust, apply, restore a tunable",
+    ),
+    ("c", "Copy the stats report to the clipboard"),
+    ("e", "Save the report and a JSON snapshot to files"),
+    ("?", "Show or hide this help"),
+    ("q  Esc", "Quit"),
+    ("", ""),
+    ("Overview", "Summary, wait history, heatmap, event log"),
+    ("Tiers", "Per-tier counters, waits, preempts, rate chart"),
+    ("Tasks", "Processes and threads, or the cgroup tree"),
+    (
+        "CPUs",
+        "Per-CPU counters with LLC totals, heatmap, topology",
+    ),
+    ("Config", "Live tunables (editable), loaded tier plan"),
+    ("", ""),
+    (
+        "Critical",
+        "Runs < 100µs: IRQs, input, audio — first in line",
+    ),
+    (
+        "Interactive",
+        "Runs < 2ms: compositor, game logic, short workers",
+    ),
+    ("Frame", "Runs < 8ms: render threads, video encode"),
+    ("Bulk", "Runs ≥ 8ms: builds, indexing — yields to the rest"),
+];
+
+/// Status line: whether cake's struct_ops is still what the kernel runs,
+/// how many tasks it manages and whether stats are counting, so numbers
+/// left over from an aborted scheduler never pass for live ones
+fn draw_health(frame: &mut Frame, app: &TuiApp, area: Rect) {
+    let health = &app.health;
+    if let Some(reason) = &health.exited {
+        let line = Paragraph::new(format!(
+            " ✗ BPF scheduler exited: {}  │  numbers are from before the exit  │  [q] Quit",
+            reason
+        ))
+        .style(
+            Style::default()
+                .fg(Color::White)
+                .bg(Color::Red)
+                .add_modifier(Modifier::BOLD),
+        );
+        frame.render_widget(line, area);
+        return;
+    }
+    let state = match health.attached() {
+        true => Span::styled(
+            format!(" ● {} attached (sched_ext {})", health.ops, health.state),
+            Style::default().fg(Color::Green),
+        ),
+        false => Span::styled(
+            format!(
+                " ⚠ struct_ops not active: sched_ext {}, root ops '{}'",
+                health.state, health.ops
+            ),
             Style::default()
                 .fg(Color::Yellow)
                 .add_modifier(Modifier::BOLD),
-        )
-    });
-    let header_row = Row::new(header_cells).height(1);
+        ),
+    };
+    let stats = match app.stats_on {
+        true => Span::styled("Stats on", Style::default().fg(Color::Green)),
+        false => Span::styled(
+            "Stats off: counters frozen ([s] resume)",
+            Style::default().fg(Color::Yellow),
+        ),
+    };
+    let sep = || Span::styled("  │  ", Style::default().fg(Color::DarkGray));
+    let line = Line::from(vec![
+        state,
+        sep(),
+        Span::raw(format!("{} tasks managed", health.nr_tasks)),
+        sep(),
+        stats,
+    ]);
+    frame.render_widget(Paragraph::new(line), area);
+}
 
-    let rows: Vec<Row> = TIER_NAMES
+/// Centered help popup over whatever view is open
+fn draw_help(frame: &mut Frame, remapped: &[(String, Action)]) {
+    let tiers = HELP.len() - TIER_NAMES.len();
+    let mut lines: Vec<Line> = HELP
         .iter()
         .enumerate()
-        .map(|(i, name)| {
-            let cells = vec![
-                Cell::from(*name).style(tier_style(i)),
-                Cell::from(format!("{}", stats.nr_tier_dispatches[i])),
-                Cell::from(format!("{}", stats.nr_starvation_preempts_tier[i])),
-            ];
-            Row::new(cells).height(1)
+        .map(|(i, (key, what))| {
+            let style = match i.checked_sub(tiers) {
+                Some(t) => tier_style(t),
+                None => Style::default()
+                    .fg(Color::Yellow)
+                    .add_modifier(Modifier::BOLD),
+            };
+            Line::from(vec![
+                Span::styled(format!(" {:<20}", key), style),
+                Span::raw(*what),
+            ])
         })
         .collect();
+    if !remapped.is_empty() {
+        lines.push(Line::raw(""));
+        lines.push(Line::raw(" Remapped in the --config [keys] table:"));
+        lines.extend(remapped.iter().map(|(key, action)| {
+            Line::from(vec![
+                Span::styled(
+                    format!(" {:<20}", key),
+                    Style::default()
+                        .fg(Color::Yellow)
+                        .add_modifier(Modifier::BOLD),
+                ),
+                Span::raw(
+                    action
+                        .to_possible_value()
+                        .map_or(String::new(), |v| v.get_name().to_string()),
+                ),
+            ])
+        }));
+    }
 
-    let table = Table::new(
-        rows,
-        [
-            Constraint::Length(12),
-            Constraint::Length(12),
-            Constraint::Length(14),
-        ],
-    )
-    .header(header_row)
-    .block(
+    let area = frame.area();
+    let width = 76.min(area.width);
+    let height = (lines.len() as u16 + 2).min(area.height);
+    let popup = Rect::new(
+        area.x + (area.width - width) / 2,
+        area.y + (area.height - height) / 2,
+        width,
+        height,
+    );
+    let help = Paragraph::new(lines).block(
         Block::default()
-            .title(" Per-Tier Statistics ")
+            .title(" Help (any key closes) ")
             .borders(Borders::ALL)
-            .border_style(Style::default().fg(Color::Blue)),
+            .border_type(BorderType::Rounded)
+            .border_style(Style::default().fg(Color::Cyan)),
     );
-    frame.render_widget(table, layout[1]);
+    frame.render_widget(Clear, popup);
+    frame.render_widget(help, popup);
+}
 
-    // --- Summary ---
+/// Landing tab: the run summary, per-tier wait history and the CPU heatmap
+fn draw_overview(frame: &mut Frame, app: &TuiApp, stats: &cake_stats, area: Rect) {
+    let layout = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(7),  // Summary
+            Constraint::Length(10), // Wait history
+            Constraint::Min(4),     // Heatmap
+        ])
+        .split(area);
     let total_starvation: u64 = stats.nr_starvation_preempts_tier.iter().sum();
     let summary_text = format!(
-        " Dispatches: {} | Starvation preempts: {}",
+        " Dispatches: {} | Starvation preempts: {} | SMT spread: {} (co-located: {})\n \
+         Rebalance: {} passes, {} tasks moved | Launch warm-ups: {} ({} dispatches)\n \
+         User: {} | Idle Bulk→Frame promotions: {} ({:.1}/s) | Avg wait: {:.1}µs\n \
+         Migrations/s: {:.1} in-LLC | {:.1} cross-LLC ({}) | {:.1} cross-node | \
+         P→E {:.1}, E→P {:.1}\n \
+         Wakeup placement: {}",
         stats.nr_new_flow_dispatches + stats.nr_old_flow_dispatches,
-        total_starvation
+        total_starvation,
+        stats.nr_smt_spread,
+        stats.nr_smt_colocated,
+        stats.nr_rebalance_passes,
+        stats.nr_rebalance_moves,
+        stats.nr_warmup_launches,
+        stats.nr_warmup_dispatches,
+        if app.user_idle {
+            "idle (policy relaxed)"
+        } else {
+            "active"
+        },
+        stats.nr_idle_promotions,
+        app.rates.promotions,
+        avg_wait_us(stats),
+        app.rates.migrate_local,
+        app.rates.migrate_cross_llc.iter().sum::<f64>(),
+        TIER_NAMES
+            .iter()
+            .zip(app.rates.migrate_cross_llc)
+            .map(|(name, r)| format!("{} {:.1}", name, r))
+            .collect::<Vec<_>>()
+            .join(", "),
+        app.rates.migrate_cross_node,
+        app.rates.migrate_p_to_e,
+        app.rates.migrate_e_to_p,
+        format_select_outcomes(stats)
     );
 
     let summary = Paragraph::new(summary_text).block(
@@ -919,111 +1840,2026 @@ fn draw_ui(frame: &mut Frame, app: &TuiApp, stats: &cake_stats) {
             .borders(Borders::ALL)
             .border_style(Style::default().fg(Color::Blue)),
     );
-    frame.render_widget(summary, layout[2]);
-
-    // --- Footer (key bindings + status) ---
-    let footer_text = match app.get_status() {
-        Some(status) => format!(" [q] Quit  [c] Copy  [r] Reset  │  {}", status),
-        None => " [q] Quit  [c] Copy to clipboard  [r] Reset stats".to_string(),
-    };
-    let (fg_color, border_color) = if app.get_status().is_some() {
-        (Color::Green, Color::Green)
-    } else {
-        (Color::DarkGray, Color::DarkGray)
-    };
-    let footer = Paragraph::new(footer_text)
-        .style(Style::default().fg(fg_color))
-        .block(
-            Block::default()
-                .borders(Borders::ALL)
-                .border_style(Style::default().fg(border_color)),
-        );
-    frame.render_widget(footer, layout[3]);
+    frame.render_widget(summary, layout[0]);
+    draw_wait_trend(frame, app, layout[1]);
+    draw_heatmap_view(frame, app, layout[2]);
 }
 
-/// Get color style for a tier
-fn tier_style(tier: usize) -> Style {
-    match tier {
-        0 => Style::default()
-            .fg(Color::Cyan)
-            .add_modifier(Modifier::BOLD), // Critical (<100µs)
-        1 => Style::default().fg(Color::Green), // Interactive (<2ms)
-        2 => Style::default().fg(Color::Yellow), // Frame (<8ms)
-        3 => Style::default().fg(Color::DarkGray), // Bulk (≥8ms)
-        _ => Style::default(),
-    }
-}
+/// Configuration the scheduler was loaded with, and what `scx_cake set`
+/// changed since
+fn draw_config_view(frame: &mut Frame, app: &mut TuiApp, area: Rect) {
+    let split = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(6), Constraint::Min(5)])
+        .split(area);
 
-/// Run the TUI event loop
-pub fn run_tui(
-    skel: &mut BpfSkel,
-    shutdown: Arc<AtomicBool>,
-    interval_secs: u64,
-    topology: TopologyInfo,
-) -> Result<()> {
-    let mut terminal = setup_terminal()?;
+    let header_cells = ["Tunable", "Loaded µs", "Live µs", "New µs", "Range µs"]
+        .iter()
+        .map(|h| {
+            Cell::from(*h).style(
+                Style::default()
+                    .fg(Color::Yellow)
+                    .add_modifier(Modifier::BOLD),
+            )
+        });
+    let header_row = Row::new(header_cells).height(1);
+    let selected = app.tune_table.selected().unwrap_or(0);
+    let rows: Vec<Row> = Tunable::ALL
+        .iter()
+        .enumerate()
+        .map(|(i, t)| {
+            let (lo, hi) = t.range();
+            let edit = match app.tune_edit {
+                Some(us) if i == selected => us.to_string(),
+                _ => String::new(),
+            };
+            let live_style = match app.tune_live[i] == app.tune_loaded[i] {
+                true => Style::default(),
+                false => Style::default().fg(Color::Yellow),
+            };
+            Row::new(vec![
+                Cell::from(t.name()),
+                Cell::from(app.tune_loaded[i].to_string()),
+                Cell::from(app.tune_live[i].to_string()).style(live_style),
+                Cell::from(edit).style(Style::default().fg(Color::Green)),
+                Cell::from(format!("{}-{}", lo, hi)),
+            ])
+        })
+        .collect();
+    let tunables = Table::new(
+        rows,
+        [
+            Constraint::Length(16),
+            Constraint::Length(11),
+            Constraint::Length(11),
+            Constraint::Length(11),
+            Constraint::Length(16),
+        ],
+    )
+    .header(header_row)
+    .block(
+        Block::default()
+            .title(format!(
+                " Live Tunables (stats {}) ",
+                if app.stats_on { "on" } else { "off" }
+            ))
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(Color::Blue)),
+    )
+    .row_highlight_style(Style::default().bg(Color::DarkGray));
+    frame.render_stateful_widget(tunables, split[0], &mut app.tune_table);
+
+    let config = Paragraph::new(app.config.as_str()).block(
+        Block::default()
+            .title(" Configuration ")
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(Color::Blue)),
+    );
+    frame.render_widget(config, split[1]);
+}
+
+/// Cgroup hierarchy annotated with tier distribution, CPU share, dispatches and wait
+fn draw_cgroup_view(frame: &mut Frame, app: &TuiApp, area: Rect) {
+    let header_cells = [
+        "Cgroup",
+        "Tasks",
+        "CPU%",
+        "Runs/s",
+        "Wait ms/s",
+        "AvgWait µs",
+        "T0/T1/T2/T3",
+    ]
+    .iter()
+    .map(|h| {
+        Cell::from(*h).style(
+            Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(Modifier::BOLD),
+        )
+    });
+    let header_row = Row::new(header_cells).height(1);
+
+    let rows: Vec<Row> = app
+        .cgroups
+        .iter()
+        .map(|n| {
+            // Colour each cgroup by its dominant tier
+            let dominant = (0..4).max_by_key(|&i| n.tier_counts[i]).unwrap_or(0);
+            Row::new(vec![
+                Cell::from(format!("{}{}", "  ".repeat(n.depth), n.name())),
+                Cell::from(n.tasks.to_string()),
+                Cell::from(format!("{:.1}", n.cpu_share)),
+                Cell::from(format!("{:.0}", n.runs_per_s)),
+                Cell::from(format!("{:.2}", n.wait_ms_per_s)),
+                Cell::from(format!("{:.1}", n.avg_wait_us())),
+                Cell::from(n.tier_counts.map(|c| c.to_string()).join("/"))
+                    .style(tier_style(dominant)),
+            ])
+        })
+        .collect();
+
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Min(32),
+            Constraint::Length(7),
+            Constraint::Length(7),
+            Constraint::Length(9),
+            Constraint::Length(10),
+            Constraint::Length(11),
+            Constraint::Length(16),
+        ],
+    )
+    .header(header_row)
+    .block(
+        Block::default()
+            .title(" Cgroups (CPU% = share of cake-managed CPU time) ")
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(Color::Blue)),
+    );
+    frame.render_widget(table, area);
+}
+
+/// Per-CPU dispatch / preempt / wait table with per-LLC totals at the end
+fn draw_cpu_view(frame: &mut Frame, app: &mut TuiApp, area: Rect) {
+    let header_cells = [
+        "CPU",
+        "LLC",
+        "Dispatches",
+        "StarvPreempt",
+        "Runs",
+        "AvgWait µs",
+        "LocalQ",
+        "MHz",
+    ]
+    .iter()
+    .map(|h| {
+        Cell::from(*h).style(
+            Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(Modifier::BOLD),
+        )
+    });
+    let header_row = Row::new(header_cells).height(1);
+
+    let rows: Vec<Row> = app
+        .cpus
+        .iter()
+        .map(|c| {
+            let style = match c.label.starts_with("llc") {
+                true => Style::default().add_modifier(Modifier::BOLD),
+                false => Style::default(),
+            };
+            Row::new(vec![
+                Cell::from(c.label.clone()),
+                Cell::from(c.llc.to_string()),
+                Cell::from(c.dispatches.to_string()),
+                Cell::from(c.preempts.to_string()),
+                Cell::from(c.runs.to_string()),
+                Cell::from(format!("{:.1}", c.avg_wait_us)),
+                Cell::from(format!("{:.1}", c.queued)),
+                Cell::from(c.freq_mhz.map_or("-".to_string(), |f| f.to_string())),
+            ])
+            .style(style)
+        })
+        .collect();
+
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Length(8),
+            Constraint::Length(5),
+            Constraint::Length(12),
+            Constraint::Length(14),
+            Constraint::Length(12),
+            Constraint::Length(12),
+            Constraint::Length(8),
+            Constraint::Length(6),
+        ],
+    )
+    .header(header_row)
+    .block(
+        Block::default()
+            .title(format!(
+                " Per-CPU Statistics (since start / reset) {}/{} ",
+                app.cpu_table.selected().map_or(0, |i| i + 1),
+                app.cpus.len()
+            ))
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(Color::Blue)),
+    )
+    .row_highlight_style(Style::default().bg(Color::DarkGray));
+    // Borders + header
+    app.page = area.height.saturating_sub(3).max(1) as usize;
+    frame.render_stateful_widget(table, area, &mut app.cpu_table);
+}
+
+/// Per-LLC (CCD) rates over the last tick, with the busy gap between the
+/// busiest and idlest LLC in the title, to check cross-CCD balancing; on
+/// multi-node machines the per-NUMA-node totals below
+fn draw_llc_view(frame: &mut Frame, app: &TuiApp, area: Rect) {
+    let busy = app.llcs.iter().map(|l| l.busy_pct);
+    let gap = busy.clone().fold(0.0, f64::max) - busy.fold(100.0, f64::min);
+    let title = match app.llcs.len() {
+        0 | 1 => " Per-LLC (last tick; one LLC, nothing to balance) ".to_string(),
+        _ => format!(" Per-LLC (last tick; busy gap {:.1}%) ", gap),
+    };
+    if app.nodes.is_empty() {
+        frame.render_widget(domain_table(&app.llcs, "llc", title), area);
+        return;
+    }
+    // Multi-node: the per-node totals under the LLCs
+    let split = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Min(4),
+            Constraint::Length(app.nodes.len() as u16 + 3),
+        ])
+        .split(area);
+    frame.render_widget(domain_table(&app.llcs, "llc", title), split[0]);
+    frame.render_widget(
+        domain_table(
+            &app.nodes,
+            "node",
+            " Per-NUMA-node (last tick) ".to_string(),
+        ),
+        split[1],
+    );
+}
+
+/// LLC or NUMA node rows, labelled "`kind` N"
+fn domain_table<'a>(domains: &[LlcLoad], kind: &str, title: String) -> Table<'a> {
+    let header_cells = [
+        kind.to_uppercase().as_str(),
+        "CPUs",
+        "Busy %",
+        "Disp/s",
+        "Disp %",
+        "Runs/s",
+        "Wait µs",
+        "XLLC in/s",
+        "XNode in/s",
+    ]
+    .map(|h| {
+        Cell::from(h.to_string()).style(
+            Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(Modifier::BOLD),
+        )
+    });
+    let header_row = Row::new(header_cells).height(1);
+    let rows: Vec<Row> = domains
+        .iter()
+        .map(|l| {
+            Row::new(vec![
+                Cell::from(format!("{} {}", kind, l.llc)),
+                Cell::from(l.nr_cpus.to_string()),
+                Cell::from(format!("{:.1}", l.busy_pct)),
+                Cell::from(format!("{:.0}", l.dispatches_s)),
+                Cell::from(format!("{:.1}", l.dispatch_pct)),
+                Cell::from(format!("{:.0}", l.runs_s)),
+                Cell::from(format!("{:.1}", l.avg_wait_us)),
+                Cell::from(format!("{:.1}", l.cross_llc_in_s)),
+                Cell::from(format!("{:.1}", l.cross_node_in_s)),
+            ])
+        })
+        .collect();
+
+    Table::new(
+        rows,
+        [
+            Constraint::Length(8),
+            Constraint::Length(6),
+            Constraint::Length(8),
+            Constraint::Length(10),
+            Constraint::Length(8),
+            Constraint::Length(10),
+            Constraint::Length(9),
+            Constraint::Length(10),
+            Constraint::Length(11),
+        ],
+    )
+    .header(header_row)
+    .block(
+        Block::default()
+            .title(title)
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(Color::Blue)),
+    )
+}
+
+/// Detected topology as BPF sees it, one row per CPU, beside the LLC / SMT /
+/// hybrid / CPPC / NUMA summary and the idle search order from the selected CPU
+fn draw_topology_view(frame: &mut Frame, app: &mut TuiApp, area: Rect) {
+    let split = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Length(46), Constraint::Min(30)])
+        .split(area);
+    let topo = &app.topology;
+    let nr_cpus = topo.nr_cpus.min(MAX_CPUS);
+
+    let header_cells = ["CPU", "LLC", "Core", "Sibling", "Type", "Managed"]
+        .iter()
+        .map(|h| {
+            Cell::from(*h).style(
+                Style::default()
+                    .fg(Color::Yellow)
+                    .add_modifier(Modifier::BOLD),
+            )
+        });
+    let header_row = Row::new(header_cells).height(1);
+    let rows: Vec<Row> = (0..nr_cpus)
+        .map(|cpu| {
+            let managed = topo.managed_mask.test(cpu);
+            let style = match managed {
+                true => Style::default(),
+                false => Style::default().fg(Color::DarkGray),
+            };
+            Row::new(vec![
+                Cell::from(cpu.to_string()),
+                Cell::from(topo.cpu_llc_id[cpu].to_string()),
+                Cell::from(topo.cpu_core_id[cpu].to_string()),
+                Cell::from(match topo.cpu_sibling_map[cpu] as usize {
+                    sib if sib == cpu => "-".to_string(),
+                    sib => sib.to_string(),
+                }),
+                Cell::from(match topo.cpu_is_big[cpu] != 0 {
+                    true => "P",
+                    false => "E",
+                }),
+                Cell::from(match managed {
+                    true => "yes",
+                    false if !topo.online_mask.test(cpu) => "offline",
+                    false if topo.isolated_mask.test(cpu) => "isolated",
+                    false => "no",
+                }),
+            ])
+            .style(style)
+        })
+        .collect();
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Length(5),
+            Constraint::Length(5),
+            Constraint::Length(6),
+            Constraint::Length(9),
+            Constraint::Length(6),
+            Constraint::Length(8),
+        ],
+    )
+    .header(header_row)
+    .block(
+        Block::default()
+            .title(format!(
+                " CPUs {}/{} ",
+                app.topo_table.selected().map_or(0, |i| i + 1),
+                nr_cpus
+            ))
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(Color::Blue)),
+    )
+    .row_highlight_style(Style::default().bg(Color::DarkGray));
+
+    let label = Style::default()
+        .fg(Color::Yellow)
+        .add_modifier(Modifier::BOLD);
+    let mut lines = vec![
+        Line::from(vec![
+            Span::styled("CPUs:     ", label),
+            Span::raw(format!(
+                "{} online, managed {}",
+                topo.nr_online(),
+                check::mask_list(topo.managed_mask)
+            )),
+        ]),
+        Line::from(vec![
+            Span::styled("SMT:      ", label),
+            Span::raw(if topo.smt_enabled { "on" } else { "off" }),
+        ]),
+        Line::from(vec![
+            Span::styled("Hybrid:   ", label),
+            Span::raw(match topo.has_hybrid_cores {
+                true => format!("yes, P-cores {}", check::mask_list(topo.big_cpu_mask)),
+                false => "no".to_string(),
+            }),
+        ]),
+        Line::from(vec![
+            Span::styled("L2:       ", label),
+            Span::raw(match topo.has_clusters {
+                true => {
+                    let clusters: Vec<String> =
+                        topo.clusters().into_iter().map(check::mask_list).collect();
+                    format!("clusters {}", clusters.join(" "))
+                }
+                false => "per core".to_string(),
+            }),
+        ]),
+        Line::from(vec![
+            Span::styled("Boost:    ", label),
+            Span::raw(match topo.has_preferred_cores {
+                true => {
+                    let best: Vec<String> = topo
+                        .perf_order()
+                        .iter()
+                        .take(8)
+                        .map(|c| c.to_string())
+                        .collect();
+                    format!("CPPC ranked, best {}", best.join(","))
+                }
+                false => "unranked".to_string(),
+            }),
+        ]),
+        Line::from(Span::styled(format!("LLCs:     {}", topo.nr_llcs), label)),
+    ];
+    if topo.has_numa {
+        lines.push(Line::from(Span::styled(
+            format!("Nodes:    {}", topo.nr_nodes),
+            label,
+        )));
+        lines.extend((0..topo.nr_nodes).map(|node| {
+            let mask = (0..nr_cpus)
+                .filter(|&c| topo.cpu_node_id[c] as usize == node)
+                .collect();
+            Line::from(format!("  Node {:<2} {}", node, check::mask_list(mask)))
+        }));
+    }
+    let vcache = topo.vcache_llc();
+    lines.extend((0..topo.nr_llcs).map(|llc| {
+        Line::from(format!(
+            "  LLC {:<3} {}{}",
+            llc,
+            check::mask_list(topo.llc_cpu_mask[llc]),
+            match topo.llc_cache_kb[llc] {
+                0 => String::new(),
+                kb if vcache == Some(llc) => format!("  {}M L3, V-Cache", kb / 1024),
+                kb => format!("  {}M L3", kb / 1024),
+            }
+        ))
+    }));
+    let cpu = app.topo_table.selected().unwrap_or(0).min(nr_cpus - 1);
+    lines.push(Line::from(""));
+    lines.push(Line::from(Span::styled(
+        format!("Idle search from CPU {}:", cpu),
+        label,
+    )));
+    for (rung, cpus) in topo.preference(cpu).iter().enumerate() {
+        let set = cpus.iter().copied().collect();
+        lines.push(Line::from(format!(
+            "  {:<8} {}",
+            SELECT_RUNGS[rung + 1],
+            match cpus.is_empty() {
+                true => "—".to_string(),
+                false => check::mask_list(set),
+            }
+        )));
+    }
+    let summary = Paragraph::new(lines).block(
+        Block::default()
+            .title(" Topology ")
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(Color::Blue)),
+    );
+
+    // Borders + header
+    app.page = split[0].height.saturating_sub(3).max(1) as usize;
+    frame.render_stateful_widget(table, split[0], &mut app.topo_table);
+    frame.render_widget(summary, split[1]);
+}
+
+/// Heatmap cell color for a busy percentage
+fn heat_color(pct: f64) -> Color {
+    match pct {
+        p if p >= 90.0 => Color::Red,
+        p if p >= 70.0 => Color::LightRed,
+        p if p >= 40.0 => Color::Yellow,
+        p if p >= 10.0 => Color::Green,
+        _ => Color::DarkGray,
+    }
+}
+
+/// Mean local queue depth as one bar glyph: ▁ for a fraction of a task up
+/// to █ for four or more
+fn depth_glyph(queued: f64) -> char {
+    const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+    match queued {
+        q if q < 0.05 => ' ',
+        q => BARS[((q / 4.0 * 7.0) as usize).min(7)],
+    }
+}
+
+/// Managed CPUs grouped by LLC, P-cores before E-cores on hybrid parts
+fn heatmap_groups(topo: &TopologyInfo) -> Vec<(String, Vec<usize>)> {
+    let cpus: Vec<usize> = (0..topo.nr_cpus.min(topo.cpu_llc_id.len()))
+        .filter(|&c| topo.managed_mask.test(c))
+        .collect();
+    let mut llcs: Vec<u8> = cpus.iter().map(|&c| topo.cpu_llc_id[c]).collect();
+    llcs.sort_unstable();
+    llcs.dedup();
+
+    let kinds: &[(Option<bool>, &str)] = match topo.has_hybrid_cores {
+        true => &[(Some(true), " P"), (Some(false), " E")],
+        false => &[(None, "")],
+    };
+    let mut groups = Vec::new();
+    for llc in llcs {
+        for &(big, suffix) in kinds {
+            let members: Vec<usize> = cpus
+                .iter()
+                .copied()
+                .filter(|&c| topo.cpu_llc_id[c] == llc)
+                .filter(|&c| big.is_none_or(|b| (topo.cpu_is_big[c] != 0) == b))
+                .collect();
+            if !members.is_empty() {
+                groups.push((format!("LLC {}{}", llc, suffix), members));
+            }
+        }
+    }
+    groups
+}
+
+/// CPU grid colored by busy % over the last tick with each CPU's mean local
+/// queue depth as a bar, one block per LLC (and core type)
+fn draw_heatmap_view(frame: &mut Frame, app: &TuiApp, area: Rect) {
+    const LABEL: usize = 10;
+    // "{cpu:>3}{bar} " plus a gap
+    const CELL: usize = 6;
+    let per_line = ((area.width as usize).saturating_sub(2 + LABEL) / CELL).max(1);
+    let busy = |c: usize| app.cpu_busy.get(c).copied().unwrap_or(0.0);
+    let queued = |c: usize| app.depth.cpu.get(c).copied().unwrap_or(0.0);
+
+    let mut lines = Vec::new();
+    for (label, cpus) in heatmap_groups(&app.topology) {
+        for (i, chunk) in cpus.chunks(per_line).enumerate() {
+            let name = if i == 0 { label.as_str() } else { "" };
+            let mut spans = vec![Span::styled(
+                format!("{:<LABEL$}", name),
+                Style::default().add_modifier(Modifier::BOLD),
+            )];
+            for &c in chunk {
+                let bg = heat_color(busy(c));
+                let fg = match bg {
+                    Color::DarkGray => Color::White,
+                    _ => Color::Black,
+                };
+                spans.push(Span::styled(
+                    format!("{:>3}{} ", c, depth_glyph(queued(c))),
+                    Style::default().fg(fg).bg(bg),
+                ));
+                spans.push(Span::raw(" "));
+            }
+            lines.push(Line::from(spans));
+        }
+        let avg = cpus.iter().map(|&c| busy(c)).sum::<f64>() / cpus.len() as f64;
+        let total: f64 = cpus.iter().map(|&c| queued(c)).sum();
+        lines.push(Line::from(Span::styled(
+            format!("{:LABEL$}{:.0}% busy, {:.1} queued", "", avg, total),
+            Style::default().fg(Color::Gray),
+        )));
+        lines.push(Line::from(""));
+    }
+
+    let mut legend = vec![Span::raw(" Busy: ")];
+    for (pct, text) in [
+        (0.0, "<10%"),
+        (10.0, "10-40"),
+        (40.0, "40-70"),
+        (70.0, "70-90"),
+        (90.0, "≥90%"),
+    ] {
+        legend.push(Span::styled(
+            format!(" {} ", text),
+            Style::default().fg(Color::Black).bg(heat_color(pct)),
+        ));
+        legend.push(Span::raw(" "));
+    }
+    legend.push(Span::raw("  Local queue: ▁ <1 … █ ≥4 tasks"));
+    lines.push(Line::from(legend));
+
+    let heatmap = Paragraph::new(lines).block(
+        Block::default()
+            .title(" CPU Heatmap (last tick) ")
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(Color::Blue)),
+    );
+    frame.render_widget(heatmap, area);
+}
+
+/// Per-tier log2 wait histogram: one row per bucket, share of each tier's runs
+fn draw_wait_view(frame: &mut Frame, app: &TuiApp, area: Rect) {
+    let split = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(8), Constraint::Length(10)])
+        .split(area);
+    let area = split[0];
+    draw_wait_trend(frame, app, split[1]);
+
+    let header_cells = std::iter::once("Wait").chain(TIER_NAMES).map(|h| {
+        Cell::from(h).style(
+            Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(Modifier::BOLD),
+        )
+    });
+    let header_row = Row::new(header_cells).height(1);
+
+    // Up to the longest wait seen in any tier
+    let last = (0..WAIT_BUCKETS)
+        .rev()
+        .find(|&b| app.waits.iter().any(|h| h[b] != 0))
+        .unwrap_or(0);
+    let totals = app.waits.map(|h| h.iter().sum::<u64>().max(1));
+    // Tail percentiles first: what a frame actually feels, not the mean
+    let mut rows: Vec<Row> = WAIT_PERCENTILES
+        .iter()
+        .map(|&pct| {
+            let mut cells = vec![Cell::from(format!("p{}", pct))];
+            cells.extend((0..4).map(|t| {
+                let style = match pct == 99.0 {
+                    true => tier_style(t).patch(alert_style(app, t, Some(AlertMetric::P99))),
+                    false => tier_style(t),
+                };
+                Cell::from(format!("{:>14.1}µs", wait_percentile(&app.waits[t], pct))).style(style)
+            }));
+            Row::new(cells).style(Style::default().add_modifier(Modifier::BOLD))
+        })
+        .collect();
+    rows.extend((0..=last).map(|b| {
+        let mut cells = vec![Cell::from(wait_bucket_label(b))];
+        cells.extend((0..4).map(|t| {
+            let n = app.waits[t][b];
+            Cell::from(format!(
+                "{:>9} {:>5.1}%",
+                n,
+                n as f64 * 100.0 / totals[t] as f64
+            ))
+            .style(tier_style(t))
+        }));
+        Row::new(cells)
+    }));
+
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Length(14),
+            Constraint::Length(17),
+            Constraint::Length(17),
+            Constraint::Length(17),
+            Constraint::Length(17),
+        ],
+    )
+    .header(header_row)
+    .block(
+        Block::default()
+            .title(" Runnable Wait per Tier (percentiles, then runs and % of tier) ")
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(Color::Blue)),
+    );
+    frame.render_widget(table, area);
+}
+
+/// Last tick's wait distribution as one bar chart per tier, log2 buckets on
+/// a shared axis so the tiers line up
+fn draw_wait_bars(frame: &mut Frame, app: &TuiApp, area: Rect) {
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Ratio(1, 4); 4])
+        .split(area);
+    // Through the longest wait any tier saw this tick
+    let last = (0..WAIT_BUCKETS)
+        .rev()
+        .find(|&b| app.wait_tick.iter().any(|h| h[b] != 0))
+        .unwrap_or(0);
+    for (t, row) in rows.iter().enumerate() {
+        let hist = &app.wait_tick[t];
+        let runs = hist.iter().sum::<u64>();
+        let bars: Vec<Bar> = (0..=last)
+            .map(|b| {
+                let pct = hist[b] as f64 * 100.0 / runs.max(1) as f64;
+                Bar::default()
+                    .value(hist[b])
+                    .text_value(format!("{:.0}%", pct))
+                    .label(Line::from(wait_bucket_tick(b)))
+            })
+            .collect();
+        let inner = row.width.saturating_sub(2) / (last as u16 + 1);
+        let chart = BarChart::default()
+            .block(
+                Block::default()
+                    .title(format!(
+                        " {}: {} runs, p50 {:.1}µs, p99 {:.1}µs (wait µs, last tick) ",
+                        TIER_NAMES[t],
+                        runs,
+                        wait_percentile(hist, 50.0),
+                        wait_percentile(hist, 99.0)
+                    ))
+                    .borders(Borders::ALL)
+                    .border_style(Style::default().fg(Color::Blue)),
+            )
+            .data(BarGroup::default().bars(&bars))
+            .bar_width(inner.saturating_sub(1).max(1))
+            .bar_gap(1)
+            .bar_style(tier_style(t))
+            .value_style(Style::default().add_modifier(Modifier::REVERSED));
+        frame.render_widget(chart, *row);
+    }
+}
+
+/// Sparklines of each tier's mean and max wait per tick, so a spike stays
+/// on screen after the tick that had it
+fn draw_wait_trend(frame: &mut Frame, app: &TuiApp, area: Rect) {
+    let block = Block::default()
+        .title(" Wait History (per tick: mean │ max) ")
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Blue));
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(2); 4])
+        .split(inner);
+    for (t, row) in rows.iter().enumerate() {
+        let cols = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([
+                Constraint::Length(26),
+                Constraint::Percentage(50),
+                Constraint::Percentage(50),
+            ])
+            .split(*row);
+        let (label, avg, max) = (cols[0], cols[1], cols[2]);
+        let (avg_us, max_us) = (
+            app.wait_trend.avg[t].back().copied().unwrap_or(0),
+            app.wait_trend.max[t].back().copied().unwrap_or(0),
+        );
+        frame.render_widget(
+            Paragraph::new(format!(
+                "{:<12}{:>5}│{:>6}µs",
+                TIER_NAMES[t], avg_us, max_us
+            ))
+            .style(tier_style(t)),
+            label,
+        );
+        for (series, area) in [(&app.wait_trend.avg[t], avg), (&app.wait_trend.max[t], max)] {
+            // Sparkline draws from the front: keep the newest that fit
+            let data: Vec<u64> = series
+                .iter()
+                .skip(series.len().saturating_sub(area.width as usize))
+                .copied()
+                .collect();
+            frame.render_widget(Sparkline::default().data(&data).style(tier_style(t)), area);
+        }
+    }
+}
+
+/// Total and per-tier dispatches/s over the last RATE_WINDOW_S, so a game
+/// launch or a build starting shows as a shift between the tier lines
+fn draw_rate_chart(frame: &mut Frame, app: &TuiApp, area: Rect) {
+    let now = app.start_time.elapsed().as_secs_f64();
+    let points = &app.rate_trend.points;
+    let total: Vec<(f64, f64)> = points
+        .iter()
+        .map(|(t, d)| (t - now, d.iter().sum()))
+        .collect();
+    let tiers: Vec<Vec<(f64, f64)>> = (0..TIER_NAMES.len())
+        .map(|tier| points.iter().map(|(t, d)| (t - now, d[tier])).collect())
+        .collect();
+    let latest = points.back().map_or([0.0; 4], |&(_, d)| d);
+    let peak = total.iter().map(|&(_, y)| y).fold(0.0, f64::max).max(1.0) * 1.1;
+
+    let mut datasets = vec![Dataset::default()
+        .name(format!("Total {:.0}/s", latest.iter().sum::<f64>()))
+        .marker(Marker::Braille)
+        .graph_type(GraphType::Line)
+        .style(Style::default().fg(Color::White))
+        .data(&total)];
+    datasets.extend(tiers.iter().enumerate().map(|(t, data)| {
+        Dataset::default()
+            .name(format!("{} {:.0}/s", TIER_NAMES[t], latest[t]))
+            .marker(Marker::Braille)
+            .graph_type(GraphType::Line)
+            .style(tier_style(t))
+            .data(data)
+    }));
+
+    let chart = Chart::new(datasets)
+        .block(
+            Block::default()
+                .title(" Dispatches/s, total and per tier (last 5 min) ")
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(Color::Blue)),
+        )
+        .x_axis(
+            Axis::default()
+                .style(Style::default().fg(Color::DarkGray))
+                .bounds([-RATE_WINDOW_S, 0.0])
+                .labels(["-5m", "-2.5m", "now"]),
+        )
+        .y_axis(
+            Axis::default()
+                .style(Style::default().fg(Color::DarkGray))
+                .bounds([0.0, peak])
+                .labels([
+                    "0".to_string(),
+                    format!("{:.0}", peak / 2.0),
+                    format!("{:.0}", peak),
+                ]),
+        );
+    frame.render_widget(chart, area);
+}
+
+/// Starvation preempts by waiting tier (rows) and preempted tier (columns)
+fn draw_preempt_view(frame: &mut Frame, stats: &cake_stats, area: Rect) {
+    let header_cells = std::iter::once("Waiter \\ Victim")
+        .chain(TIER_NAMES)
+        .enumerate()
+        .map(|(i, h)| {
+            let style = match i {
+                0 => Style::default().fg(Color::Yellow),
+                _ => tier_style(i - 1),
+            };
+            Cell::from(h).style(style.add_modifier(Modifier::BOLD))
+        });
+    let header_row = Row::new(header_cells).height(1);
+
+    let rows: Vec<Row> = PREEMPT_SOURCES
+        .iter()
+        .zip(stats.preempt_matrix)
+        .enumerate()
+        .map(|(src, (name, row))| {
+            let total = row.iter().sum::<u64>().max(1);
+            // tier_style has no colour past Bulk: the Local row stays plain
+            let mut cells = vec![Cell::from(*name).style(tier_style(src))];
+            cells.extend(row.iter().map(|&n| {
+                Cell::from(format!(
+                    "{:>9} {:>5.1}%",
+                    n,
+                    n as f64 * 100.0 / total as f64
+                ))
+            }));
+            Row::new(cells)
+        })
+        .collect();
+
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Length(18),
+            Constraint::Length(17),
+            Constraint::Length(17),
+            Constraint::Length(17),
+            Constraint::Length(17),
+        ],
+    )
+    .header(header_row)
+    .block(
+        Block::default()
+            .title(" Starvation Preempts: waiter tier × preempted tier (count, % of row) ")
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(Color::Blue)),
+    );
+    frame.render_widget(table, area);
+}
+
+/// Per-tier dispatch / starvation table: totals since the last reset next to
+/// dispatch and preempt rates, or (`d`) every counter as a per-second rate
+fn draw_tier_table(frame: &mut Frame, app: &mut TuiApp, stats: &cake_stats, area: Rect) {
+    let rates = &app.rates;
+    // Rate mode drops the Dispatches / StarvPreempt totals, which already
+    // have a rate column beside them
+    let shown = |col: &usize| !(app.tier_rates && matches!(col, 2 | 5));
+    let (immune, cap) = match app.tier_rates {
+        true => ("Immune/s", "CapDefer/s"),
+        false => ("ImmuneSkip", "CapDefer"),
+    };
+    let header_cells: Vec<Cell> = [
+        "Tier".to_string(),
+        app.sort_header("CPU%", TopBy::Cpu),
+        "Dispatches".to_string(),
+        app.sort_header("Disp/s", TopBy::Runs),
+        app.sort_header("Wait µs", TopBy::Wait),
+        "StarvPreempt".to_string(),
+        "Preempt/s".to_string(),
+        immune.to_string(),
+        cap.to_string(),
+        "CapQueue".to_string(),
+        "Queued".to_string(),
+    ]
+    .into_iter()
+    .enumerate()
+    .filter(|(col, _)| shown(col))
+    .map(|(_, h)| {
+        Cell::from(h).style(
+            Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(Modifier::BOLD),
+        )
+    })
+    .collect();
+    let header_row = Row::new(header_cells).height(1);
+
+    // Mean wait over the last tick
+    let wait = |t: usize| app.wait_trend.avg[t].back().copied().unwrap_or(0) as f64;
+    let counter = |total: u64, rate: f64| match app.tier_rates {
+        true => format!("{:.1}", rate),
+        false => total.to_string(),
+    };
+    let mut tiers: Vec<usize> = (0..TIER_NAMES.len()).collect();
+    if let Some(by) = app.sort {
+        let key = |t: usize| match by {
+            TopBy::Cpu => app.cpu_share[t],
+            TopBy::Wait => wait(t),
+            TopBy::Runs => rates.dispatches[t],
+        };
+        tiers.sort_by(|&a, &b| sort_cmp(key(a), key(b), app.sort_asc));
+    }
+    let rows: Vec<Row> = tiers
+        .into_iter()
+        .map(|i| {
+            let cells: Vec<Cell> = [
+                Cell::from(TIER_NAMES[i]).style(tier_style(i)),
+                Cell::from(format!("{:.1}", app.cpu_share[i])),
+                Cell::from(format!("{}", stats.nr_tier_dispatches[i])),
+                Cell::from(format!("{:.0}", rates.dispatches[i])),
+                Cell::from(format!("{:.0}", wait(i))).style(alert_style(app, i, None)),
+                Cell::from(format!("{}", stats.nr_starvation_preempts_tier[i])),
+                Cell::from(format!("{:.1}", rates.preempts[i])),
+                Cell::from(counter(stats.nr_starvation_immune_tier[i], rates.immune[i])),
+                Cell::from(counter(stats.nr_cap_deferrals[i], rates.cap_deferrals[i])),
+                Cell::from(format!("{:.1}", cap_queue_len(stats, i))),
+                Cell::from(format!("{:.1}", app.depth.tier[i])),
+            ]
+            .into_iter()
+            .enumerate()
+            .filter(|(col, _)| shown(col))
+            .map(|(_, cell)| cell)
+            .collect();
+            Row::new(cells).height(1)
+        })
+        .collect();
+
+    let widths: Vec<u16> = [12, 7, 12, 10, 9, 14, 11, 12, 11, 10, 8]
+        .into_iter()
+        .enumerate()
+        .filter(|(col, _)| shown(col))
+        .map(|(_, w)| w)
+        .collect();
+    let sorts: Vec<Option<TopBy>> = (0..11)
+        .filter(shown)
+        .map(|col| match col {
+            1 => Some(TopBy::Cpu),
+            3 => Some(TopBy::Runs),
+            4 => Some(TopBy::Wait),
+            _ => None,
+        })
+        .collect();
+    let clicks = header_clicks(area, &widths, &sorts);
+    let table = Table::new(rows, widths.into_iter().map(Constraint::Length))
+        .header(header_row)
+        .block(
+            Block::default()
+                .title(match app.tier_rates {
+                    true => " Per-Tier Statistics (per second, last tick) ",
+                    false => " Per-Tier Statistics ",
+                })
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(Color::Blue)),
+        );
+    frame.render_widget(table, area);
+    app.clicks.extend(clicks);
+}
+
+/// Per-process (or flat per-thread) task table with expandable processes
+fn draw_task_view(frame: &mut Frame, app: &mut TuiApp, area: Rect) {
+    let header_cells = [
+        "Task".to_string(),
+        "PID".to_string(),
+        "Threads".to_string(),
+        app.sort_header("CPU%", TopBy::Cpu),
+        app.sort_header("Runs/s", TopBy::Runs),
+        app.sort_header("Wait ms/s", TopBy::Wait),
+        "AvgWait µs".to_string(),
+        "AvgRun µs".to_string(),
+        "CPU".to_string(),
+        "Tier".to_string(),
+    ]
+    .into_iter()
+    .map(|h| {
+        Cell::from(h).style(
+            Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(Modifier::BOLD),
+        )
+    });
+    let header_row = Row::new(header_cells).height(1);
+
+    let lines = app.task_lines();
+    let rows: Vec<Row> = lines
+        .iter()
+        .map(|line| match *line {
+            TaskLine::Process(pi) => {
+                let p = &app.processes[pi];
+                let marker = if app.expanded.contains(&p.tgid) {
+                    "▼"
+                } else {
+                    "▶"
+                };
+                let dist = p
+                    .tier_counts
+                    .iter()
+                    .map(|n| n.to_string())
+                    .collect::<Vec<_>>()
+                    .join("/");
+                let runs: f64 = p.threads.iter().map(|t| t.runs_per_s).sum();
+                let avg_wait = if runs > 0.0 {
+                    p.wait_ms_per_s * 1000.0 / runs
+                } else {
+                    0.0
+                };
+                Row::new(vec![
+                    Cell::from(format!("{} {}", marker, p.comm))
+                        .style(Style::default().add_modifier(Modifier::BOLD)),
+                    Cell::from(p.tgid.to_string()),
+                    Cell::from(p.threads.len().to_string()),
+                    Cell::from(format!("{:.1}", p.cpu_pct)),
+                    Cell::from(format!("{:.0}", runs)),
+                    Cell::from(format!("{:.2}", p.wait_ms_per_s)),
+                    Cell::from(format!("{:.1}", avg_wait)),
+                    Cell::from(""),
+                    Cell::from(""),
+                    Cell::from(dist),
+                ])
+            }
+            TaskLine::Thread(pi, ti) => {
+                let t = &app.processes[pi].threads[ti];
+                let name = if app.grouped {
+                    format!("  └ {}", t.comm)
+                } else {
+                    t.comm.clone()
+                };
+                Row::new(vec![
+                    Cell::from(name),
+                    Cell::from(t.pid.to_string()),
+                    Cell::from(""),
+                    Cell::from(format!("{:.1}", t.cpu_pct)),
+                    Cell::from(format!("{:.0}", t.runs_per_s)),
+                    Cell::from(format!("{:.2}", t.wait_ms_per_s)),
+                    Cell::from(format!("{:.1}", t.avg_wait_us())),
+                    Cell::from(t.avg_runtime_us.to_string()),
+                    Cell::from(t.cpu.to_string()),
+                    Cell::from(match app.pins.get(t.pid) {
+                        Some(_) => format!("{} 📌", TIER_NAMES[(t.tier & 3) as usize]),
+                        None => TIER_NAMES[(t.tier & 3) as usize].to_string(),
+                    })
+                    .style(tier_style(t.tier as usize)),
+                ])
+            }
+        })
+        .collect();
+
+    let title = format!(
+        " {}{} {}/{} ",
+        if app.grouped {
+            "Tasks by Process (Tier: T0/T1/T2/T3 thread counts)"
+        } else {
+            "Tasks (flat)"
+        },
+        match app.filter.is_empty() {
+            true => String::new(),
+            false => format!(" matching '{}'", app.filter),
+        },
+        app.selected() + lines.len().min(1),
+        lines.len()
+    );
+    let widths = [24, 8, 8, 7, 8, 11, 11, 10, 4, 14];
+    let sorts = [
+        None,
+        None,
+        None,
+        Some(TopBy::Cpu),
+        Some(TopBy::Runs),
+        Some(TopBy::Wait),
+    ];
+    app.clicks.extend(header_clicks(area, &widths, &sorts));
+    let table = Table::new(rows, widths.map(Constraint::Length))
+        .header(header_row)
+        .block(
+            Block::default()
+                .title(title)
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(Color::Blue)),
+        )
+        .row_highlight_style(Style::default().bg(Color::DarkGray));
+    // Borders + header
+    app.page = area.height.saturating_sub(3).max(1) as usize;
+    frame.render_stateful_widget(table, area, &mut app.task_table);
+}
+
+/// Click targets for the sortable header cells of a bordered table, columns
+/// laid out at `widths` with ratatui's one-cell column spacing
+fn header_clicks(area: Rect, widths: &[u16], sorts: &[Option<TopBy>]) -> Vec<(Rect, Click)> {
+    let mut x = area.x + 1;
+    let mut clicks = Vec::new();
+    for (&width, sort) in widths.iter().zip(sorts) {
+        if let Some(by) = *sort {
+            let cell = Rect::new(x, area.y + 1, width, 1).intersection(area);
+            clicks.push((cell, Click::Sort(by)));
+        }
+        x = x.saturating_add(width + 1);
+    }
+    clicks
+}
+
+/// Compare two sort keys, largest first unless `asc`
+fn sort_cmp(a: f64, b: f64, asc: bool) -> std::cmp::Ordering {
+    match asc {
+        true => a.total_cmp(&b),
+        false => b.total_cmp(&a),
+    }
+}
+
+/// Get color style for a tier
+fn tier_style(tier: usize) -> Style {
+    match tier {
+        0 => Style::default()
+            .fg(Color::Cyan)
+            .add_modifier(Modifier::BOLD), // Critical (<100µs)
+        1 => Style::default().fg(Color::Green), // Interactive (<2ms)
+        2 => Style::default().fg(Color::Yellow), // Frame (<8ms)
+        3 => Style::default().fg(Color::DarkGray), // Bulk (≥8ms)
+        _ => Style::default(),
+    }
+}
+
+/// Event log pane: the ring buffer's tier changes, new tasks, spent
+/// new-flow bonuses and starvation preempts, newest at the bottom
+fn draw_event_view(frame: &mut Frame, shown: &[events::Event], state: &mut TableState, area: Rect) {
+    let header_cells = ["Time", "Event", "CPU", "PID", "Comm", "Tiers"]
+        .iter()
+        .map(|h| {
+            Cell::from(*h).style(
+                Style::default()
+                    .fg(Color::Yellow)
+                    .add_modifier(Modifier::BOLD),
+            )
+        });
+    let header_row = Row::new(header_cells).height(1);
+    let rows: Vec<Row> = shown
+        .iter()
+        .map(|ev| {
+            let (tiers, style) = match ev.kind {
+                events::EV_TIER_CHANGE => (
+                    format!(
+                        "{} → {}",
+                        events::tier_name(ev.from),
+                        events::tier_name(ev.to)
+                    ),
+                    tier_style(ev.to as usize),
+                ),
+                events::EV_STARVATION => (
+                    format!(
+                        "{} preempted for {}",
+                        events::tier_name(ev.from),
+                        events::tier_name(ev.to)
+                    ),
+                    Style::default().fg(Color::Red),
+                ),
+                _ => (
+                    events::tier_name(ev.to).to_string(),
+                    tier_style(ev.to as usize),
+                ),
+            };
+            Row::new(vec![
+                Cell::from(ev.clock()),
+                Cell::from(ev.kind_name()),
+                Cell::from(ev.cpu.to_string()),
+                Cell::from(ev.pid.to_string()),
+                Cell::from(ev.comm.clone()),
+                Cell::from(tiers).style(style),
+            ])
+        })
+        .collect();
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Length(13),
+            Constraint::Length(19),
+            Constraint::Length(4),
+            Constraint::Length(8),
+            Constraint::Length(16),
+            Constraint::Min(20),
+        ],
+    )
+    .header(header_row)
+    .block(
+        Block::default()
+            .title(format!(
+                " Events {}/{} ",
+                state.selected().map_or(0, |i| i + 1),
+                shown.len()
+            ))
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(Color::Blue)),
+    )
+    .row_highlight_style(Style::default().bg(Color::DarkGray));
+    frame.render_stateful_widget(table, area, state);
+}
+
+/// TUI settings from the command line
+pub struct TuiParams<'a> {
+    pub interval_secs: u64,
+    pub theme: Theme,
+    /// Where `e` writes snapshots
+    pub snapshot_dir: &'a Path,
+    /// Event log pane source
+    pub feed: Option<&'a EventFeed>,
+    pub alerts: &'a [TierAlert],
+    pub alert_bell: bool,
+    pub alert_log: Option<&'a Path>,
+    pub keys: &'a Keymap,
+}
+
+/// Append one alert line to --alert-log, prefixed with the unix time
+fn log_alert(path: &Path, line: &str) -> Result<()> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("Failed to open alert log {}", path.display()))?;
+    writeln!(file, "{} {}", now, line)
+        .with_context(|| format!("Failed to write alert log {}", path.display()))
+}
+
+/// Red when an --alert on `tier` (just `metric`, if given) is over its limit
+fn alert_style(app: &TuiApp, tier: usize, metric: Option<AlertMetric>) -> Style {
+    match app.alerts.firing(tier, metric) {
+        true => Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+        false => Style::default(),
+    }
+}
+
+/// Run the TUI event loop
+pub fn run_tui(
+    skel: &mut BpfSkel,
+    shutdown: Arc<AtomicBool>,
+    topology: TopologyInfo,
+    mut history: Option<&mut Recorder>,
+    epoch: &mut StatsEpoch,
+    params: TuiParams,
+) -> Result<()> {
+    let mut terminal = setup_terminal()?;
     let mut app = TuiApp::new(topology);
-    let tick_rate = Duration::from_secs(interval_secs);
+    app.alerts = Alerts::new(params.alerts);
+    app.remapped = params.keys.remapped();
+    app.rate_prev = aggregate_stats(skel);
+    app.depth_prev = dsq_depth(skel);
+    app.wait_prev = wait_histograms(skel);
+    if let Some(rodata) = &skel.maps.rodata_data {
+        app.config = check::format_rodata(rodata, &app.topology);
+        app.tune_loaded = [
+            rodata.quantum_ns / 1000,
+            rodata.new_flow_bonus_ns / 1000,
+            rodata.starvation_ns / 1000,
+        ];
+    }
+    if let Some(bss) = &skel.maps.bss_data {
+        let n = app.topology.nr_cpus.min(bss.global_stats.len());
+        app.cpu_runtime_prev = cpu_runtime_ns(&bss.global_stats[..n]);
+        app.llc_prev = bss.global_stats[..n].to_vec();
+    }
+    let tick_rate = Duration::from_secs(params.interval_secs);
     let mut last_tick = Instant::now();
 
     // Initialize clipboard (may fail on headless systems)
     let mut clipboard = Clipboard::new().ok();
 
-    loop {
+    // Task view sampler (iterator attach may fail on older kernels)
+    let mut tasks = TaskSampler::new(skel).ok();
+
+    let mut raw = app.rate_prev;
+    let mut stats = epoch.since(&raw);
+    // First key of a possible sequence (gg), waiting for the second
+    let mut pending = None;
+
+    'ui: loop {
         // Check for shutdown signal
         if shutdown.load(Ordering::Relaxed) {
             break;
         }
 
-        // Check for UEI exit
-        if scx_utils::uei_exited!(skel, uei) {
+        // BPF state for the status line. On a UEI exit, stay up on the last
+        // numbers with the reason shown until quit, rather than vanishing;
+        // an exit that wants a reload (CPU hotplug) hands back to the loader
+        let health = Health::read(skel);
+        if health.restart {
             break;
         }
-
-        // Get current stats (aggregate from per-cpu BSS array)
-        let stats = aggregate_stats(skel);
+        if health.exited.is_some() && app.health.exited.is_none() {
+            app.frozen = true;
+        }
+        app.health = health;
+
+        // Frozen: keep drawing the last read until unfrozen or refreshed
+        if !app.frozen || app.refresh {
+            app.refresh = false;
+            // Raw counters drive the rates; totals shown are since the last reset
+            raw = aggregate_stats(skel);
+            stats = epoch.since(&raw);
+            app.pins.prune(skel);
+            app.user_idle = skel
+                .maps
+                .bss_data
+                .as_ref()
+                .is_some_and(|b| b.user_idle != 0);
+            if let Some(bss) = &skel.maps.bss_data {
+                // 0 = never set: running as loaded
+                let live = [
+                    bss.live_quantum_ns,
+                    bss.live_new_flow_bonus_ns,
+                    bss.live_starvation_ns,
+                ];
+                for (i, ns) in live.into_iter().enumerate() {
+                    app.tune_live[i] = match ns {
+                        0 => app.tune_loaded[i],
+                        ns => ns / 1000,
+                    };
+                }
+                app.stats_on = bss.enable_stats != 0;
+            }
+            if let (View::Events, Some(feed)) = (app.view, params.feed) {
+                // Keep following the newest event unless scrolled up
+                let follow = app
+                    .event_table
+                    .selected()
+                    .is_none_or(|i| i + 1 >= app.events.len());
+                app.events = feed.snapshot();
+                if follow {
+                    app.event_table.select(app.events.len().checked_sub(1));
+                }
+            }
+            if app.view == View::Cpus {
+                if let Some(bss) = &skel.maps.bss_data {
+                    let n = app.topology.nr_cpus.min(bss.global_stats.len());
+                    app.cpus = cpu_breakdown(
+                        &epoch.per_cpu(&bss.global_stats[..n]),
+                        &app.topology.cpu_llc_id,
+                        &app.depth.cpu,
+                        &app.cpu_freq,
+                    );
+                }
+            }
+            if app.view == View::Waits {
+                app.waits = epoch.waits(&wait_histograms(skel));
+            }
+        }
 
         // Draw UI
-        terminal.draw(|frame| draw_ui(frame, &app, &stats))?;
-
-        // Handle events with timeout
-        let timeout = tick_rate.saturating_sub(last_tick.elapsed());
+        terminal.draw(|frame| {
+            draw_ui(frame, &mut app, &stats);
+            params.theme.apply(frame.buffer_mut());
+        })?;
+
+        // Handle events with timeout; a pending gg prefix wakes the loop
+        // in time to act on its own
+        let timeout = match app.frozen {
+            true => tick_rate,
+            false => tick_rate.saturating_sub(last_tick.elapsed()),
+        };
+        let timeout = pending.map_or(timeout, |(_, at)| {
+            timeout.min(SEQ_TIMEOUT.saturating_sub(at.elapsed()))
+        });
+        let mut pressed = Vec::new();
         if event::poll(timeout)? {
-            if let Event::Key(key) = event::read()? {
-                if key.kind == KeyEventKind::Press {
-                    match key.code {
-                        KeyCode::Char('q') | KeyCode::Esc => {
-                            shutdown.store(true, Ordering::Relaxed);
-                            break;
-                        }
-                        KeyCode::Char('c') => {
-                            // Copy stats to clipboard
-                            let text = format_stats_for_clipboard(&stats, &app.format_uptime());
-                            match &mut clipboard {
-                                Some(cb) => match cb.set_text(text) {
-                                    Ok(_) => app.set_status("✓ Copied to clipboard!"),
-                                    Err(_) => app.set_status("✗ Failed to copy"),
-                                },
-                                None => app.set_status("✗ Clipboard not available"),
-                            }
+            match event::read()? {
+                Event::Key(key) if key.kind == KeyEventKind::Press => {
+                    if app.filter_editing {
+                        app.edit_filter(key.code);
+                    } else if app.show_help {
+                        app.show_help = false;
+                    } else {
+                        pressed = params.keys.press(key.code, &mut pending);
+                    }
+                }
+                Event::Mouse(mouse) => app.mouse(mouse, &mut tasks),
+                _ => {}
+            }
+        }
+        pressed.extend(params.keys.expire(&mut pending));
+
+        for candidates in pressed {
+            let Some(&action) = candidates.iter().find(|&&a| app.applies(a)) else {
+                continue;
+            };
+            match action {
+                Action::Help => app.show_help = true,
+                Action::Quit => {
+                    shutdown.store(true, Ordering::Relaxed);
+                    break 'ui;
+                }
+                Action::Copy => {
+                    // Copy stats to clipboard
+                    let text = format_stats_for_clipboard(&stats, &app.format_uptime());
+                    match &mut clipboard {
+                        Some(cb) => match cb.set_text(text) {
+                            Ok(_) => app.set_status("✓ Copied to clipboard!"),
+                            Err(_) => app.set_status("✗ Failed to copy"),
+                        },
+                        None => app.set_status("✗ Clipboard not available"),
+                    }
+                }
+                Action::Snapshot => {
+                    // Snapshot to files: works over SSH, where the clipboard doesn't
+                    let text = format_stats_for_clipboard(&stats, &app.format_uptime());
+                    let json = format_json(
+                        &stats,
+                        &Default::default(),
+                        &epoch.waits(&wait_histograms(skel)),
+                        &app.depth,
+                        epoch.since.elapsed().as_secs_f64(),
+                        app.topology.nr_online(),
+                        app.user_idle,
+                    );
+                    match write_snapshot(params.snapshot_dir, &text, &json) {
+                        Ok(base) => {
+                            app.set_status(&format!("✓ Saved {}.{{txt,json}}", base.display()))
                         }
-                        KeyCode::Char('r') => {
-                            // Reset stats (clear the BSS array)
-                            if let Some(bss) = &mut skel.maps.bss_data {
-                                for s in &mut bss.global_stats {
-                                    *s = Default::default();
+                        Err(e) => app.set_status(&format!("✗ {:#}", e)),
+                    }
+                }
+                Action::Tasks => {
+                    let view = match app.view {
+                        View::Tasks => View::Cgroups,
+                        View::Cgroups => View::Overview,
+                        _ => View::Tasks,
+                    };
+                    app.show(view, &mut tasks);
+                }
+                Action::NextTab => {
+                    let view = app.view.tab().next().view();
+                    app.show(view, &mut tasks);
+                }
+                Action::Tab1 | Action::Tab2 | Action::Tab3 | Action::Tab4 | Action::Tab5 => {
+                    let view = Tab::ORDER[action.tab().unwrap_or(0)].view();
+                    app.show(view, &mut tasks);
+                }
+                Action::Sort => {
+                    app.sort = TopBy::cycle(app.sort);
+                    let sort = app.sort.map_or("natural".to_string(), |by| {
+                        format!("{:?}", by).to_lowercase()
+                    });
+                    app.set_status(&format!("Sort: {}", sort));
+                }
+                Action::SortDirection => app.sort_asc = !app.sort_asc,
+                Action::TierRates => {
+                    app.tier_rates = !app.tier_rates;
+                    if app.view.tab() != Tab::Tiers {
+                        app.show(View::Tiers, &mut tasks);
+                    }
+                }
+                Action::Filter => {
+                    app.filter_editing = true;
+                    if app.view != View::Tasks {
+                        app.show(View::Tasks, &mut tasks);
+                    }
+                }
+                Action::Freeze => {
+                    app.frozen = !app.frozen;
+                    app.set_status(match app.frozen {
+                        true => "⏸ Display frozen",
+                        false => "✓ Display live",
+                    });
+                }
+                Action::Refresh => app.refresh = true,
+                Action::Heatmap => app.toggle(View::Heatmap),
+                Action::Topology => app.toggle(View::Topology),
+                Action::Llcs => app.toggle(View::Llcs),
+                Action::Waits => app.toggle(View::Waits),
+                Action::WaitBars => app.toggle(View::WaitBars),
+                Action::Events => app.toggle(View::Events),
+                Action::Preempts => app.toggle(View::Preempts),
+                Action::RateChart => app.toggle(View::RateChart),
+                Action::Group => {
+                    app.grouped = !app.grouped;
+                    app.task_table = TableState::default().with_selected(Some(0));
+                }
+                Action::Up => app.scroll(-1),
+                Action::Down => app.scroll(1),
+                Action::PageUp => app.scroll(-(app.page as isize)),
+                Action::PageDown => app.scroll(app.page as isize),
+                Action::Top => app.scroll(isize::MIN),
+                Action::Bottom => app.scroll(isize::MAX),
+                Action::Left => app.nudge_tunable(-1),
+                Action::Right => app.nudge_tunable(1),
+                Action::Apply | Action::Restore => {
+                    // Enter applies the edit, Delete goes back to the loaded value
+                    let row = app.tune_table.selected().unwrap_or(0);
+                    let us = match action {
+                        Action::Apply => app.tune_edit.take(),
+                        _ => Some(app.tune_loaded[row]),
+                    };
+                    if let (Some(us), Some(bss)) = (us, skel.maps.bss_data.as_mut()) {
+                        let live = match Tunable::ALL[row] {
+                            Tunable::Quantum => &mut bss.live_quantum_ns,
+                            Tunable::NewFlowBonus => &mut bss.live_new_flow_bonus_ns,
+                            _ => &mut bss.live_starvation_ns,
+                        };
+                        *live = us * 1000;
+                        app.tune_live[row] = us;
+                        app.tune_edit = None;
+                        app.set_status(&format!("✓ {} = {}µs", Tunable::ALL[row].name(), us));
+                    }
+                }
+                Action::Expand => {
+                    app.toggle_expand();
+                }
+                Action::PinTier => {
+                    // Cycle from the first thread's pin; a process moves as one
+                    let pids = app.selected_pids();
+                    if let Some(&first) = pids.first() {
+                        let tier = rules::next_pin(app.pins.get(first));
+                        match app.pins.set(skel, &pids, tier) {
+                            Ok(()) => app.set_status(&match tier {
+                                Some(tier) => {
+                                    format!("📌 {} thread(s) pinned to {:?}", pids.len(), tier)
                                 }
-                                app.set_status("✓ Stats reset");
-                            }
+                                None => format!("✓ {} thread(s) unpinned", pids.len()),
+                            }),
+                            Err(e) => app.set_status(&format!("✗ {:#}", e)),
                         }
-                        _ => {}
+                    }
+                }
+                Action::Reset => {
+                    // New stats epoch: BPF counters are left alone
+                    epoch.reset(skel);
+                    app.set_status(&format!("✓ Stats reset (epoch {})", epoch.epoch));
+                }
+                Action::PauseStats => {
+                    // Flip BPF counting; paused counters keep their values
+                    if let Some(bss) = skel.maps.bss_data.as_mut() {
+                        bss.enable_stats ^= 1;
+                        app.set_status(match bss.enable_stats {
+                            0 => "⏸ Stats paused",
+                            _ => "✓ Stats resumed",
+                        });
                     }
                 }
             }
         }
 
-        if last_tick.elapsed() >= tick_rate {
+        if app.refresh || (!app.frozen && last_tick.elapsed() >= tick_rate) {
+            if app.refresh {
+                // Rates up to the keypress, not to the frame before it
+                raw = aggregate_stats(skel);
+            }
             last_tick = Instant::now();
+            let secs = app.rate_at.elapsed().as_secs_f64();
+            app.rates = Rates::between(&raw, &app.rate_prev, secs);
+            app.rate_trend
+                .push(app.start_time.elapsed().as_secs_f64(), app.rates.dispatches);
+            app.cpu_share = cpu_share(&raw, &app.rate_prev, secs, app.topology.nr_online());
+            app.rate_prev = raw;
+            app.rate_at = Instant::now();
+            let depth = dsq_depth(skel);
+            app.depth = QueueDepth::between(&depth, &app.depth_prev, app.topology.nr_cpus);
+            app.depth_prev = depth;
+            if let Some(bss) = &skel.maps.bss_data {
+                let n = app.topology.nr_cpus.min(bss.global_stats.len());
+                let runtime = cpu_runtime_ns(&bss.global_stats[..n]);
+                app.cpu_busy = cpu_busy_pct(&runtime, &app.cpu_runtime_prev, secs);
+                app.cpu_runtime_prev = runtime;
+                let per_cpu = &bss.global_stats[..n];
+                app.llcs = llc_breakdown(
+                    per_cpu,
+                    &app.llc_prev,
+                    &app.topology.cpu_llc_id,
+                    &app.cpu_busy,
+                    secs,
+                );
+                if app.topology.has_numa {
+                    app.nodes = node_breakdown(
+                        per_cpu,
+                        &app.llc_prev,
+                        &app.topology.cpu_node_id,
+                        &app.cpu_busy,
+                        secs,
+                    );
+                }
+                app.llc_prev = per_cpu.to_vec();
+            }
+            if app.view == View::Cpus {
+                app.cpu_freq = cpu_freq_mhz(app.topology.nr_cpus.min(MAX_CPUS));
+            }
+            let waits = wait_histograms(skel);
+            app.wait_tick = wait_hist_delta(&waits, &app.wait_prev);
+            app.wait_trend.push(&app.wait_tick);
+            let fired = app.alerts.update(&app.wait_tick);
+            if !fired.is_empty() {
+                app.set_status(&format!("⚠ {}", fired.join(", ")));
+                if params.alert_bell {
+                    print!("\x07");
+                    let _ = std::io::stdout().flush();
+                }
+                if let Some(path) = params.alert_log {
+                    for line in &fired {
+                        if let Err(e) = log_alert(path, line) {
+                            app.set_status(&format!("✗ {:#}", e));
+                        }
+                    }
+                }
+            }
+            app.wait_prev = waits;
+            if let (View::Tasks | View::Cgroups, Some(sampler)) = (app.view, &mut tasks) {
+                if let Ok(procs) = sampler.sample() {
+                    app.update_tasks(procs);
+                }
+            }
+            if let Some(h) = history.as_deref_mut() {
+                if h.record(&aggregate_stats(skel)).is_err() {
+                    app.set_status("✗ History snapshot failed");
+                }
+            }
+        }
+    }
+
+    restore_terminal()?;
+    Ok(())
+}
+
+/// Playback speeds `+`/`-` step through
+const REPLAY_SPEEDS: [f64; 7] = [0.25, 0.5, 1.0, 2.0, 4.0, 8.0, 16.0];
+
+/// Rows `monitor --tui` keeps; older ones are dropped
+const LIVE_ROWS: usize = 3600;
+
+/// `monitor --replay` / `monitor --tui` position and playback state
+struct Replay {
+    session: Session,
+    pos: usize,
+    /// Replay: stepping forward. Live: following the newest row
+    playing: bool,
+    speed: usize,
+    events: TableState,
+    /// Rows are polled from the running scheduler rather than read from a file
+    live: bool,
+    /// Last failed poll, shown in the header until one succeeds
+    error: Option<String>,
+}
+
+impl Replay {
+    fn new(session: Session, live: bool) -> Self {
+        let mut replay = Self {
+            session,
+            pos: 0,
+            playing: true,
+            speed: REPLAY_SPEEDS.iter().position(|&s| s == 1.0).unwrap_or(0),
+            events: TableState::default(),
+            live,
+            error: None,
+        };
+        replay.seek(0);
+        replay
+    }
+
+    /// Append a polled row, dropping the oldest past LIVE_ROWS; the cursor
+    /// follows it while playing and otherwise stays on the same row
+    fn push(&mut self, row: replay::Frame) {
+        self.session.frames.push(row);
+        if self.session.frames.len() > LIVE_ROWS {
+            self.session.frames.remove(0);
+            self.pos = self.pos.saturating_sub(1);
+        }
+        if self.playing {
+            self.seek(isize::MAX);
+        }
+    }
+
+    /// Move by `delta` rows, clamped; the event pane follows the new position
+    fn seek(&mut self, delta: isize) {
+        if self.session.frames.is_empty() {
+            return;
+        }
+        let last = self.session.frames.len() as isize - 1;
+        self.pos = (self.pos as isize).saturating_add(delta).clamp(0, last) as usize;
+        let shown = self.session.events_until(self.pos).len();
+        self.events.select(shown.checked_sub(1));
+    }
+
+    fn scroll_events(&mut self, delta: isize) {
+        if self.session.frames.is_empty() {
+            return;
+        }
+        let last = self.session.events_until(self.pos).len() as isize - 1;
+        let cur = self.events.selected().unwrap_or(0) as isize;
+        self.events.select(Some(
+            cur.saturating_add(delta).clamp(0, last.max(0)) as usize
+        ));
+    }
+}
+
+/// Replay screen: position, mean wait up to the cursor, the row's per-tier
+/// rates and the events logged by then
+fn draw_replay(frame: &mut Frame, replay: &mut Replay) {
+    let session = &replay.session;
+    if session.frames.is_empty() {
+        let wait = Paragraph::new(match &replay.error {
+            Some(err) => format!(" {}", err),
+            None => " Waiting for the first interval...  [q] Quit".into(),
+        })
+        .block(
+            Block::default()
+                .title(" scx_cake Live ")
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(Color::Blue)),
+        );
+        frame.render_widget(wait, frame.area());
+        return;
+    }
+    let cur = &session.frames[replay.pos];
+    let delta = cur.delta();
+    let layout = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(3),
+            Constraint::Length(6),
+            Constraint::Length(7),
+            Constraint::Min(5),
+            Constraint::Length(3),
+        ])
+        .split(frame.area());
+
+    let state = match (replay.live, replay.playing) {
+        (true, true) => "● live".to_string(),
+        (true, false) => "⏸ held".to_string(),
+        (false, playing) => format!(
+            "{} {}x",
+            if playing { "▶" } else { "⏸" },
+            REPLAY_SPEEDS[replay.speed]
+        ),
+    };
+    let header = Paragraph::new(format!(
+        " {}  │  {}  │  Row {}/{} ({:.1}s)  │  {}  │  Mean wait {:.1}µs{}{}",
+        session.name,
+        events::local_clock(cur.ts as f64),
+        replay.pos + 1,
+        session.frames.len(),
+        cur.interval_s,
+        state,
+        avg_wait_us(&delta),
+        if cur.user_idle {
+            "  │  User idle"
+        } else {
+            ""
+        },
+        replay
+            .error
+            .as_ref()
+            .map_or(String::new(), |err| format!("  │  {}", err))
+    ))
+    .block(
+        Block::default()
+            .title(if replay.live {
+                " scx_cake Live "
+            } else {
+                " scx_cake Replay "
+            })
+            .title_style(
+                Style::default()
+                    .fg(Color::Cyan)
+                    .add_modifier(Modifier::BOLD),
+            )
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(Color::Blue)),
+    );
+    frame.render_widget(header, layout[0]);
+
+    // As many rows as fit, ending at the cursor
+    let width = layout[1].width.saturating_sub(2) as usize;
+    let first = (replay.pos + 1).saturating_sub(width);
+    let waits: Vec<u64> = session.frames[first..=replay.pos]
+        .iter()
+        .map(|f| avg_wait_us(&f.delta()).round() as u64)
+        .collect();
+    let trend = Sparkline::default()
+        .block(
+            Block::default()
+                .title(format!(
+                    " Mean wait per run, µs (peak {}) ",
+                    waits.iter().max().unwrap_or(&0)
+                ))
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(Color::Blue)),
+        )
+        .data(&waits)
+        .style(Style::default().fg(Color::Yellow));
+    frame.render_widget(trend, layout[1]);
+
+    let rates = Rates::between(&delta, &cake_stats::default(), cur.interval_s);
+    let share = runtime_split(&delta);
+    let totals = session.totals(replay.pos);
+    let header_cells = [
+        "Tier",
+        "Run%",
+        "Disp/s",
+        "Preempt/s",
+        "Immune/s",
+        "CapDefer/s",
+        "Dispatches",
+    ]
+    .iter()
+    .map(|h| {
+        Cell::from(*h).style(
+            Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(Modifier::BOLD),
+        )
+    });
+    let rows: Vec<Row> = (0..TIER_NAMES.len())
+        .map(|t| {
+            Row::new(vec![
+                Cell::from(TIER_NAMES[t]).style(tier_style(t)),
+                Cell::from(format!("{:.1}", share[t])),
+                Cell::from(format!("{:.0}", rates.dispatches[t])),
+                Cell::from(format!("{:.1}", rates.preempts[t])),
+                Cell::from(format!("{:.1}", rates.immune[t])),
+                Cell::from(format!("{:.1}", rates.cap_deferrals[t])),
+                Cell::from(totals.nr_tier_dispatches[t].to_string()),
+            ])
+        })
+        .collect();
+    let table = Table::new(rows, [12, 7, 10, 11, 10, 11, 12].map(Constraint::Length))
+        .header(Row::new(header_cells))
+        .block(
+            Block::default()
+                .title(" Per-Tier (this row; Run% of scheduled time, Dispatches so far) ")
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(Color::Blue)),
+        );
+    frame.render_widget(table, layout[2]);
+
+    draw_event_view(
+        frame,
+        session.events_until(replay.pos),
+        &mut replay.events,
+        layout[3],
+    );
+
+    let footer = Paragraph::new(if replay.live {
+        " [q] Quit  [Space/p] Follow/hold  [←→] Step  [PgUp/PgDn] 10 rows  [Home/End] Oldest/newest  [↑↓] Events"
+    } else {
+        " [q] Quit  [Space/p] Play/pause  [←→] Step  [PgUp/PgDn] 10 rows  [Home/End] Start/end  [+/-] Speed  [↑↓] Events"
+    })
+    .style(Style::default().fg(Color::DarkGray))
+    .block(Block::default().borders(Borders::ALL));
+    frame.render_widget(footer, layout[4]);
+}
+
+/// Keys shared by replay and live; false to quit
+fn replay_key(replay: &mut Replay, code: KeyCode) -> bool {
+    match code {
+        KeyCode::Char('q') | KeyCode::Esc => return false,
+        KeyCode::Char(' ') | KeyCode::Char('p') => {
+            replay.playing = !replay.playing;
+            if replay.live && replay.playing {
+                replay.seek(isize::MAX);
+            }
+        }
+        KeyCode::Left => replay.seek(-1),
+        KeyCode::Right => replay.seek(1),
+        KeyCode::PageUp => replay.seek(-10),
+        KeyCode::PageDown => replay.seek(10),
+        KeyCode::Home => replay.seek(isize::MIN),
+        KeyCode::End => replay.seek(isize::MAX),
+        KeyCode::Char('+') | KeyCode::Char('=') if !replay.live => {
+            replay.speed = (replay.speed + 1).min(REPLAY_SPEEDS.len() - 1)
+        }
+        KeyCode::Char('-') if !replay.live => replay.speed = replay.speed.saturating_sub(1),
+        KeyCode::Up | KeyCode::Char('k') => replay.scroll_events(-1),
+        KeyCode::Down | KeyCode::Char('j') => replay.scroll_events(1),
+        _ => {}
+    }
+    true
+}
+
+/// Wait up to `timeout` for a key press; None when it expired
+fn next_key(timeout: Duration) -> Result<Option<KeyCode>> {
+    if event::poll(timeout)? {
+        if let Event::Key(key) = event::read()? {
+            if key.kind == KeyEventKind::Press {
+                return Ok(Some(key.code));
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// `monitor --replay`: step through a recorded session, each row held for
+/// its own interval at 1x, with pause and seek
+pub fn run_replay(session: Session, theme: Theme) -> Result<()> {
+    let mut terminal = setup_terminal()?;
+    let mut replay = Replay::new(session, false);
+    let mut last_step = Instant::now();
+
+    loop {
+        terminal.draw(|frame| {
+            draw_replay(frame, &mut replay);
+            theme.apply(frame.buffer_mut());
+        })?;
+
+        let hold = Duration::from_secs_f64(
+            replay.session.frames[replay.pos].interval_s.max(0.05) / REPLAY_SPEEDS[replay.speed],
+        );
+        let timeout = match replay.playing {
+            true => hold.saturating_sub(last_step.elapsed()),
+            false => Duration::from_millis(250),
+        };
+        match next_key(timeout)? {
+            Some(code) => {
+                let was_playing = replay.playing;
+                if !replay_key(&mut replay, code) {
+                    break;
+                }
+                if replay.playing != was_playing {
+                    last_step = Instant::now();
+                }
+            }
+            None if replay.playing && last_step.elapsed() >= hold => {
+                match replay.pos + 1 < replay.session.frames.len() {
+                    true => replay.seek(1),
+                    false => replay.playing = false,
+                }
+                last_step = Instant::now();
+            }
+            None => {}
+        }
+    }
+
+    restore_terminal()?;
+    Ok(())
+}
+
+/// One `stats` poll of the running scheduler
+fn poll_stats() -> Result<(cake_stats, bool)> {
+    let reply = query::request("stats")?;
+    query::stats_from_reply(&reply).context("unexpected stats reply")
+}
+
+/// `monitor --tui`: the replay screen fed by polling a running scheduler
+/// over the query socket every `interval`, so a daemon started with
+/// --headless-stats can be watched (and left again) at any time
+pub fn run_attach(interval: Duration, theme: Theme) -> Result<()> {
+    // Fail before taking over the terminal if nothing is listening
+    let mut prev = Some((poll_stats()?, Instant::now()));
+    let mut terminal = setup_terminal()?;
+    let session = Session {
+        name: query::SOCKET_PATH.into(),
+        frames: Vec::new(),
+        events: Vec::new(),
+    };
+    let mut replay = Replay::new(session, true);
+    let mut last_poll = Instant::now();
+
+    loop {
+        terminal.draw(|frame| {
+            draw_replay(frame, &mut replay);
+            theme.apply(frame.buffer_mut());
+        })?;
+
+        match next_key(interval.saturating_sub(last_poll.elapsed()))? {
+            Some(code) => {
+                if !replay_key(&mut replay, code) {
+                    break;
+                }
+            }
+            None if last_poll.elapsed() >= interval => {
+                last_poll = Instant::now();
+                match poll_stats() {
+                    Ok((cur, idle)) => {
+                        // After a failed poll the scheduler may have
+                        // restarted: start counting again from this sample
+                        if let Some((before, at)) = prev.filter(|_| replay.error.is_none()) {
+                            let now = SystemTime::now()
+                                .duration_since(UNIX_EPOCH)
+                                .map_or(0, |d| d.as_secs());
+                            replay.push(replay::Frame::between(
+                                now,
+                                at.elapsed().as_secs_f64(),
+                                &cur,
+                                &before,
+                                idle,
+                            ));
+                        }
+                        replay.error = None;
+                        prev = Some((cur, Instant::now()));
+                    }
+                    Err(err) => replay.error = Some(format!("{:#}", err)),
+                }
+            }
+            None => {}
         }
     }
 